@@ -0,0 +1,112 @@
+//! Lookup benchmarks that run on stable, with `cargo bench`.  Each case is
+//! timed over a number of samples, and the median time per lookup is
+//! printed, so that it can be compared before and after a change.  Under
+//! `cargo test`, each case only runs once, to check that it still works.
+
+use pathmaker::{Route, Router};
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+/// How many samples are taken of each case.
+const SAMPLES: usize = 25;
+/// Roughly how long each sample runs for.
+const SAMPLE_TIME: Duration = Duration::from_millis(20);
+
+type Table = Router<&'static str, usize>;
+
+fn main() {
+    let bench = std::env::args().any(|arg| arg == "--bench");
+    let filter = std::env::args().skip(1).find(|arg| !arg.starts_with("--"));
+    let small = small();
+    let large_set = large(false);
+    let large_trie = large(true);
+    let params = params();
+
+    let cases: Vec<(&str, &Table, &str)> = vec![
+        ("small/static", &small, "/foo/bar"),
+        ("small/param", &small, "/foo/quux"),
+        ("small/no_match", &small, "/missing/path"),
+        ("large/first", &large_set, "/resource0/5/items"),
+        ("large/last", &large_set, "/resource999/5/items"),
+        ("large/no_match", &large_set, "/resource1000/5/items"),
+        ("large_trie/last", &large_trie, "/resource999/5/items"),
+        ("large_trie/no_match", &large_trie, "/resource1000/5/items"),
+        ("params/typed", &params, "/orgs/acme/teams/7/users/5/posts/2019-10-01/v1.2.3"),
+        ("params/no_match", &params, "/orgs/acme/teams/x/users/5/posts/2019-10-01/v1.2.3"),
+    ];
+    for (name, router, path) in cases {
+        if filter.as_ref().is_some_and(|filter| !name.contains(filter.as_str())) {
+            continue;
+        }
+        if bench {
+            println!("{:<24} {:>10.1} ns/lookup", name, measure(|| router.lookup(&"GET", path)));
+        } else {
+            black_box(router.lookup(&"GET", path));
+        }
+    }
+}
+
+/// The median time a single call to `f` takes.
+fn measure<T, F: FnMut() -> T>(mut f: F) -> f64 {
+    // Find how many calls fill a sample, which also warms up the caches.
+    let mut iters = 1u64;
+    loop {
+        let start = Instant::now();
+        (0..iters).for_each(|_| drop(black_box(f())));
+        if start.elapsed() >= SAMPLE_TIME / 4 {
+            iters *= 4;
+            break;
+        }
+        iters *= 2;
+    }
+
+    let mut samples = (0..SAMPLES)
+        .map(|_| {
+            let start = Instant::now();
+            (0..iters).for_each(|_| drop(black_box(f())));
+            start.elapsed().as_nanos() as f64 / iters as f64
+        })
+        .collect::<Vec<_>>();
+    samples.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    samples[SAMPLES / 2]
+}
+
+/// The same routes as the `bench_mark` benchmark in the library.
+fn small() -> Table {
+    let mut build = Router::build();
+    build
+        .add(Route::new("/", "GET", 1))
+        .add(Route::new("/hello", "GET", 2))
+        .add(Route::new("/hello/world", "GET", 3))
+        .add(Route::new("/foo", "GET", 4))
+        .add(Route::new("/foo/bar", "GET", 5))
+        .add(Route::new("/foo/baz", "GET", 6))
+        .add(Route::new("/foo/{}", "GET", 7));
+    build.finish()
+}
+
+/// A thousand routes, each with its own literal prefix.
+fn large(trie: bool) -> Table {
+    let mut build = Router::build();
+    for i in 0..1000 {
+        build.add(Route::new(format!("/resource{}/{{}}/items", i), "GET", i));
+    }
+    if trie {
+        build.trie_matching();
+    }
+    build.finish()
+}
+
+/// Routes with many typed parameters, and constraints.
+fn params() -> Table {
+    let mut build = Router::build();
+    build
+        .add(Route::new("/orgs/{org}", "GET", 1))
+        .add(Route::new("/orgs/{org}/teams/{team:uint}", "GET", 2))
+        .add(Route::new(
+            "/orgs/{org}/teams/{team:uint(1..100)}/users/{user:uint}/posts/{date}/v{version:semver}",
+            "GET",
+            3,
+        ));
+    build.finish()
+}
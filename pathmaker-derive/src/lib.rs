@@ -0,0 +1,384 @@
+//! The derive macro for `pathmaker::PathParams`, and the `routes!` macro;
+//! see the `derive` feature of `pathmaker`, which re-exports them.
+
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::ext::IdentExt;
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::{parse_macro_input, Data, DataStruct, DeriveInput, Error, Expr, Fields, Ident, LitStr, Token};
+
+/// Derives `PathParams` for a struct with named fields, taking each field
+/// from the parameter of the route with the same name, e.g. `id` from
+/// `{id:uint}`.
+#[proc_macro_derive(PathParams)]
+pub fn derive_path_params(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input)
+        .unwrap_or_else(|error| error.to_compile_error())
+        .into()
+}
+
+fn expand(input: &DeriveInput) -> Result<TokenStream2, Error> {
+    let fields = match &input.data {
+        Data::Struct(DataStruct {
+            fields: Fields::Named(fields),
+            ..
+        }) => &fields.named,
+        _ => {
+            return Err(Error::new_spanned(
+                input,
+                "PathParams can only be derived for structs with named fields",
+            ))
+        }
+    };
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let idents = fields
+        .iter()
+        .map(|field| field.ident.as_ref().unwrap())
+        .collect::<Vec<_>>();
+    let names = idents.iter().map(|ident| ident.unraw().to_string());
+    let positions = 0..idents.len();
+
+    Ok(quote! {
+        impl #impl_generics ::pathmaker::PathParams for #name #ty_generics #where_clause {
+            const NAMES: &'static [&'static str] = &[#(#names),*];
+
+            fn from_params<S: ::std::convert::AsRef<str>>(
+                indices: &[usize],
+                params: &[S],
+            ) -> ::std::result::Result<Self, ::pathmaker::ParamError> {
+                ::std::result::Result::Ok(#name {
+                    #(#idents: ::pathmaker::ParseParams::parse(params, indices[#positions])?,)*
+                })
+            }
+        }
+    })
+}
+
+/// Builds routes for the `http` crate's types from a list of methods, paths,
+/// and handlers, e.g. `routes! { GET "/users/{id:uint}" => show_user }`,
+/// checking each path when it's compiled.  The method is one of the
+/// standard ones, in upper case, or `ANY`.  This expands to a
+/// `pathmaker::http::Build`, which uses `trie_matching`; the routes with
+/// literal paths are found by a generated `match` on the path first.
+#[proc_macro]
+pub fn routes(input: TokenStream) -> TokenStream {
+    let routes = parse_macro_input!(input as Routes);
+    let routes = routes.0.iter().collect::<Vec<_>>();
+    let adds = routes.iter().map(|route| {
+        let method = Ident::new(&route.method.to_string().to_lowercase(), route.method.span());
+        let (path, handler) = (&route.path, &route.handler);
+        quote! { build.#method(#path, #handler); }
+    });
+    let statics = routes
+        .iter()
+        .map(|route| (route.method.to_string(), route.path.value()))
+        .collect::<Vec<_>>();
+    let statics = dispatch(&literal_routes(&statics));
+
+    let output = quote! {{
+        let mut build: ::pathmaker::http::Build<_, _> = ::pathmaker::Router::build();
+        build.trie_matching();
+        #(#adds)*
+        #statics
+        build
+    }};
+    output.into()
+}
+
+/// The routes with literal paths, given their methods and paths, grouped by
+/// path, in the order the paths first appear, with the methods and indices
+/// of their routes.  A route is only left to the `match` if none of the
+/// routes before it could match its path with one of its methods, so that
+/// it's the route the router would find anyway.
+fn literal_routes(routes: &[(String, String)]) -> Vec<(String, Vec<(String, usize)>)> {
+    let mut paths: Vec<(String, Vec<(String, usize)>)> = vec![];
+    for (index, (method, path)) in routes.iter().enumerate() {
+        if !is_literal(path) {
+            continue;
+        }
+        let shadowed = routes[..index].iter().any(|(other, before)| {
+            (other == method || other == "ANY" || method == "ANY") && could_match(before, path)
+        });
+        if shadowed {
+            continue;
+        }
+        match paths.iter_mut().find(|(other, _)| other == path) {
+            Some((_, methods)) => methods.push((method.clone(), index)),
+            None => paths.push((path.clone(), vec![(method.clone(), index)])),
+        }
+    }
+    paths
+}
+
+/// Generates the `match` that finds the routes with literal paths (see
+/// [`literal_routes`]), by the index they're added at.
+fn dispatch(paths: &[(String, Vec<(String, usize)>)]) -> TokenStream2 {
+    if paths.is_empty() {
+        return quote! {};
+    }
+
+    let arms = paths.iter().map(|(path, methods)| {
+        let checks = methods.iter().map(|(method, index)| match &**method {
+            "ANY" => quote! { return ::std::option::Option::Some(#index); },
+            method => quote! {
+                if method.as_str() == #method {
+                    return ::std::option::Option::Some(#index);
+                }
+            },
+        });
+        quote! { #path => { #(#checks)* ::std::option::Option::None } }
+    });
+    quote! {
+        build.static_routes(|method, path| match path {
+            #(#arms)*
+            _ => ::std::option::Option::None,
+        });
+    }
+}
+
+/// Whether the path has no parameters, and matches only itself: it has no
+/// braces (even escaped ones), or escapes, which the router decodes, and
+/// is ASCII, so that normalization can't change it.
+fn is_literal(path: &str) -> bool {
+    path.is_ascii() && !path.contains(['{', '}', '%'])
+}
+
+/// Whether the route with the given path could match the given literal
+/// path.  No parameter can match a `/`, so a route can only match paths
+/// with as many segments as it has, and the same literal segments.
+fn could_match(route: &str, path: &str) -> bool {
+    if is_literal(route) {
+        return route == path;
+    }
+    if route.contains('%') || !route.is_ascii() {
+        return true;
+    }
+    let (route, path) = (route.split('/').collect::<Vec<_>>(), path.split('/').collect::<Vec<_>>());
+    route.len() == path.len()
+        && route
+            .iter()
+            .zip(&path)
+            .all(|(segment, part)| segment.contains(['{', '}']) || segment == part)
+}
+
+/// The methods that `routes!` accepts, which are the ones that
+/// `pathmaker::http::Build` has a shortcut for.
+const METHODS: &[&str] = &[
+    "GET", "POST", "PUT", "DELETE", "HEAD", "OPTIONS", "TRACE", "CONNECT", "PATCH", "ANY",
+];
+
+/// The kinds of parameters that don't take any arguments.  This has to be
+/// kept in step with `MATCH_KINDS` in `pathmaker`.
+const KINDS: &[&str] = &[
+    "string", "int", "uint", "uuid", "semver", "ipv4", "ipv6", "email", "grpc_method", "b64",
+];
+
+struct Routes(Punctuated<RouteDef, Token![,]>);
+
+struct RouteDef {
+    method: Ident,
+    path: LitStr,
+    handler: Expr,
+}
+
+impl Parse for Routes {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        Punctuated::parse_terminated(input).map(Routes)
+    }
+}
+
+impl Parse for RouteDef {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let method = input.parse::<Ident>()?;
+        if !METHODS.contains(&&*method.to_string()) {
+            let message = format!("unknown method `{}`, expected one of {}", method, METHODS.join(", "));
+            return Err(Error::new(method.span(), message));
+        }
+        let path = input.parse::<LitStr>()?;
+        check_path(&path.value()).map_err(|message| Error::new(path.span(), message))?;
+        input.parse::<Token![=>]>()?;
+        let handler = input.parse()?;
+        Ok(RouteDef { method, path, handler })
+    }
+}
+
+/// Checks a path the same way `pathmaker::Route::try_new` does, except that
+/// a brace that isn't a part of a parameter or an escape (`{{` or `}}`) is
+/// an error, instead of literal text, since it's almost always a typo.
+fn check_path(path: &str) -> Result<(), String> {
+    if !path.starts_with('/') {
+        return Err(format!("the path `{}` has to start with a `/`", path));
+    }
+    let mut rest = path;
+    while let Some(index) = rest.find(['{', '}']) {
+        let after = &rest[index + 1..];
+        if after.starts_with(&rest[index..=index]) {
+            rest = &after[1..];
+            continue;
+        }
+        if rest[index..].starts_with('}') {
+            return Err(format!("the path `{}` has a `}}` without a `{{`; use `}}}}` for a literal one", path));
+        }
+        let end = after
+            .find('}')
+            .ok_or_else(|| format!("the path `{}` has a `{{` without a `}}`; use `{{{{` for a literal one", path))?;
+        check_param(&after[..end]).map_err(|message| format!("the parameter `{{{}}}` {}", &after[..end], message))?;
+        rest = &after[end + 1..];
+    }
+    Ok(())
+}
+
+/// Checks the inside of a parameter, e.g. `id:uint(1..)`.
+fn check_param(param: &str) -> Result<(), &'static str> {
+    let (name, kind) = match param.find(':') {
+        Some(index) => (&param[..index], Some(&param[index + 1..])),
+        None => (param, None),
+    };
+    if !name.is_empty() && !is_ident(name, true) {
+        return Err("has an invalid name");
+    }
+    let kind = match kind {
+        Some(kind) => kind,
+        None => return Ok(()),
+    };
+    let (kind, args) = match kind.find('(') {
+        Some(index) if kind.ends_with(')') => (&kind[..index], Some(&kind[index + 1..kind.len() - 1])),
+        Some(_) => return Err("has arguments without a closing `)`"),
+        None => (kind, None),
+    };
+    if !is_ident(kind, false) || args.is_some_and(|args| args.contains(')')) {
+        return Err("isn't valid");
+    }
+    let valid = match (kind, args) {
+        ("b64", Some(args)) => parse_range(args).is_some_and(|(min, max)| {
+            max.is_none_or(|max| max >= std::cmp::max(min.unwrap_or(1), 1))
+        }),
+        ("one_of", Some(args)) => args
+            .split('|')
+            .map(str::trim)
+            .all(|choice| !choice.is_empty() && !choice.contains('/')),
+        ("int", Some(args)) | ("uint", Some(args)) => parse_range(args).is_some_and(|range| match range {
+            (Some(min), Some(max)) => min <= max,
+            _ => true,
+        }),
+        (kind, None) => KINDS.contains(&kind),
+        _ => false,
+    };
+    if valid {
+        Ok(())
+    } else {
+        Err("has an unknown kind, or invalid arguments")
+    }
+}
+
+fn is_ident(text: &str, underscore: bool) -> bool {
+    let mut chars = text.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || (underscore && c == '_'))
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Parses a range argument the same way `pathmaker` does, into its
+/// inclusive bounds.
+fn parse_range(args: &str) -> Option<(Option<i128>, Option<i128>)> {
+    fn bound(value: &str) -> Option<Option<i128>> {
+        match value.trim() {
+            "" => Some(None),
+            value => value.parse().ok().map(Some),
+        }
+    }
+
+    let args = args.trim();
+    if let Some(index) = args.find("..=") {
+        let min = bound(&args[..index])?;
+        let max = bound(&args[index + 3..])??;
+        Some((min, Some(max)))
+    } else if let Some(index) = args.find("..") {
+        let min = bound(&args[..index])?;
+        let max = match bound(&args[index + 2..])? {
+            Some(max) => Some(max.checked_sub(1)?),
+            None => None,
+        };
+        Some((min, max))
+    } else {
+        let exact = args.parse().ok()?;
+        Some((Some(exact), Some(exact)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_path() {
+        for path in &[
+            "/",
+            "/users/{id:uint}/posts/{}",
+            "/range/{from:uint(1..)}-{to:int(..=5)}",
+            "/report/{id}.{format:one_of(json|csv)}",
+            "/confirm/{:b64(22)}",
+            "/literal/{{x}}",
+        ] {
+            assert_eq!(check_path(path), Ok(()), "{}", path);
+        }
+        for (path, error) in &[
+            ("users", "the path `users` has to start with a `/`"),
+            ("/users/{id:uint", "the path `/users/{id:uint` has a `{` without a `}`; use `{{` for a literal one"),
+            ("/users/id}", "the path `/users/id}` has a `}` without a `{`; use `}}` for a literal one"),
+            ("/users/{id:unit}", "the parameter `{id:unit}` has an unknown kind, or invalid arguments"),
+            ("/users/{:uint(5..1)}", "the parameter `{:uint(5..1)}` has an unknown kind, or invalid arguments"),
+            ("/report/{:one_of(a||c)}", "the parameter `{:one_of(a||c)}` has an unknown kind, or invalid arguments"),
+            ("/report/{:one_of}", "the parameter `{:one_of}` has an unknown kind, or invalid arguments"),
+            ("/users/{1d}", "the parameter `{1d}` has an invalid name"),
+            ("/users/{id:uint(1..}", "the parameter `{id:uint(1..}` has arguments without a closing `)`"),
+        ] {
+            assert_eq!(check_path(path).unwrap_err(), *error);
+        }
+    }
+
+    #[test]
+    fn test_literal_routes() {
+        let routes = [
+            ("GET", "/users/{name}"),
+            ("GET", "/users/me"),
+            ("POST", "/users/me"),
+            ("GET", "/users/{id:uint}/posts"),
+            ("GET", "/health"),
+            ("ANY", "/health"),
+            ("GET", "/health"),
+            ("GET", "/users/5/posts"),
+            ("GET", "/users/5/comments"),
+            ("GET", "/caf%C3%A9"),
+            ("ANY", "/{}/{}"),
+            ("DELETE", "/users/me"),
+        ]
+        .iter()
+        .map(|(method, path)| (method.to_string(), path.to_string()))
+        .collect::<Vec<_>>();
+        let paths = literal_routes(&routes);
+        let paths = paths
+            .iter()
+            .map(|(path, methods)| {
+                let methods = methods.iter().map(|(method, index)| (&**method, *index)).collect::<Vec<_>>();
+                (&**path, methods)
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            paths,
+            vec![
+                ("/users/me", vec![("POST", 2)]),
+                ("/health", vec![("GET", 4)]),
+                ("/users/5/comments", vec![("GET", 8)]),
+            ]
+        );
+    }
+}
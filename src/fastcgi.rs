@@ -0,0 +1,217 @@
+//! Adapts the router to a FastCGI (or CGI) request loop, e.g. behind nginx's
+//! `fastcgi_pass`.  The request is built from the parameters the server
+//! sends with it, and the response is written back in the CGI format, so
+//! any FastCGI library that gives the parameters, and a writer for the
+//! output, can be used with a [`crate::sync::Router`].
+
+use ::http::header::{HeaderName, HeaderValue};
+use ::http::{Request, Response, Version};
+use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
+use std::error::Error;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::io::{Result as IoResult, Write};
+
+/// The characters that are percent-encoded in the path, so that it can be
+/// put into a uri.  The server has already decoded `PATH_INFO`, so this
+/// includes `%`; the router decodes the path again before routing it.
+const PATH: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// The `SCRIPT_NAME` of a request, i.e. the prefix of the path that the
+/// application is mounted under, which isn't a part of the path that's
+/// routed.  It's put into the extensions of every request made by
+/// [`request`].
+pub struct ScriptName(pub String);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// The reason a request couldn't be made by [`request`].
+pub enum FastCgiError {
+    /// The server didn't send a `REQUEST_METHOD`.
+    MissingMethod,
+    /// A parameter couldn't be used for the request, e.g. a method or a
+    /// header with characters that aren't allowed.
+    Invalid(String),
+}
+
+impl Display for FastCgiError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            FastCgiError::MissingMethod => f.write_str("the request has no REQUEST_METHOD"),
+            FastCgiError::Invalid(message) => write!(f, "the request is invalid: {}", message),
+        }
+    }
+}
+
+impl Error for FastCgiError {}
+
+/// Makes a request from the parameters of a FastCGI request, and its body.
+/// The method is `REQUEST_METHOD`; the path that's routed is `PATH_INFO`,
+/// or `SCRIPT_NAME` if there isn't one (nginx puts the whole path there,
+/// unless it's told to split it with `fastcgi_split_path_info`); the query
+/// is `QUERY_STRING`; and the headers are `CONTENT_TYPE`, `CONTENT_LENGTH`,
+/// and every `HTTP_` parameter, e.g. `HTTP_USER_AGENT` as `user-agent`.
+/// When `PATH_INFO` is given, `SCRIPT_NAME` is kept in the request's
+/// extensions as a [`ScriptName`].
+pub fn request<I, K, V, B>(params: I, body: B) -> Result<Request<B>, FastCgiError>
+where
+    I: IntoIterator<Item = (K, V)>,
+    K: AsRef<str>,
+    V: AsRef<str>,
+{
+    let mut builder = Request::builder();
+    let mut method = None;
+    let mut script_name = String::new();
+    let mut path_info = String::new();
+    let mut query = String::new();
+    for (name, value) in params {
+        let (name, value) = (name.as_ref(), value.as_ref());
+        match name {
+            "REQUEST_METHOD" => method = Some(value.to_string()),
+            "SCRIPT_NAME" => script_name = value.to_string(),
+            "PATH_INFO" => path_info = value.to_string(),
+            "QUERY_STRING" => query = value.to_string(),
+            "SERVER_PROTOCOL" => {
+                builder.version(version(value));
+            }
+            "CONTENT_TYPE" | "CONTENT_LENGTH" => {
+                builder.header(header(name)?, header_value(value)?);
+            }
+            _ if name.starts_with("HTTP_") => {
+                builder.header(header(&name["HTTP_".len()..])?, header_value(value)?);
+            }
+            _ => {}
+        }
+    }
+
+    let method = method.ok_or(FastCgiError::MissingMethod)?;
+    let path = if path_info.is_empty() {
+        std::mem::take(&mut script_name)
+    } else {
+        path_info
+    };
+    let mut uri = utf8_percent_encode(&path, PATH).to_string();
+    if !uri.starts_with('/') {
+        uri.insert(0, '/');
+    }
+    if !query.is_empty() {
+        uri.push('?');
+        uri.push_str(&query);
+    }
+    builder.method(method.as_str()).uri(uri.as_str());
+    if !script_name.is_empty() {
+        builder.extension(ScriptName(script_name));
+    }
+    builder
+        .body(body)
+        .map_err(|error| FastCgiError::Invalid(error.to_string()))
+}
+
+/// Writes the given response in the CGI format that FastCGI servers expect
+/// on the request's output: a `Status` header, the other headers, then a
+/// blank line, and the body.
+pub fn write_response<B: AsRef<[u8]>, W: Write>(response: &Response<B>, mut out: W) -> IoResult<()> {
+    let status = response.status();
+    write!(
+        out,
+        "Status: {} {}\r\n",
+        status.as_str(),
+        status.canonical_reason().unwrap_or("")
+    )?;
+    for (name, value) in response.headers() {
+        out.write_all(name.as_str().as_bytes())?;
+        out.write_all(b": ")?;
+        out.write_all(value.as_bytes())?;
+        out.write_all(b"\r\n")?;
+    }
+    out.write_all(b"\r\n")?;
+    out.write_all(response.body().as_ref())?;
+    out.flush()
+}
+
+fn version(protocol: &str) -> Version {
+    match protocol {
+        "HTTP/0.9" => Version::HTTP_09,
+        "HTTP/1.0" => Version::HTTP_10,
+        "HTTP/2" | "HTTP/2.0" => Version::HTTP_2,
+        _ => Version::HTTP_11,
+    }
+}
+
+fn header(name: &str) -> Result<HeaderName, FastCgiError> {
+    HeaderName::from_bytes(name.replace('_', "-").to_ascii_lowercase().as_bytes())
+        .map_err(|error| FastCgiError::Invalid(error.to_string()))
+}
+
+fn header_value(value: &str) -> Result<HeaderValue, FastCgiError> {
+    HeaderValue::from_str(value).map_err(|error| FastCgiError::Invalid(error.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sync::Router;
+    use ::http::header::CONTENT_TYPE;
+    use ::http::Method;
+
+    #[test]
+    fn test_request() {
+        let params = vec![
+            ("REQUEST_METHOD", "POST"),
+            ("SCRIPT_NAME", "/app"),
+            ("PATH_INFO", "/users/jane doe"),
+            ("QUERY_STRING", "page=2"),
+            ("SERVER_PROTOCOL", "HTTP/1.0"),
+            ("CONTENT_TYPE", "text/plain"),
+            ("HTTP_X_REQUEST_ID", "abc"),
+            ("REMOTE_ADDR", "127.0.0.1"),
+        ];
+        let req = request(params, ()).unwrap();
+        assert_eq!(req.method(), Method::POST);
+        assert_eq!(req.uri(), "/users/jane%20doe?page=2");
+        assert_eq!(req.version(), Version::HTTP_10);
+        assert_eq!(req.headers()[CONTENT_TYPE], "text/plain");
+        assert_eq!(req.headers()["x-request-id"], "abc");
+        assert_eq!(
+            req.extensions().get::<ScriptName>(),
+            Some(&ScriptName("/app".to_string()))
+        );
+
+        let req = request(vec![("REQUEST_METHOD", "GET"), ("SCRIPT_NAME", "/users")], ()).unwrap();
+        assert_eq!(req.uri(), "/users");
+        assert!(req.extensions().get::<ScriptName>().is_none());
+        assert_eq!(
+            request(vec![("PATH_INFO", "/")], ()).unwrap_err(),
+            FastCgiError::MissingMethod
+        );
+    }
+
+    #[test]
+    fn test_respond() {
+        let mut build = Router::build();
+        build.get("/users/{}", |_, params| {
+            Response::builder()
+                .header(CONTENT_TYPE, "text/plain")
+                .body(params[0].to_string().into_bytes())
+                .unwrap()
+        });
+        let router = build.finish();
+        let params = vec![("REQUEST_METHOD", "GET"), ("PATH_INFO", "/users/jane doe")];
+
+        let mut out = vec![];
+        write_response(&router.respond(request(params, vec![]).unwrap()), &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "Status: 200 OK\r\ncontent-type: text/plain\r\n\r\njane doe"
+        );
+    }
+}
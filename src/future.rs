@@ -0,0 +1,185 @@
+//! Routing for async servers that aren't built on tokio or hyper (e.g. ones
+//! that run on async-std or smol, like `async-h1`).  Handlers return a
+//! `std::future`, so they can be written with `async`/`.await`, and no
+//! runtime is needed by the router itself.  The request and response are
+//! the `http` crate's, so a server that has its own types converts to and
+//! from them around [`Router::respond`].
+
+use ::http::header::{HeaderValue, ALLOW, LOCATION};
+use ::http::{Method, Request, Response, StatusCode};
+use super::http::Dispatch;
+use failure::Error;
+use std::borrow::Cow;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+/// The future that's returned by every handler in the router.
+pub type HandlerFuture<B> = Pin<Box<dyn Future<Output = Result<Response<B>, Error>> + Send>>;
+
+/// The handler that's stored as a part of every route in the router.  It's
+/// given the request, and the parameters of the path, and returns a future
+/// of the response.
+pub type Handler<B> = super::http::Handler<B, HandlerFuture<B>>;
+
+/// A single route, with an async [`Handler`].  It has the same shortcut
+/// methods as [`super::http::Route`].
+pub type Route<B> = super::http::Route<B, HandlerFuture<B>>;
+
+/// The router type, with an async [`Handler`].  Requests are given to it
+/// with [`Router::respond`].
+pub type Router<B> = super::http::Router<B, HandlerFuture<B>>;
+
+/// A builder for building routes, with an async [`Handler`].  It has the
+/// same shortcut methods as [`super::http::Build`].
+pub type Build<B> = super::http::Build<B, HandlerFuture<B>>;
+
+/// Makes a [`Handler`] from an `async` function (or a closure that returns
+/// an `async` block), so that it doesn't have to box its own future.  The
+/// error can be anything that converts into a [`failure::Error`].  The
+/// future can't borrow the parameters, so they're given to it as owned
+/// strings.
+pub fn async_handler<B, F, Fut, E>(handler: F) -> Handler<B>
+where
+    F: Fn(Request<B>, Vec<String>) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<Response<B>, E>> + Send + 'static,
+    E: Into<Error> + 'static,
+{
+    Arc::new(move |req, params| -> HandlerFuture<B> {
+        let future = handler(req, params.iter().map(|param| param.to_string()).collect());
+        Box::pin(async move { future.await.map_err(Into::into) })
+    })
+}
+
+impl<B: Send + 'static> Route<B> {
+    /// Creates a route whose handler is an `async` function.  See
+    /// [`async_handler`].
+    pub fn async_fn<P, F, Fut, E>(path: P, method: Method, handler: F) -> Self
+    where
+        P: Into<Cow<'static, str>>,
+        F: Fn(Request<B>, Vec<String>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Response<B>, E>> + Send + 'static,
+        E: Into<Error> + 'static
+    {
+        Self::new(path, method, async_handler(handler))
+    }
+}
+
+impl<B: Send + 'static> Build<B> {
+    /// Adds a route whose handler is an `async` function.  See
+    /// [`async_handler`].
+    pub fn async_fn<P, F, Fut, E>(&mut self, method: Method, path: P, handler: F) -> &mut Self
+    where
+        P: Into<Cow<'static, str>>,
+        F: Fn(Request<B>, Vec<String>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Response<B>, E>> + Send + 'static,
+        E: Into<Error> + 'static
+    {
+        self.add(Route::async_fn(path, method, handler))
+    }
+}
+
+impl<B: Default + Send + 'static> Router<B> {
+    /// Routes the given request, and returns the future of the handler that
+    /// it matches.  This answers the request the same way the hyper
+    /// `Service` does when nothing matches: with a 204 No Content for an
+    /// automatic `OPTIONS` answer, a redirect with its `Location` for a
+    /// trailing slash (see [`super::http::Build::redirect_trailing_slash`]),
+    /// or an empty response with the status from
+    /// [`super::http::Router::dispatch`] otherwise.  The body of a response
+    /// to a `HEAD` request is dropped, but its headers are kept.
+    pub fn respond(&self, req: Request<B>) -> HandlerFuture<B> {
+        let head = req.method() == Method::HEAD;
+        let response = match self.dispatch(req) {
+            Dispatch::Handled(future) => future,
+            Dispatch::Options(allow) => {
+                let mut response = status(StatusCode::NO_CONTENT);
+                if let Ok(allow) = HeaderValue::from_str(&allow) {
+                    response.headers_mut().insert(ALLOW, allow);
+                }
+                return Box::pin(std::future::ready(Ok(response)));
+            }
+            Dispatch::Redirect(code, location) => {
+                let mut response = status(code);
+                if let Ok(location) = HeaderValue::from_str(&location) {
+                    response.headers_mut().insert(LOCATION, location);
+                }
+                return Box::pin(std::future::ready(Ok(response)));
+            }
+            Dispatch::Status(code) => return Box::pin(std::future::ready(Ok(status(code)))),
+        };
+        if !head {
+            return response;
+        }
+        Box::pin(async move {
+            let mut response = response.await?;
+            *response.body_mut() = B::default();
+            Ok(response)
+        })
+    }
+}
+
+fn status<B: Default>(code: StatusCode) -> Response<B> {
+    let mut response = Response::new(B::default());
+    *response.status_mut() = code;
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::task::{Context, Poll, Wake, Waker};
+
+    struct Noop;
+
+    impl Wake for Noop {
+        fn wake(self: Arc<Self>) {}
+    }
+
+    /// Polls the future until it's ready; every future here is ready
+    /// without having to wait on anything.
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let waker = Waker::from(Arc::new(Noop));
+        let mut cx = Context::from_waker(&waker);
+        let mut future = Box::pin(future);
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+        }
+    }
+
+    async fn show(_: Request<String>, params: Vec<String>) -> Result<Response<String>, Error> {
+        let id = params[0].parse::<u64>()?;
+        Ok(Response::new(id.to_string()))
+    }
+
+    #[test]
+    fn test_respond() {
+        let mut build = Router::build();
+        build
+            .async_fn(Method::GET, "/users/{}", show)
+            .post("/users", |req, _| Box::pin(async move { Ok(Response::new(req.into_body())) }))
+            .head_fallback()
+            .options_fallback();
+        let router = build.finish();
+        let call = |method: Method, path: &str| {
+            let request = Request::builder()
+                .method(method)
+                .uri(path)
+                .body("jane".to_string())
+                .unwrap();
+            block_on(router.respond(request))
+        };
+
+        assert_eq!(call(Method::GET, "/users/5").unwrap().body(), "5");
+        assert!(call(Method::GET, "/users/me").is_err());
+        assert_eq!(call(Method::POST, "/users").unwrap().body(), "jane");
+        assert!(call(Method::HEAD, "/users/5").unwrap().body().is_empty());
+
+        let response = call(Method::OPTIONS, "/users").unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert_eq!(response.headers()[ALLOW], "POST, OPTIONS");
+        assert_eq!(call(Method::GET, "/posts").unwrap().status(), StatusCode::NOT_FOUND);
+    }
+}
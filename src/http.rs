@@ -0,0 +1,485 @@
+use ::http::header::HOST;
+use ::http::{Method, Request, StatusCode, Version};
+use std::borrow::Cow;
+use std::sync::Arc;
+use super::router::{Allowed, HttpVersion, Params, RequestInfo};
+
+/// The handler that's stored as a part of every route in the router, for
+/// any framework that's built on the `http` crate's types.  It's given the
+/// request, with a body of type `B`, and returns an `R`, which is whatever
+/// the framework expects of a handler, e.g. a response, or a future of one.
+///
+/// The [`Route`] and [`Build`] types automatically wrap the closure in an
+/// `Arc` as a part of its shortcut methods, so that the same handler can be
+/// given to more than one route (with a `clone`), and the router can be
+/// cloned.  The handler has to be `Sync`, so that the router can be shared
+/// between threads (see [`SharedRouter`]).
+pub type Handler<B, R> = Arc<dyn Fn(Request<B>, &Params<'_>) -> R + Send + Sync + 'static>;
+
+/// A single route, tied to the `http` crate's types, and our [`Handler`].
+/// We add some shortcut methods onto this type in order to make building
+/// routes easier.
+pub type Route<B, R> = super::router::Route<Method, Handler<B, R>>;
+
+/// The router type, tied to the `http` crate's types, and our [`Handler`].
+/// Requests are given to it with [`Router::dispatch`].
+pub type Router<B, R> = super::router::Router<Method, Handler<B, R>>;
+
+/// A router whose routes can be changed after it's built, tied to the
+/// `http` crate's types, and our [`Handler`].
+pub type DynamicRouter<B, R> = super::router::DynamicRouter<Method, Handler<B, R>>;
+
+/// A router that can be replaced while it's serving requests, tied to the
+/// `http` crate's types, and our [`Handler`].
+pub type SharedRouter<B, R> = super::router::SharedRouter<Method, Handler<B, R>>;
+
+/// A builder for building routes, tied to the `http` crate's types and our
+/// [`Handler`].  We add some shortcut methods onto this type in order to
+/// make building routes easier.
+pub type Build<B, R> = super::router::Build<Method, Handler<B, R>>;
+
+/// A scope of a builder, tied to the `http` crate's types and our
+/// [`Handler`].  This has the same shortcut methods as [`Build`], which add
+/// the route under the scope's prefix.
+pub type Scope<'b, B, R> = super::router::Scope<'b, Method, Handler<B, R>>;
+
+/// The handlers that routes loaded from configuration can refer to, tied to
+/// our [`Handler`].  See [`Build::load_config`].
+pub type HandlerRegistry<B, R> = super::router::HandlerRegistry<Handler<B, R>>;
+
+macro_rules! route {
+    (
+        $(#$meta:tt)*
+        $name:ident => $method:expr
+    ) => {
+        $(#$meta)*
+        pub fn $name<P, F>(path: P, handler: F) -> Self
+        where
+            P: Into<Cow<'static, str>>,
+            F: Fn(Request<B>, &Params<'_>) -> R + Send + Sync + 'static
+        {
+            Self::new(path, $method, Arc::new(handler))
+        }
+    };
+}
+
+impl<B: 'static, R: 'static> Route<B, R> {
+    route!(options => Method::OPTIONS);
+    route!(get => Method::GET);
+    route!(post => Method::POST);
+    route!(put => Method::PUT);
+    route!(delete => Method::DELETE);
+    route!(head => Method::HEAD);
+    route!(trace => Method::TRACE);
+    route!(connect => Method::CONNECT);
+    route!(patch => Method::PATCH);
+
+    /// Creates a route that matches any method.
+    pub fn any<P, F>(path: P, handler: F) -> Self
+    where
+        P: Into<Cow<'static, str>>,
+        F: Fn(Request<B>, &Params<'_>) -> R + Send + Sync + 'static
+    {
+        Self::new_any(path, Arc::new(handler))
+    }
+}
+
+macro_rules! build {
+    (
+        $(#$meta:tt)*
+        $name:ident
+    ) => {
+        $(#$meta)*
+        pub fn $name<P, F>(&mut self, path: P, handler: F) -> &mut Self
+        where
+            P: Into<Cow<'static, str>>,
+            F: Fn(Request<B>, &Params<'_>) -> R + Send + Sync + 'static
+        {
+            self.add(Route::$name(path, handler))
+        }
+    }
+}
+
+impl<B: 'static, R: 'static> Build<B, R> {
+    build!(options);
+    build!(get);
+    build!(post);
+    build!(put);
+    build!(delete);
+    build!(head);
+    build!(trace);
+    build!(connect);
+    build!(patch);
+    build!(
+        /// Adds a route that matches any method.
+        any
+    );
+
+    /// Adds a `GET` route that only matches requests whose query has every
+    /// one of the given name and value pairs.  See [`Route::when_query`].
+    pub fn get_with_query<P, F>(&mut self, path: P, query: &[(&str, &str)], handler: F) -> &mut Self
+    where
+        P: Into<Cow<'static, str>>,
+        F: Fn(Request<B>, &Params<'_>) -> R + Send + Sync + 'static
+    {
+        self.add(Route::get(path, handler).when_query(query))
+    }
+
+    /// Adds a route that matches any of the given methods.  The handler is
+    /// only wrapped once, and shared between all of them.
+    pub fn methods<P, F>(&mut self, methods: &[Method], path: P, handler: F) -> &mut Self
+    where
+        P: Into<Cow<'static, str>>,
+        F: Fn(Request<B>, &Params<'_>) -> R + Send + Sync + 'static
+    {
+        self.add(Route::for_methods(path, methods.iter().cloned(), Arc::new(handler)))
+    }
+
+    /// Adds a `POST` route for every method of the given gRPC service, e.g.
+    /// `users.v1.UserService`, at the path that gRPC (and Connect) clients
+    /// call, `/users.v1.UserService/{method:grpc_method}`.  The name of the
+    /// method is the handler's only parameter, so that it can dispatch the
+    /// call itself; the body is left as-is, to be decoded by the handler.
+    pub fn grpc_service<F>(&mut self, service: &str, handler: F) -> &mut Self
+    where
+        F: Fn(Request<B>, &Params<'_>) -> R + Send + Sync + 'static
+    {
+        self.post(format!("/{}/{{method:grpc_method}}", service), handler)
+    }
+
+    /// Handles `HEAD` requests with the `GET` routes, if there's no `HEAD`
+    /// route for the path.  The hyper `Service` drops the body of the
+    /// response, but keeps its headers; with [`Router::dispatch`], that's
+    /// up to the caller.
+    pub fn head_fallback(&mut self) -> &mut Self {
+        self.fallback_method(Method::HEAD, Method::GET)
+    }
+
+    /// Answers `OPTIONS` requests automatically, for any path that has
+    /// routes, but no `OPTIONS` route.  The response is a 204 No Content,
+    /// with the methods allowed for the path in the `Allow` header.
+    pub fn options_fallback(&mut self) -> &mut Self {
+        self.auto_options(Method::OPTIONS)
+    }
+
+    pub fn default_fn<F>(&mut self, default: F) -> &mut Self
+        where F: Fn(Request<B>, &Params<'_>) -> R + Send + Sync + 'static
+    {
+        self.with_default(Arc::new(default))
+    }
+}
+
+impl<B: 'static, R: 'static> Scope<'_, B, R> {
+    build!(options);
+    build!(get);
+    build!(post);
+    build!(put);
+    build!(delete);
+    build!(head);
+    build!(trace);
+    build!(connect);
+    build!(patch);
+    build!(
+        /// Adds a route that matches any method.
+        any
+    );
+
+    /// Adds a route that matches any of the given methods.  See
+    /// [`Build::methods`].
+    pub fn methods<P, F>(&mut self, methods: &[Method], path: P, handler: F) -> &mut Self
+    where
+        P: Into<Cow<'static, str>>,
+        F: Fn(Request<B>, &Params<'_>) -> R + Send + Sync + 'static
+    {
+        self.add(Route::for_methods(path, methods.iter().cloned(), Arc::new(handler)))
+    }
+}
+
+impl<B: 'static, R: 'static> HandlerRegistry<B, R> {
+    /// Registers the given closure as the handler with the given name.
+    /// Every route that uses it shares the same closure.
+    pub fn handle<F>(&mut self, name: &str, handler: F) -> &mut Self
+    where
+        F: Fn(Request<B>, &Params<'_>) -> R + Send + Sync + 'static
+    {
+        self.register(name, Arc::new(handler) as Handler<B, R>)
+    }
+}
+
+#[derive(Debug)]
+/// What became of a request given to [`Router::dispatch`].
+pub enum Dispatch<R> {
+    /// A route (or the default) matched, and this is what its handler
+    /// returned.
+    Handled(R),
+    /// The request is an `OPTIONS` request that the router answers itself
+    /// (see [`Build::options_fallback`]), with a 204 No Content, and this
+    /// as the `Allow` header.
+    Options(String),
+    /// No route matched, but one would with the trailing slash of the path
+    /// removed (or one added), and the router redirects those (see
+    /// [`Build::redirect_trailing_slash`]); the request should be answered
+    /// with this status, and this as the `Location` header.
+    Redirect(StatusCode, String),
+    /// No handler was called, and the request should be answered with this
+    /// status: a 404 Not Found if nothing matched, a 415 Unsupported Media
+    /// Type if a route would have matched but for its content type, or a
+    /// 400 Bad Request if the path couldn't be decoded or normalized.
+    Status(StatusCode),
+}
+
+impl<B: 'static, R: 'static> Router<B, R> {
+    /// Routes the given request, and calls the handler that it matches.
+    /// This does everything the hyper `Service` does, short of making the
+    /// response, so that any framework built on the `http` crate can be
+    /// served by the router: the path is decoded and normalized, guards
+    /// and content types are checked against the request, the default is
+    /// used if nothing matches, and the parameters are decoded.
+    pub fn dispatch(&self, req: Request<B>) -> Dispatch<R> {
+        let path = match self.decode(req.uri().path()) {
+            Ok(path) => path,
+            Err(_) => return Dispatch::Status(StatusCode::BAD_REQUEST),
+        };
+        let path = match self.normalize(&path) {
+            Ok(path) => path,
+            Err(_) => return Dispatch::Status(StatusCode::BAD_REQUEST),
+        };
+        if let Some(allowed) = self.auto_options(req.method(), &path) {
+            return Dispatch::Options(allow(allowed));
+        }
+        let found = match self.lookup_route_with(req.method(), &path, &req) {
+            Some((route, params)) => {
+                self.hit(req.method(), &path, route);
+                Some((route.handler(), params))
+            }
+            None if self.rejects_content_type(req.method(), &path, &req) => {
+                return Dispatch::Status(StatusCode::UNSUPPORTED_MEDIA_TYPE);
+            }
+            None if self.redirects_trailing_slash(req.method(), &path, &req) => {
+                if let Some((status, location)) = redirect(self, &req, &path) {
+                    return Dispatch::Redirect(status, location);
+                }
+                self.miss(req.method(), &path)
+            }
+            None => self.miss(req.method(), &path),
+        };
+        match found {
+            Some((handler, params)) => {
+                let params = params
+                    .into_iter()
+                    .map(|param| self.decode_param(param))
+                    .collect::<Vec<_>>();
+                Dispatch::Handled(handler(req, &params))
+            }
+            None => Dispatch::Status(StatusCode::NOT_FOUND),
+        }
+    }
+}
+
+/// The status and `Location` of the redirect for a request whose path only
+/// matches with its trailing slash removed (or one added).  The location is
+/// built from the given path, which is the one that was looked up (see
+/// [`Router::redirect_location`]), and keeps the request's query.  `GET`
+/// and `HEAD` requests get a 301 Moved Permanently; anything else gets a
+/// 308 Permanent Redirect, so that the client repeats the method and body.
+pub(crate) fn redirect<H, B>(
+    router: &super::router::Router<Method, H>,
+    req: &Request<B>,
+    path: &str,
+) -> Option<(StatusCode, String)> {
+    let mut location = router.redirect_location(path)?;
+    if let Some(query) = req.uri().query() {
+        location.push('?');
+        location.push_str(query);
+    }
+    let status = if req.method() == Method::GET || req.method() == Method::HEAD {
+        StatusCode::MOVED_PERMANENTLY
+    } else {
+        StatusCode::PERMANENT_REDIRECT
+    };
+    Some((status, location))
+}
+
+/// The value of an `Allow` header for the given methods.
+pub(crate) fn allow(allowed: Allowed<'_, Method>) -> String {
+    match allowed {
+        Allowed::Any => "OPTIONS, GET, HEAD, POST, PUT, DELETE, TRACE, CONNECT, PATCH".to_string(),
+        Allowed::Only(methods) => methods
+            .iter()
+            .map(|method| method.as_str())
+            .collect::<Vec<_>>()
+            .join(", "),
+    }
+}
+
+impl<B> RequestInfo for Request<B> {
+    fn host(&self) -> Option<&str> {
+        self.uri().host().or_else(|| {
+            self.headers()
+                .get(HOST)
+                .and_then(|host| host.to_str().ok())
+        })
+    }
+
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+    }
+
+    fn query(&self) -> Option<&str> {
+        self.uri().query()
+    }
+
+    fn scheme(&self) -> Option<&str> {
+        self.uri().scheme_str()
+    }
+
+    fn version(&self) -> Option<HttpVersion> {
+        let version = match Request::version(self) {
+            Version::HTTP_09 => HttpVersion::Http09,
+            Version::HTTP_10 => HttpVersion::Http10,
+            Version::HTTP_11 => HttpVersion::Http11,
+            Version::HTTP_2 => HttpVersion::Http2,
+        };
+        Some(version)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Router = super::Router<String, String>;
+
+    #[test]
+    fn test_dispatch() {
+        let mut build = Router::build();
+        build
+            .get("/users/{}", |req, params| format!("{} {}", req.body(), params[0]))
+            .post("/users", |_, _| "created".to_string())
+            .options_fallback()
+            .redirect_trailing_slash();
+        let router = build.finish();
+        let request = |method: Method, path: &str| {
+            Request::builder()
+                .method(method)
+                .uri(path)
+                .body("body".to_string())
+                .unwrap()
+        };
+
+        match router.dispatch(request(Method::GET, "/users/jane%20doe")) {
+            Dispatch::Handled(response) => assert_eq!(response, "body jane doe"),
+            other => panic!("unexpected {:?}", other),
+        }
+        match router.dispatch(request(Method::OPTIONS, "/users")) {
+            Dispatch::Options(allow) => assert_eq!(allow, "POST, OPTIONS"),
+            other => panic!("unexpected {:?}", other),
+        }
+        match router.dispatch(request(Method::GET, "/posts")) {
+            Dispatch::Status(status) => assert_eq!(status, StatusCode::NOT_FOUND),
+            other => panic!("unexpected {:?}", other),
+        }
+        match router.dispatch(request(Method::GET, "/users/jane%20doe/?page=2")) {
+            Dispatch::Redirect(status, location) => {
+                assert_eq!(status, StatusCode::MOVED_PERMANENTLY);
+                assert_eq!(location, "/users/jane%20doe?page=2");
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        match router.dispatch(request(Method::POST, "/users/")) {
+            Dispatch::Redirect(status, location) => {
+                assert_eq!(status, StatusCode::PERMANENT_REDIRECT);
+                assert_eq!(location, "/users");
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_grpc_service() {
+        let mut build = Router::build();
+        build.grpc_service("users.v1.UserService", |_, params| params[0].to_string());
+        let router = build.finish();
+        let request = |method: Method, path: &str| {
+            Request::builder()
+                .method(method)
+                .uri(path)
+                .body(String::new())
+                .unwrap()
+        };
+
+        match router.dispatch(request(Method::POST, "/users.v1.UserService/GetUser")) {
+            Dispatch::Handled(method) => assert_eq!(method, "GetUser"),
+            other => panic!("unexpected {:?}", other),
+        }
+        match router.dispatch(request(Method::GET, "/users.v1.UserService/GetUser")) {
+            Dispatch::Status(status) => assert_eq!(status, StatusCode::NOT_FOUND),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[cfg(feature = "derive")]
+    #[test]
+    fn test_routes_macro() {
+        fn show(_: Request<String>, params: &Params<'_>) -> String {
+            format!("user {}", params[0])
+        }
+
+        let mut build = crate::routes! {
+            GET "/users/{id:uint}" => show,
+            POST "/users" => |req: Request<String>, _| req.into_body(),
+            ANY "/literal/{{x}}" => |_, _| "literal".to_string(),
+        };
+        build.options_fallback();
+        let router: Router = build.finish();
+        let request = |method: Method, path: &str| {
+            Request::builder()
+                .method(method)
+                .uri(path)
+                .body("jane".to_string())
+                .unwrap()
+        };
+
+        for (method, path, expected) in &[
+            (Method::GET, "/users/5", "user 5"),
+            (Method::POST, "/users", "jane"),
+            (Method::DELETE, "/literal/%7Bx%7D", "literal"),
+        ] {
+            match router.dispatch(request(method.clone(), path)) {
+                Dispatch::Handled(response) => assert_eq!(response, *expected),
+                other => panic!("unexpected {:?}", other),
+            }
+        }
+        match router.dispatch(request(Method::GET, "/users/jane")) {
+            Dispatch::Status(status) => assert_eq!(status, StatusCode::NOT_FOUND),
+            other => panic!("unexpected {:?}", other),
+        }
+
+        // Literal paths are found by the `match`, but only when no route
+        // before them could match.
+        let build: super::Build<(), &str> = crate::routes! {
+            GET "/users/{name}" => |_, _| "name",
+            GET "/users/me" => |_, _| "me",
+            POST "/users/me" => |_, _| "create me",
+            GET "/users/{id:uint}/posts" => |_, _| "posts",
+            GET "/health" => |_, _| "health",
+            ANY "/health" => |_, _| "any health",
+            GET "/users/5/posts" => |_, _| "shadowed posts",
+            GET "/users/5/comments" => |_, _| "comments",
+        };
+        let router = build.finish();
+        for (method, path, expected) in &[
+            (Method::GET, "/users/me", "name"),
+            (Method::POST, "/users/me", "create me"),
+            (Method::GET, "/health", "health"),
+            (Method::PUT, "/health", "any health"),
+            (Method::GET, "/users/5/posts", "posts"),
+            (Method::GET, "/users/5/comments", "comments"),
+        ] {
+            let handler = router.lookup(method, path).map(|(handler, _)| handler(Request::new(()), &[]));
+            assert_eq!(handler, Some(*expected), "{} {}", method, path);
+        }
+    }
+}
@@ -1,18 +1,68 @@
 use failure::{Compat, Error};
-use hyper::service::Service;
+use hyper::body::Payload;
+use hyper::service::{MakeService, Service};
+use hyper::header::{HeaderValue, ALLOW, LOCATION, ORIGIN};
 use hyper::{Body, Method, Request, Response, StatusCode};
 use std::borrow::Cow;
+use futures::future::FutureResult;
 use futures::prelude::*;
+use std::convert::Infallible;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+use super::http::{allow, redirect};
+use super::router::{Allowed, ParamError, Params, PathParams, Query, QueryError};
+
+mod access_log;
+mod compat;
+mod cors;
+mod hooks;
+mod limit;
+mod middleware;
+mod panic;
+mod proxy;
+pub mod reply;
+mod sse;
+mod state;
+mod status;
+mod timeout;
+
+use self::access_log::{AccessLog, Entry};
+use self::hooks::Hooks;
+use self::limit::BodyLimit;
+use self::middleware::Stack;
+use self::panic::CatchPanics;
+use self::state::States;
+use self::status::{ErrorHandler, MethodNotAllowed, NotFound};
+pub use self::status::StatusResponse;
+use self::timeout::Timeout;
+pub use self::access_log::LogFormat;
+pub use self::compat::async_handler;
+pub use self::cors::Cors;
+pub use self::middleware::Next;
+pub use self::panic::Panic;
+pub use self::proxy::{proxy_to, Proxy};
+pub use self::sse::{sse_handler, sse_response, Event};
+pub use self::state::State;
 
 type HandlerFuture = Box<dyn Future<Item = Response<Body>, Error = failure::Error> + Send + 'static>;
+type ResponseFuture = Box<dyn Future<Item = Response<Body>, Error = Compat<Error>> + Send + 'static>;
+
+/// Marks a router whose requests have their query parsed for the handler.
+/// See [`Build::parse_query`].
+#[derive(Debug)]
+struct ParseQuery;
 
 /// The handler that's stored as a part of every route in the router.  Since
-/// we're dealing with Hyper, it must return a future; we use the `Box<Fn>`
-/// type in order to keep flexibility.
+/// we're dealing with Hyper, it must return a future; we use the `Arc<Fn>`
+/// type in order to keep flexibility, and so that the same handler can be
+/// given to more than one route, and the router can be cloned.
 ///
-/// The [`Route`] and [`Build`] types automatically box the closure as a part
-/// of its shortcut methods.
-pub type Handler = Box<dyn Fn(Request<Body>, Vec<String>) -> HandlerFuture + Send + 'static>;
+/// The [`Route`] and [`Build`] types automatically wrap the closure as a
+/// part of its shortcut methods, which come from the [`crate::http`] module,
+/// since this is one of its handlers.  The handler has to be `Sync`, so
+/// that the router can be shared between threads (see [`SharedRouter`]).
+pub type Handler = super::http::Handler<Body, HandlerFuture>;
 
 /// A single route, tied to Hyper's types, and our [`Handler`].  We add some
 /// shortcut methods onto this type in order to make building routes for hyper
@@ -24,70 +74,350 @@ pub type Route = super::router::Route<Method, Handler>;
 /// is given, it returns an empty 404 response.
 pub type Router = super::router::Router<Method, Handler>;
 
+/// A router whose routes can be changed after it's built, tied to Hyper's
+/// types, and our [`Handler`].  Like [`Router`], this implements
+/// [`hyper::service::Service`].
+pub type DynamicRouter = super::router::DynamicRouter<Method, Handler>;
+
+/// A router that can be replaced while it's serving requests, tied to
+/// Hyper's types, and our [`Handler`].  This implements both
+/// [`hyper::service::Service`] and [`hyper::service::MakeService`], so it
+/// can be given to a server directly, and every connection shares the same
+/// router.
+pub type SharedRouter = super::router::SharedRouter<Method, Handler>;
+
 /// A builder for building routes, tied to Hyper's types and our [`Handler`].
 /// We add some shortcut methods onto this type in order to make building
 /// routes for hyper easier.
 pub type Build = super::router::Build<Method, Handler>;
 
-macro_rules! route {
-    (
-        $(#$meta:tt)*
-        $name:ident => $method:expr
-    ) => {
-        $(#$meta)*
-        pub fn $name<P, F>(path: P, handler: F) -> Self
-        where
-            P: Into<Cow<'static, str>>,
-            F: Fn(Request<Body>, Vec<String>) -> HandlerFuture + Send + 'static
-        {
-            Self::new(path, $method, Box::new(handler))
-        }
-    };
+/// A scope of a builder, tied to Hyper's types and our [`Handler`].  This
+/// has the same shortcut methods as [`Build`], which add the route under
+/// the scope's prefix.
+pub type Scope<'b> = super::router::Scope<'b, Method, Handler>;
+
+/// The handlers that routes loaded from configuration can refer to, tied to
+/// our [`Handler`].  See [`Build::load_config`].
+pub type HandlerRegistry = super::router::HandlerRegistry<Handler>;
+
+impl Route {
+    /// Creates a route whose handler is given its parameters as a
+    /// [`PathParams`] struct, taken from the parameters of the path by
+    /// their names.  If a parameter can't be converted, the request is
+    /// answered with a 404 Not Found.
+    ///
+    /// # Panics
+    ///
+    /// This panics if the path has no parameter for one of the fields of
+    /// the struct, or if the path can't be parsed (see [`Route::new`]).
+    pub fn typed<P, T, F>(path: P, method: Method, handler: F) -> Self
+    where
+        P: Into<Cow<'static, str>>,
+        T: PathParams + 'static,
+        F: Fn(Request<Body>, T) -> HandlerFuture + Send + Sync + 'static
+    {
+        Self::with_path_params::<_, T, _>(path, method, |indices| {
+            Arc::new(move |req, params| match T::from_params(&indices, params) {
+                Ok(params) => handler(req, params),
+                Err(error) => Box::new(futures::future::err(error.into())),
+            })
+        })
+    }
+
+    /// Creates a route whose handler is an `async` function (or a closure
+    /// that returns an `async` block).  See [`async_handler`].
+    pub fn async_fn<P, F, Fut, E>(path: P, method: Method, handler: F) -> Self
+    where
+        P: Into<Cow<'static, str>>,
+        F: Fn(Request<Body>, Vec<String>) -> Fut + Send + Sync + 'static,
+        Fut: std::future::Future<Output = Result<Response<Body>, E>> + Send + 'static,
+        E: Into<Error> + 'static
+    {
+        Self::new(path, method, async_handler(handler))
+    }
+
+    /// Creates a route whose handler is also given the router's state of
+    /// the given type (see [`Build::with_state`]).  If the router has no
+    /// state of that type, the handler isn't called, and the request fails
+    /// with an error.
+    pub fn stateful<P, S, F>(path: P, method: Method, handler: F) -> Self
+    where
+        P: Into<Cow<'static, str>>,
+        S: Send + Sync + 'static,
+        F: Fn(Request<Body>, &Params<'_>, &S) -> HandlerFuture + Send + Sync + 'static
+    {
+        Self::new(path, method, Arc::new(move |req, params| {
+            match State::<S>::from_request(&req) {
+                Some(state) => handler(req, params, &state),
+                None => Box::new(futures::future::err(format_err!(
+                    "the router has no state of type `{}`",
+                    std::any::type_name::<S>()
+                ))),
+            }
+        }))
+    }
 }
 
 impl Route {
-    route!(options => Method::OPTIONS);
-    route!(get => Method::GET);
-    route!(post => Method::POST);
-    route!(put => Method::PUT);
-    route!(delete => Method::DELETE);
-    route!(head => Method::HEAD);
-    route!(trace => Method::TRACE);
-    route!(connect => Method::CONNECT);
-    route!(patch => Method::PATCH);
-}
-
-macro_rules! build {
-    (
-        $(#$meta:tt)*
-        $name:ident
-    ) => {
-        $(#$meta)*
-        pub fn $name<P, F>(&mut self, path: P, handler: F) -> &mut Self
-        where
-            P: Into<Cow<'static, str>>,
-            F: Fn(Request<Body>, Vec<String>) -> HandlerFuture + Send + 'static
-        {
-            self.add(Route::$name(path, handler))
-        }
+    /// Gives the route its own CORS policy, instead of the router's (see
+    /// [`Build::cors`]).  Preflight requests for the route are answered
+    /// from the policy, and the responses to its actual cross-origin
+    /// requests get its headers.
+    pub fn with_cors(self, cors: Cors) -> Self {
+        self.with_extension(cors)
+    }
+
+    /// Gives the route's handler (and the middleware around it) the given
+    /// time to answer, after which the request is answered with a 504
+    /// Gateway Timeout, and the handler's future is dropped.  The deadline
+    /// is kept by the timer of the tokio runtime that's running the
+    /// service (as hyper's server is); without one, the request fails.
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        self.with_extension(Timeout(timeout))
+    }
+
+    /// Rejects requests to the route whose body is larger than the given
+    /// number of bytes with a 413 Payload Too Large, instead of the
+    /// router's limit (see [`Build::body_limit`]).
+    pub fn with_body_limit(self, bytes: u64) -> Self {
+        self.with_extension(BodyLimit(bytes))
+    }
+}
+
+impl Scope<'_> {
+    /// Gives every route added to the scope from here on its CORS policy,
+    /// unless the route has its own.  See [`Route::with_cors`].
+    pub fn with_cors(&mut self, cors: Cors) -> &mut Self {
+        self.with_extension(cors)
     }
 }
 
 impl Build {
-    build!(options);
-    build!(get);
-    build!(post);
-    build!(put);
-    build!(delete);
-    build!(head);
-    build!(trace);
-    build!(connect);
-    build!(patch);
-
-    pub fn default_fn<F>(&mut self, default: F) -> &mut Self
-        where F: Fn(Request<Body>, Vec<String>) -> HandlerFuture + Send + 'static
+    /// Adds a route whose handler is given its parameters as a
+    /// [`PathParams`] struct.  See [`Route::typed`].
+    pub fn typed<P, T, F>(&mut self, method: Method, path: P, handler: F) -> &mut Self
+    where
+        P: Into<Cow<'static, str>>,
+        T: PathParams + 'static,
+        F: Fn(Request<Body>, T) -> HandlerFuture + Send + Sync + 'static
     {
-        self.with_default(Box::new(default))
+        self.add(Route::typed(path, method, handler))
+    }
+
+    /// Adds a route whose handler is an `async` function.  See
+    /// [`async_handler`].
+    pub fn async_fn<P, F, Fut, E>(&mut self, method: Method, path: P, handler: F) -> &mut Self
+    where
+        P: Into<Cow<'static, str>>,
+        F: Fn(Request<Body>, Vec<String>) -> Fut + Send + Sync + 'static,
+        Fut: std::future::Future<Output = Result<Response<Body>, E>> + Send + 'static,
+        E: Into<Error> + 'static
+    {
+        self.add(Route::async_fn(path, method, handler))
+    }
+
+    /// Adds a route whose handler is also given the router's state of the
+    /// given type.  See [`Route::stateful`].
+    pub fn stateful<P, S, F>(&mut self, method: Method, path: P, handler: F) -> &mut Self
+    where
+        P: Into<Cow<'static, str>>,
+        S: Send + Sync + 'static,
+        F: Fn(Request<Body>, &Params<'_>, &S) -> HandlerFuture + Send + Sync + 'static
+    {
+        self.add(Route::stateful(path, method, handler))
+    }
+
+    /// Shares the given state (e.g. a database pool, or the application's
+    /// configuration) with every handler, so that it doesn't have to be
+    /// captured by each of them.  Handlers added with [`Build::stateful`]
+    /// are given it, and any handler can find it with
+    /// [`State::from_request`].  The router can hold one state of each type.
+    pub fn with_state<S: Send + Sync + 'static>(&mut self, state: S) -> &mut Self {
+        let mut states = self.extensions_mut().get::<States>().cloned().unwrap_or_default();
+        states.push(state);
+        self.extensions_mut().insert(states);
+        self
+    }
+
+    /// Logs every request the router handles, along with the route it
+    /// matched, and the status, length, and latency of its response.  Each
+    /// line is given to the sink (without a trailing newline) once the
+    /// response is ready, in the given format.  The length is only known
+    /// if the body of the response has a fixed size.
+    pub fn with_access_log<F>(&mut self, format: LogFormat, sink: F) -> &mut Self
+        where F: Fn(&str) + Send + Sync + 'static
+    {
+        self.extensions_mut().insert(AccessLog::new(format, sink));
+        self
+    }
+
+    /// Wraps the handlers of the router with the given middleware, e.g. for
+    /// authentication, or for adding headers to every response.  The
+    /// middleware is given the request, the parameters of the route that
+    /// matched, and the rest of the chain as a [`Next`]; it can pass the
+    /// request on with [`Next::run`] (and change the response it gets
+    /// back), or answer the request itself.
+    ///
+    /// Middleware wraps both the routes and the default handler, but not
+    /// the responses the router makes itself (e.g. a 404 with no default,
+    /// or an automatic `OPTIONS` response).  The middleware added first is
+    /// the outermost, and so is called first.
+    pub fn wrap<F>(&mut self, middleware: F) -> &mut Self
+        where F: Fn(Request<Body>, &Params<'_>, Next<'_>) -> HandlerFuture + Send + Sync + 'static
+    {
+        let mut stack = self.extensions_mut().get::<Stack>().cloned().unwrap_or_default();
+        stack.push(middleware);
+        self.extensions_mut().insert(stack);
+        self
+    }
+
+    /// Sets the CORS policy of every route that doesn't have its own (see
+    /// [`Route::with_cors`]), and of the default handler.  A preflight
+    /// request (an `OPTIONS` request with an `Origin` and an
+    /// `Access-Control-Request-Method`) is answered by the router for any
+    /// path that has a route for the requested method, with the methods the
+    /// path allows, unless the policy lists its own.  The responses to
+    /// actual requests with an `Origin` get the policy's headers, unless
+    /// the handler set them itself.
+    pub fn cors(&mut self, cors: Cors) -> &mut Self {
+        self.extensions_mut().insert(cors);
+        self
+    }
+
+    /// Rejects requests whose body is larger than the given number of bytes
+    /// with a 413 Payload Too Large, unless their route has its own limit
+    /// (see [`Route::with_body_limit`]).  A request that declares a larger
+    /// `Content-Length` is rejected before its handler runs; any other body
+    /// fails once it's read past the limit, and if the handler fails
+    /// because of it, the request is rejected all the same.
+    pub fn body_limit(&mut self, bytes: u64) -> &mut Self {
+        self.extensions_mut().insert(BodyLimit(bytes));
+        self
+    }
+
+    /// Parses the query string of every request that's given to a handler,
+    /// into a [`Query`] in the request's extensions, so that the handler
+    /// can read it with `req.extensions().get::<Query>()`.  If a handler
+    /// fails with a [`QueryError`] (from [`Query::parse`]), the request is
+    /// answered with a 400 Bad Request.
+    pub fn parse_query(&mut self) -> &mut Self {
+        self.extensions_mut().insert(ParseQuery);
+        self
+    }
+
+    /// Sets the handler for requests that match no routes, instead of the
+    /// default.  This includes requests whose path has routes, but not for
+    /// their method, unless there's a [`Build::method_not_allowed`]
+    /// handler.
+    pub fn not_found<F>(&mut self, handler: F) -> &mut Self
+        where F: Fn(Request<Body>, &Params<'_>) -> HandlerFuture + Send + Sync + 'static
+    {
+        self.extensions_mut().insert(NotFound(Arc::new(handler)));
+        self
+    }
+
+    /// Sets the handler for requests whose path has routes, but none for
+    /// their method, instead of the not-found handler (or the default).
+    /// The response gets an `Allow` header with the methods the path
+    /// does allow, unless the handler sets one itself.
+    pub fn method_not_allowed<F>(&mut self, handler: F) -> &mut Self
+        where F: Fn(Request<Body>, &Params<'_>) -> HandlerFuture + Send + Sync + 'static
+    {
+        self.extensions_mut().insert(MethodNotAllowed(Arc::new(handler)));
+        self
+    }
+
+    /// Answers requests that match no routes with the given response,
+    /// e.g. a JSON error document, instead of an empty 404.  This is a
+    /// not-found handler (see [`Build::not_found`]) that always gives the
+    /// same response, and so replaces any other.
+    pub fn not_found_response(&mut self, response: StatusResponse) -> &mut Self {
+        self.extensions_mut().insert(NotFound(response.into_handler()));
+        self
+    }
+
+    /// Answers requests whose path has routes, but none for their method,
+    /// with the given response, along with an `Allow` header.  This is a
+    /// method-not-allowed handler (see [`Build::method_not_allowed`]) that
+    /// always gives the same response, and so replaces any other.
+    pub fn method_not_allowed_response(&mut self, response: StatusResponse) -> &mut Self {
+        self.extensions_mut().insert(MethodNotAllowed(response.into_handler()));
+        self
+    }
+
+    /// Sets the handler for errors returned by the other handlers (and by
+    /// middleware), which makes the response for them, e.g. a 500 Internal
+    /// Server Error.  Without one, the error is returned to hyper, which
+    /// drops the connection.
+    pub fn error_handler<F>(&mut self, handler: F) -> &mut Self
+        where F: Fn(Error) -> Response<Body> + Send + Sync + 'static
+    {
+        self.extensions_mut().insert(ErrorHandler(Arc::new(handler)));
+        self
+    }
+
+    /// Catches panics in the handlers (and middleware), whether they
+    /// happen when the handler is called, or while its future is polled,
+    /// so that a panicking handler doesn't take its connection down with
+    /// it.  The panic becomes a [`Panic`] error, which is given to the
+    /// error handler (see [`Build::error_handler`]), or answered with a
+    /// 500 Internal Server Error if there isn't one.
+    pub fn catch_panics(&mut self) -> &mut Self {
+        self.extensions_mut().insert(CatchPanics);
+        self
+    }
+
+    /// Adds a hook that's called with every request the router handles,
+    /// before it's routed, e.g. to stamp it with a request id.  Hooks are
+    /// called in the order they were added.
+    pub fn before<F>(&mut self, hook: F) -> &mut Self
+        where F: Fn(&mut Request<Body>) + Send + Sync + 'static
+    {
+        let mut hooks = self.extensions_mut().get::<Hooks>().cloned().unwrap_or_default();
+        hooks.push_before(hook);
+        self.extensions_mut().insert(hooks);
+        self
+    }
+
+    /// Adds a hook that's called with every response the router makes,
+    /// including the ones it makes itself (e.g. a 404 with no default),
+    /// e.g. to add security headers.  Hooks are called in the order they
+    /// were added.
+    pub fn after<F>(&mut self, hook: F) -> &mut Self
+        where F: Fn(&mut Response<Body>) + Send + Sync + 'static
+    {
+        let mut hooks = self.extensions_mut().get::<Hooks>().cloned().unwrap_or_default();
+        hooks.push_after(hook);
+        self.extensions_mut().insert(hooks);
+        self
+    }
+}
+
+impl Router {
+    /// Adds a hook that's called with every request before it's routed.
+    /// This is similar to [`Build::before`].
+    pub fn before<F>(&mut self, hook: F)
+        where F: Fn(&mut Request<Body>) + Send + Sync + 'static
+    {
+        let mut hooks = self.extensions_mut().get::<Hooks>().cloned().unwrap_or_default();
+        hooks.push_before(hook);
+        self.extensions_mut().insert(hooks);
+    }
+
+    /// Adds a hook that's called with every response.  This is similar to
+    /// [`Build::after`].
+    pub fn after<F>(&mut self, hook: F)
+        where F: Fn(&mut Response<Body>) + Send + Sync + 'static
+    {
+        let mut hooks = self.extensions_mut().get::<Hooks>().cloned().unwrap_or_default();
+        hooks.push_after(hook);
+        self.extensions_mut().insert(hooks);
+    }
+
+    /// Shares the router between every connection of a hyper server.  See
+    /// [`RouterMakeService`].
+    pub fn into_make_service(self) -> RouterMakeService {
+        RouterMakeService::new(self)
     }
 }
 
@@ -95,20 +425,440 @@ impl Service for Router {
     type ReqBody = Body;
     type ResBody = Body;
     type Error = Compat<Error>;
-    type Future = Box<dyn Future<Item = Response<Body>, Error = Compat<Error>> + Send + 'static>;
+    type Future = ResponseFuture;
+
+    fn call(&mut self, req: Request<Self::ReqBody>) -> Self::Future {
+        respond(self, req)
+    }
+}
+
+impl Service for SharedRouter {
+    type ReqBody = Body;
+    type ResBody = Body;
+    type Error = Compat<Error>;
+    type Future = <Router as Service>::Future;
 
     fn call(&mut self, req: Request<Self::ReqBody>) -> Self::Future {
-        let path = req.uri().path();
-        if let Some((handler, params)) = self.lookup(req.method(), path) {
-            let params = params.into_iter().map(str::to_string).collect();
-            Box::new(handler(req, params).map_err(Error::compat))
+        respond(&self.load(), req)
+    }
+}
+
+impl<Ctx> MakeService<Ctx> for SharedRouter {
+    type ReqBody = Body;
+    type ResBody = Body;
+    type Error = Compat<Error>;
+    type Service = SharedRouter;
+    type Future = FutureResult<SharedRouter, Infallible>;
+    type MakeError = Infallible;
+
+    fn make_service(&mut self, _: Ctx) -> Self::Future {
+        futures::future::ok(self.clone())
+    }
+}
+
+/// A router that's built once, and shared by every connection of a hyper
+/// server, e.g. `Server::bind(&address).serve(router.into_make_service())`.
+/// Making a service for a connection only clones an `Arc`, so the routes
+/// (and their patterns) aren't rebuilt, unlike making a new router with
+/// `make_service_fn` for each connection.
+///
+/// Unlike [`SharedRouter`], the router can't be replaced once it's being
+/// served; in exchange, there's no lock around it.
+#[derive(Clone)]
+pub struct RouterMakeService(Arc<Router>);
+
+impl RouterMakeService {
+    /// Creates the service from the given router.
+    pub fn new(router: Router) -> Self {
+        RouterMakeService(Arc::new(router))
+    }
+
+    /// The router that's being served.
+    pub fn router(&self) -> &Router {
+        &self.0
+    }
+}
+
+impl From<Router> for RouterMakeService {
+    fn from(router: Router) -> Self {
+        RouterMakeService::new(router)
+    }
+}
+
+impl Service for RouterMakeService {
+    type ReqBody = Body;
+    type ResBody = Body;
+    type Error = Compat<Error>;
+    type Future = <Router as Service>::Future;
+
+    fn call(&mut self, req: Request<Self::ReqBody>) -> Self::Future {
+        respond(&self.0, req)
+    }
+}
+
+impl<Ctx> MakeService<Ctx> for RouterMakeService {
+    type ReqBody = Body;
+    type ResBody = Body;
+    type Error = Compat<Error>;
+    type Service = RouterMakeService;
+    type Future = FutureResult<RouterMakeService, Infallible>;
+    type MakeError = Infallible;
+
+    fn make_service(&mut self, _: Ctx) -> Self::Future {
+        futures::future::ok(self.clone())
+    }
+}
+
+impl Service for DynamicRouter {
+    type ReqBody = Body;
+    type ResBody = Body;
+    type Error = Compat<Error>;
+    type Future = <Router as Service>::Future;
+
+    fn call(&mut self, req: Request<Self::ReqBody>) -> Self::Future {
+        respond(self, req)
+    }
+}
+
+/// Routes the given request with the given router, and calls the handler
+/// that it matches.
+pub(crate) fn respond(
+    router: &Router,
+    mut req: Request<Body>,
+) -> ResponseFuture {
+    let hooks = router.extensions().get::<Hooks>();
+    if let Some(hooks) = hooks {
+        hooks.before(&mut req);
+    }
+    let time = SystemTime::now();
+    let start = Instant::now();
+    let method = req.method().clone();
+    let target = req
+        .uri()
+        .path_and_query()
+        .map_or_else(|| req.uri().path().to_string(), |target| target.as_str().to_string());
+    let version = req.version();
+    let (route, response) = dispatch(router, req);
+    let response: ResponseFuture = match hooks {
+        Some(hooks) if hooks.has_after() => {
+            let hooks = hooks.clone();
+            Box::new(response.map(move |mut response| {
+                hooks.after(&mut response);
+                response
+            }))
+        }
+        _ => response,
+    };
+    let log = match router.extensions().get::<AccessLog>() {
+        Some(log) => log.clone(),
+        None => return response,
+    };
+    Box::new(response.then(move |result| {
+        let (status, bytes) = match &result {
+            Ok(response) => (response.status(), response.body().content_length()),
+            Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, None),
+        };
+        log.write(&Entry {
+            time,
+            method,
+            target,
+            version,
+            route,
+            status,
+            bytes,
+            latency: start.elapsed(),
+        });
+        result
+    }))
+}
+
+/// Does the work of [`respond`], also returning the path of the route that
+/// matched, if one did, for the access log.
+fn dispatch(
+    router: &Router,
+    mut req: Request<Body>,
+) -> (Option<String>, ResponseFuture) {
+    let path = match router.decode(req.uri().path()) {
+        Ok(path) => path,
+        Err(_) => return (None, empty_response(StatusCode::BAD_REQUEST)),
+    };
+    let path = match router.normalize(&path) {
+        Ok(path) => path,
+        Err(_) => return (None, empty_response(StatusCode::BAD_REQUEST)),
+    };
+    if let Some(response) = cors::preflight(router, &req, &path) {
+        return (None, Box::new(futures::future::ok(response)));
+    }
+    if let Some(allowed) = router.auto_options(req.method(), &path) {
+        return (None, options_response(allowed));
+    }
+    let mut matched = None;
+    let mut policy = None;
+    let mut deadline = None;
+    let mut limit = None;
+    let found = router
+        .lookup_route_with(req.method(), &path, &req)
+        .map(|(route, params)| {
+            router.hit(req.method(), &path, route);
+            matched = Some(route.path().to_string());
+            policy = route.extension::<Cors>();
+            deadline = route.extension::<Timeout>().copied();
+            limit = route.extension::<BodyLimit>().copied();
+            (route.handler(), params)
+        });
+    let mut allow = None;
+    let found = match found {
+        Some(found) => Some(found),
+        None if router.rejects_content_type(req.method(), &path, &req) => {
+            return (None, empty_response(StatusCode::UNSUPPORTED_MEDIA_TYPE));
+        }
+        None if router.redirects_trailing_slash(req.method(), &path, &req) => {
+            if let Some((status, location)) = redirect(router, &req, &path) {
+                return (None, redirect_response(status, &location));
+            }
+            router.miss(req.method(), &path)
+        }
+        None => match unmatched(router, req.method(), &path) {
+            Some((handler, allowed)) => {
+                allow = allowed;
+                Some((handler, vec![]))
+            }
+            None => router.miss(req.method(), &path),
+        },
+    };
+    if let Some((handler, params)) = found {
+        let params = params
+            .into_iter()
+            .map(|param| router.decode_param(param))
+            .collect::<Vec<_>>();
+        let head = req.method() == Method::HEAD;
+        let cors = req
+            .headers()
+            .get(ORIGIN)
+            .cloned()
+            .and_then(|origin| Some((policy.or_else(|| router.extensions().get())?.clone(), origin)));
+        let limit = limit.or_else(|| router.extensions().get().copied());
+        if limit.is_some_and(|limit| limit.rejects(&req)) {
+            return (matched, empty_response(StatusCode::PAYLOAD_TOO_LARGE));
+        }
+        let exceeded = limit.map(|limit| limit.apply(&mut req));
+        if let Some(states) = router.extensions().get::<States>() {
+            states.inject(&mut req);
+        }
+        if router.extensions().contains::<ParseQuery>() {
+            let query = Query::from_request(&req);
+            req.extensions_mut().insert(query);
+        }
+        let call = || match router.extensions().get::<Stack>() {
+            Some(stack) => stack.run(handler, req, &params),
+            None => handler(req, &params),
+        };
+        let response = if router.extensions().contains::<CatchPanics>() {
+            panic::catch(call)
         } else {
-            let response = Response::builder()
-                .status(StatusCode::NOT_FOUND)
+            call()
+        };
+        let response = match deadline {
+            Some(deadline) => timeout::race(response, deadline),
+            None => response,
+        };
+        let response = response.map(move |mut response| {
+            if let Some((cors, origin)) = cors {
+                cors.apply(&origin, &mut response);
+            }
+            if let Some(allow) = allow {
+                if !response.headers().contains_key(ALLOW) {
+                    response.headers_mut().insert(ALLOW, allow);
+                }
+            }
+            // A response to a `HEAD` request must not have a body; this
+            // matters when the request was handled by a `GET` route.
+            if head {
+                response.map(|_| Body::empty())
+            } else {
+                response
+            }
+        });
+        // A parameter that couldn't be converted means the path doesn't
+        // name anything, just as if the route hadn't matched; a query
+        // value that couldn't be is the client's mistake, as is a body
+        // that's too large.  A panic is left to the error handler, if
+        // there is one.
+        let has_error_handler = router.extensions().contains::<ErrorHandler>();
+        let response = response.or_else(move |error| {
+            let status = match error.downcast_ref::<ParamError>() {
+                Some(ParamError::Invalid { .. }) => StatusCode::NOT_FOUND,
+                _ if error.downcast_ref::<QueryError>().is_some() => StatusCode::BAD_REQUEST,
+                _ if exceeded.is_some_and(|exceeded| exceeded.load(Ordering::SeqCst)) => {
+                    StatusCode::PAYLOAD_TOO_LARGE
+                }
+                _ if !has_error_handler && error.downcast_ref::<Panic>().is_some() => {
+                    StatusCode::INTERNAL_SERVER_ERROR
+                }
+                _ => return Err(error),
+            };
+            Response::builder()
+                .status(status)
+                .body(Body::empty())
+                .map_err(Error::from)
+        });
+        let response: HandlerFuture = match router.extensions().get::<ErrorHandler>() {
+            Some(ErrorHandler(handler)) => {
+                let handler = handler.clone();
+                Box::new(response.or_else(move |error| Ok(handler(error))))
+            }
+            None => Box::new(response),
+        };
+        (matched, Box::new(response.map_err(Error::compat)))
+    } else {
+        (None, empty_response(StatusCode::NOT_FOUND))
+    }
+}
+
+/// The handler for a request that matched no routes, if it's answered by
+/// the not-found or method-not-allowed handler rather than the default,
+/// along with the `Allow` header for the latter.
+fn unmatched<'r>(router: &'r Router, method: &Method, path: &str) -> Option<(&'r Handler, Option<HeaderValue>)> {
+    if let Some(MethodNotAllowed(handler)) = router.extensions().get() {
+        if let Some(allowed @ Allowed::Only(_)) = router.allowed_methods(path) {
+            router.no_match(method, path);
+            return Some((handler, HeaderValue::from_str(&allow(allowed)).ok()));
+        }
+    }
+    let NotFound(handler) = router.extensions().get()?;
+    router.no_match(method, path);
+    Some((handler, None))
+}
+
+fn options_response(
+    allowed: Allowed<'_, Method>,
+) -> ResponseFuture {
+    let response = HeaderValue::from_str(&allow(allowed))
+        .map_err(Error::from)
+        .and_then(|allowed| {
+            Response::builder()
+                .status(StatusCode::NO_CONTENT)
+                .header(ALLOW, allowed)
                 .body(Body::empty())
                 .map_err(Error::from)
-                .map_err(Error::compat);
-            Box::new(futures::future::result(response))
+        })
+        .map_err(Error::compat);
+    Box::new(futures::future::result(response))
+}
+
+fn redirect_response(
+    status: StatusCode,
+    location: &str,
+) -> ResponseFuture {
+    let response = Response::builder()
+        .status(status)
+        .header(LOCATION, location)
+        .body(Body::empty())
+        .map_err(Error::from)
+        .map_err(Error::compat);
+    Box::new(futures::future::result(response))
+}
+
+fn empty_response(
+    status: StatusCode,
+) -> ResponseFuture {
+    let response = Response::builder()
+        .status(status)
+        .body(Body::empty())
+        .map_err(Error::from)
+        .map_err(Error::compat);
+    Box::new(futures::future::result(response))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_make_service() {
+        let mut build = Router::build();
+        build.get("/", |_, _| Box::new(futures::future::ok(Response::new(Body::from("home")))));
+        let mut make = build.finish().into_make_service();
+
+        let mut first = make.make_service(()).wait().unwrap();
+        let second = make.make_service(()).wait().unwrap();
+        assert!(Arc::ptr_eq(&first.0, &second.0));
+        let response = first
+            .call(Request::get("/").body(Body::empty()).unwrap())
+            .wait()
+            .unwrap();
+        assert_eq!(&*response.into_body().concat2().wait().unwrap(), b"home");
+    }
+
+    #[test]
+    fn test_shared_handler() {
+        let handler: Handler = Arc::new(|_, params| {
+            Box::new(futures::future::ok(Response::new(Body::from(params.join(",")))))
+        });
+        let mut build = Router::build();
+        build
+            .add(Route::new("/users/{}", Method::GET, handler.clone()))
+            .add(Route::new("/people/{}", Method::GET, handler));
+        let router = build.finish();
+        let mut clone = router.clone();
+
+        for path in &["/users/5", "/people/5"] {
+            let response = clone
+                .call(Request::get(*path).body(Body::empty()).unwrap())
+                .wait()
+                .unwrap();
+            assert_eq!(&*response.into_body().concat2().wait().unwrap(), b"5");
+        }
+        let (first, _) = router.lookup(&Method::GET, "/users/5").unwrap();
+        let (second, _) = clone.lookup(&Method::GET, "/people/5").unwrap();
+        assert!(Arc::ptr_eq(first, second));
+    }
+
+    #[test]
+    fn test_redirect_trailing_slash() {
+        let mut build = Router::build();
+        build
+            .get("/users", |_, _| Box::new(futures::future::ok(Response::new(Body::empty()))))
+            .default_fn(|_, _| {
+                let mut response = Response::new(Body::empty());
+                *response.status_mut() = StatusCode::IM_A_TEAPOT;
+                Box::new(futures::future::ok(response))
+            })
+            .redirect_trailing_slash();
+        let mut router = build.finish();
+
+        let response = router
+            .call(Request::get("/users/?page=2").body(Body::empty()).unwrap())
+            .wait()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::MOVED_PERMANENTLY);
+        assert_eq!(response.headers()[LOCATION], "/users?page=2");
+
+        let response = router
+            .call(Request::get("/posts/").body(Body::empty()).unwrap())
+            .wait()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::IM_A_TEAPOT);
+
+        // The location is built from the path that was matched, so a path
+        // that collapses into a route can't redirect to another host.
+        let mut build = Router::build();
+        build
+            .get("/{slug}", |_, _| Box::new(futures::future::ok(Response::new(Body::empty()))))
+            .collapse_slashes()
+            .redirect_trailing_slash();
+        let mut router = build.finish();
+        let paths = [
+            ("//evil.com/", "/evil.com"),
+            ("/caf%C3%A9%20au%20lait/", "/caf%C3%A9%20au%20lait"),
+        ];
+        for (path, location) in &paths {
+            let response = router
+                .call(Request::get(*path).body(Body::empty()).unwrap())
+                .wait()
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::MOVED_PERMANENTLY);
+            assert_eq!(response.headers()[LOCATION], *location);
         }
     }
 }
@@ -0,0 +1,215 @@
+use hyper::{Method, StatusCode, Version};
+use std::fmt::{Debug, Formatter, Result as FmtResult, Write};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// The format of the lines of an access log.  See
+/// [`super::Build::with_access_log`].
+pub enum LogFormat {
+    /// The Common Log Format, with the matched route and the latency added
+    /// to the end, e.g.
+    /// `- - - [16/Oct/2026:12:00:00 +0000] "GET /users/5 HTTP/1.1" 200 27 "/users/{:uint}" 0.412ms`.
+    /// The remote host isn't known to the router, so it's always `-`; so
+    /// is the route, if the request didn't match one.
+    Common,
+    /// A JSON object on each line, e.g.
+    /// `{"time":"2026-10-16T12:00:00Z","method":"GET","target":"/users/5","version":"HTTP/1.1","route":"/users/{:uint}","status":200,"bytes":27,"latency_ms":0.412}`.
+    /// The route and the bytes are `null` if they aren't known.
+    Json,
+}
+
+type Sink = dyn Fn(&str) + Send + Sync + 'static;
+
+/// The access log of a router, kept in its extensions.
+#[derive(Clone)]
+pub(super) struct AccessLog {
+    format: LogFormat,
+    sink: Arc<Sink>,
+}
+
+impl AccessLog {
+    pub(super) fn new<F>(format: LogFormat, sink: F) -> Self
+    where
+        F: Fn(&str) + Send + Sync + 'static,
+    {
+        AccessLog {
+            format,
+            sink: Arc::new(sink),
+        }
+    }
+
+    pub(super) fn write(&self, entry: &Entry) {
+        let line = match self.format {
+            LogFormat::Common => entry.common(),
+            LogFormat::Json => entry.json(),
+        };
+        (self.sink)(&line)
+    }
+}
+
+impl Debug for AccessLog {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_tuple("AccessLog").field(&self.format).finish()
+    }
+}
+
+/// A request that was handled, and its response.
+#[derive(Debug)]
+pub(super) struct Entry {
+    pub(super) time: SystemTime,
+    pub(super) method: Method,
+    /// The path and query of the request.
+    pub(super) target: String,
+    pub(super) version: Version,
+    /// The path of the route that matched, if one did.
+    pub(super) route: Option<String>,
+    pub(super) status: StatusCode,
+    /// The length of the body of the response, if it's known up front.
+    pub(super) bytes: Option<u64>,
+    /// The time taken until the response was ready to be sent.
+    pub(super) latency: Duration,
+}
+
+impl Entry {
+    fn common(&self) -> String {
+        let (year, month, day, hour, minute, second) = civil(self.time);
+        let mut line = format!(
+            "- - - [{:02}/{}/{}:{:02}:{:02}:{:02} +0000] \"{} {} {:?}\" {} ",
+            day,
+            MONTHS[month - 1],
+            year,
+            hour,
+            minute,
+            second,
+            self.method,
+            self.target,
+            self.version,
+            self.status.as_u16()
+        );
+        match self.bytes {
+            Some(bytes) => write!(line, "{}", bytes).unwrap(),
+            None => line.push('-'),
+        }
+        match &self.route {
+            Some(route) => write!(line, " \"{}\"", route).unwrap(),
+            None => line.push_str(" -"),
+        }
+        write!(line, " {:.3}ms", millis(self.latency)).unwrap();
+        line
+    }
+
+    fn json(&self) -> String {
+        let (year, month, day, hour, minute, second) = civil(self.time);
+        let mut line = format!(
+            "{{\"time\":\"{}-{:02}-{:02}T{:02}:{:02}:{:02}Z\",\"method\":{},\"target\":{},\"version\":\"{:?}\",\"route\":",
+            year,
+            month,
+            day,
+            hour,
+            minute,
+            second,
+            string(self.method.as_str()),
+            string(&self.target),
+            self.version
+        );
+        match &self.route {
+            Some(route) => line.push_str(&string(route)),
+            None => line.push_str("null"),
+        }
+        write!(line, ",\"status\":{},\"bytes\":", self.status.as_u16()).unwrap();
+        match self.bytes {
+            Some(bytes) => write!(line, "{}", bytes).unwrap(),
+            None => line.push_str("null"),
+        }
+        write!(line, ",\"latency_ms\":{:.3}}}", millis(self.latency)).unwrap();
+        line
+    }
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+/// Splits the given time into its year, month, day, hour, minute, and
+/// second, in UTC.
+fn civil(time: SystemTime) -> (i64, usize, u64, u64, u64, u64) {
+    let secs = time.duration_since(UNIX_EPOCH).map_or(0, |since| since.as_secs());
+    let (days, secs) = (secs / 86_400, secs % 86_400);
+    // Howard Hinnant's `civil_from_days`, with eras of 400 years starting
+    // on the 1st of March.
+    let z = days as i64 + 719_468;
+    let era = z / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u64;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as usize;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day, secs / 3_600, secs % 3_600 / 60, secs % 60)
+}
+
+/// Encodes the given text as a JSON string.
+fn string(text: &str) -> String {
+    let mut json = String::with_capacity(text.len() + 2);
+    json.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            c if (c as u32) < 0x20 => write!(json, "\\u{:04x}", c as u32).unwrap(),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(route: Option<&str>, bytes: Option<u64>) -> Entry {
+        Entry {
+            time: UNIX_EPOCH + Duration::from_secs(1_792_152_000),
+            method: Method::GET,
+            target: "/users/5?q=\"x\"".to_string(),
+            version: Version::HTTP_11,
+            route: route.map(str::to_string),
+            status: StatusCode::OK,
+            bytes,
+            latency: Duration::from_micros(412),
+        }
+    }
+
+    #[test]
+    fn test_formats() {
+        assert_eq!(
+            entry(Some("/users/{:uint}"), Some(27)).common(),
+            "- - - [16/Oct/2026:12:00:00 +0000] \"GET /users/5?q=\"x\" HTTP/1.1\" 200 27 \"/users/{:uint}\" 0.412ms"
+        );
+        assert_eq!(
+            entry(None, None).common(),
+            "- - - [16/Oct/2026:12:00:00 +0000] \"GET /users/5?q=\"x\" HTTP/1.1\" 200 - - 0.412ms"
+        );
+        assert_eq!(
+            entry(Some("/users/{:uint}"), None).json(),
+            concat!(
+                r#"{"time":"2026-10-16T12:00:00Z","method":"GET","target":"/users/5?q=\"x\"","#,
+                r#""version":"HTTP/1.1","route":"/users/{:uint}","status":200,"bytes":null,"latency_ms":0.412}"#
+            )
+        );
+    }
+
+    #[test]
+    fn test_civil() {
+        assert_eq!(civil(UNIX_EPOCH), (1970, 1, 1, 0, 0, 0));
+        assert_eq!(civil(UNIX_EPOCH + Duration::from_secs(951_782_400)), (2000, 2, 29, 0, 0, 0));
+        assert_eq!(civil(UNIX_EPOCH + Duration::from_secs(4_102_444_799)), (2099, 12, 31, 23, 59, 59));
+    }
+}
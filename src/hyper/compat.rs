@@ -0,0 +1,118 @@
+//! Runs `std::future`s (e.g. from `async` blocks) as the `futures` 0.1
+//! futures that hyper expects, since the two can't be mixed directly.
+
+use super::{Handler, HandlerFuture};
+use futures::{Async, Poll};
+use hyper::{Body, Request, Response};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll as StdPoll, Wake, Waker};
+
+/// Wakes the `futures` 0.1 task that's polling the `std::future`.
+struct TaskWaker(futures::task::Task);
+
+impl Wake for TaskWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.notify()
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.0.notify()
+    }
+}
+
+/// A `std::future` that resolves to a result, as a `futures` 0.1 future.
+struct Compat<F>(Pin<Box<F>>);
+
+impl<F, E> futures::Future for Compat<F>
+where
+    F: Future<Output = Result<Response<Body>, E>>,
+    E: Into<failure::Error>,
+{
+    type Item = Response<Body>;
+    type Error = failure::Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        let waker = Waker::from(Arc::new(TaskWaker(futures::task::current())));
+        let mut cx = Context::from_waker(&waker);
+        match self.0.as_mut().poll(&mut cx) {
+            StdPoll::Ready(Ok(response)) => Ok(Async::Ready(response)),
+            StdPoll::Ready(Err(error)) => Err(error.into()),
+            StdPoll::Pending => Ok(Async::NotReady),
+        }
+    }
+}
+
+/// Makes a [`Handler`] from an `async` function (or a closure that returns
+/// an `async` block), so that it can be written with `.await`, instead of
+/// with combinators, e.g.
+/// `Route::new("/users/{}", Method::GET, async_handler(show_user))` for
+/// `async fn show_user(req: Request<Body>, params: Vec<String>) -> Result<Response<Body>, Error>`.
+/// The error can be anything that converts into a [`failure::Error`].  The
+/// future can't borrow the parameters, so they're given to it as owned
+/// strings.
+pub fn async_handler<F, Fut, E>(handler: F) -> Handler
+where
+    F: Fn(Request<Body>, Vec<String>) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<Response<Body>, E>> + Send + 'static,
+    E: Into<failure::Error> + 'static,
+{
+    Arc::new(move |req, params| -> HandlerFuture {
+        let params = params.iter().map(|param| param.to_string()).collect();
+        Box::new(Compat(Box::pin(handler(req, params))))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::Router;
+    use futures::prelude::*;
+    use hyper::service::Service;
+    use hyper::{Body, Method, Request, Response, StatusCode};
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+
+    /// Isn't ready the first time it's polled, so that the task has to be
+    /// woken up.
+    struct Yield(bool);
+
+    impl std::future::Future for Yield {
+        type Output = ();
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+            if self.0 {
+                Poll::Ready(())
+            } else {
+                self.0 = true;
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+        }
+    }
+
+    async fn show(_: Request<Body>, params: Vec<String>) -> Result<Response<Body>, failure::Error> {
+        Yield(false).await;
+        let id = params[0].parse::<u64>()?;
+        Ok(Response::new(Body::from(id.to_string())))
+    }
+
+    #[test]
+    fn test_async_handler() {
+        let mut build = Router::build();
+        build
+            .async_fn(Method::GET, "/users/{}", show)
+            .async_fn(Method::GET, "/", |_, _| async {
+                Ok::<_, failure::Error>(Response::new(Body::from("home")))
+            });
+        let mut router = build.finish();
+        let mut call = |path: &str| router.call(Request::get(path).body(Body::empty()).unwrap()).wait();
+
+        let response = call("/users/5").unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(&*response.into_body().concat2().wait().unwrap(), b"5");
+        let response = call("/").unwrap();
+        assert_eq!(&*response.into_body().concat2().wait().unwrap(), b"home");
+        assert!(call("/users/me").is_err());
+    }
+}
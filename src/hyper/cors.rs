@@ -0,0 +1,318 @@
+use super::{allow, Router};
+use hyper::header::{
+    HeaderMap, HeaderValue, ACCESS_CONTROL_ALLOW_CREDENTIALS, ACCESS_CONTROL_ALLOW_HEADERS,
+    ACCESS_CONTROL_ALLOW_METHODS, ACCESS_CONTROL_ALLOW_ORIGIN, ACCESS_CONTROL_EXPOSE_HEADERS,
+    ACCESS_CONTROL_MAX_AGE, ACCESS_CONTROL_REQUEST_HEADERS, ACCESS_CONTROL_REQUEST_METHOD, ORIGIN,
+    VARY,
+};
+use hyper::{Body, Method, Request, Response, StatusCode};
+use std::time::Duration;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// The Cross-Origin Resource Sharing policy of a route, a scope, or the
+/// whole router.  See [`super::Build::cors`], [`super::Route::with_cors`],
+/// and [`super::Scope::with_cors`].  A policy allows no origins until it's
+/// given some, so that it's never more open than it says.
+pub struct Cors {
+    /// The allowed origins, or `None` if any origin is.
+    origins: Option<Vec<String>>,
+    methods: Vec<Method>,
+    headers: Vec<String>,
+    exposed: Vec<String>,
+    credentials: bool,
+    max_age: Option<Duration>,
+}
+
+impl Cors {
+    /// Creates a policy that allows no origins.
+    pub fn new() -> Self {
+        Cors {
+            origins: Some(vec![]),
+            methods: vec![],
+            headers: vec![],
+            exposed: vec![],
+            credentials: false,
+            max_age: None,
+        }
+    }
+
+    /// Allows requests from the given origin, e.g.
+    /// `https://app.example.com`.
+    pub fn with_origin(mut self, origin: &str) -> Self {
+        if let Some(origins) = &mut self.origins {
+            origins.push(origin.trim_end_matches('/').to_string());
+        }
+        self
+    }
+
+    /// Allows requests from any origin.  Without credentials, this is
+    /// answered with `*`; with them, the request's origin is echoed back,
+    /// since browsers reject `*` for those.
+    pub fn allow_any_origin(mut self) -> Self {
+        self.origins = None;
+        self
+    }
+
+    /// Allows the given methods in preflight requests.  Without any, the
+    /// methods allowed are the ones the route table has for the path.
+    pub fn with_methods(mut self, methods: &[Method]) -> Self {
+        self.methods.extend_from_slice(methods);
+        self
+    }
+
+    /// Allows the given request headers in preflight requests.  Without
+    /// any, whatever headers the preflight asks for are allowed.
+    pub fn with_headers(mut self, headers: &[&str]) -> Self {
+        self.headers.extend(headers.iter().map(|header| header.to_string()));
+        self
+    }
+
+    /// Lets scripts read the given response headers, beyond the few that
+    /// are always safe.
+    pub fn with_exposed_headers(mut self, headers: &[&str]) -> Self {
+        self.exposed.extend(headers.iter().map(|header| header.to_string()));
+        self
+    }
+
+    /// Allows requests with credentials (cookies, or an `Authorization`
+    /// header).
+    pub fn with_credentials(mut self) -> Self {
+        self.credentials = true;
+        self
+    }
+
+    /// Lets the browser cache the answer to a preflight request for the
+    /// given time.
+    pub fn with_max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// The value of `Access-Control-Allow-Origin` for the given origin, if
+    /// it's allowed.
+    fn allowed_origin(&self, origin: &HeaderValue) -> Option<HeaderValue> {
+        match &self.origins {
+            None if self.credentials => Some(origin.clone()),
+            None => Some(HeaderValue::from_static("*")),
+            Some(origins) => {
+                let origin_str = origin.to_str().ok()?;
+                origins
+                    .iter()
+                    .any(|allowed| allowed.eq_ignore_ascii_case(origin_str))
+                    .then(|| origin.clone())
+            }
+        }
+    }
+
+    /// Adds the headers for an actual (not preflight) request from the
+    /// given origin to its response, unless the handler set them itself.
+    pub(super) fn apply(&self, origin: &HeaderValue, response: &mut Response<Body>) {
+        let headers = response.headers_mut();
+        headers.append(VARY, HeaderValue::from_static("Origin"));
+        if headers.contains_key(ACCESS_CONTROL_ALLOW_ORIGIN) {
+            return;
+        }
+        let allowed = match self.allowed_origin(origin) {
+            Some(allowed) => allowed,
+            None => return,
+        };
+        headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, allowed);
+        if self.credentials {
+            headers.insert(ACCESS_CONTROL_ALLOW_CREDENTIALS, HeaderValue::from_static("true"));
+        }
+        insert_list(headers, ACCESS_CONTROL_EXPOSE_HEADERS, &self.exposed);
+    }
+
+    /// The answer to a preflight request from the given origin.  If the
+    /// origin isn't allowed, the answer has no CORS headers, and so the
+    /// browser won't send the actual request.
+    fn preflight(&self, origin: &HeaderValue, req: &Request<Body>, methods: String) -> Response<Body> {
+        let mut response = Response::new(Body::empty());
+        *response.status_mut() = StatusCode::NO_CONTENT;
+        let headers = response.headers_mut();
+        headers.insert(
+            VARY,
+            HeaderValue::from_static("Origin, Access-Control-Request-Method, Access-Control-Request-Headers"),
+        );
+        let allowed = match self.allowed_origin(origin) {
+            Some(allowed) => allowed,
+            None => return response,
+        };
+        headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, allowed);
+        if self.credentials {
+            headers.insert(ACCESS_CONTROL_ALLOW_CREDENTIALS, HeaderValue::from_static("true"));
+        }
+        let methods = if self.methods.is_empty() {
+            methods
+        } else {
+            self.methods.iter().map(Method::as_str).collect::<Vec<_>>().join(", ")
+        };
+        if let Ok(methods) = HeaderValue::from_str(&methods) {
+            headers.insert(ACCESS_CONTROL_ALLOW_METHODS, methods);
+        }
+        if self.headers.is_empty() {
+            if let Some(requested) = req.headers().get(ACCESS_CONTROL_REQUEST_HEADERS) {
+                headers.insert(ACCESS_CONTROL_ALLOW_HEADERS, requested.clone());
+            }
+        } else {
+            insert_list(headers, ACCESS_CONTROL_ALLOW_HEADERS, &self.headers);
+        }
+        if let Some(max_age) = self.max_age {
+            headers.insert(ACCESS_CONTROL_MAX_AGE, HeaderValue::from(max_age.as_secs()));
+        }
+        response
+    }
+}
+
+impl Default for Cors {
+    fn default() -> Self {
+        Cors::new()
+    }
+}
+
+/// Answers the given request if it's a CORS preflight request for a route
+/// that has a policy (or for any route, if the router has one).  The route
+/// is the one the request's `Access-Control-Request-Method` would match,
+/// and the methods allowed are taken from the route table, unless the
+/// policy lists them.  Anything else is routed as usual.
+pub(super) fn preflight(router: &Router, req: &Request<Body>, path: &str) -> Option<Response<Body>> {
+    if req.method() != Method::OPTIONS {
+        return None;
+    }
+    let origin = req.headers().get(ORIGIN)?;
+    let method = req.headers().get(ACCESS_CONTROL_REQUEST_METHOD)?;
+    let method = Method::from_bytes(method.as_bytes()).ok()?;
+    let (route, _) = router.lookup_route_with(&method, path, req)?;
+    let cors = route.extension::<Cors>().or_else(|| router.extensions().get())?;
+    let methods = router.allowed_methods(path).map(allow).unwrap_or_default();
+    Some(cors.preflight(origin, req, methods))
+}
+
+fn insert_list(headers: &mut HeaderMap, name: hyper::header::HeaderName, values: &[String]) {
+    if values.is_empty() {
+        return;
+    }
+    if let Ok(value) = HeaderValue::from_str(&values.join(", ")) {
+        headers.insert(name, value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{Build, Route};
+    use futures::prelude::*;
+    use hyper::header::LOCATION;
+    use hyper::service::Service;
+
+    fn ok() -> super::super::HandlerFuture {
+        Box::new(futures::future::ok(Response::new(Body::empty())))
+    }
+
+    fn call(router: &mut Router, req: Request<Body>) -> Response<Body> {
+        router.call(req).wait().unwrap()
+    }
+
+    fn preflight(path: &str, origin: &str, method: &str) -> Request<Body> {
+        Request::options(path)
+            .header(ORIGIN, origin)
+            .header(ACCESS_CONTROL_REQUEST_METHOD, method)
+            .header(ACCESS_CONTROL_REQUEST_HEADERS, "content-type")
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_route_cors() {
+        let cors = Cors::new()
+            .with_origin("https://app.example.com")
+            .with_exposed_headers(&["x-total"])
+            .with_max_age(Duration::from_secs(600));
+        let mut build = Build::default();
+        build
+            .add(Route::get("/users", |_, _| ok()).with_cors(cors))
+            .post("/users", |_, _| ok())
+            .get("/private", |_, _| ok());
+        let mut router = build.finish();
+
+        let response = call(&mut router, preflight("/users", "https://app.example.com", "GET"));
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        let headers = response.headers();
+        assert_eq!(headers[ACCESS_CONTROL_ALLOW_ORIGIN], "https://app.example.com");
+        assert_eq!(headers[ACCESS_CONTROL_ALLOW_METHODS], "GET, POST");
+        assert_eq!(headers[ACCESS_CONTROL_ALLOW_HEADERS], "content-type");
+        assert_eq!(headers[ACCESS_CONTROL_MAX_AGE], "600");
+
+        let response = call(&mut router, preflight("/users", "https://evil.example.com", "GET"));
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert!(response.headers().get(ACCESS_CONTROL_ALLOW_ORIGIN).is_none());
+
+        // Only the `GET` route has a policy, so preflights for the others
+        // are routed as usual.
+        let response = call(&mut router, preflight("/users", "https://app.example.com", "POST"));
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let response = call(&mut router, preflight("/private", "https://app.example.com", "GET"));
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let request = Request::get("/users")
+            .header(ORIGIN, "https://APP.example.com")
+            .body(Body::empty())
+            .unwrap();
+        let response = call(&mut router, request);
+        let headers = response.headers();
+        assert_eq!(headers[ACCESS_CONTROL_ALLOW_ORIGIN], "https://APP.example.com");
+        assert_eq!(headers[ACCESS_CONTROL_EXPOSE_HEADERS], "x-total");
+        assert_eq!(headers[VARY], "Origin");
+        assert!(headers.get(ACCESS_CONTROL_ALLOW_CREDENTIALS).is_none());
+
+        let request = Request::get("/private")
+            .header(ORIGIN, "https://app.example.com")
+            .body(Body::empty())
+            .unwrap();
+        assert!(call(&mut router, request).headers().get(ACCESS_CONTROL_ALLOW_ORIGIN).is_none());
+    }
+
+    #[test]
+    fn test_router_and_scope_cors() {
+        let mut build = Build::default();
+        build
+            .cors(Cors::new().allow_any_origin())
+            .scope("/api", |api| {
+                api.with_cors(
+                    Cors::new()
+                        .allow_any_origin()
+                        .with_credentials()
+                        .with_methods(&[Method::GET, Method::PUT])
+                        .with_headers(&["authorization", "content-type"]),
+                )
+                .put("/users/{}", |_, _| ok());
+            })
+            .get("/public", |_, _| {
+                let mut response = Response::new(Body::empty());
+                *response.status_mut() = StatusCode::SEE_OTHER;
+                response.headers_mut().insert(LOCATION, HeaderValue::from_static("/"));
+                Box::new(futures::future::ok(response))
+            });
+        let mut router = build.finish();
+
+        let response = call(&mut router, preflight("/api/users/5", "https://a.example.com", "PUT"));
+        let headers = response.headers();
+        assert_eq!(headers[ACCESS_CONTROL_ALLOW_ORIGIN], "https://a.example.com");
+        assert_eq!(headers[ACCESS_CONTROL_ALLOW_CREDENTIALS], "true");
+        assert_eq!(headers[ACCESS_CONTROL_ALLOW_METHODS], "GET, PUT");
+        assert_eq!(headers[ACCESS_CONTROL_ALLOW_HEADERS], "authorization, content-type");
+
+        let request = Request::get("/public")
+            .header(ORIGIN, "https://b.example.com")
+            .body(Body::empty())
+            .unwrap();
+        let response = call(&mut router, request);
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        assert_eq!(response.headers()[ACCESS_CONTROL_ALLOW_ORIGIN], "*");
+
+        // Requests without an origin aren't cross-origin.
+        let response = call(&mut router, Request::get("/public").body(Body::empty()).unwrap());
+        assert!(response.headers().get(ACCESS_CONTROL_ALLOW_ORIGIN).is_none());
+        assert!(response.headers().get(VARY).is_none());
+    }
+}
@@ -0,0 +1,98 @@
+use hyper::{Body, Request, Response};
+use std::fmt::{Debug, Formatter, Result as FmtResult};
+use std::sync::Arc;
+
+type BeforeFn = dyn Fn(&mut Request<Body>) + Send + Sync + 'static;
+type AfterFn = dyn Fn(&mut Response<Body>) + Send + Sync + 'static;
+
+/// The hooks called around every request a router handles, in the order
+/// they were added, kept in its extensions.
+#[derive(Clone, Default)]
+pub(super) struct Hooks {
+    before: Vec<Arc<BeforeFn>>,
+    after: Vec<Arc<AfterFn>>,
+}
+
+impl Hooks {
+    pub(super) fn push_before<F>(&mut self, hook: F)
+    where
+        F: Fn(&mut Request<Body>) + Send + Sync + 'static,
+    {
+        self.before.push(Arc::new(hook))
+    }
+
+    pub(super) fn push_after<F>(&mut self, hook: F)
+    where
+        F: Fn(&mut Response<Body>) + Send + Sync + 'static,
+    {
+        self.after.push(Arc::new(hook))
+    }
+
+    pub(super) fn before(&self, req: &mut Request<Body>) {
+        for hook in &self.before {
+            hook(req)
+        }
+    }
+
+    pub(super) fn after(&self, response: &mut Response<Body>) {
+        for hook in &self.after {
+            hook(response)
+        }
+    }
+
+    pub(super) fn has_after(&self) -> bool {
+        !self.after.is_empty()
+    }
+}
+
+impl Debug for Hooks {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("Hooks")
+            .field("before", &self.before.len())
+            .field("after", &self.after.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::Router;
+    use futures::prelude::*;
+    use hyper::header::HeaderValue;
+    use hyper::service::Service;
+    use hyper::{Body, Request, Response, StatusCode};
+
+    #[test]
+    fn test_hooks() {
+        let mut build = Router::build();
+        build
+            .get("/", |req, _| {
+                let id = req.headers()["x-request-id"].clone();
+                Box::new(futures::future::ok(Response::new(Body::from(id.as_bytes().to_vec()))))
+            })
+            .before(|req| {
+                req.headers_mut()
+                    .insert("x-request-id", HeaderValue::from_static("42"));
+            });
+        let mut router = build.finish();
+        router.after(|response| {
+            response
+                .headers_mut()
+                .insert("x-frame-options", HeaderValue::from_static("DENY"));
+        });
+
+        let response = router
+            .call(Request::get("/").body(Body::empty()).unwrap())
+            .wait()
+            .unwrap();
+        assert_eq!(response.headers()["x-frame-options"], "DENY");
+        assert_eq!(&*response.into_body().concat2().wait().unwrap(), b"42");
+
+        let response = router
+            .call(Request::get("/missing").body(Body::empty()).unwrap())
+            .wait()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(response.headers()["x-frame-options"], "DENY");
+    }
+}
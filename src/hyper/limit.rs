@@ -0,0 +1,110 @@
+use futures::prelude::*;
+use hyper::header::CONTENT_LENGTH;
+use hyper::{Body, Request};
+use std::error::Error;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// The largest body, in bytes, that a handler is given.  See
+/// [`super::Route::with_body_limit`] and [`super::Build::body_limit`].
+#[derive(Debug, Clone, Copy)]
+pub(super) struct BodyLimit(pub(super) u64);
+
+/// The error the body of a request fails with once it goes over its limit.
+#[derive(Debug)]
+struct TooLarge;
+
+impl Display for TooLarge {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.write_str("request body is too large")
+    }
+}
+
+impl Error for TooLarge {}
+
+impl BodyLimit {
+    /// Whether the request declares a body that's over the limit, so that
+    /// it can be rejected before the handler runs.
+    pub(super) fn rejects(self, req: &Request<Body>) -> bool {
+        req.headers()
+            .get(CONTENT_LENGTH)
+            .and_then(|length| length.to_str().ok())
+            .and_then(|length| length.parse::<u64>().ok())
+            .is_some_and(|length| length > self.0)
+    }
+
+    /// Replaces the body of the request with one that fails as soon as
+    /// it's read past the limit, for bodies whose length isn't declared
+    /// (or is declared wrongly).  The flag that's returned is set once
+    /// that happens, so that the handler's failure can be answered with a
+    /// 413 Payload Too Large.
+    pub(super) fn apply(self, req: &mut Request<Body>) -> Arc<AtomicBool> {
+        let exceeded = Arc::new(AtomicBool::new(false));
+        let flag = exceeded.clone();
+        let limit = self.0;
+        let mut seen = 0u64;
+        let body = std::mem::replace(req.body_mut(), Body::empty())
+            .map_err(|error| Box::new(error) as Box<dyn Error + Send + Sync>)
+            .and_then(move |chunk| {
+                seen += chunk.len() as u64;
+                if seen > limit {
+                    flag.store(true, Ordering::SeqCst);
+                    Err(Box::new(TooLarge) as Box<dyn Error + Send + Sync>)
+                } else {
+                    Ok(chunk)
+                }
+            });
+        *req.body_mut() = Body::wrap_stream(body);
+        exceeded
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Route, Router};
+    use futures::prelude::*;
+    use hyper::service::Service;
+    use hyper::{Body, Request, Response, StatusCode};
+
+    fn echo(req: Request<Body>) -> super::super::HandlerFuture {
+        Box::new(
+            req.into_body()
+                .concat2()
+                .map_err(failure::Error::from)
+                .map(|body| Response::new(Body::from(body))),
+        )
+    }
+
+    #[test]
+    fn test_body_limit() {
+        let mut build = Router::build();
+        build
+            .body_limit(8)
+            .add(Route::post("/uploads", |req, _| echo(req)).with_body_limit(16))
+            .post("/comments", |req, _| echo(req));
+        let mut router = build.finish();
+        let mut call = |path: &str, body: Body, length: Option<usize>| {
+            let mut request = Request::post(path);
+            if let Some(length) = length {
+                request.header("content-length", length);
+            }
+            router.call(request.body(body).unwrap()).wait().unwrap()
+        };
+
+        let response = call("/comments", Body::from("short"), Some(5));
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(&*response.into_body().concat2().wait().unwrap(), b"short");
+        let response = call("/comments", Body::from("far too long"), Some(12));
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        let response = call("/uploads", Body::from("far too long"), Some(12));
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // A body without a declared length is cut off once it's read past
+        // the limit.
+        let chunks = vec!["far ", "too ", "long"];
+        let body = Body::wrap_stream(futures::stream::iter_ok::<_, std::io::Error>(chunks));
+        let response = call("/comments", body, None);
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+}
@@ -0,0 +1,131 @@
+use super::{Handler, HandlerFuture, Params};
+use hyper::{Body, Request};
+use std::fmt::{Debug, Formatter, Result as FmtResult};
+use std::sync::Arc;
+
+type MiddlewareFn =
+    dyn Fn(Request<Body>, &Params<'_>, Next<'_>) -> HandlerFuture + Send + Sync + 'static;
+
+/// The middleware of a router, in the order they were added, kept in its
+/// extensions.
+#[derive(Clone, Default)]
+pub(super) struct Stack(Vec<Arc<MiddlewareFn>>);
+
+impl Stack {
+    pub(super) fn push<F>(&mut self, middleware: F)
+    where
+        F: Fn(Request<Body>, &Params<'_>, Next<'_>) -> HandlerFuture + Send + Sync + 'static,
+    {
+        self.0.push(Arc::new(middleware))
+    }
+
+    /// Calls the first middleware with the given handler at the end of the
+    /// chain, or the handler itself if there's no middleware.
+    pub(super) fn run<'a>(&'a self, handler: &'a Handler, req: Request<Body>, params: &Params<'_>) -> HandlerFuture {
+        Next {
+            handler,
+            rest: &self.0,
+        }
+        .run(req, params)
+    }
+}
+
+impl Debug for Stack {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("Stack").field("len", &self.0.len()).finish()
+    }
+}
+
+/// The rest of the chain of middleware, ending with the handler of the
+/// route that matched.  See [`super::Build::wrap`].
+pub struct Next<'a> {
+    handler: &'a Handler,
+    rest: &'a [Arc<MiddlewareFn>],
+}
+
+impl Next<'_> {
+    /// Passes the request and its parameters on to the next middleware,
+    /// or to the handler, if this is the last one.
+    pub fn run(self, req: Request<Body>, params: &Params<'_>) -> HandlerFuture {
+        match self.rest.split_first() {
+            Some((middleware, rest)) => middleware(
+                req,
+                params,
+                Next {
+                    handler: self.handler,
+                    rest,
+                },
+            ),
+            None => (self.handler)(req, params),
+        }
+    }
+}
+
+impl Debug for Next<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("Next").field("rest", &self.rest.len()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::Router;
+    use futures::prelude::*;
+    use hyper::header::HeaderValue;
+    use hyper::service::Service;
+    use hyper::{Body, Request, Response, StatusCode};
+
+    #[test]
+    fn test_wrap() {
+        let mut build = Router::build();
+        build
+            .get("/users/{:uint}", |_, params| {
+                Box::new(futures::future::ok(Response::new(Body::from(params[0].to_string()))))
+            })
+            .wrap(|req, params, next| {
+                let response = next.run(req, params).map(|mut response| {
+                    response
+                        .headers_mut()
+                        .insert("x-outer", HeaderValue::from_static("1"));
+                    response
+                });
+                Box::new(response)
+            })
+            .wrap(|req, params, next| {
+                if req.headers().contains_key("authorization") {
+                    next.run(req, params)
+                } else {
+                    let response = Response::builder()
+                        .status(StatusCode::UNAUTHORIZED)
+                        .body(Body::empty())
+                        .map_err(Into::into);
+                    Box::new(futures::future::result(response))
+                }
+            });
+        let mut router = build.finish();
+
+        let request = |authorized: bool| {
+            let mut request = Request::get("/users/5");
+            if authorized {
+                request.header("authorization", "yes");
+            }
+            request.body(Body::empty()).unwrap()
+        };
+
+        let response = router.call(request(true)).wait().unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["x-outer"], "1");
+        assert_eq!(&*response.into_body().concat2().wait().unwrap(), b"5");
+
+        let response = router.call(request(false)).wait().unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(response.headers()["x-outer"], "1");
+
+        let response = router
+            .call(Request::get("/posts").body(Body::empty()).unwrap())
+            .wait()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert!(!response.headers().contains_key("x-outer"));
+    }
+}
@@ -0,0 +1,113 @@
+use super::HandlerFuture;
+use futures::prelude::*;
+use std::any::Any;
+use std::error::Error;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+/// Marks a router whose handlers' panics are caught.  See
+/// [`super::Build::catch_panics`].
+#[derive(Debug)]
+pub(super) struct CatchPanics;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// The error a handler fails with when it panics, if the router catches
+/// panics (see [`super::Build::catch_panics`]).  This is given to the
+/// error handler, if there is one.
+pub struct Panic {
+    message: String,
+}
+
+impl Panic {
+    fn new(payload: Box<dyn Any + Send>) -> Self {
+        let message = match payload.downcast::<String>() {
+            Ok(message) => *message,
+            Err(payload) => match payload.downcast::<&'static str>() {
+                Ok(message) => message.to_string(),
+                Err(_) => "Box<dyn Any>".to_string(),
+            },
+        };
+        Panic { message }
+    }
+
+    /// The message the handler panicked with.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl Display for Panic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "handler panicked: {}", self.message)
+    }
+}
+
+impl Error for Panic {}
+
+/// Calls the handler, turning a panic, either in the call itself or while
+/// its future is polled, into a [`Panic`] error.
+pub(super) fn catch<F: FnOnce() -> HandlerFuture>(call: F) -> HandlerFuture {
+    match catch_unwind(AssertUnwindSafe(call)) {
+        Ok(response) => Box::new(AssertUnwindSafe(response).catch_unwind().then(|result| {
+            match result {
+                Ok(result) => result,
+                Err(payload) => Err(Panic::new(payload).into()),
+            }
+        })),
+        Err(payload) => Box::new(futures::future::err(Panic::new(payload).into())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::Router;
+    use super::Panic;
+    use futures::prelude::*;
+    use hyper::service::Service;
+    use hyper::{Body, Request, Response, StatusCode};
+
+    #[test]
+    fn test_catch_panics() {
+        let mut build = Router::build();
+        build
+            .get("/now", |_, _| panic!("boom"))
+            .get("/later", |_, _| {
+                Box::new(futures::future::lazy(|| -> Result<Response<Body>, failure::Error> {
+                    panic!("{} later", "boom")
+                }))
+            })
+            .catch_panics();
+        let mut router = build.finish();
+
+        for path in &["/now", "/later"] {
+            let response = router
+                .call(Request::get(*path).body(Body::empty()).unwrap())
+                .wait()
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        }
+
+        let mut build = Router::build();
+        build
+            .get("/later", |_, _| {
+                Box::new(futures::future::lazy(|| -> Result<Response<Body>, failure::Error> {
+                    panic!("{} later", "boom")
+                }))
+            })
+            .catch_panics()
+            .error_handler(|error| {
+                let message = error.downcast_ref::<Panic>().unwrap().message().to_string();
+                let mut response = Response::new(Body::from(message));
+                *response.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
+                response
+            });
+        let mut router = build.finish();
+
+        let response = router
+            .call(Request::get("/later").body(Body::empty()).unwrap())
+            .wait()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(&*response.into_body().concat2().wait().unwrap(), b"boom later");
+    }
+}
@@ -0,0 +1,371 @@
+use super::HandlerFuture;
+use crate::router::Params;
+use futures::prelude::*;
+use hyper::client::HttpConnector;
+use hyper::header::{HeaderMap, HeaderName, HeaderValue, CONNECTION, FORWARDED, HOST};
+use hyper::{Body, Client, Request, Response, StatusCode, Uri};
+use std::net::{IpAddr, SocketAddr};
+
+/// The headers that only apply to a single connection, and so aren't
+/// forwarded, in either direction.
+static HOP_BY_HOP: &[&str] = &[
+    "connection",
+    "keep-alive",
+    "proxy-authenticate",
+    "proxy-authorization",
+    "proxy-connection",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+];
+
+#[derive(Debug, Clone)]
+/// A handler that forwards requests to an upstream server, and answers
+/// with its response; see [`proxy_to`].  Only `http` upstreams are
+/// supported.
+pub struct Proxy {
+    upstream: Uri,
+    strip_prefix: Option<String>,
+    proto: Option<String>,
+    client: Client<HttpConnector, Body>,
+}
+
+impl Proxy {
+    /// Creates a proxy to the given upstream, e.g. `http://10.0.0.5:8080`.
+    /// The path of the request is appended to the upstream's path, so an
+    /// upstream of `http://backend/api` gets `/users` as `/api/users`.
+    ///
+    /// # Panics
+    ///
+    /// This panics if the upstream isn't a valid absolute URI.
+    pub fn new(upstream: &str) -> Self {
+        let upstream = upstream
+            .parse::<Uri>()
+            .ok()
+            .filter(|uri| uri.scheme_part().is_some() && uri.authority_part().is_some())
+            .unwrap_or_else(|| panic!("invalid upstream: {:?}", upstream));
+        Proxy {
+            upstream,
+            strip_prefix: None,
+            proto: None,
+            client: Client::new(),
+        }
+    }
+
+    /// Removes the given prefix from the path of each request before it's
+    /// forwarded, e.g. so that `/api/users` is forwarded as `/users` for a
+    /// route under `/api`.  A path that doesn't start with the prefix is
+    /// forwarded as it is.
+    pub fn strip_prefix(mut self, prefix: &str) -> Self {
+        self.strip_prefix = Some(prefix.trim_end_matches('/').to_string());
+        self
+    }
+
+    /// Sets the scheme the requests were made with, e.g. `https` when the
+    /// server is behind TLS, for `X-Forwarded-Proto` and `Forwarded`.  The
+    /// server doesn't know the scheme of the requests it's given, so by
+    /// default, the scheme from the request's own `X-Forwarded-Proto` is
+    /// kept, if it has one, and `http` is assumed otherwise.
+    pub fn with_proto(mut self, proto: &str) -> Self {
+        self.proto = Some(proto.to_ascii_lowercase());
+        self
+    }
+
+    /// Uses the given client to make the upstream requests, e.g. one with
+    /// its own connection pool settings.
+    pub fn with_client(mut self, client: Client<HttpConnector, Body>) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// Makes the proxy into a handler, for a route of any method.
+    pub fn handler(self) -> impl Fn(Request<Body>, &Params<'_>) -> HandlerFuture + Send + Sync + 'static {
+        move |req, _| self.forward(req)
+    }
+
+    /// Forwards the given request upstream.  If the upstream can't be
+    /// reached, this answers with a 502 Bad Gateway.  The path is forwarded
+    /// as it was given, not as the router normalized it, so a path with a
+    /// dot segment (e.g. `/api/../admin`), which could reach outside of the
+    /// route's prefix upstream, is answered with a 400 Bad Request instead.
+    pub fn forward(&self, req: Request<Body>) -> HandlerFuture {
+        if has_dot_segment(req.uri().path()) {
+            return Box::new(futures::future::ok(status(StatusCode::BAD_REQUEST)));
+        }
+        let (mut parts, body) = req.into_parts();
+        let uri = match self.upstream_uri(&parts.uri) {
+            Some(uri) => uri,
+            None => return Box::new(futures::future::ok(status(StatusCode::BAD_GATEWAY))),
+        };
+        let proto = self.proto(&parts.headers);
+        let host = parts.headers.remove(HOST);
+        let client = parts.extensions.get::<SocketAddr>().map(SocketAddr::ip);
+        strip_hop_by_hop(&mut parts.headers);
+        forwarded(&mut parts.headers, &proto, host, client);
+        parts.uri = uri;
+
+        let request = Request::from_parts(parts, body);
+        Box::new(self.client.request(request).then(|result| match result {
+            Ok(mut response) => {
+                strip_hop_by_hop(response.headers_mut());
+                Ok(response)
+            }
+            Err(_) => Ok(status(StatusCode::BAD_GATEWAY)),
+        }))
+    }
+
+    /// The scheme the request was made with: the configured one, the one
+    /// from its `X-Forwarded-Proto`, or `http`.
+    fn proto(&self, headers: &HeaderMap) -> String {
+        if let Some(proto) = &self.proto {
+            return proto.clone();
+        }
+        headers
+            .get("x-forwarded-proto")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split(',').next())
+            .map(str::trim)
+            .filter(|proto| {
+                !proto.is_empty() && proto.chars().all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c))
+            })
+            .unwrap_or("http")
+            .to_ascii_lowercase()
+    }
+
+    /// The URI the request for the given URI is forwarded to: the
+    /// upstream's, with the request's path (without the prefix) and query.
+    fn upstream_uri(&self, uri: &Uri) -> Option<Uri> {
+        let mut path = uri.path();
+        if let Some(prefix) = &self.strip_prefix {
+            if let Some(rest) = path.strip_prefix(prefix.as_str()) {
+                if rest.is_empty() || rest.starts_with('/') {
+                    path = rest;
+                }
+            }
+        }
+        let base = self.upstream.path().trim_end_matches('/');
+        let mut target = format!("{}{}", base, path);
+        if target.is_empty() {
+            target.push('/');
+        }
+        if let Some(query) = uri.query() {
+            target.push('?');
+            target.push_str(query);
+        }
+        Uri::builder()
+            .scheme(self.upstream.scheme_str()?)
+            .authority(self.upstream.authority_part()?.as_str())
+            .path_and_query(target.as_str())
+            .build()
+            .ok()
+    }
+}
+
+/// Creates a handler that forwards requests to the given upstream, e.g.
+/// `Route::any("/api/{}", proxy_to("http://10.0.0.5:8080"))`.  The body is
+/// streamed both ways, hop-by-hop headers (like `Connection`) are dropped,
+/// and the standard forwarding headers are set.  See [`Proxy`] to rewrite
+/// the path, or give it a client.
+///
+/// # Panics
+///
+/// This panics if the upstream isn't a valid absolute URI.
+pub fn proxy_to(upstream: &str) -> impl Fn(Request<Body>, &Params<'_>) -> HandlerFuture + Send + Sync + 'static {
+    Proxy::new(upstream).handler()
+}
+
+/// Whether the given (encoded) path has a segment that's `.` or `..` once
+/// it's decoded, or that decodes to something with a slash in it, and so
+/// could be resolved into a different path by the upstream.
+fn has_dot_segment(path: &str) -> bool {
+    path.split('/').any(|segment| {
+        let decoded = percent_encoding::percent_decode(segment.as_bytes()).decode_utf8_lossy();
+        decoded == "." || decoded == ".." || decoded.contains(['/', '\\'])
+    })
+}
+
+/// Removes the hop-by-hop headers, including any named by `Connection`.
+fn strip_hop_by_hop(headers: &mut HeaderMap) {
+    let named = headers
+        .get_all(CONNECTION)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|name| HeaderName::from_bytes(name.trim().as_bytes()).ok())
+        .collect::<Vec<_>>();
+    for name in named {
+        headers.remove(name);
+    }
+    for name in HOP_BY_HOP {
+        headers.remove(*name);
+    }
+}
+
+/// Sets `X-Forwarded-Proto`, `X-Forwarded-Host`, and `Forwarded`, and adds
+/// the client's address to `X-Forwarded-For`.  The router doesn't know the
+/// client's address, so it's only added if the request has it as a
+/// `SocketAddr` extension, e.g. one added by the `MakeService`.
+fn forwarded(headers: &mut HeaderMap, proto: &str, host: Option<HeaderValue>, client: Option<IpAddr>) {
+    let mut forwarded = vec![];
+    if let Some(client) = client {
+        let value = match headers.get("x-forwarded-for").and_then(|value| value.to_str().ok()) {
+            Some(previous) => format!("{}, {}", previous, client),
+            None => client.to_string(),
+        };
+        if let Ok(value) = HeaderValue::from_str(&value) {
+            headers.insert("x-forwarded-for", value);
+        }
+        forwarded.push(match client {
+            IpAddr::V4(client) => format!("for={}", client),
+            IpAddr::V6(client) => format!("for=\"[{}]\"", client),
+        });
+    }
+    if let Some(host) = host {
+        if let Ok(name) = host.to_str() {
+            forwarded.push(format!("host={}", name));
+        }
+        headers.insert("x-forwarded-host", host);
+    }
+    forwarded.push(format!("proto={}", proto));
+    if let Ok(value) = HeaderValue::from_str(proto) {
+        headers.insert("x-forwarded-proto", value);
+    }
+    if let Ok(value) = HeaderValue::from_str(&forwarded.join(";")) {
+        headers.append(FORWARDED, value);
+    }
+}
+
+fn status(status: StatusCode) -> Response<Body> {
+    let mut response = Response::new(Body::empty());
+    *response.status_mut() = status;
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{Route, Router};
+    use hyper::service::service_fn;
+    use hyper::Server;
+    use tokio::runtime::Runtime;
+
+    #[test]
+    fn test_upstream_uri() {
+        let proxy = Proxy::new("http://backend:8080/v2/");
+        let uri = |path: &str| proxy.upstream_uri(&path.parse().unwrap()).unwrap().to_string();
+        assert_eq!(uri("/users?page=2"), "http://backend:8080/v2/users?page=2");
+
+        let proxy = Proxy::new("http://backend").strip_prefix("/api/");
+        let uri = |path: &str| proxy.upstream_uri(&path.parse().unwrap()).unwrap().to_string();
+        assert_eq!(uri("/api/users/5"), "http://backend/users/5");
+        assert_eq!(uri("/api"), "http://backend/");
+        assert_eq!(uri("/apix"), "http://backend/apix");
+    }
+
+    #[test]
+    fn test_dot_segments() {
+        assert!(!has_dot_segment("/api/users/5"));
+        assert!(!has_dot_segment("/api/v1.2/..."));
+        let paths = ["/api/../admin", "/api/./users", "/api/%2e%2E/admin", "/api/..%2Fadmin", "/api/%5C"];
+        for path in &paths {
+            assert!(has_dot_segment(path), "{}", path);
+        }
+
+        let mut build = Router::build();
+        let proxy = Proxy::new("http://127.0.0.1:1").strip_prefix("/api");
+        build.add(Route::any("/api/{}/{}", proxy.handler()));
+        let router = build.finish();
+        let request = Request::get("/api/../admin").body(Body::empty()).unwrap();
+        let response = super::super::respond(&router, request).wait().unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn test_proto() {
+        let headers = |proto: Option<&'static str>| {
+            let mut headers = HeaderMap::new();
+            if let Some(proto) = proto {
+                headers.insert("x-forwarded-proto", HeaderValue::from_static(proto));
+            }
+            headers
+        };
+        let proxy = Proxy::new("http://backend");
+        assert_eq!(proxy.proto(&headers(None)), "http");
+        assert_eq!(proxy.proto(&headers(Some("HTTPS"))), "https");
+        assert_eq!(proxy.proto(&headers(Some("https, http"))), "https");
+        assert_eq!(proxy.proto(&headers(Some("h;ttp"))), "http");
+        let proxy = proxy.with_proto("https");
+        assert_eq!(proxy.proto(&headers(None)), "https");
+        assert_eq!(proxy.proto(&headers(Some("http"))), "https");
+    }
+
+    #[test]
+    fn test_forwarded_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONNECTION, HeaderValue::from_static("close, x-internal"));
+        headers.insert("x-internal", HeaderValue::from_static("secret"));
+        headers.insert("x-forwarded-for", HeaderValue::from_static("10.0.0.1"));
+        headers.insert("accept", HeaderValue::from_static("text/plain"));
+        strip_hop_by_hop(&mut headers);
+        forwarded(
+            &mut headers,
+            "https",
+            Some(HeaderValue::from_static("example.com")),
+            Some("10.0.0.2".parse().unwrap()),
+        );
+
+        assert!(headers.get(CONNECTION).is_none());
+        assert!(headers.get("x-internal").is_none());
+        assert_eq!(headers["accept"], "text/plain");
+        assert_eq!(headers["x-forwarded-for"], "10.0.0.1, 10.0.0.2");
+        assert_eq!(headers["x-forwarded-host"], "example.com");
+        assert_eq!(headers["x-forwarded-proto"], "https");
+        assert_eq!(headers[FORWARDED], "for=10.0.0.2;host=example.com;proto=https");
+    }
+
+    #[test]
+    fn test_proxy() {
+        let mut runtime = Runtime::new().unwrap();
+        let upstream = Server::bind(&([127, 0, 0, 1], 0).into()).serve(|| {
+            service_fn(|req: Request<Body>| {
+                let summary = format!(
+                    "{} {} {}",
+                    req.method(),
+                    req.uri(),
+                    req.headers()["x-forwarded-host"].to_str().unwrap()
+                );
+                req.into_body()
+                    .concat2()
+                    .map(move |body| {
+                        let body = String::from_utf8_lossy(&body);
+                        Response::new(Body::from(format!("{} {}", summary, body)))
+                    })
+            })
+        });
+        let address = upstream.local_addr();
+        runtime.spawn(upstream.map_err(|error| panic!("{}", error)));
+
+        let mut build = Router::build();
+        build.add(Route::any(
+            "/api/{}",
+            Proxy::new(&format!("http://{}", address)).strip_prefix("/api").handler(),
+        ));
+        let router = build.finish();
+        let request = Request::post("/api/users?page=2")
+            .header(HOST, "example.com")
+            .body(Body::from("hello"))
+            .unwrap();
+        let response = runtime.block_on(super::super::respond(&router, request)).unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = runtime.block_on(response.into_body().concat2()).unwrap();
+        assert_eq!(&*body, b"POST /users?page=2 example.com hello");
+
+        let mut build = Router::build();
+        build.add(Route::any("/{}", proxy_to("http://127.0.0.1:1")));
+        let router = build.finish();
+        let request = Request::get("/down").body(Body::empty()).unwrap();
+        let response = runtime.block_on(super::super::respond(&router, request)).unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+    }
+}
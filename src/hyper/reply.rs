@@ -0,0 +1,93 @@
+//! Shortcuts for the trivial replies a handler makes, so that they don't
+//! have to build the response by hand, e.g.
+//! `build.get("/", |_, _| reply::text("hello"))`.  Each of them makes the
+//! handler's result, a [`super::Handler`] future that's already ready.
+
+use super::HandlerFuture;
+use hyper::header::{HeaderValue, CONTENT_LENGTH, CONTENT_TYPE};
+use hyper::{Body, Response, StatusCode};
+
+/// Answers with the given JSON, as `application/json`.  The router doesn't
+/// depend on a serialization library, so the value has to be serialized
+/// already, e.g. with `serde_json::to_string`.
+pub fn json<T: AsRef<str> + ?Sized>(body: &T) -> HandlerFuture {
+    content("application/json", body.as_ref())
+}
+
+/// Answers with the given text, as `text/plain; charset=utf-8`.
+pub fn text(body: &str) -> HandlerFuture {
+    content("text/plain; charset=utf-8", body)
+}
+
+/// Answers with the given HTML, as `text/html; charset=utf-8`.
+pub fn html(body: &str) -> HandlerFuture {
+    content("text/html; charset=utf-8", body)
+}
+
+/// Answers with the given status, and an empty body.
+pub fn status(status: StatusCode) -> HandlerFuture {
+    let mut response = Response::new(Body::empty());
+    *response.status_mut() = status;
+    response.headers_mut().insert(CONTENT_LENGTH, HeaderValue::from(0));
+    Box::new(futures::future::ok(response))
+}
+
+fn content(content_type: &'static str, body: &str) -> HandlerFuture {
+    let mut response = Response::new(Body::from(body.to_string()));
+    let headers = response.headers_mut();
+    headers.insert(CONTENT_TYPE, HeaderValue::from_static(content_type));
+    headers.insert(CONTENT_LENGTH, HeaderValue::from(body.len()));
+    Box::new(futures::future::ok(response))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::Router;
+    use futures::prelude::*;
+    use hyper::service::Service;
+    use hyper::Request;
+
+    #[test]
+    fn test_reply() {
+        let mut build = Router::build();
+        build
+            .get("/text", |_, _| text("hello"))
+            .get("/html", |_, _| html("<p>hello</p>"))
+            .get("/json", |_, _| json(r#"{"hello":"world"}"#))
+            .delete("/users/{}", |_, _| status(StatusCode::NO_CONTENT));
+        let mut router = build.finish();
+
+        let mut call = |path: &str| {
+            router
+                .call(Request::get(path).body(Body::empty()).unwrap())
+                .wait()
+                .unwrap()
+        };
+
+        let response = call("/text");
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[CONTENT_TYPE], "text/plain; charset=utf-8");
+        assert_eq!(response.headers()[CONTENT_LENGTH], "5");
+        assert_eq!(&*response.into_body().concat2().wait().unwrap(), &b"hello"[..]);
+
+        let response = call("/html");
+        assert_eq!(response.headers()[CONTENT_TYPE], "text/html; charset=utf-8");
+        assert_eq!(response.headers()[CONTENT_LENGTH], "12");
+
+        let response = call("/json");
+        assert_eq!(response.headers()[CONTENT_TYPE], "application/json");
+        assert_eq!(
+            &*response.into_body().concat2().wait().unwrap(),
+            &br#"{"hello":"world"}"#[..]
+        );
+
+        let response = router
+            .call(Request::delete("/users/1").body(Body::empty()).unwrap())
+            .wait()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert_eq!(response.headers()[CONTENT_LENGTH], "0");
+        assert!(response.headers().get(CONTENT_TYPE).is_none());
+    }
+}
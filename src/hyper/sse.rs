@@ -0,0 +1,140 @@
+use super::HandlerFuture;
+use futures::prelude::*;
+use hyper::header::{CACHE_CONTROL, CONTENT_TYPE};
+use hyper::{Body, Chunk, Response};
+use std::error::Error;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::time::Duration;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A single Server-Sent Event.  The data can have more than one line; each
+/// one is sent as its own `data:` field, and the client joins them back
+/// together.  The event name and id can't have line breaks.
+pub struct Event {
+    data: String,
+    event: Option<String>,
+    id: Option<String>,
+    retry: Option<Duration>,
+}
+
+impl Event {
+    /// Creates an event with the given data, and no name, so that the
+    /// client sees it as a `message` event.
+    pub fn new<D: Into<String>>(data: D) -> Self {
+        Event {
+            data: data.into(),
+            event: None,
+            id: None,
+            retry: None,
+        }
+    }
+
+    /// Gives the event a name, which the client listens for, instead of
+    /// `message`.
+    pub fn with_event<E: Into<String>>(mut self, event: E) -> Self {
+        self.event = Some(event.into());
+        self
+    }
+
+    /// Gives the event an id, which the client sends back as the
+    /// `Last-Event-ID` header when it reconnects.
+    pub fn with_id<I: Into<String>>(mut self, id: I) -> Self {
+        self.id = Some(id.into());
+        self
+    }
+
+    /// Tells the client how long to wait before it reconnects, if the
+    /// connection is lost.
+    pub fn with_retry(mut self, retry: Duration) -> Self {
+        self.retry = Some(retry);
+        self
+    }
+}
+
+impl Display for Event {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        if let Some(event) = &self.event {
+            writeln!(f, "event: {}", event)?;
+        }
+        if let Some(id) = &self.id {
+            writeln!(f, "id: {}", id)?;
+        }
+        if let Some(retry) = self.retry {
+            writeln!(f, "retry: {}", retry.as_millis())?;
+        }
+        for line in self.data.split('\n') {
+            writeln!(f, "data: {}", line.trim_end_matches('\r'))?;
+        }
+        writeln!(f)
+    }
+}
+
+/// Makes a `text/event-stream` response that sends each of the events of
+/// the given stream as it's ready, for as long as the stream lasts.  The
+/// response isn't cached.
+pub fn sse_response<S, E>(events: S) -> Response<Body>
+where
+    S: Stream<Item = Event, Error = E> + Send + 'static,
+    E: Into<Box<dyn Error + Send + Sync>> + 'static,
+{
+    let chunks = events.map(|event| Chunk::from(event.to_string()));
+    let mut response = Response::new(Body::wrap_stream(chunks));
+    let headers = response.headers_mut();
+    headers.insert(CONTENT_TYPE, "text/event-stream".parse().unwrap());
+    headers.insert(CACHE_CONTROL, "no-cache".parse().unwrap());
+    response
+}
+
+/// Answers a request with the given stream of events, as a handler's
+/// result; see [`sse_response`].  This makes a streaming route a one-liner,
+/// e.g. `build.get("/events", |_, _| sse_handler(updates()))`.
+pub fn sse_handler<S, E>(events: S) -> HandlerFuture
+where
+    S: Stream<Item = Event, Error = E> + Send + 'static,
+    E: Into<Box<dyn Error + Send + Sync>> + 'static,
+{
+    Box::new(futures::future::ok(sse_response(events)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::Router;
+    use hyper::service::Service;
+    use hyper::{Request, StatusCode};
+
+    #[test]
+    fn test_event() {
+        assert_eq!(Event::new("hello").to_string(), "data: hello\n\n");
+        let event = Event::new("one\r\ntwo")
+            .with_event("update")
+            .with_id("5")
+            .with_retry(Duration::from_secs(3));
+        assert_eq!(
+            event.to_string(),
+            "event: update\nid: 5\nretry: 3000\ndata: one\ndata: two\n\n"
+        );
+    }
+
+    #[test]
+    fn test_sse_handler() {
+        let mut build = Router::build();
+        build.get("/events", |_, _| {
+            let events = vec![Event::new("first"), Event::new("second").with_id("2")];
+            sse_handler(futures::stream::iter_ok::<_, std::io::Error>(events))
+        });
+        let mut router = build.finish();
+
+        let response = router
+            .call(Request::get("/events").body(Body::empty()).unwrap())
+            .wait()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[CONTENT_TYPE], "text/event-stream");
+        assert_eq!(response.headers()[CACHE_CONTROL], "no-cache");
+        assert_eq!(
+            &*response.into_body().concat2().wait().unwrap(),
+            &b"data: first\n\nid: 2\ndata: second\n\n"[..]
+        );
+    }
+}
@@ -0,0 +1,113 @@
+use hyper::{Body, Request};
+use std::fmt::{Debug, Formatter, Result as FmtResult};
+use std::ops::Deref;
+use std::sync::Arc;
+
+type InjectFn = dyn Fn(&mut Request<Body>) + Send + Sync + 'static;
+
+/// The states of a router, kept in its extensions.  Each one is put into
+/// the extensions of every request that's given to a handler.
+#[derive(Clone, Default)]
+pub(super) struct States(Vec<Arc<InjectFn>>);
+
+impl States {
+    pub(super) fn push<S: Send + Sync + 'static>(&mut self, state: S) {
+        let state = State(Arc::new(state));
+        self.0.push(Arc::new(move |req: &mut Request<Body>| {
+            req.extensions_mut().insert(state.clone());
+        }))
+    }
+
+    pub(super) fn inject(&self, req: &mut Request<Body>) {
+        for inject in &self.0 {
+            inject(req)
+        }
+    }
+}
+
+impl Debug for States {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("States").field("len", &self.0.len()).finish()
+    }
+}
+
+/// State shared by the handlers of a router, e.g. a database pool, or the
+/// application's configuration.  See [`super::Build::with_state`].
+pub struct State<S>(Arc<S>);
+
+impl<S: Send + Sync + 'static> State<S> {
+    /// The state of the given type, from a request that was given to a
+    /// handler, if the router has one.
+    pub fn from_request(req: &Request<Body>) -> Option<Self> {
+        req.extensions().get::<Self>().cloned()
+    }
+}
+
+impl<S> Clone for State<S> {
+    fn clone(&self) -> Self {
+        State(self.0.clone())
+    }
+}
+
+impl<S> Deref for State<S> {
+    type Target = S;
+
+    fn deref(&self) -> &S {
+        &self.0
+    }
+}
+
+impl<S: Debug> Debug for State<S> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_tuple("State").field(&self.0).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::Router;
+    use super::State;
+    use futures::prelude::*;
+    use hyper::service::Service;
+    use hyper::{Body, Method, Request, Response, StatusCode};
+
+    struct Config {
+        greeting: &'static str,
+    }
+
+    #[test]
+    fn test_state() {
+        let mut build = Router::build();
+        build
+            .stateful(Method::GET, "/hello/{}", |_, params, config: &Config| {
+                let body = format!("{}, {}", config.greeting, params[0]);
+                Box::new(futures::future::ok(Response::new(Body::from(body))))
+            })
+            .stateful(Method::GET, "/count", |_, _, count: &u32| {
+                Box::new(futures::future::ok(Response::new(Body::from(count.to_string()))))
+            })
+            .get("/plain", |req, _| {
+                let config = State::<Config>::from_request(&req).unwrap();
+                Box::new(futures::future::ok(Response::new(Body::from(config.greeting))))
+            })
+            .with_state(Config { greeting: "hello" })
+            .error_handler(|_| {
+                let mut response = Response::new(Body::empty());
+                *response.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+                response
+            });
+        let mut router = build.finish();
+        let mut call = |path: &str| {
+            router
+                .call(Request::get(path).body(Body::empty()).unwrap())
+                .wait()
+                .unwrap()
+        };
+
+        let response = call("/hello/jane");
+        assert_eq!(&*response.into_body().concat2().wait().unwrap(), b"hello, jane");
+        let response = call("/plain");
+        assert_eq!(&*response.into_body().concat2().wait().unwrap(), b"hello");
+        assert_eq!(call("/count").status(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+}
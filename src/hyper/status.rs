@@ -0,0 +1,297 @@
+use super::Handler;
+use failure::Error;
+use hyper::header::{HeaderName, HeaderValue, CONTENT_LENGTH, CONTENT_TYPE};
+use hyper::{Body, HeaderMap, Response, StatusCode};
+use std::fmt::{Debug, Formatter, Result as FmtResult};
+use std::sync::Arc;
+
+type ErrorFn = dyn Fn(Error) -> Response<Body> + Send + Sync + 'static;
+
+/// The handler for requests that match no routes.  See
+/// [`super::Build::not_found`].
+pub(super) struct NotFound(pub(super) Handler);
+
+/// The handler for requests whose path has routes, but not for their
+/// method.  See [`super::Build::method_not_allowed`].
+pub(super) struct MethodNotAllowed(pub(super) Handler);
+
+/// The handler for errors returned by the other handlers.  See
+/// [`super::Build::error_handler`].
+#[derive(Clone)]
+pub(super) struct ErrorHandler(pub(super) Arc<ErrorFn>);
+
+#[derive(Debug, Clone)]
+/// A fixed response for requests that match no routes, or whose path has
+/// routes but not for their method, instead of an empty one.  See
+/// [`super::Build::not_found_response`] and
+/// [`super::Build::method_not_allowed_response`].
+pub struct StatusResponse {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: String,
+}
+
+impl StatusResponse {
+    /// Creates a response with the given status, and an empty body.
+    pub fn new(status: StatusCode) -> Self {
+        StatusResponse {
+            status,
+            headers: HeaderMap::new(),
+            body: String::new(),
+        }
+    }
+
+    /// Gives the response the given body, of the given content type, e.g.
+    /// `application/problem+json`.
+    pub fn with_body<B: Into<String>>(mut self, content_type: &'static str, body: B) -> Self {
+        self.headers.insert(CONTENT_TYPE, HeaderValue::from_static(content_type));
+        self.body = body.into();
+        self
+    }
+
+    /// Adds the given header to the response.  A header of the same name
+    /// is kept, so that a header can be given more than one value.
+    pub fn with_header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.headers.append(name, value);
+        self
+    }
+
+    /// Makes the response into a handler that answers every request with
+    /// it.
+    pub(super) fn into_handler(self) -> Handler {
+        Arc::new(move |_, _| {
+            let mut response = Response::new(Body::from(self.body.clone()));
+            *response.status_mut() = self.status;
+            *response.headers_mut() = self.headers.clone();
+            response
+                .headers_mut()
+                .insert(CONTENT_LENGTH, HeaderValue::from(self.body.len()));
+            Box::new(futures::future::ok(response))
+        })
+    }
+}
+
+impl Debug for NotFound {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.write_str("NotFound")
+    }
+}
+
+impl Debug for MethodNotAllowed {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.write_str("MethodNotAllowed")
+    }
+}
+
+impl Debug for ErrorHandler {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.write_str("ErrorHandler")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::Router;
+    use crate::router::ParseParams;
+    use futures::prelude::*;
+    use hyper::header::ALLOW;
+    use hyper::service::Service;
+    use hyper::{Body, Request, Response, StatusCode};
+
+    fn status(status: StatusCode) -> Response<Body> {
+        Response::builder().status(status).body(Body::empty()).unwrap()
+    }
+
+    #[test]
+    fn test_status_handlers() {
+        let mut build = Router::build();
+        build
+            .get("/users", |_, _| Box::new(futures::future::ok(status(StatusCode::OK))))
+            .post("/users", |_, _| Box::new(futures::future::err(failure::err_msg("nope"))))
+            .default_fn(|_, _| Box::new(futures::future::ok(status(StatusCode::IM_A_TEAPOT))))
+            .not_found(|_, _| Box::new(futures::future::ok(status(StatusCode::NOT_FOUND))))
+            .method_not_allowed(|_, _| {
+                Box::new(futures::future::ok(status(StatusCode::METHOD_NOT_ALLOWED)))
+            })
+            .error_handler(|error| {
+                Response::builder()
+                    .status(StatusCode::INTERNAL_SERVER_ERROR)
+                    .body(Body::from(error.to_string()))
+                    .unwrap()
+            });
+        let mut router = build.finish();
+        let mut call = |method: &str, path: &str| {
+            let request = Request::builder()
+                .method(method)
+                .uri(path)
+                .body(Body::empty())
+                .unwrap();
+            router.call(request).wait().unwrap()
+        };
+
+        assert_eq!(call("GET", "/users").status(), StatusCode::OK);
+        assert_eq!(call("GET", "/posts").status(), StatusCode::NOT_FOUND);
+
+        let response = call("DELETE", "/users");
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(response.headers()[ALLOW], "GET, POST");
+
+        let response = call("POST", "/users");
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(&*response.into_body().concat2().wait().unwrap(), b"nope");
+    }
+
+    #[test]
+    fn test_status_responses() {
+        use super::StatusResponse;
+        use hyper::header::{HeaderValue, CONTENT_TYPE, CACHE_CONTROL};
+
+        let mut build = Router::build();
+        build
+            .get("/users", |_, _| Box::new(futures::future::ok(status(StatusCode::OK))))
+            .not_found_response(
+                StatusResponse::new(StatusCode::NOT_FOUND)
+                    .with_body("application/json", r#"{"error":"not found"}"#)
+                    .with_header(CACHE_CONTROL, HeaderValue::from_static("no-store")),
+            )
+            .method_not_allowed_response(
+                StatusResponse::new(StatusCode::METHOD_NOT_ALLOWED).with_body("text/plain", "nope"),
+            );
+        let mut router = build.finish();
+        let mut call = |method: &str, path: &str| {
+            let request = Request::builder()
+                .method(method)
+                .uri(path)
+                .body(Body::empty())
+                .unwrap();
+            router.call(request).wait().unwrap()
+        };
+
+        let response = call("GET", "/posts");
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(response.headers()[CONTENT_TYPE], "application/json");
+        assert_eq!(response.headers()[CACHE_CONTROL], "no-store");
+        assert_eq!(response.headers()["content-length"], "21");
+        assert_eq!(
+            &*response.into_body().concat2().wait().unwrap(),
+            &br#"{"error":"not found"}"#[..]
+        );
+
+        let response = call("DELETE", "/users");
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(response.headers()[ALLOW], "GET");
+        assert_eq!(response.headers()[CONTENT_TYPE], "text/plain");
+        assert_eq!(&*response.into_body().concat2().wait().unwrap(), b"nope");
+
+        // A `HEAD` request is still answered without a body.
+        let response = call("HEAD", "/posts");
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert!(response.into_body().concat2().wait().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_default_without_status_handlers() {
+        let mut build = Router::build();
+        build
+            .get("/users", |_, _| Box::new(futures::future::ok(status(StatusCode::OK))))
+            .default_fn(|_, _| Box::new(futures::future::ok(status(StatusCode::IM_A_TEAPOT))));
+        let mut router = build.finish();
+
+        let response = router
+            .call(Request::delete("/users").body(Body::empty()).unwrap())
+            .wait()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::IM_A_TEAPOT);
+    }
+
+    #[test]
+    fn test_invalid_param() {
+        let mut build = Router::build();
+        build
+            .get("/users/{}", |_, params| {
+                let response = params
+                    .parse::<u64>(0)
+                    .map(|id| Response::new(Body::from(id.to_string())))
+                    .map_err(Into::into);
+                Box::new(futures::future::result(response))
+            })
+            .error_handler(|_| status(StatusCode::INTERNAL_SERVER_ERROR));
+        let mut router = build.finish();
+        let mut call = |path: &str| {
+            router
+                .call(Request::get(path).body(Body::empty()).unwrap())
+                .wait()
+                .unwrap()
+                .status()
+        };
+
+        assert_eq!(call("/users/5"), StatusCode::OK);
+        assert_eq!(call("/users/me"), StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn test_typed() {
+        use crate::router::{ParamError, PathParams};
+        use hyper::Method;
+
+        struct UserPath {
+            id: u64,
+        }
+
+        impl PathParams for UserPath {
+            const NAMES: &'static [&'static str] = &["id"];
+
+            fn from_params<S: AsRef<str>>(indices: &[usize], params: &[S]) -> Result<Self, ParamError> {
+                Ok(UserPath {
+                    id: params.parse(indices[0])?,
+                })
+            }
+        }
+
+        let mut build = Router::build();
+        build.typed(Method::GET, "/users/{id}", |_, path: UserPath| {
+            Box::new(futures::future::ok(Response::new(Body::from(path.id.to_string()))))
+        });
+        let mut router = build.finish();
+
+        let response = router
+            .call(Request::get("/users/5").body(Body::empty()).unwrap())
+            .wait()
+            .unwrap();
+        assert_eq!(&*response.into_body().concat2().wait().unwrap(), b"5");
+        let response = router
+            .call(Request::get("/users/me").body(Body::empty()).unwrap())
+            .wait()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn test_parse_query() {
+        use crate::router::Query;
+
+        let mut build = Router::build();
+        build
+            .get("/search", |req, _| {
+                let response = req.extensions().get::<Query>().unwrap().parse::<u32>("page");
+                let response = response
+                    .map(|page| Response::new(Body::from(page.unwrap_or(1).to_string())))
+                    .map_err(Into::into);
+                Box::new(futures::future::result(response))
+            })
+            .parse_query();
+        let mut router = build.finish();
+        let mut call = |path: &str| {
+            router
+                .call(Request::get(path).body(Body::empty()).unwrap())
+                .wait()
+                .unwrap()
+        };
+
+        let response = call("/search?q=cats&page=3");
+        assert_eq!(&*response.into_body().concat2().wait().unwrap(), b"3");
+        let response = call("/search");
+        assert_eq!(&*response.into_body().concat2().wait().unwrap(), b"1");
+        assert_eq!(call("/search?page=last").status(), StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,73 @@
+use super::HandlerFuture;
+use futures::prelude::*;
+use hyper::{Body, Response, StatusCode};
+use std::time::Duration;
+use tokio_timer::Timeout as Deadline;
+
+/// How long a route's handler has to answer.  See
+/// [`super::Route::with_timeout`].
+#[derive(Debug, Clone, Copy)]
+pub(super) struct Timeout(pub(super) Duration);
+
+/// Races the given response against the timeout, answering with a 504
+/// Gateway Timeout if the timeout elapses first.  The handler's future is
+/// dropped then, which cancels whatever it was waiting on.
+pub(super) fn race(response: HandlerFuture, Timeout(timeout): Timeout) -> HandlerFuture {
+    Box::new(Deadline::new(response, timeout).or_else(|error| {
+        if error.is_elapsed() {
+            let mut response = Response::new(Body::empty());
+            *response.status_mut() = StatusCode::GATEWAY_TIMEOUT;
+            Ok(response)
+        } else if error.is_timer() {
+            Err(failure::Error::from(error.into_timer().unwrap()))
+        } else {
+            Err(error.into_inner().unwrap())
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Route, Router};
+    use futures::prelude::*;
+    use hyper::service::Service;
+    use hyper::{Body, Request, Response, StatusCode};
+    use std::time::{Duration, Instant};
+    use tokio::runtime::current_thread::Runtime;
+    use tokio_timer::Delay;
+
+    fn after(delay: Duration) -> super::HandlerFuture {
+        Box::new(
+            Delay::new(Instant::now() + delay)
+                .map_err(failure::Error::from)
+                .map(|_| Response::new(Body::from("done"))),
+        )
+    }
+
+    #[test]
+    fn test_timeout() {
+        let mut build = Router::build();
+        build
+            .add(Route::get("/slow", |_, _| after(Duration::from_secs(5))).with_timeout(Duration::from_millis(20)))
+            .add(Route::get("/fast", |_, _| after(Duration::from_millis(1))).with_timeout(Duration::from_secs(5)))
+            .get("/failing", |_, _| Box::new(futures::future::err(failure::err_msg("nope"))));
+        let mut router = build.finish();
+        let mut runtime = Runtime::new().unwrap();
+
+        let start = Instant::now();
+        let response = runtime
+            .block_on(router.call(Request::get("/slow").body(Body::empty()).unwrap()))
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::GATEWAY_TIMEOUT);
+        assert!(start.elapsed() < Duration::from_secs(5));
+
+        let response = runtime
+            .block_on(router.call(Request::get("/fast").body(Body::empty()).unwrap()))
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(&*runtime.block_on(response.into_body().concat2()).unwrap(), b"done");
+
+        let error = runtime.block_on(router.call(Request::get("/failing").body(Body::empty()).unwrap()));
+        assert!(error.is_err());
+    }
+}
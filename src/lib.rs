@@ -27,7 +27,6 @@
 //! extern crate futures;
 //!
 //! use hyper::{Request, Response, Method, Body, Server};
-//! use hyper::service::make_service_fn;
 //! use hyper::header::CONTENT_LENGTH;
 //! use pathmaker::hyper::Router;
 //! use failure::Error;
@@ -49,13 +48,47 @@
 //! fn main() {
 //!     let address = "0.0.0.0:8080".parse().unwrap();
 //!     let server = Server::bind(&address)
-//!         .serve(make_service_fn(|_| Ok::<_, hyper::Error>(router()))).map_err(|e| {
+//!         .serve(router().into_make_service()).map_err(|e| {
 //!             eprintln!("error: {:?}", e);
 //!         });
 //!     // hyper::rt::run(server)
 //! }
 //! ```
 //!
+//! The router is built once, by `into_make_service`, and shared by every
+//! connection; each one only clones an `Arc` to it.
+//!
+//! For any other library that's built on the `http` crate's types, the
+//! `with-http` feature adds the `pathmaker::http` module, which has the same
+//! shortcut methods (`get`, `post`, `methods`, and so on) for handlers that
+//! take an `http::Request<B>` and return whatever the library expects, e.g.
+//! `pathmaker::http::Router<Body, Response>`.  `Router::dispatch` routes a
+//! request the way the hyper `Service` does, and says what became of it: the
+//! handler's result, an automatic `OPTIONS` answer, or the status to respond
+//! with.
+//!
+//! Servers that aren't async (e.g. rouille, or tiny_http) can use the
+//! `pathmaker::sync` module, also added by the `with-http` feature, whose
+//! handlers return an `http::Response<B>` directly.  `Router::respond` routes
+//! a request, and returns the response: the handler's, or an empty one with
+//! the status the hyper `Service` would have answered with.  The server's
+//! own request and response types are converted to and from the `http`
+//! crate's around it.
+//!
+//! Async servers that aren't built on tokio or hyper (e.g. `async-h1`, on
+//! async-std or smol) can use the `pathmaker::future` module instead, whose
+//! handlers return a `std::future` of the response, so they can be `async`
+//! functions (see `Build::async_fn`).  Its `Router::respond` returns the
+//! future to await, and needs no runtime of its own.
+//!
+//! Applications behind a FastCGI server (e.g. nginx's `fastcgi_pass`) can
+//! use the `fastcgi` feature, whose `pathmaker::fastcgi::request` makes an
+//! `http::Request` from the parameters the server sends: the method from
+//! `REQUEST_METHOD`, the path from `PATH_INFO` (or `SCRIPT_NAME`, if there
+//! isn't one), the query from `QUERY_STRING`, and the headers from the
+//! `HTTP_` parameters.  `write_response` writes a response back in the CGI
+//! format, so the loop is just a `sync::Router::respond` between the two.
+//!
 //! ## Query Parameters
 //!
 //! Support for query parameters is allowed by using `{}` in the path:
@@ -67,13 +100,14 @@
 //! # use hyper::{Request, Response, Body};
 //! # use hyper::header::CONTENT_LENGTH;
 //! # use failure::Error;
-//! # fn handler(_: Request<Body>, _: Vec<String>) -> Box<dyn Future<Item = Response<Body>, Error = Error> + Send> {
+//! # use pathmaker::Params;
+//! # fn handler(_: Request<Body>, _: &Params) -> Box<dyn Future<Item = Response<Body>, Error = Error> + Send> {
 //! #   let body = "Hello, world!";
 //! #   Box::new(futures::future::result(Response::builder()
 //! #       .header(CONTENT_LENGTH, body.len() as u64).body(Body::from(body))
 //! #       .map_err(Error::from)))
 //! # }
-//! # fn hello_handler(a: Request<Body>, b: Vec<String>) -> Box<dyn Future<Item = Response<Body>, Error = Error> + Send> { handler(a, b) }
+//! # fn hello_handler(a: Request<Body>, b: &Params) -> Box<dyn Future<Item = Response<Body>, Error = Error> + Send> { handler(a, b) }
 //! fn router() -> Router {
 //!     let mut build = Router::build();
 //!     build.get("/foo", handler)
@@ -91,8 +125,9 @@
 //! # use failure::Error;
 //! # use futures::prelude::*;
 //! # use hyper::header::CONTENT_LENGTH;
+//! use pathmaker::Params;
 //! //...
-//! fn hello_handler(_: Request<Body>, params: Vec<String>) -> Box<dyn Future<Item = Response<Body>, Error = Error> + Send> {
+//! fn hello_handler(_: Request<Body>, params: &Params) -> Box<dyn Future<Item = Response<Body>, Error = Error> + Send> {
 //!     let body = format!("Hello, {}!", params[0]);
 //!     Box::new(futures::future::result(
 //!         Response::builder()
@@ -104,34 +139,666 @@
 //! // ...
 //! ```
 //!
+//! The parameters are `Params`, a slice of `Cow<str>`: each one borrows from
+//! the path, unless it had to be URL decoded, so they aren't copied for every
+//! request.  They're indexed like a list of strings, and `params.parse(0)`
+//! converts one (see below); a handler that keeps one past its call, e.g. in
+//! the future it returns, copies it with `to_string`.
+//!
 //! Query parameters can be filtered down by format:
 //!
 //! - `{}`, `{:string}` (the default): anything that isn't a `/` character is
 //!   matched.
 //! - `{:int}`: a positive or negative number.
 //! - `{:uint}`: just a number, no sign allowed.
+//! - `{:int(-10..10)}`, `{:uint(1..=9999)}`: a number, as above, that must fall
+//!   within the given range.  Any of Rust's range forms may be used.
 //! - `{:uuid}`: a UUID, in 8-4-4-4-12 format.
+//! - `{:semver}`: a semantic version, e.g. `1.2.3`, `1.0.0-rc.1`, or
+//!   `1.0.0+build.5`.
+//! - `{:ipv4}`: an IPv4 address in dotted-decimal form, e.g. `192.168.0.1`.
+//! - `{:ipv6}`: an IPv6 address, e.g. `2001:db8::1` or `::ffff:192.168.0.1`.  The
+//!   `:` may be given percent-encoded (`%3A`), since paths are decoded before
+//!   they're matched.
+//! - `{:email}`: an email address, e.g. `someone@example.com`.  This only checks
+//!   that the address looks like `local@domain.tld`; it doesn't attempt to
+//!   validate it fully.
+//! - `{:grpc_method}`: the name of a gRPC method, e.g. `GetUser` in
+//!   `/users.v1.UserService/GetUser`; that is, an identifier made of letters,
+//!   digits, and `_`, that doesn't start with a digit.  With hyper,
+//!   `Build::grpc_service("users.v1.UserService", handler)` adds a `POST` route
+//!   for every method of a service, so raw gRPC (or Connect) calls can be
+//!   dispatched by path.
+//! - `{:b64}`: URL-safe base64 (`A-Z`, `a-z`, `0-9`, `-`, and `_`), optionally
+//!   padded with `=`.  The length of the value (not counting padding) can be
+//!   constrained with an exact length or a range, e.g. `{:b64(22)}` or
+//!   `{:b64(16..=64)}`.
+//! - `{:one_of(json|xml|csv)}`: exactly one of the given literals, separated by
+//!   `|`.  The literal that matched is given as the parameter.
+//!
+//! Any other kind, or arguments that a kind doesn't take (e.g. `{:unit}` or
+//! `{:uint(5..1)}`), is an error: `Route::new` panics, and `Route::try_new`
+//! returns a `RouteParseError`.
+//!
+//! Parameters can also be given a name before the kind, e.g. `{id:uint}` or
+//! `{format:one_of(json|xml)}`; `{id}` is the same as `{id:string}`.  The name
+//! can be used to find the parameter with [`Router::lookup_route`] and
+//! `Route::param_index`; otherwise, parameters are given to the handler in the
+//! order they appear in the path.
+//!
+//! `Router::lookup_index` returns the index of the route that matched (its
+//! position in `Router::routes`) instead of its handler, so the router can
+//! be used purely as a matcher, e.g. a `Router<Method, ()>`, with the
+//! handlers kept in a table of their own.
+//!
+//! Parameters are given as strings, but `ParseParams::parse` converts one
+//! by its index into any type that implements `FromParam`, e.g.
+//! `let id: u64 = params.parse(0)?;`.  `FromParam` is implemented for every
+//! type that implements `FromStr`, so this covers the integers, `String`, IP
+//! addresses, and `Uuid`.  If a hyper handler fails with a `ParamError` for
+//! an invalid value, the `Service` answers with a 404 Not Found, as if the
+//! route hadn't matched.
+//!
+//! Parameters can be taken by name into a struct that implements
+//! `PathParams`, with `Route::path_params`, e.g. `struct UserPath { id: u64,
+//! post: Uuid }` for `/users/{id:uint}/posts/{post:uuid}`.  With the `derive`
+//! feature, `#[derive(PathParams)]` implements it for structs with named
+//! fields.  With hyper, `Route::typed` (or `Build::typed`) creates a route
+//! whose handler is given the struct instead of the list of parameters; it
+//! panics if the path is missing one of the struct's parameters, and answers
+//! with a 404 Not Found if one can't be converted.
+//!
+//! With the `derive` feature, and the `http` crate's types, the `routes!`
+//! macro builds routes from a list, checking each path when it's compiled, so
+//! that a typo like `{id:unit}` or a missing `}` is a compile error instead of
+//! a panic at startup:
+//!
+//! ```rust,ignore
+//! let mut build: pathmaker::hyper::Build = pathmaker::routes! {
+//!     GET "/users/{id:uint}" => show_user,
+//!     POST "/users" => create_user,
+//!     ANY "/proxy/{}" => proxy,
+//! };
+//! ```
+//!
+//! The method is one of the standard ones, in upper case, or `ANY`.  The macro
+//! returns the builder, which looks routes up with `Build::trie_matching`, so
+//! routes with only literal segments are found a segment at a time, without
+//! matching a regex.  A route whose whole path is literal, and that no route
+//! before it could match, is found by a `match` on the path the macro
+//! generates, before the router looks at its routes at all.
+//!
+//! A parameter doesn't have to take up a whole segment; it can be mixed with
+//! literal text, like a prefix or an extension, e.g. `/orders/order-{id:uint}`,
+//! `/v{version:uint}/users`, `/report/{id:uint}.{format}`, or
+//! `/archive/{name}.tar.gz`.
+//!
+//! A segment can also hold more than one parameter, e.g.
+//! `/range/{from:uint}-{to:uint}`.  Since `{:string}` matches as much as it
+//! can, it's best to use a narrower kind for all but the last parameter in a
+//! segment.
+//!
+//! To match a literal brace, double it: `/templates/{{name}}` matches the
+//! path `/templates/{name}`, and `/templates/{{{name}}}` matches
+//! `/templates/{user}`, giving `user` as the parameter.
 //!
 //! More can be added if requested.
 //!
+//! ## Hyper
+//!
+//! Everything in this section needs the `with-hyper` feature (on by default),
+//! and lives in the `pathmaker::hyper` module.
+//!
+//! Handlers can also be `async`, so that they're written with `.await`
+//! rather than with combinators.  `Build::async_fn` (or `Route::async_fn`)
+//! adds a route whose handler returns a `std::future::Future` of a
+//! `Result<Response<Body>, E>`, where the error converts into a
+//! `failure::Error`, e.g. `build.async_fn(Method::GET, "/hello/{}", hello)`
+//! for an `async fn hello(req: Request<Body>, params: Vec<String>)`, which is
+//! given its parameters as owned strings, since its future can't borrow them;
+//! `async_handler` turns one into a `Handler` for any other use.
+//!
+//! The hyper integration is built on hyper 0.12, whose `Service` trait is
+//! defined in terms of `futures` 0.1, so `Handler` and the `Service`
+//! implementations still return `futures` 0.1 futures; `async` handlers are
+//! adapted to them as they're polled.
+//!
+//! Streaming endpoints can answer with Server-Sent Events: `sse_handler`
+//! takes a `Stream` of `Event`s, and makes the handler's result, a
+//! `text/event-stream` response that sends each event as it's ready, e.g.
+//! `build.get("/events", |_, _| sse_handler(updates()))`.  An `Event` has its
+//! data, and optionally a name (`with_event`), an id (`with_id`), and a
+//! reconnection delay (`with_retry`).  `sse_response` makes the response by
+//! itself, for handlers that do more than that.
+//!
+//! Trivial replies don't need a hand-built response: the `hyper::reply`
+//! module has `text`, `html`, and `json`, which answer with the given body
+//! and set its `Content-Type` and `Content-Length`, and `status`, which
+//! answers with an empty body, e.g.
+//! `build.get("/", |_, _| reply::text("hello"))`.  Since the router doesn't
+//! depend on a serialization library, `json` takes JSON that's already been
+//! serialized.
+//!
+//! Requests can also be forwarded to another server, turning the router into
+//! a small API gateway: `proxy_to("http://10.0.0.5:8080")` is a handler that
+//! sends the request upstream, streaming its body both ways, and answers with
+//! the upstream's response (or a 502 Bad Gateway if it can't be reached).
+//! Hop-by-hop headers (like `Connection`) are dropped, and `X-Forwarded-Host`,
+//! `X-Forwarded-Proto`, and `Forwarded` are set; the client's address is added
+//! to `X-Forwarded-For` if the request has it as a `SocketAddr` extension.  The
+//! server can't tell the scheme of a request, so an incoming
+//! `X-Forwarded-Proto` is kept, and `http` is assumed otherwise;
+//! `Proxy::with_proto("https")` sets it, e.g. behind TLS.
+//! `Proxy::new(upstream).strip_prefix("/api").handler()` also removes the
+//! matched prefix from the path, e.g. for
+//! `build.add(Route::any("/api/{}", handler))`.  The path is forwarded as it
+//! was given, so one with a dot segment (like `/api/../admin`), which could
+//! reach outside of the prefix upstream, is answered with a 400 Bad Request.
+//! Only `http` upstreams are supported.
+//!
+//! ## Building URLs
+//!
+//! A route can be named with `Route::with_name`, so that its url can be built
+//! from its parameters with `Router::url_for`, e.g.
+//! `router.url_for("user", &[&5])` gives `/users/5` for the route
+//! `/users/{id:uint}`.  `Router::url` does the same with the parameters given
+//! by name, e.g. `router.url("user")?.param("id", 5).build()`.  Either way,
+//! each value is checked against the kind of its parameter, and
+//! percent-encoded; giving the wrong number of values, or `abc` for a
+//! `{:uint}`, is an error (`UrlError`) that says what was wrong.
+//!
 //! ## Route Evaluation
 //!
 //! Routes are evaluated from top to bottom.  The first route that matches is used.
+//!
+//! If keeping the routes in order is a chore, `Build::by_specificity` sorts them
+//! by how specific they are instead, so that `/users/new` beats
+//! `/users/{id:uint}`, which beats `/users/{id}`, no matter which was added
+//! first.  Paths are compared segment by segment, from left to right; routes
+//! that are equally specific are still evaluated in the order they were added.
+//!
+//! A route can also be given a priority with `Route::with_priority`; routes
+//! with a higher priority are evaluated before those with a lower one, no
+//! matter the order they were added in, or how specific they are.  Routes
+//! have a priority of `0` by default.
+//!
+//! For large route tables, `Build::trie_matching` has the router look routes
+//! up with a trie, a segment of the path at a time, instead of checking the
+//! path against every route's pattern at once.  Only routes made of literal
+//! segments and string parameters (e.g. `/users/{id}/posts`) go in the trie;
+//! the rest (like `{:uint}`, or `{id}.{format}`) are still matched by their
+//! patterns.  Routes are evaluated in the same order either way.
+//!
+//! Routers with more than a hundred or so routes split their regex sets into
+//! shards, by the first segment of each route's path when it's literal text
+//! (e.g. `users` in `/users/{id}`), so a lookup only checks the routes that
+//! could match, and no single set gets close to the regex size limit.  This
+//! is automatic, and doesn't change the order routes are evaluated in.
+//! Routes with the same pattern (like `GET` and `PUT` routes for the same
+//! path) share a single compiled regex, and a single entry in their set.
+//!
+//! Each route's pattern is compiled when the router is built, so a pattern
+//! that can't be compiled is reported by `Build::try_finish`.  With
+//! thousands of routes, `Build::lazy_patterns` leaves that until the first
+//! lookup that needs the pattern instead, which makes startup much quicker;
+//! with `trie_matching`, routes in the trie never need theirs at all.
+//!
+//! `Build::with_lookup_cache(capacity)` also keeps the routes that matched the
+//! most recently looked up paths, so a hot path doesn't have to be matched
+//! again.  It's keyed by the path alone, since that's all the patterns
+//! depend on; methods and guards are still checked on every lookup.  Clones
+//! of the router (like the ones hyper makes for every connection) share the
+//! cache.
+//!
+//! A route can match every method, instead of just one, by creating it with
+//! `Route::new_any` (or `any` with hyper); this is handy for catch-all
+//! proxies.  It's still evaluated in order with the other routes.
+//!
+//! Similarly, `Route::for_methods` (or `Build::methods` with hyper, e.g.
+//! `build.methods(&[Method::GET, Method::POST], "/form", handler)`) creates a
+//! single route that matches a few methods, so the handler only has to be
+//! given once.  The hyper `Handler` is an `Arc`, so a handler can also be
+//! shared between routes with different paths by cloning it, e.g.
+//! `Route::new("/old", Method::GET, handler.clone())`; it also means the
+//! router itself can be cloned.
+//!
+//! `Build::fallback_method` lets one method fall back to the routes of
+//! another, when none of its own routes match.  With hyper,
+//! `Build::head_fallback` uses this to handle `HEAD` requests with the `GET`
+//! routes, dropping the body of the response.
+//!
+//! `Router::allowed_methods` lists the methods of the routes that match a
+//! path.  `Build::auto_options` uses this to answer a method (usually
+//! `OPTIONS`) automatically, for paths that have routes but none for that
+//! method; with hyper, `Build::options_fallback` responds to these `OPTIONS`
+//! requests with a 204 No Content and an `Allow` header.
+//!
+//! With hyper, the default handles every request that no route matches,
+//! without knowing why.  `Build::not_found` and `Build::method_not_allowed`
+//! set separate handlers for paths that have no routes, and for paths that
+//! have routes but none for the request's method (the response gets an
+//! `Allow` header with the methods that are allowed); either takes the place
+//! of the default.  `Build::error_handler` makes the response for an error
+//! returned by a handler, e.g. a 500 Internal Server Error.  For a fixed
+//! answer, `Build::not_found_response` and `Build::method_not_allowed_response`
+//! take a `StatusResponse`, with its own status, headers, and body (with its
+//! content type), e.g. a JSON error document for an API, instead of an empty
+//! response.
+//! `Build::catch_panics` catches panics in the handlers, whether they happen
+//! when the handler is called or while its future is polled, and turns them
+//! into a `Panic` error, which is given to the error handler (or answered
+//! with a 500 Internal Server Error without one), rather than taking the
+//! connection down.
+//!
+//! `Build::validate` finds the routes that can never match, because a route
+//! that's evaluated before them always matches first, e.g. `/users/{id:uint}`
+//! added after `/users/{id}` for the same method.  Each one is reported as a
+//! `Conflict`, with the index, path, and pattern of both routes; routes that
+//! were added twice (with the same pattern and methods) are reported as
+//! duplicates.  This is conservative, so it only reports the routes it's sure
+//! of.
+//!
+//! `Build::finish` panics if the router can't be built, e.g. because there are
+//! so many routes that their patterns go over the regex size limit.
+//! `Build::try_finish` returns a `BuildError` instead, which says which route
+//! was the first that couldn't be added.
+//! In the same way, `Route::new` panics if the route's pattern can't be
+//! compiled, and `Route::try_new` returns a `RouteParseError` instead.
+//!
+//! `PathPattern::parse` parses a path on its own, without making a route, so
+//! that paths given by users can be checked before they're used.  It's
+//! stricter than `Route::new`: a brace that isn't doubled, and doesn't open or
+//! close a parameter, is an error instead of literal text.  It returns a
+//! `PatternError` saying what's wrong, and where (e.g. an unmatched brace, a
+//! parameter that isn't written like one, or an unknown kind), and never
+//! panics, whatever it's given.  A parsed pattern has the path's parameter
+//! names, and can be matched against paths with `PathPattern::captures`.
+//!
+//! `Router::routes` lists the routes of a router in the order they're
+//! evaluated in, as `RouteInfo`s with their path, methods, name, parameters,
+//! and so on, e.g. for debugging, or for generating documentation.
+//! Printing a router (with `{}`) shows the same as a table, with a row for
+//! each route's methods, path, name, and notes like its priority and guards,
+//! which is handy to log at startup.  For snapshot tests (e.g. with `insta`),
+//! `Router::snapshot` dumps the same routes as plain text, with a line for
+//! each of their details, including their patterns and parameters, and
+//! nothing aligned, so that the snapshot only changes where the routing does.
+//!
+//! `Router::explain` shows why a request matches the route it does, or none
+//! at all: for each route, in the order they're evaluated in, it gives the
+//! step at which the route stopped matching, e.g. the path or method didn't
+//! match, a parameter was out of its range, or a guard rejected the request.
+//! `Router::explain_with` checks guards and content types against the request,
+//! like `Router::lookup_with`.
+//!
+//! For metrics, `Build::on_match` sets a hook that's called whenever a lookup
+//! matches a route (or falls back to the default), with the `RouteInfo` of
+//! the route, so that requests can be counted by route, rather than by path.
+//!
+//! With hyper, `Build::with_access_log` logs every request the router
+//! handles, with its method, the route it matched, and the status, length,
+//! and latency of its response, to a sink such as `|line| eprintln!("{}", line)`.
+//! `LogFormat::Common` writes the Common Log Format, with the route and
+//! latency added to the end, and `LogFormat::Json` writes a JSON object per
+//! line.
+//!
+//! `Build::wrap` adds middleware around the handlers, for concerns shared
+//! by every route, like authentication, or headers added to every response.
+//! The middleware is given the request, the route's parameters, and a
+//! `Next`, which passes the request on to the rest of the chain with
+//! `Next::run`; it can also answer the request itself, without calling it.
+//! The middleware added first is called first.
+//!
+//! For smaller jobs, `Build::before` adds a hook that's called with every
+//! request (as `&mut Request`) before it's routed, e.g. to stamp it with a
+//! request id, and `Build::after` adds one that's called with every response
+//! (as `&mut Response`), e.g. to add security headers.  `Router::before` and
+//! `Router::after` add them to a router that's already built.
+//!
+//! `Build::with_state` shares state, like a database pool or the
+//! application's configuration, with every handler, instead of each closure
+//! capturing an `Arc` of it.  `Build::stateful` (or `Route::stateful`) adds a
+//! route whose handler is also given a reference to the state of the type it
+//! asks for, e.g. `build.stateful(Method::GET, "/users", |req, params, db: &Db| ...)`,
+//! and any handler can find it with `State::from_request`.
+//!
+//! `Build::cors` sets a Cross-Origin Resource Sharing policy (a `Cors`) for
+//! the router; `Route::with_cors` and `Scope::with_cors` give routes their
+//! own.  A policy allows no origins until it's given some with `with_origin`
+//! (or `allow_any_origin`), and can restrict the methods and request headers
+//! of preflight requests, expose response headers, allow credentials, and
+//! let the browser cache its preflight answers.  The router answers preflight
+//! requests itself, for any path with a route for the requested method that
+//! has a policy, taking the allowed methods from the route table unless the
+//! policy lists them, and adds the policy's headers to the responses of
+//! actual cross-origin requests.
+//!
+//! `Route::with_timeout` gives a route's handler a deadline: the hyper
+//! `Service` races the handler's future against it, and answers with a 504
+//! Gateway Timeout if it elapses first, dropping the handler's future, so
+//! that a slow downstream call can't tie up the connection indefinitely.  The
+//! deadline is kept by the timer of the tokio runtime that's running the
+//! service.
+//!
+//! `Build::body_limit` rejects requests whose body is larger than the given
+//! number of bytes with a 413 Payload Too Large, and `Route::with_body_limit`
+//! gives a route its own limit.  A request that declares a larger
+//! `Content-Length` is rejected before its handler runs; a body without one
+//! fails once it's read past the limit, and the request is rejected all the
+//! same.
+//!
+//! The `test` module has a `TestClient`, which gives requests straight to a
+//! hyper router, the way its `Service` does, so that routes and their
+//! handlers can be tested without binding a socket, e.g.
+//! `client.get("/users/5").header("accept", "application/json").send().await`.
+//! The `TestResponse` it gives back has the status, headers, and the whole
+//! body of the response.
+//!
+//! Routes can also carry typed metadata, e.g. the scopes they require, or
+//! their rate-limit class, with `Route::with_extension`.  The router ignores
+//! it, but it can be read back with `Route::extension` from the route a lookup
+//! matched (see `Router::lookup_route`), or from `RouteInfo`, so that
+//! middleware and documentation generators can use it.
+//!
+//! With the `openapi` feature, `Router::openapi` builds an OpenAPI 3 document
+//! (as JSON) from the routes, with an operation for each route's path and
+//! method.  Path parameters are typed by their kinds, e.g. `{id:uint}` is a
+//! non-negative integer, and `{:one_of(json|xml)}` is an enum.  A route's
+//! name is used as its operation id, and an `ApiOperation` attached with
+//! `Route::with_extension` adds a summary, description, and tags.
+//! `Build::load_openapi` goes the other way, for APIs that are written
+//! spec-first: it adds a route for each operation of an OpenAPI document (as
+//! JSON), bound to the handler registered under its `operationId` in a
+//! `HandlerRegistry` (see below), with kinds for its path parameters taken
+//! from their schemas.
+//!
+//! ## Nesting
+//!
+//! A whole router can be mounted under a prefix with `Build::mount`, e.g.
+//! `build.mount("/api/v1", api)`, so that its route `/users/{id}` matches
+//! `/api/v1/users/42`.  The prefix stays in the path, and can have parameters
+//! of its own, which are given to the handler before the route's.  If the
+//! mounted router has a default, it handles any path under the prefix that no
+//! other route matches.
+//!
+//! A whole router can also be layered over another, with `Router::or`, e.g.
+//! `core.or(plugins)`.  Lookups that miss the first router, including those
+//! whose path matches but whose method doesn't, are retried against the
+//! second, before falling back to the first router's default;
+//! `Router::or_for_missing_paths` only retries paths the first router has no
+//! routes for at all.  Only the second router's routes are used, as with
+//! `Build::mount`; mounting the first router mounts the second's routes after
+//! its own.
+//!
+//! Routes can also be grouped under a prefix without building a separate
+//! router, with `Build::scope`.  It gives a scope to a function, and every
+//! route added to the scope (including with `get`, `post`, etc. with hyper) is
+//! added under the prefix:
+//!
+//! ```rust
+//! # use pathmaker::hyper::Router;
+//! # use futures::prelude::*;
+//! # use hyper::{Request, Response, Body};
+//! # use failure::Error;
+//! # use pathmaker::Params;
+//! # fn handler(_: Request<Body>, _: &Params) -> Box<dyn Future<Item = Response<Body>, Error = Error> + Send> {
+//! #   Box::new(futures::future::result(Response::builder().body(Body::empty()).map_err(Error::from)))
+//! # }
+//! # let (show_user, list_posts, show_post) = (handler, handler, handler);
+//! # let mut build = Router::build();
+//! build.scope("/users", |users| {
+//!     users.get("/{id:uint}", show_user);
+//!     users.scope("/{id:uint}/posts", |posts| {
+//!         posts.get("/", list_posts);
+//!         posts.get("/{post:uint}", show_post);
+//!     });
+//! });
+//! # let _ = build.finish();
+//! ```
+//!
+//! Nested scopes join their prefixes.  `Scope::with_guard` adds a guard to
+//! every route in the scope, including the ones in nested scopes, and
+//! `Scope::with_extension` attaches metadata to them, unless a route has its
+//! own of the same type.
+//!
+//! The versions of an API can be built the same way, with `Build::version`,
+//! instead of repeating each version's routes by hand.  Its routes are added
+//! under the version's prefix, e.g. `/v1`, or, after
+//! `Build::version_header("Accept-Version")`, only match requests with that
+//! version in the header (as `1` or `v1`).  `Build::default_version` picks the
+//! version used by requests that don't ask for one.  Both apply to every
+//! version, whenever they're set.  With the header, `Router::lookup` only
+//! matches the default version, since it has no headers to look at:
+//!
+//! ```rust
+//! # use pathmaker::hyper::Router;
+//! # use futures::prelude::*;
+//! # use hyper::{Request, Response, Body};
+//! # use failure::Error;
+//! # use pathmaker::Params;
+//! # fn handler(_: Request<Body>, _: &Params) -> Box<dyn Future<Item = Response<Body>, Error = Error> + Send> {
+//! #   Box::new(futures::future::result(Response::builder().body(Body::empty()).map_err(Error::from)))
+//! # }
+//! # let (list_users_v1, list_users) = (handler, handler);
+//! # let mut build = Router::build();
+//! build.default_version(2);
+//! build.version(1, |v1| {
+//!     v1.get("/users", list_users_v1);
+//! });
+//! build.version(2, |v2| {
+//!     v2.get("/users", list_users);
+//! });
+//! # let _ = build.finish();
+//! ```
+//!
+//! ## Dynamic Routes
+//!
+//! A router's routes are fixed once it's built.  If they have to change
+//! afterwards, e.g. for webhook endpoints that are registered at runtime,
+//! `Build::finish_dynamic` returns a `DynamicRouter` instead.  It's looked up
+//! like any other router, and routes can be added to it with
+//! `DynamicRouter::add_route`, or removed with `DynamicRouter::remove_route`
+//! (given the method, and the path the route was created with).  Each change
+//! rebuilds the router's lookup set, so it's best for routes that change now
+//! and then.  Adding a route fails, like `Build::try_finish`, if its pattern
+//! can't be compiled, and leaves the router as it was.
+//!
+//! To replace the whole route table instead, e.g. when it's loaded from a
+//! configuration file that can be reloaded without a restart, wrap the router
+//! in a `SharedRouter`.  Every clone of it shares the same router, which
+//! `SharedRouter::store` replaces atomically; requests that are already being
+//! handled keep the router they started with.  With hyper, it can be given to
+//! the server directly:
+//!
+//! ```rust
+//! # use pathmaker::hyper::{Router, SharedRouter};
+//! # use hyper::Server;
+//! # use futures::prelude::*;
+//! # fn router() -> Router { Router::build().finish() }
+//! # let address = "127.0.0.1:0".parse().unwrap();
+//! let shared = SharedRouter::new(router());
+//! let server = Server::bind(&address).serve(shared.clone());
+//! // Later, when the configuration changes:
+//! shared.store(router());
+//! # let _ = server.map_err(|_| ());
+//! ```
+//!
+//! Routes can also be described by configuration, so that the paths of an
+//! application can be rearranged without recompiling it.  The handlers are
+//! registered by name in a `HandlerRegistry`, and `Build::load_config` adds
+//! the routes of a TOML file (YAML isn't supported), each of which names its
+//! handler:
+//!
+//! ```toml
+//! [[route]]
+//! method = "GET"
+//! path = "/users/{id:uint}"
+//! handler = "show_user"
+//! ```
+//!
+//! ```rust,no_run
+//! # use pathmaker::hyper::{HandlerRegistry, Router};
+//! # use hyper::{Body, Response};
+//! # fn show_user(_: &str) -> Box<dyn futures::Future<Item = Response<Body>, Error = failure::Error> + Send> {
+//! #     Box::new(futures::future::ok(Response::new(Body::empty())))
+//! # }
+//! # fn main() -> Result<(), failure::Error> {
+//! let mut handlers = HandlerRegistry::new();
+//! handlers.handle("show_user", |_, params| show_user(&params[0]));
+//! let mut build = Router::build();
+//! build.load_config(&std::fs::read_to_string("routes.toml")?, &handlers)?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! A route can give `methods` as an array instead, or leave them out to match
+//! any method, and can also have a `name` and a `priority`.  If anything is
+//! wrong with the configuration, e.g. it names a handler that isn't
+//! registered, a `ConfigError` says which line, and none of its routes are
+//! added.
+//!
+//! ## Guards
+//!
+//! Routes can also be guarded on more than the method and path, like the host
+//! the request was made to.  `Route::with_host` restricts a route to a host,
+//! and `Build::host` does the same for every route added in a block:
+//!
+//! ```rust
+//! # use pathmaker::{Router, Route};
+//! let mut build = Router::build();
+//! build
+//!     .host("api.example.com", |build| {
+//!         build.add(Route::new("/users", "GET", 1));
+//!     })
+//!     .add(Route::new("/users", "GET", 2));
+//! # let _ = build.finish();
+//! ```
+//!
+//! A route whose guards don't allow a request is skipped, as if its path
+//! hadn't matched.  Guards need to know about the request, so they're only
+//! checked by `Router::lookup_with`, which takes a `RequestInfo`; the hyper
+//! `Service` does this automatically.
+//!
+//! `Route::when_header` guards a route on a request header, e.g.
+//! `.when_header("X-Api-Key", |key| key == "secret")`; requests without the
+//! header don't match.  `Route::with_scheme` (or `Build::scheme`, for a block of
+//! routes) guards a route on the scheme, honoring `X-Forwarded-Proto`, so that
+//! plain HTTP traffic can be sent to a redirect.  Similarly, `Route::when_query`
+//! guards a route on the query, e.g. `.when_query(&[("type", "user")])` matches
+//! `/search?type=user`; with hyper, `Build::get_with_query` is a shortcut for
+//! this.  `Route::with_version` guards a route on the HTTP version, e.g. for an
+//! HTTP/2-only streaming endpoint.  `Route::with_guard` adds a custom guard, which
+//! is only called once the method and path of the route have matched; if it
+//! rejects the request, the lookup moves on to the next matching route.
+//!
+//! `Query` parses a query string into its decoded names and values, e.g.
+//! `Query::from_request(&request)`, and `Query::parse` converts a value like
+//! `ParseParams::parse` does, e.g. `let page: Option<u32> = query.parse("page")?;`.
+//! With hyper, `Build::parse_query` parses the query of every request that's
+//! given to a handler, which finds it with `req.extensions().get::<Query>()`;
+//! if a handler fails with a `QueryError`, the `Service` answers with a 400
+//! Bad Request.
+//!
+//! `Route::consumes` restricts a route to requests with a given content type,
+//! e.g. `.consumes("application/json")`, so that the same path can be handled
+//! differently for JSON and form posts.  If some route would have matched
+//! but for the content type, `Router::rejects_content_type` says so, and the
+//! hyper `Service` answers with a 415 Unsupported Media Type.
+//!
+//! `Route::produces` is the other side of this: it declares the content type
+//! a route responds with, e.g. `.produces("text/html")`.  When several of
+//! these routes match a request, the one whose type is preferred by the
+//! request's `Accept` header (taking q-values into account) is used, so a
+//! path can have separate JSON and HTML handlers.
+//!
+//! ## Normalization
+//!
+//! By default, paths are matched exactly as they're given (after being URL
+//! decoded).  The builder has a few options to clean up paths before they're
+//! matched; `Router::decode` and `Router::normalize` apply them, and the hyper
+//! `Service` does so automatically.
+//!
+//! - `Build::collapse_slashes`: runs of slashes are collapsed into one, so
+//!   `/foo//bar` matches `/foo/bar`.
+//! - `Build::resolve_dot_segments`: `.` and `..` segments are resolved, so
+//!   `/static/css/../app.js` matches `/static/app.js`.  A `..` that would go
+//!   above the root stays at the root.
+//! - `Build::reject_root_escape`: dot segments are resolved as above, but a
+//!   path with a `..` that would go above the root (e.g.
+//!   `/static/../../etc/passwd`) is rejected.  The hyper `Service` answers
+//!   these with a 400 Bad Request.
+//! - `Build::keep_encoded_slashes`: encoded slashes (`%2F`) are left encoded
+//!   when the path is decoded, so a parameter can contain a slash, e.g.
+//!   `/files/docs%2Freadme.md/raw` matches `/files/{}/raw`.  `%23` and `%25`
+//!   are also left encoded; the parameters are decoded after the lookup with
+//!   `Router::decode_param`.
+//! - `Build::strict_decoding`: paths with malformed percent-encoding (a `%`
+//!   that isn't followed by two hex digits, or escapes that don't decode to
+//!   valid UTF-8) are rejected, instead of being passed through with
+//!   replacement characters.  The hyper `Service` answers these with a 400
+//!   Bad Request.
+//! - `Build::nfc_normalization`: both the routes and the paths are put in
+//!   Unicode Normalization Form C, so that `/café` matches the same routes
+//!   whether the `é` is written as one character (`U+00E9`), or as an `e`
+//!   followed by a combining accent (`U+0301`).  This requires the
+//!   `with-unicode` feature.
+//!
+//! Rather than treating `/users/` and `/users` as the same path,
+//! `Build::redirect_trailing_slash` redirects a path that matches no route
+//! to its other form, when a route matches that instead: `/users/` is sent to
+//! `/users` if only the latter is registered, and vice versa.  The hyper
+//! `Service` answers with a 301 Moved Permanently (or a 308 Permanent
+//! Redirect for methods other than `GET` and `HEAD`) whose `Location` keeps
+//! the query, ahead of the default handler.  `Router::redirects_trailing_slash`
+//! does the check by itself.  The `Location` is built from the normalized path
+//! that was looked up, by `Router::redirect_location`, with its leading
+//! slashes collapsed, so that a path like `//evil.com/` can't redirect to
+//! another host.
 
-#[macro_use]
+#![cfg_attr(feature = "test", feature(test))]
+
+#[cfg_attr(feature = "hyper", macro_use)]
 extern crate failure;
+// Named apart from the `test` module.
 #[cfg(feature = "test")]
-extern crate test;
+extern crate test as libtest;
+
+// The derive macro refers to this crate by its name, which has to work in
+// its own tests, too.
+#[cfg(feature = "derive")]
+extern crate self as pathmaker;
+
+#[cfg(feature = "derive")]
+pub use pathmaker_derive::PathParams;
+
+#[cfg(all(feature = "derive", feature = "http"))]
+pub use pathmaker_derive::routes;
+
+#[cfg(feature = "fastcgi")]
+pub mod fastcgi;
+
+#[cfg(feature = "http")]
+pub mod future;
+
+#[cfg(feature = "http")]
+pub mod http;
 
 #[cfg(feature = "hyper")]
 pub mod hyper;
 
 pub mod router;
+
+#[cfg(feature = "http")]
+pub mod sync;
+
+#[cfg(feature = "hyper")]
+pub mod test;
+
 pub use self::router::*;
 
 #[cfg_attr(feature = "test", bench)]
 #[cfg(feature = "test")]
-pub fn bench_mark(b: &mut test::Bencher) {
+pub fn bench_mark(b: &mut libtest::Bencher) {
     #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
     enum Method {
         Get,
@@ -156,6 +823,41 @@ pub fn bench_mark(b: &mut test::Bencher) {
     b.iter(|| route.lookup(&Method::Get, "/foo/bar"));
 }
 
+#[cfg_attr(feature = "test", bench)]
+#[cfg(feature = "test")]
+pub fn bench_mark_params(b: &mut libtest::Bencher) {
+    let mut build = Router::build();
+    build
+        .add(Route::new("/", "GET", 1))
+        .add(Route::new("/users", "GET", 2))
+        .add(Route::new("/users/{id:uint}", "GET", 3))
+        .add(Route::new("/users/{id}/posts/{post}", "GET", 4));
+    let route = build.finish();
+    assert_eq!(
+        route.lookup(&"GET", "/users/5/posts/7").unwrap(),
+        (&4, vec!["5", "7"])
+    );
+
+    b.iter(|| route.lookup(&"GET", "/users/5/posts/7"));
+}
+
+#[cfg_attr(feature = "test", bench)]
+#[cfg(feature = "test")]
+pub fn bench_mark_trie(b: &mut libtest::Bencher) {
+    let mut build = Router::build();
+    for i in 0..500 {
+        build.add(Route::new(format!("/resource{}/{{}}/items", i), "GET", i));
+    }
+    build.trie_matching();
+    let route = build.finish();
+    assert_eq!(
+        route.lookup(&"GET", "/resource250/5/items").unwrap(),
+        (&250, vec!["5"])
+    );
+
+    b.iter(|| route.lookup(&"GET", "/resource250/5/items"));
+}
+
 fn normalize_url<V: AsRef<str>>(string: V) -> String {
     let str = string.as_ref();
     let url = str.split_terminator("?").next().unwrap_or(str).as_bytes();
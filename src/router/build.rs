@@ -1,5 +1,13 @@
-use super::{Route, Router};
+use super::validate::{duplicates, shadows};
+use super::cache::LookupCache;
+use super::matcher::{Matcher, StaticDispatch, Statics};
+use super::route::{check_prefix, intern};
+use super::{Conflict, ConflictKind, DynamicRouter, Extensions, RouteRef, Guard, Match, MatchHook, NoMatch, NoMatchHook, Normalize, RequestInfo, Route, Router};
 use regex::RegexSet;
+use std::any::Any;
+use std::cmp::Reverse;
+use std::error::Error;
+use std::fmt::{Display, Formatter, Result as FmtResult};
 
 #[derive(Debug, Clone)]
 /// The builder for the router.  This collects all of the routes that the router
@@ -8,6 +16,36 @@ use regex::RegexSet;
 pub struct Build<M, H> {
     routes: Vec<Route<M, H>>,
     default: Option<H>,
+    no_match: Option<NoMatchHook<M>>,
+    on_match: Option<MatchHook<M>>,
+    by_specificity: bool,
+    trie: bool,
+    cache: Option<usize>,
+    lazy_patterns: bool,
+    normalize: Normalize,
+    fallbacks: Vec<(M, M)>,
+    auto_options: Option<M>,
+    redirect_trailing_slash: bool,
+    version_header: Option<String>,
+    default_version: Option<u32>,
+    versions: Vec<Versioned<M, H>>,
+    statics: Option<Statics<M>>,
+    extensions: Extensions,
+}
+
+#[derive(Debug, Clone)]
+/// The routes added for a version of the API by [`Build::version`].  They
+/// are kept as they were given, so that they can be added again whenever
+/// the version header or the default version changes.
+struct Versioned<M, H> {
+    version: u32,
+    /// Where the version's routes start in the builder's routes.
+    start: usize,
+    /// How many routes the version added to the builder's routes.
+    len: usize,
+    routes: Vec<Route<M, H>>,
+    /// Clones a route; captured where `M` and `H` are known to be `Clone`.
+    duplicate: fn(&Route<M, H>) -> Route<M, H>,
 }
 
 impl<M, H> Build<M, H> {
@@ -17,6 +55,134 @@ impl<M, H> Build<M, H> {
         self
     }
 
+    /// Adds the routes built by the given function, restricted to the given
+    /// host (see [`Route::with_host`]).  Only the routes are taken from the
+    /// builder given to the function; any other options set on it (like the
+    /// default) are ignored.
+    pub fn host<F>(&mut self, host: &str, build: F) -> &mut Self
+    where
+        F: FnOnce(&mut Build<M, H>),
+    {
+        let mut inner = Build::default();
+        build(&mut inner);
+        self.routes
+            .extend(inner.routes.into_iter().map(|route| route.with_host(host)));
+        self
+    }
+
+    /// Adds the routes built by the given function, restricted to the given
+    /// scheme (see [`Route::with_scheme`]).  Like [`Build::host`], only the
+    /// routes are taken from the builder given to the function.
+    pub fn scheme<F>(&mut self, scheme: &str, build: F) -> &mut Self
+    where
+        F: FnOnce(&mut Build<M, H>),
+    {
+        let mut inner = Build::default();
+        build(&mut inner);
+        self.routes
+            .extend(inner.routes.into_iter().map(|route| route.with_scheme(scheme)));
+        self
+    }
+
+    /// Mounts the given router under the given prefix, so that e.g. its
+    /// route `/users/{id}` matches `/api/v1/users/42` when it's mounted at
+    /// `/api/v1`; its route `/` matches the prefix itself.  The prefix isn't
+    /// stripped from the path, so the whole path is matched, and the prefix
+    /// can have parameters of its own, which come before the parameters of
+    /// the route.  If the router has a default, it handles every path under
+    /// the prefix that no other route matches, with any method; the only
+    /// parameters it's given are the prefix's.  If the router falls back to
+    /// others (see [`Router::or`]), their routes are mounted after its own,
+    /// in the order they're tried, so they're still used when none of its
+    /// routes match.  A fallback added with [`Router::or_for_missing_paths`]
+    /// is then also used when its routes only miss by method, like one
+    /// added with `Router::or`.  Everything else about the router (like its
+    /// normalization) is ignored, as with [`Build::host`].
+    ///
+    /// # Panics
+    ///
+    /// This panics if the prefix can't be parsed, like [`Route::new`].
+    pub fn mount(&mut self, prefix: &str, mut router: Router<M, H>) -> &mut Self {
+        check_prefix(prefix);
+        let default = router.default.take();
+        let mut next = Some(router);
+        while let Some(router) = next {
+            self.routes
+                .extend(router.routes.into_iter().map(|route| route.with_prefix(prefix)));
+            next = router.or.map(|or| or.router);
+        }
+        if let Some(default) = default {
+            self.routes.push(Route::catch_all(prefix, default));
+        }
+        self
+    }
+
+    /// Adds the routes given to the function under the given prefix (see
+    /// [`Build::mount`]), so that a group of routes doesn't have to repeat
+    /// it, e.g. `/admin`.  The function is given a [`Scope`], which can
+    /// nest further scopes with [`Scope::scope`], and add guards shared by
+    /// all of its routes with [`Scope::with_guard`].
+    ///
+    /// # Panics
+    ///
+    /// This panics if the prefix can't be parsed, like [`Route::new`].
+    pub fn scope<F>(&mut self, prefix: &str, build: F) -> &mut Self
+    where
+        F: FnOnce(&mut Scope<'_, M, H>),
+    {
+        check_prefix(prefix);
+        build(&mut Scope {
+            build: self,
+            prefix: prefix.trim_end_matches('/').to_string(),
+            guards: vec![],
+            extensions: Extensions::default(),
+        });
+        self
+    }
+
+    /// Tells the versions of the API (see [`Build::version`]) apart by the
+    /// given header, e.g. `Accept-Version`, instead of by a prefix of the
+    /// path.  The header can be given as `1` or `v1`.  This applies to the
+    /// versions added before it as well as after it.
+    ///
+    /// The versions are told apart by guards, so a lookup without the
+    /// request (like [`Router::lookup`]) only matches the routes of the
+    /// default version; use [`Router::lookup_with`] to match the others.
+    pub fn version_header(&mut self, name: &str) -> &mut Self {
+        self.version_header = Some(name.to_string());
+        self.reversion();
+        self
+    }
+
+    /// Makes the given version of the API the one used by requests that
+    /// don't ask for a version: those without the version's prefix, or
+    /// without the version header, if there is one (see
+    /// [`Build::version_header`]).  Like the header, this applies to the
+    /// versions added before it as well as after it.
+    pub fn default_version(&mut self, version: u32) -> &mut Self {
+        self.default_version = Some(version);
+        self.reversion();
+        self
+    }
+
+    /// Replaces the routes of each version with the routes it has under the
+    /// current version header and default version.
+    fn reversion(&mut self) {
+        // The routes move, so a static dispatch can't find them any more.
+        if !self.versions.is_empty() {
+            self.statics = None;
+        }
+        let mut shift = 0isize;
+        for group in &mut self.versions {
+            group.start = (group.start as isize + shift) as usize;
+            let routes = expand(group, self.version_header.as_deref(), self.default_version);
+            shift += routes.len() as isize - group.len as isize;
+            let end = group.start + group.len;
+            group.len = routes.len();
+            self.routes.splice(group.start..end, routes);
+        }
+    }
+
     /// Sets the default of the builder.  If no other route matches the given
     /// path, the default is instead returned.  Because there was no route
     /// to match, there will obviously be no url parameters in that match,
@@ -25,18 +191,551 @@ impl<M, H> Build<M, H> {
         self.default = Some(default);
         self
     }
+
+    /// Sets a hook that's called whenever a lookup matches none of the
+    /// routes, before the default is returned.  The hook is given the
+    /// method and path that were looked up, along with the routes that came
+    /// closest to matching, so that it can log (or show) a "did you mean"
+    /// diagnostic.
+    pub fn on_no_match<F>(&mut self, hook: F) -> &mut Self
+    where
+        F: Fn(&NoMatch<'_, M>) + Send + Sync + 'static,
+    {
+        self.no_match = Some(NoMatchHook::new(hook));
+        self
+    }
+
+    /// Sets a hook that's called whenever a lookup matches a route, or
+    /// falls back to the default.  The hook is given the method and path
+    /// that were looked up, along with the route that matched, so that it
+    /// can count requests (or time them) by route, without wrapping every
+    /// handler.  Lookups that only find a route, like
+    /// [`Router::lookup_route`], don't call it, since they don't handle
+    /// the request.
+    pub fn on_match<F>(&mut self, hook: F) -> &mut Self
+    where
+        F: Fn(&Match<'_, M>) + Send + Sync + 'static,
+    {
+        self.on_match = Some(MatchHook::new(hook));
+        self
+    }
+
+    /// Settings for the integration with an HTTP library, e.g. the access
+    /// log of the hyper `Service`.  These are kept by the router.
+    #[cfg(feature = "hyper")]
+    pub(crate) fn extensions_mut(&mut self) -> &mut Extensions {
+        &mut self.extensions
+    }
+
+    /// Sorts the routes by how specific they are when the router is built,
+    /// instead of keeping them in the order they were added.  Paths are
+    /// compared segment by segment: a literal segment beats a segment with
+    /// typed parameters (e.g. `{:uint}`), which beats a segment with string
+    /// parameters.  This way, `/users/new` always beats `/users/{id}`, no
+    /// matter which was added first.  Routes that are equally specific keep
+    /// the order they were added in.  A route's priority (see
+    /// [`Route::with_priority`]) still takes precedence over its specificity.
+    pub fn by_specificity(&mut self) -> &mut Self {
+        self.by_specificity = true;
+        self
+    }
+
+    /// Looks routes up with a trie when the router is finished, instead of
+    /// checking the path against the pattern of every route at once.  Each
+    /// segment of the path is looked up in turn, which is much cheaper for
+    /// large route tables.  Only routes whose segments are all literal text
+    /// or a single string parameter (e.g. `/users/{id}/posts`) go in the
+    /// trie; the rest (like `{:uint}`, `{id}.{format}`, or a mounted
+    /// router's default) are still matched by their patterns.  The routes
+    /// are evaluated in the same order either way, so lookups give the same
+    /// results.
+    pub fn trie_matching(&mut self) -> &mut Self {
+        self.trie = true;
+        self
+    }
+
+    /// Finds the routes added so far with the given function, which
+    /// `match`es a method and a path against their literal paths, before
+    /// the matcher is tried.  This is generated by the `routes!` macro,
+    /// which makes sure the function only finds a route if none of the
+    /// routes added before it could match the path.  The function isn't
+    /// used for a route that's evaluated after any of the routes added
+    /// later (e.g. one with a higher priority), or that has guards.
+    #[doc(hidden)]
+    pub fn static_routes(&mut self, dispatch: StaticDispatch<M>) -> &mut Self {
+        self.statics = Some(Statics::new(dispatch, self.routes.len()));
+        self
+    }
+
+    /// Caches which routes match the paths that are looked up, keeping up to
+    /// the given number of the most recently used paths, so that repeated
+    /// lookups of the same path (e.g. a health check, or a hot endpoint)
+    /// don't match the patterns again.  Which patterns match only depends
+    /// on the path, so the method, constraints, and guards of the routes are
+    /// still checked on every lookup.  Clones of the router share the cache,
+    /// which is behind a lock; it's only worth it when matching the patterns
+    /// costs more than that, e.g. for large route tables.
+    pub fn with_lookup_cache(&mut self, capacity: usize) -> &mut Self {
+        self.cache = Some(capacity);
+        self
+    }
+
+    /// Leaves compiling the pattern of each route until the first lookup
+    /// that needs it, instead of compiling them all when the router is
+    /// built.  This makes building a router with thousands of routes much
+    /// quicker, especially with [`Build::trie_matching`], since routes in
+    /// the trie only need their pattern for [`Router::explain`].  A pattern
+    /// that can't be compiled (e.g. one over the regex size limit) then
+    /// panics on that lookup, instead of being reported by
+    /// [`Build::try_finish`]; routes created with [`Route::try_new`] are
+    /// still checked when they're created.
+    pub fn lazy_patterns(&mut self) -> &mut Self {
+        self.lazy_patterns = true;
+        self
+    }
+
+    /// Falls back to the routes for the method `to` when a lookup for the
+    /// method `from` matches none of its own routes, e.g. so that `HEAD`
+    /// requests are handled by `GET` routes.  Routes for `from` are always
+    /// tried first, so they can still be given explicitly.
+    pub fn fallback_method(&mut self, from: M, to: M) -> &mut Self {
+        self.fallbacks.push((from, to));
+        self
+    }
+
+    /// Answers the given method (usually `OPTIONS`) automatically for any
+    /// path that has routes, but none for that method.  The answer is the
+    /// list of methods allowed for the path, which is given by
+    /// [`Router::auto_options`]; the hyper `Service` responds with a 204 No
+    /// Content, with that list in the `Allow` header.
+    pub fn auto_options(&mut self, method: M) -> &mut Self {
+        self.auto_options = Some(method);
+        self
+    }
+
+    /// Redirects a path that matches no route to the same path with its
+    /// trailing slash removed (or one added), when a route matches that
+    /// instead, e.g. `/users/` to `/users` if only the latter is registered.
+    /// The check is done by [`Router::redirects_trailing_slash`]; the hyper
+    /// `Service` answers with a 301 Moved Permanently (or a 308 Permanent
+    /// Redirect for methods other than `GET` and `HEAD`, so that the method
+    /// and body are kept), which takes priority over the default handler.
+    pub fn redirect_trailing_slash(&mut self) -> &mut Self {
+        self.redirect_trailing_slash = true;
+        self
+    }
+
+    /// Collapses runs of slashes in a path into a single slash before it's
+    /// looked up, so that `/foo//bar` matches the route `/foo/bar`.  This is
+    /// done by [`Router::normalize`].
+    pub fn collapse_slashes(&mut self) -> &mut Self {
+        self.normalize.collapse_slashes = true;
+        self
+    }
+
+    /// Resolves dot segments in a path before it's looked up, so that
+    /// `/static/css/../app.js` matches the route `/static/app.js`.  A `..`
+    /// that would go above the root stays at the root; use
+    /// [`Build::reject_root_escape`] to reject those paths instead.  This is
+    /// done by [`Router::normalize`].
+    pub fn resolve_dot_segments(&mut self) -> &mut Self {
+        self.normalize.resolve_dot_segments = true;
+        self
+    }
+
+    /// Resolves dot segments in a path, like
+    /// [`Build::resolve_dot_segments`], but rejects any path with a `..`
+    /// that would go above the root (e.g. `/static/../../etc/passwd`), with
+    /// [`super::NormalizeError::RootEscape`].  The hyper `Service` answers
+    /// these with a 400 Bad Request.
+    pub fn reject_root_escape(&mut self) -> &mut Self {
+        self.normalize.reject_root_escape = true;
+        self
+    }
+
+    /// Leaves encoded slashes in a path encoded when it's decoded by
+    /// [`Router::decode`], so that a parameter can contain a slash, e.g.
+    /// `/files/docs%2Freadme.md/raw` matches `/files/{}/raw`.  Encoded hashes
+    /// and percent signs (`%23` and `%25`) are left encoded as well, and the
+    /// parameters should be decoded afterwards with [`Router::decode_param`].
+    /// The hyper `Service` does both.
+    pub fn keep_encoded_slashes(&mut self) -> &mut Self {
+        self.normalize.keep_encoded_slashes = true;
+        self
+    }
+
+    /// Rejects paths with malformed percent-encoding when they're decoded by
+    /// [`Router::decode`], instead of passing the bad escapes through and
+    /// replacing invalid UTF-8 with `U+FFFD`.  The error is either
+    /// [`super::NormalizeError::MalformedEscape`] or
+    /// [`super::NormalizeError::InvalidUtf8`]; the hyper `Service` answers
+    /// both with a 400 Bad Request.
+    pub fn strict_decoding(&mut self) -> &mut Self {
+        self.normalize.strict_decoding = true;
+        self
+    }
+
+    /// Puts both the routes and the paths being looked up in Unicode
+    /// Normalization Form C, so that a path matches a route with the same
+    /// visible text, even if one uses combining characters (e.g. `e` and
+    /// `U+0301`) and the other doesn't (`U+00E9`).  The routes are normalized
+    /// when the router is built, and paths by [`Router::normalize`].  This
+    /// requires the `with-unicode` feature.
+    #[cfg(feature = "unicode-normalization")]
+    pub fn nfc_normalization(&mut self) -> &mut Self {
+        self.normalize.nfc = true;
+        self
+    }
+}
+
+impl<M: Clone, H: Clone> Build<M, H> {
+    /// Adds the routes built by the given function as the given version of
+    /// the API, so that the routes of each version don't have to be added
+    /// by hand.  Their paths are prefixed with the version, e.g. `/v1`,
+    /// unless the versions are told apart by a header (see
+    /// [`Build::version_header`]), in which case they only match requests
+    /// with that version in the header.  The routes of the default version
+    /// (see [`Build::default_version`]) are also added without the prefix,
+    /// or match requests without the header.  Like [`Build::host`], only
+    /// the routes are taken from the builder given to the function.
+    pub fn version<F>(&mut self, version: u32, build: F) -> &mut Self
+    where
+        F: FnOnce(&mut Build<M, H>),
+    {
+        let mut inner = Build::default();
+        build(&mut inner);
+        let mut group = Versioned {
+            version,
+            start: self.routes.len(),
+            len: 0,
+            routes: inner.routes,
+            duplicate: Route::clone,
+        };
+        let routes = expand(&group, self.version_header.as_deref(), self.default_version);
+        group.len = routes.len();
+        self.routes.extend(routes);
+        self.versions.push(group);
+        self
+    }
+}
+
+/// The routes of the given version, either prefixed with the version or
+/// guarded by the given header.
+fn expand<M, H>(group: &Versioned<M, H>, header: Option<&str>, default: Option<u32>) -> Vec<Route<M, H>> {
+    let version = group.version;
+    let default = default == Some(version);
+    let mut routes = vec![];
+    match header {
+        Some(header) => {
+            for route in &group.routes {
+                let header = header.to_string();
+                routes.push((group.duplicate)(route).with_guard(move |request| {
+                    match request.header(&header) {
+                        Some(given) => parse_version(given) == Some(version),
+                        None => default,
+                    }
+                }));
+            }
+        }
+        None => {
+            let prefix = format!("/v{}", version);
+            for route in &group.routes {
+                if default {
+                    routes.push((group.duplicate)(route));
+                }
+                routes.push((group.duplicate)(route).with_prefix(&prefix));
+            }
+        }
+    }
+    routes
+}
+
+/// Parses a version given in a header, e.g. `1` or `v1`.
+fn parse_version(given: &str) -> Option<u32> {
+    let given = given.trim();
+    let given = given.strip_prefix(['v', 'V']).unwrap_or(given);
+    given.parse().ok()
 }
 
 impl<M: Eq, H> Build<M, H> {
-    /// Completes the build, returning the router.
+    /// Completes the build, returning the router.  This panics if the
+    /// router can't be built; see [`Build::try_finish`].
     pub fn finish(self) -> Router<M, H> {
-        let set = RegexSet::new(self.routes.iter().map(|route| route.pattern.as_str())).unwrap();
-        Router {
+        match self.try_finish() {
+            Ok(router) => router,
+            Err(error) => panic!("{}", error),
+        }
+    }
+
+    /// Completes the build, returning the router, or an error if the
+    /// patterns of the routes can't be compiled, or combined into sets,
+    /// e.g. because there are so many that they go over the regex size
+    /// limit.  The error says which route was the first that couldn't be
+    /// added.
+    pub fn try_finish(mut self) -> Result<Router<M, H>, BuildError> {
+        let statics = self.statics.take().map(|statics| {
+            // This is the same sort as the one the routes are about to get.
+            let mut order = self.routes.iter().enumerate().collect::<Vec<_>>();
+            sort(&mut order, |(_, route)| route, self.by_specificity);
+            let order = order.into_iter().map(|(index, _)| index).collect::<Vec<_>>();
+            statics.resolve(&order, &self.routes)
+        });
+        let matcher = prepare(&mut self.routes, &self.normalize, self.by_specificity, self.trie)
+            .map_err(|error| BuildError::new(&self.routes, error))?;
+        if !self.lazy_patterns {
+            for (index, route) in self.routes.iter().enumerate() {
+                route
+                    .pattern
+                    .compile()
+                    .map_err(|error| BuildError::for_route(index, route, error))?;
+            }
+        }
+        Ok(Router {
             routes: self.routes,
-            set,
+            matcher,
+            cache: self.cache.map(LookupCache::new),
             default: self.default,
+            no_match: self.no_match,
+            on_match: self.on_match,
+            normalize: self.normalize,
+            fallbacks: self.fallbacks,
+            auto_options: self.auto_options,
+            redirect_trailing_slash: self.redirect_trailing_slash,
+            extensions: self.extensions,
+            or: None,
+            statics,
+        })
+    }
+}
+
+#[derive(Debug)]
+/// A scope of a [`Build`], created by [`Build::scope`].  Every route added
+/// to the scope is added to the builder under the scope's prefix.
+pub struct Scope<'b, M, H> {
+    build: &'b mut Build<M, H>,
+    prefix: String,
+    guards: Vec<Guard>,
+    extensions: Extensions,
+}
+
+impl<M, H> Scope<'_, M, H> {
+    /// Adds the given route to the builder, under the scope's prefix, and
+    /// with the scope's guards and extensions.
+    pub fn add(&mut self, route: Route<M, H>) -> &mut Self {
+        let mut route = route.with_prefix(&self.prefix);
+        route.guards.splice(0..0, self.guards.iter().cloned());
+        route.extensions.merge(&self.extensions);
+        self.build.add(route);
+        self
+    }
+
+    /// Adds the routes given to the function under a scope nested in this
+    /// one.  Its prefix is this scope's prefix followed by the given one,
+    /// and its routes have this scope's guards, along with their own.
+    ///
+    /// # Panics
+    ///
+    /// This panics if the prefix can't be parsed, like [`Route::new`].
+    pub fn scope<F>(&mut self, prefix: &str, build: F) -> &mut Self
+    where
+        F: FnOnce(&mut Scope<'_, M, H>),
+    {
+        let prefix = format!("{}{}", self.prefix, prefix.trim_end_matches('/'));
+        check_prefix(&prefix);
+        build(&mut Scope {
+            prefix,
+            guards: self.guards.clone(),
+            extensions: self.extensions.clone(),
+            build: self.build,
+        });
+        self
+    }
+
+    /// Adds a guard to every route added to the scope from here on,
+    /// including those in nested scopes.  See [`Route::with_guard`].
+    pub fn with_guard<F>(&mut self, guard: F) -> &mut Self
+    where
+        F: Fn(&dyn RequestInfo) -> bool + Send + Sync + 'static,
+    {
+        self.guards.push(Guard::new(guard));
+        self
+    }
+
+    /// Attaches the given value as metadata to every route added to the
+    /// scope from here on, including those in nested scopes, unless the
+    /// route has its own value of the same type.  See
+    /// [`Route::with_extension`].
+    pub fn with_extension<T: Any + Send + Sync>(&mut self, value: T) -> &mut Self {
+        self.extensions.insert(value);
+        self
+    }
+
+    /// The prefix the scope adds to its routes.
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+}
+
+impl<M: Eq, H> Build<M, H> {
+    /// Finds the routes that can never match, because a route that's
+    /// evaluated before them always matches first, e.g. `/users/{id:uint}`
+    /// after `/users/{id}` for the same method.  Each route is reported
+    /// once, along with the first route that shadows it, in the order the
+    /// routes are evaluated; routes that were added twice (with the same
+    /// pattern and methods) are reported as [`ConflictKind::Duplicate`].
+    /// This is conservative: routes with guards (or that negotiate their
+    /// content type) never shadow others, and parameter kinds are only
+    /// compared in simple cases, so a route is only reported if it's
+    /// certain that it can't match.
+    pub fn validate(&self) -> Vec<Conflict<'_>> {
+        let mut routes = self.routes.iter().enumerate().collect::<Vec<_>>();
+        sort(&mut routes, |(_, route)| route, self.by_specificity);
+        routes
+            .iter()
+            .enumerate()
+            .filter_map(|(position, &(index, route))| {
+                routes[..position]
+                    .iter()
+                    .find(|(_, earlier)| shadows(earlier, route))
+                    .map(|&(earlier_index, earlier)| Conflict {
+                        route: RouteRef::new(index, route),
+                        shadowed_by: RouteRef::new(earlier_index, earlier),
+                        kind: if duplicates(earlier, route) {
+                            ConflictKind::Duplicate
+                        } else {
+                            ConflictKind::Shadowed
+                        },
+                    })
+            })
+            .collect()
+    }
+
+    /// Completes the build, returning a router whose routes can still be
+    /// added and removed afterwards.  See [`DynamicRouter`].
+    pub fn finish_dynamic(self) -> DynamicRouter<M, H> {
+        let (by_specificity, lazy_patterns) = (self.by_specificity, self.lazy_patterns);
+        DynamicRouter::new(self.finish(), by_specificity, lazy_patterns)
+    }
+}
+
+/// Prepares the given routes for a router, normalizing them if needed, and
+/// sorting them into the order they're evaluated in.  This returns the
+/// matcher used to look them up.
+pub(super) fn prepare<M, H>(
+    routes: &mut [Route<M, H>],
+    normalize: &Normalize,
+    by_specificity: bool,
+    trie: bool,
+) -> Result<Matcher, regex::Error> {
+    #[cfg(feature = "unicode-normalization")]
+    {
+        if normalize.nfc {
+            routes.iter_mut().for_each(Route::normalize_nfc);
         }
     }
+    sort(routes, |route| route, by_specificity);
+    intern(routes);
+    Matcher::new(routes, normalize, trie)
+}
+
+/// Sorts the given items by their routes, into the order the routes are
+/// evaluated in.
+fn sort<T, M, H, F>(items: &mut [T], route: F, by_specificity: bool)
+where
+    F: Fn(&T) -> &Route<M, H>,
+{
+    // These are stable sorts, so routes with the same priority (and
+    // specificity) stay in the order they were added.
+    if by_specificity {
+        items.sort_by_cached_key(|item| {
+            let route = route(item);
+            (Reverse(route.priority), route.specificity())
+        });
+    } else {
+        items.sort_by_key(|item| Reverse(route(item).priority));
+    }
+}
+
+/// Where the given route goes among the given routes, which are already in
+/// the order they're evaluated in, as if it were added after them and
+/// sorted with [`sort`].
+pub(super) fn position<M, H>(routes: &[Route<M, H>], route: &Route<M, H>, by_specificity: bool) -> usize {
+    if by_specificity {
+        let key = (Reverse(route.priority), route.specificity());
+        routes.partition_point(|other| (Reverse(other.priority), other.specificity()) <= key)
+    } else {
+        routes.partition_point(|other| other.priority >= route.priority)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// The reason a router couldn't be built by [`Build::try_finish`], or a
+/// route couldn't be added by [`DynamicRouter::add_route`].
+pub struct BuildError {
+    /// The index of the first route that couldn't be added to the router,
+    /// in the order the routes are evaluated.  Unless the routes were given
+    /// priorities, or sorted by specificity, this is the order they were
+    /// added in.
+    pub index: usize,
+    /// The path of the route.
+    pub path: String,
+    /// The pattern of the route.
+    pub pattern: String,
+    /// The error from compiling the pattern, or building the set of patterns.
+    pub error: regex::Error,
+}
+
+impl BuildError {
+    /// Finds the first route that couldn't be added to the set.  Adding
+    /// routes only ever makes the set bigger, so this is the shortest run
+    /// of routes that fails, found by bisecting.
+    pub(super) fn new<M, H>(routes: &[Route<M, H>], error: regex::Error) -> Self {
+        let patterns = routes.iter().map(|route| route.pattern.as_str()).collect::<Vec<_>>();
+        let (mut low, mut high) = (0, patterns.len());
+        while low + 1 < high {
+            let mid = (low + high) / 2;
+            if RegexSet::new(&patterns[..mid]).is_ok() {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+        let index = high.saturating_sub(1);
+        match routes.get(index) {
+            Some(route) => BuildError::for_route(index, route, error),
+            None => BuildError {
+                index,
+                path: String::new(),
+                pattern: String::new(),
+                error,
+            },
+        }
+    }
+
+    pub(super) fn for_route<M, H>(index: usize, route: &Route<M, H>, error: regex::Error) -> Self {
+        BuildError {
+            index,
+            path: route.path().to_string(),
+            pattern: route.pattern.as_str().to_string(),
+            error,
+        }
+    }
+}
+
+impl Display for BuildError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(
+            f,
+            "route #{} (`{}`, matching `{}`) couldn't be added to the router: {}",
+            self.index, self.path, self.pattern, self.error
+        )
+    }
+}
+
+impl Error for BuildError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.error)
+    }
 }
 
 impl<M, H> Default for Build<M, H> {
@@ -44,6 +743,21 @@ impl<M, H> Default for Build<M, H> {
         Build {
             routes: vec![],
             default: None,
+            no_match: None,
+            on_match: None,
+            by_specificity: false,
+            trie: false,
+            cache: None,
+            lazy_patterns: false,
+            normalize: Normalize::default(),
+            fallbacks: vec![],
+            auto_options: None,
+            redirect_trailing_slash: false,
+            version_header: None,
+            default_version: None,
+            versions: vec![],
+            statics: None,
+            extensions: Extensions::default(),
         }
     }
 }
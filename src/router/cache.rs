@@ -0,0 +1,227 @@
+use super::matcher::Found;
+use std::collections::HashMap;
+use std::fmt::{Debug, Formatter, Result as FmtResult};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+
+/// The routes that matched a path, and where each of their parameters is in
+/// the path, as byte ranges.
+type Entry = Vec<(usize, Vec<(usize, usize)>)>;
+
+/// The source of the generations of route tables; see [`LookupCache`].
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Clone)]
+/// A bounded cache of the routes whose patterns match a path, so that the
+/// paths that are looked up the most skip matching the patterns.  Only the
+/// path matters to which patterns match, so it's the only key; the methods,
+/// constraints, and guards of the routes are still checked on every lookup.
+/// Clones of the router share the same cache.  See
+/// [`super::Build::with_lookup_cache`].
+///
+/// The routes are cached by their index, which is only meaningful for the
+/// route table they came from, so every entry is tagged with the
+/// generation of its table.  When the routes of a router change (see
+/// [`super::DynamicRouter`]), its cache moves to a new generation, and
+/// ignores the entries that clones with the old table keep making.
+pub(super) struct LookupCache {
+    lru: Arc<Mutex<Lru<(u64, Entry)>>>,
+    generation: u64,
+}
+
+impl LookupCache {
+    pub(super) fn new(capacity: usize) -> Self {
+        LookupCache {
+            lru: Arc::new(Mutex::new(Lru::new(capacity))),
+            generation: GENERATION.fetch_add(1, Ordering::Relaxed),
+        }
+    }
+
+    /// The routes that matched the given path, if it's cached for this
+    /// generation.
+    pub(super) fn get<'p>(&self, path: &'p str) -> Option<Found<'p>> {
+        let mut lru = self.lru.lock().unwrap_or_else(PoisonError::into_inner);
+        let (generation, entry) = lru.get(path)?;
+        if *generation != self.generation {
+            return None;
+        }
+        Some(
+            entry
+                .iter()
+                .map(|(index, params)| {
+                    let params = params.iter().map(|&(start, end)| &path[start..end]).collect();
+                    (*index, params)
+                })
+                .collect(),
+        )
+    }
+
+    /// Caches the routes that matched the given path.  The parameters must
+    /// all borrow from the path.
+    pub(super) fn insert(&self, path: &str, found: &Found<'_>) {
+        let entry = found
+            .iter()
+            .map(|(index, params)| {
+                let params = params
+                    .iter()
+                    .map(|param| {
+                        let start = param.as_ptr() as usize - path.as_ptr() as usize;
+                        (start, start + param.len())
+                    })
+                    .collect();
+                (*index, params)
+            })
+            .collect();
+        let mut lru = self.lru.lock().unwrap_or_else(PoisonError::into_inner);
+        lru.insert(path, (self.generation, entry));
+    }
+
+    /// Moves the cache to a new generation, because the routes changed, so
+    /// that nothing cached for the old routes is used.
+    pub(super) fn renew(&mut self) {
+        self.generation = GENERATION.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+impl Debug for LookupCache {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        let lru = self.lru.lock().unwrap_or_else(PoisonError::into_inner);
+        f.debug_struct("LookupCache")
+            .field("capacity", &lru.capacity)
+            .field("len", &lru.map.len())
+            .finish()
+    }
+}
+
+/// A least-recently-used map from paths to entries.  The entries are kept
+/// in a slab, linked from the most recently used to the least, so that
+/// using one, and evicting the last, don't have to search for it.
+struct Lru<V> {
+    capacity: usize,
+    map: HashMap<String, usize>,
+    nodes: Vec<Node<V>>,
+    head: Option<usize>,
+    tail: Option<usize>,
+}
+
+struct Node<V> {
+    path: String,
+    entry: V,
+    prev: Option<usize>,
+    next: Option<usize>,
+}
+
+impl<V> Lru<V> {
+    fn new(capacity: usize) -> Self {
+        Lru {
+            capacity,
+            map: HashMap::new(),
+            nodes: vec![],
+            head: None,
+            tail: None,
+        }
+    }
+
+    fn get(&mut self, path: &str) -> Option<&V> {
+        let index = *self.map.get(path)?;
+        self.unlink(index);
+        self.push_front(index);
+        Some(&self.nodes[index].entry)
+    }
+
+    fn insert(&mut self, path: &str, entry: V) {
+        if self.capacity == 0 {
+            return;
+        }
+        if let Some(&index) = self.map.get(path) {
+            self.nodes[index].entry = entry;
+            self.unlink(index);
+            self.push_front(index);
+            return;
+        }
+        let index = if self.nodes.len() < self.capacity {
+            self.nodes.push(Node {
+                path: path.to_string(),
+                entry,
+                prev: None,
+                next: None,
+            });
+            self.nodes.len() - 1
+        } else {
+            // The cache is full, so the least recently used node is reused.
+            let index = self.tail.unwrap();
+            self.unlink(index);
+            let node = &mut self.nodes[index];
+            self.map.remove(&node.path);
+            node.path = path.to_string();
+            node.entry = entry;
+            index
+        };
+        self.map.insert(path.to_string(), index);
+        self.push_front(index);
+    }
+
+    fn unlink(&mut self, index: usize) {
+        let (prev, next) = (self.nodes[index].prev, self.nodes[index].next);
+        match prev {
+            Some(prev) => self.nodes[prev].next = next,
+            None => self.head = next,
+        }
+        match next {
+            Some(next) => self.nodes[next].prev = prev,
+            None => self.tail = prev,
+        }
+    }
+
+    fn push_front(&mut self, index: usize) {
+        self.nodes[index].prev = None;
+        self.nodes[index].next = self.head;
+        if let Some(head) = self.head {
+            self.nodes[head].prev = Some(index);
+        }
+        self.head = Some(index);
+        if self.tail.is_none() {
+            self.tail = Some(index);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use smallvec::smallvec;
+
+    #[test]
+    fn test_lru() {
+        let mut lru = Lru::<Entry>::new(2);
+        lru.insert("/a", vec![(1, vec![])]);
+        lru.insert("/b", vec![(2, vec![])]);
+        assert!(lru.get("/a").is_some());
+        // `/b` is now the least recently used, so it's the one evicted.
+        lru.insert("/c", vec![(3, vec![])]);
+        assert!(lru.get("/b").is_none());
+        assert_eq!(lru.get("/a"), Some(&vec![(1, vec![])]));
+        assert_eq!(lru.get("/c"), Some(&vec![(3, vec![])]));
+        assert_eq!(lru.map.len(), 2);
+    }
+
+    #[test]
+    fn test_offsets() {
+        let mut cache = LookupCache::new(4);
+        let path = String::from("/users/5/posts/7");
+        let found: Found<'_> = smallvec![(3, vec![&path[7..8], &path[15..16]])];
+        cache.insert(&path, &found);
+
+        let other = String::from("/users/5/posts/7");
+        let cached = cache.get(&other).unwrap();
+        assert_eq!(cached.as_slice(), &[(3, vec!["5", "7"])][..]);
+        let old = cache.clone();
+        cache.renew();
+        assert!(cache.get(&other).is_none());
+        // A clone that's still on the old generation only fills the cache
+        // for itself.
+        assert!(old.get(&other).is_some());
+        old.insert(&path, &found);
+        assert!(cache.get(&other).is_none());
+    }
+}
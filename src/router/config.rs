@@ -0,0 +1,482 @@
+//! Routes loaded from configuration (see [`Build::load_config`]).  The
+//! configuration is written in a small subset of TOML, parsed here, since
+//! the router has no other use for a full TOML library; YAML isn't
+//! supported.
+
+use super::{Build, Route, RouteParseError};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt::{Debug, Display, Formatter, Result as FmtResult};
+use std::str::FromStr;
+
+/// The handlers that routes loaded from configuration can refer to, by
+/// name.  See [`Build::load_config`].
+pub struct HandlerRegistry<H> {
+    handlers: HashMap<String, Box<dyn Fn() -> H + Send + Sync>>,
+}
+
+impl<H> HandlerRegistry<H> {
+    /// Creates an empty registry.
+    pub fn new() -> Self {
+        HandlerRegistry {
+            handlers: HashMap::new(),
+        }
+    }
+
+    /// Registers the given handler under the given name.  Since more than
+    /// one route can use the same handler, each route is given a clone of
+    /// it.  If a handler was already registered under the name, it's
+    /// replaced.
+    pub fn register(&mut self, name: &str, handler: H) -> &mut Self
+    where
+        H: Clone + Send + Sync + 'static,
+    {
+        self.register_with(name, move || handler.clone())
+    }
+
+    /// Registers a function that creates the handler with the given name,
+    /// for handlers that can't be cloned.  The function is called once for
+    /// each route that uses the handler.
+    pub fn register_with<F>(&mut self, name: &str, make: F) -> &mut Self
+    where
+        F: Fn() -> H + Send + Sync + 'static,
+    {
+        self.handlers.insert(name.to_string(), Box::new(make));
+        self
+    }
+
+    /// Whether a handler is registered under the given name.
+    pub fn contains(&self, name: &str) -> bool {
+        self.handlers.contains_key(name)
+    }
+
+    pub(super) fn make(&self, name: &str) -> Option<H> {
+        self.handlers.get(name).map(|make| make())
+    }
+}
+
+impl<H> Default for HandlerRegistry<H> {
+    fn default() -> Self {
+        HandlerRegistry::new()
+    }
+}
+
+impl<H> Debug for HandlerRegistry<H> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        let mut names = self.handlers.keys().collect::<Vec<_>>();
+        names.sort();
+        f.debug_tuple("HandlerRegistry").field(&names).finish()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// The reason the routes couldn't be loaded by [`Build::load_config`].  Each
+/// variant has the line of the configuration the problem is on, starting
+/// from 1.
+pub enum ConfigError {
+    /// The line isn't valid, e.g. a value is missing its closing quote.
+    Syntax {
+        /// The line.
+        line: usize,
+        /// What's wrong with it.
+        message: String,
+    },
+    /// A route is missing a key it needs, e.g. its `path`.  The line is
+    /// the route's `[[route]]` header.
+    MissingKey {
+        /// The line.
+        line: usize,
+        /// The key that's missing.
+        key: &'static str,
+    },
+    /// A route refers to a handler that isn't registered.
+    UnknownHandler {
+        /// The line.
+        line: usize,
+        /// The name of the handler.
+        handler: String,
+    },
+    /// A method couldn't be parsed.
+    InvalidMethod {
+        /// The line.
+        line: usize,
+        /// The method.
+        method: String,
+    },
+    /// The path of a route couldn't be parsed.
+    Route {
+        /// The line.
+        line: usize,
+        /// The error from parsing the path.
+        error: RouteParseError,
+    },
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            ConfigError::Syntax { line, message } => write!(f, "line {}: {}", line, message),
+            ConfigError::MissingKey { line, key } => {
+                write!(f, "line {}: the route has no `{}`", line, key)
+            }
+            ConfigError::UnknownHandler { line, handler } => {
+                write!(f, "line {}: no handler is registered as `{}`", line, handler)
+            }
+            ConfigError::InvalidMethod { line, method } => {
+                write!(f, "line {}: `{}` is not a valid method", line, method)
+            }
+            ConfigError::Route { line, error } => write!(f, "line {}: {}", line, error),
+        }
+    }
+}
+
+impl Error for ConfigError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ConfigError::Route { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl<M: FromStr, H> Build<M, H> {
+    /// Adds the routes described by the given configuration, with the
+    /// handlers they name taken from the given registry.  This lets the
+    /// paths of an application be rearranged without recompiling it.
+    ///
+    /// The configuration is written in TOML, as an array of `route` tables:
+    ///
+    /// ```toml
+    /// [[route]]
+    /// method = "GET"              # or `methods = ["GET", "HEAD"]`
+    /// path = "/users/{id:uint}"
+    /// handler = "show_user"
+    /// name = "user"               # optional; see `Route::with_name`
+    /// priority = 1                # optional; see `Route::with_priority`
+    /// ```
+    ///
+    /// A route without a method matches any method.  Only this much of
+    /// TOML is understood: strings, integers, and arrays of strings, with
+    /// `#` comments; YAML isn't supported.  A key that's given twice in a
+    /// route is an error, as in TOML.  If anything is wrong with the
+    /// configuration, none of its routes are added.
+    pub fn load_config(
+        &mut self,
+        config: &str,
+        registry: &HandlerRegistry<H>,
+    ) -> Result<&mut Self, ConfigError> {
+        let routes = parse_config(config)?
+            .into_iter()
+            .map(|entry| entry.into_route(registry))
+            .collect::<Result<Vec<_>, _>>()?;
+        for route in routes {
+            self.add(route);
+        }
+        Ok(self)
+    }
+}
+
+/// A single `[[route]]` table of the configuration.
+#[derive(Debug, Default)]
+struct Entry {
+    line: usize,
+    methods: Option<(usize, Vec<String>)>,
+    path: Option<(usize, String)>,
+    handler: Option<(usize, String)>,
+    name: Option<String>,
+    priority: Option<i32>,
+}
+
+impl Entry {
+    fn into_route<M, H>(self, registry: &HandlerRegistry<H>) -> Result<Route<M, H>, ConfigError>
+    where
+        M: FromStr,
+    {
+        let line = self.line;
+        let missing = |key| ConfigError::MissingKey { line, key };
+        let (path_line, path) = self.path.ok_or_else(|| missing("path"))?;
+        let (handler_line, handler) = self.handler.ok_or_else(|| missing("handler"))?;
+        let methods = match self.methods {
+            None => None,
+            Some((line, methods)) => Some(
+                methods
+                    .into_iter()
+                    .map(|method| {
+                        method
+                            .parse()
+                            .map_err(|_| ConfigError::InvalidMethod { line, method })
+                    })
+                    .collect::<Result<Vec<M>, _>>()?,
+            ),
+        };
+        let handler = registry
+            .make(&handler)
+            .ok_or(ConfigError::UnknownHandler {
+                line: handler_line,
+                handler,
+            })?;
+
+        let mut route = Route::try_from_parts(path, methods, handler).map_err(|error| {
+            ConfigError::Route {
+                line: path_line,
+                error,
+            }
+        })?;
+        if let Some(name) = &self.name {
+            route = route.with_name(name);
+        }
+        if let Some(priority) = self.priority {
+            route = route.with_priority(priority);
+        }
+        Ok(route)
+    }
+}
+
+/// A value in the configuration.
+enum Value {
+    String(String),
+    Integer(i64),
+    Array(Vec<String>),
+}
+
+fn parse_config(config: &str) -> Result<Vec<Entry>, ConfigError> {
+    let mut entries: Vec<Entry> = vec![];
+    // The keys of the current route, with the lines they're on.
+    let mut keys = HashMap::new();
+    for (index, raw) in config.lines().enumerate() {
+        let line = index + 1;
+        let syntax = |message: &str| ConfigError::Syntax {
+            line,
+            message: message.to_string(),
+        };
+        let text = strip_comment(raw).trim();
+        if text.is_empty() {
+            continue;
+        }
+        if text.starts_with('[') {
+            if text.replace(' ', "") != "[[route]]" {
+                return Err(syntax("only `[[route]]` tables are allowed"));
+            }
+            entries.push(Entry {
+                line,
+                ..Entry::default()
+            });
+            keys.clear();
+            continue;
+        }
+
+        let (key, value) = text
+            .split_once('=')
+            .ok_or_else(|| syntax("expected `key = value`"))?;
+        let key = key.trim();
+        let value = parse_value(value.trim()).ok_or_else(|| syntax("invalid value"))?;
+        let entry = entries
+            .last_mut()
+            .ok_or_else(|| syntax("keys have to be in a `[[route]]` table"))?;
+        // `method` and `methods` set the same thing, so only one of them
+        // can be given.
+        let slot = if key == "method" { "methods" } else { key };
+        if let Some(first) = keys.insert(slot.to_string(), line) {
+            return Err(syntax(&format!("`{}` is already given on line {}", key, first)));
+        }
+        match (key, value) {
+            ("method", Value::String(method)) => entry.methods = Some((line, vec![method])),
+            ("methods", Value::Array(methods)) => entry.methods = Some((line, methods)),
+            ("path", Value::String(path)) => entry.path = Some((line, path)),
+            ("handler", Value::String(handler)) => entry.handler = Some((line, handler)),
+            ("name", Value::String(name)) => entry.name = Some(name),
+            ("priority", Value::Integer(priority)) => {
+                let priority = i32::try_from(priority);
+                entry.priority = Some(priority.map_err(|_| syntax("priority out of range"))?);
+            }
+            ("method", _) | ("path", _) | ("handler", _) | ("name", _) => {
+                return Err(syntax(&format!("`{}` has to be a string", key)))
+            }
+            ("methods", _) => return Err(syntax("`methods` has to be an array of strings")),
+            ("priority", _) => return Err(syntax("`priority` has to be an integer")),
+            _ => return Err(syntax(&format!("unknown key `{}`", key))),
+        }
+    }
+    Ok(entries)
+}
+
+/// Removes a `#` comment from the end of the line, if it has one outside
+/// of a string.
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match (quote, c) {
+            (Some('"'), '\\') if !escaped => {
+                escaped = true;
+                continue;
+            }
+            (Some(open), c) if c == open && !escaped => quote = None,
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (None, '#') => return &line[..i],
+            _ => {}
+        }
+        escaped = false;
+    }
+    line
+}
+
+fn parse_value(text: &str) -> Option<Value> {
+    if let Some(items) = text.strip_prefix('[') {
+        let items = items.strip_suffix(']')?.trim();
+        let mut values = vec![];
+        let mut rest = items;
+        while !rest.is_empty() {
+            let (value, after) = parse_string(rest)?;
+            values.push(value);
+            rest = after.trim_start();
+            match rest.strip_prefix(',') {
+                Some(after) => rest = after.trim_start(),
+                None if rest.is_empty() => {}
+                None => return None,
+            }
+        }
+        return Some(Value::Array(values));
+    }
+    if text.starts_with('"') || text.starts_with('\'') {
+        return match parse_string(text)? {
+            (value, "") => Some(Value::String(value)),
+            _ => None,
+        };
+    }
+    parse_integer(text).map(Value::Integer)
+}
+
+/// Parses a decimal integer, which can have a sign, and underscores
+/// between its digits, e.g. `-1_000`.
+fn parse_integer(text: &str) -> Option<i64> {
+    let digits = text.strip_prefix(['+', '-']).unwrap_or(text);
+    let valid = digits
+        .split('_')
+        .all(|group| !group.is_empty() && group.bytes().all(|b| b.is_ascii_digit()));
+    if !valid {
+        return None;
+    }
+    text.replace('_', "").parse().ok()
+}
+
+/// Parses a basic (`"..."`) or literal (`'...'`) string from the start of
+/// the text, returning it along with the rest of the text.
+fn parse_string(text: &str) -> Option<(String, &str)> {
+    if let Some(rest) = text.strip_prefix('\'') {
+        let end = rest.find('\'')?;
+        return Some((rest[..end].to_string(), rest[end + 1..].trim_start()));
+    }
+    let rest = text.strip_prefix('"')?;
+    let mut value = String::new();
+    let mut chars = rest.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Some((value, rest[i + 1..].trim_start())),
+            '\\' => match chars.next()?.1 {
+                '"' => value.push('"'),
+                '\\' => value.push('\\'),
+                'n' => value.push('\n'),
+                't' => value.push('\t'),
+                'r' => value.push('\r'),
+                _ => return None,
+            },
+            c => value.push(c),
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Router;
+
+    #[test]
+    fn test_load_config() {
+        let mut registry = HandlerRegistry::new();
+        registry.register("list_users", 1).register("show_user", 2);
+        let config = r#"
+            # The users.
+            [[route]]
+            method = "GET"
+            path = "/users"
+            handler = "list_users"
+
+            [[route]]
+            methods = ["GET", "HEAD"]   # and HEAD, for good measure
+            path = '/users/{id:uint}'
+            handler = "show_user"
+            name = "user"
+            priority = 1
+
+            [[route]]
+            path = "/users/#{}"
+            handler = "show_user"
+        "#;
+
+        let mut build = Router::<String, i32>::build();
+        build.load_config(config, &registry).unwrap();
+        let router = build.finish();
+
+        let get = "GET".to_string();
+        assert_eq!(router.lookup(&get, "/users"), Some((&1, vec![])));
+        assert_eq!(router.lookup(&"HEAD".to_string(), "/users/5"), Some((&2, vec!["5"])));
+        assert_eq!(router.lookup(&"DELETE".to_string(), "/users/#5"), Some((&2, vec!["5"])));
+        assert_eq!(router.url_for("user", &[&5]).unwrap(), "/users/5");
+    }
+
+    #[test]
+    fn test_load_config_errors() {
+        fn load(config: &str) -> ConfigError {
+            let mut registry = HandlerRegistry::new();
+            registry.register("home", ());
+            let mut build = Router::<String, ()>::build();
+            let error = build.load_config(config, &registry).unwrap_err();
+            assert!(build.finish().routes().next().is_none());
+            error
+        }
+
+        let error = load("[[route]]\npath = \"/\"\nhandler = \"home\"\n[[route]]\nhandler = \"home\"");
+        assert_eq!(error, ConfigError::MissingKey { line: 4, key: "path" });
+        assert_eq!(error.to_string(), "line 4: the route has no `path`");
+        assert_eq!(
+            load("[[route]]\npath = \"/\"\nhandler = \"away\""),
+            ConfigError::UnknownHandler {
+                line: 3,
+                handler: "away".to_string()
+            }
+        );
+        assert!(matches!(
+            load("[[route]]\npath = \"/{:unit}\"\nhandler = \"home\""),
+            ConfigError::Route { line: 2, error: RouteParseError::UnknownKind { .. } }
+        ));
+        assert!(matches!(load("path = \"/\""), ConfigError::Syntax { line: 1, .. }));
+        assert!(matches!(load("[[route]]\npath = \"/"), ConfigError::Syntax { line: 2, .. }));
+        assert!(matches!(load("[[route]]\nmethods = \"GET\""), ConfigError::Syntax { line: 2, .. }));
+        assert!(matches!(load("[[route]]\nhost = \"example.com\""), ConfigError::Syntax { line: 2, .. }));
+        assert!(matches!(load("[routes]"), ConfigError::Syntax { line: 1, .. }));
+
+        // Keys can't be given twice in a route, but can be in the next one.
+        let error = load("[[route]]\npath = \"/\"\nhandler = \"home\"\npath = \"/home\"");
+        assert_eq!(error.to_string(), "line 4: `path` is already given on line 2");
+        let error = load("[[route]]\nmethod = \"GET\"\nmethods = [\"HEAD\"]");
+        assert!(matches!(error, ConfigError::Syntax { line: 3, .. }));
+        let error = load("[[route]]\npath = \"/\"\nhandler = \"home\"\n[[route]]\npath = \"/\"\nhandler = \"away\"");
+        assert!(matches!(error, ConfigError::UnknownHandler { line: 6, .. }));
+
+        // Underscores in integers have to be between digits.
+        for priority in &["1__0", "_5", "5_", "+_5", "-", "1_x"] {
+            let error = load(&format!("[[route]]\npriority = {}", priority));
+            assert!(matches!(error, ConfigError::Syntax { line: 2, .. }), "{}", priority);
+        }
+        let mut registry = HandlerRegistry::new();
+        registry.register("home", ());
+        let mut build = Router::<String, ()>::build();
+        build
+            .load_config("[[route]]\npath = \"/\"\nhandler = \"home\"\npriority = -1_0", &registry)
+            .unwrap();
+        assert_eq!(build.finish().routes().next().unwrap().priority, -10);
+    }
+}
@@ -0,0 +1,208 @@
+use super::build::{position, prepare};
+use super::{BuildError, Route, Router};
+use std::ops::{Deref, DerefMut};
+
+#[derive(Debug)]
+/// A router whose routes can be added and removed after it's built, e.g. for
+/// endpoints that are registered while the router is serving requests.  This
+/// is created with [`super::Build::finish_dynamic`], and dereferences to the
+/// [`Router`], so it's looked up the same way.
+///
+/// Every change rebuilds the set used for lookups, which takes time
+/// proportional to the number of routes; it's meant for routes that change
+/// now and then, not on every request.
+pub struct DynamicRouter<M, H> {
+    router: Router<M, H>,
+    by_specificity: bool,
+    lazy_patterns: bool,
+}
+
+impl<M: Eq, H> DynamicRouter<M, H> {
+    pub(super) fn new(router: Router<M, H>, by_specificity: bool, lazy_patterns: bool) -> Self {
+        DynamicRouter {
+            router,
+            by_specificity,
+            lazy_patterns,
+        }
+    }
+
+    /// Adds the given route to the router.  It's evaluated as if it had
+    /// been added to the builder last, so it still comes after the routes
+    /// with a higher priority (or that are more specific, if the router
+    /// was built with [`super::Build::by_specificity`]).
+    ///
+    /// Like [`super::Build::try_finish`], this fails if the route's pattern
+    /// can't be compiled (unless the router was built with
+    /// [`super::Build::lazy_patterns`]), or if it makes the set of patterns
+    /// too big; the router is then left as it was.
+    pub fn add_route(&mut self, route: Route<M, H>) -> Result<&mut Self, BuildError> {
+        let index = position(&self.router.routes, &route, self.by_specificity);
+        if !self.lazy_patterns {
+            route
+                .pattern
+                .compile()
+                .map_err(|error| BuildError::for_route(index, &route, error))?;
+        }
+        self.router.routes.insert(index, route);
+        if let Err(error) = self.rebuild() {
+            let error = BuildError::for_route(index, &self.router.routes[index], error);
+            self.router.routes.remove(index);
+            return Err(error);
+        }
+        Ok(self)
+    }
+
+    /// Removes the first route that was created with the given path, and
+    /// matches the given method, returning it.  The path has to be given
+    /// exactly as the route was created with, e.g. `/hooks/{id:uint}`, and
+    /// not a path that the route matches.
+    pub fn remove_route(&mut self, method: &M, path: &str) -> Option<Route<M, H>> {
+        let index = self
+            .router
+            .routes
+            .iter()
+            .position(|route| route.path == path && route.matches_method(method))?;
+        let route = self.router.routes.remove(index);
+        // The set only gets smaller, so it can always be built again.
+        self.rebuild().expect("the routes were built before");
+        Some(route)
+    }
+
+    /// Returns the router, which can't be changed any more.
+    pub fn into_router(self) -> Router<M, H> {
+        self.router
+    }
+
+    /// Builds the set used for lookups again, after the routes changed.  If
+    /// it can't be built, the old one is kept.
+    fn rebuild(&mut self) -> Result<(), regex::Error> {
+        let router = &mut self.router;
+        let trie = router.matcher.is_trie();
+        router.matcher = prepare(&mut router.routes, &router.normalize, self.by_specificity, trie)?;
+        // The routes may have moved, so the `routes!` macro's `match` can't
+        // find them any more.
+        router.statics = None;
+        // The routes (and their order) may have changed, so whatever was
+        // cached is out of date, including anything cached by clones that
+        // still have the old routes.
+        if let Some(cache) = &mut router.cache {
+            cache.renew();
+        }
+        Ok(())
+    }
+}
+
+impl<M, H> Deref for DynamicRouter<M, H> {
+    type Target = Router<M, H>;
+
+    fn deref(&self) -> &Router<M, H> {
+        &self.router
+    }
+}
+
+impl<M, H> DerefMut for DynamicRouter<M, H> {
+    fn deref_mut(&mut self) -> &mut Router<M, H> {
+        &mut self.router
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_add_and_remove_routes() {
+        let mut build = Router::build();
+        build
+            .add(Route::new("/hooks", "GET", 1))
+            .add(Route::new("/{}/{}", "POST", 2))
+            .add(Route::new("/status", "GET", 3).with_priority(1))
+            .with_default(0);
+        let mut router = build.finish_dynamic();
+
+        assert_eq!(router.lookup(&"POST", "/hooks/github"), Some((&2, vec!["hooks", "github"])));
+
+        router
+            .add_route(Route::new("/hooks/{name}", "POST", 4).with_priority(1))
+            .unwrap()
+            .add_route(Route::new("/hooks/gitlab", "POST", 5))
+            .unwrap()
+            .add_route(Route::new("/status", "GET", 6).with_priority(2))
+            .unwrap();
+        assert_eq!(router.lookup(&"POST", "/hooks/github"), Some((&4, vec!["github"])));
+        assert_eq!(router.lookup(&"POST", "/hooks/gitlab"), Some((&4, vec!["gitlab"])));
+        assert_eq!(router.lookup(&"GET", "/status"), Some((&6, vec![])));
+
+        let removed = router.remove_route(&"POST", "/hooks/{name}").unwrap();
+        assert_eq!(removed.handler(), &4);
+        assert_eq!(router.lookup(&"POST", "/hooks/github"), Some((&2, vec!["hooks", "github"])));
+        assert!(router.remove_route(&"POST", "/hooks/{name}").is_none());
+        assert!(router.remove_route(&"GET", "/hooks/gitlab").is_none());
+        assert!(router.remove_route(&"POST", "/hooks/github").is_none());
+
+        let router = router.into_router();
+        assert_eq!(router.lookup(&"GET", "/hooks"), Some((&1, vec![])));
+        assert_eq!(router.lookup(&"GET", "/missing"), Some((&0, vec![])));
+    }
+
+    #[test]
+    fn test_cache_after_rebuild() {
+        let mut build = Router::build();
+        build
+            .add(Route::new("/a", "GET", 1))
+            .add(Route::new("/b", "GET", 2))
+            .add(Route::new("/{}", "GET", 3))
+            .with_lookup_cache(8);
+        let mut router = build.finish_dynamic();
+        let snapshot = (*router).clone();
+
+        router.remove_route(&"GET", "/a").unwrap();
+        // The clone still has the old routes, so it caches the old index of
+        // `/{}`, which the rebuilt router mustn't use.
+        assert_eq!(snapshot.lookup(&"GET", "/x"), Some((&3, vec!["x"])));
+        assert_eq!(router.lookup(&"GET", "/x"), Some((&3, vec!["x"])));
+        assert_eq!(router.lookup(&"GET", "/a"), Some((&3, vec!["a"])));
+        assert_eq!(snapshot.lookup(&"GET", "/a"), Some((&1, vec![])));
+    }
+
+    #[test]
+    fn test_add_routes_by_specificity() {
+        let mut build = Router::build();
+        build.add(Route::new("/hooks/{name}", "POST", 1)).by_specificity();
+        let mut router = build.finish_dynamic();
+        router.add_route(Route::new("/hooks/github", "POST", 2)).unwrap();
+
+        assert_eq!(router.lookup(&"POST", "/hooks/github"), Some((&2, vec![])));
+        assert_eq!(router.lookup(&"POST", "/hooks/gitlab"), Some((&1, vec!["gitlab"])));
+    }
+
+    #[test]
+    fn test_add_route_errors() {
+        let mut build = Router::build();
+        build.add(Route::new("/a", "GET", 1)).add(Route::new("/{}", "GET", 2));
+        let mut router = build.finish_dynamic();
+
+        // The pattern is too big to compile on its own.
+        let error = router
+            .add_route(Route::new("/{:ipv6}".repeat(64), "GET", 3).with_priority(1))
+            .unwrap_err();
+        assert_eq!(error.index, 0);
+        assert!(matches!(error.error, regex::Error::CompiledTooBig(_)));
+        assert_eq!(router.routes().count(), 2);
+        assert_eq!(router.lookup(&"GET", "/a"), Some((&1, vec![])));
+        assert_eq!(router.lookup(&"GET", "/b"), Some((&2, vec!["b"])));
+
+        router.add_route(Route::new("/b", "GET", 4).with_priority(1)).unwrap();
+        assert_eq!(router.lookup(&"GET", "/b"), Some((&4, vec![])));
+
+        // With lazy patterns, it's the set that can't be built, and the
+        // route is taken out again.
+        let mut build = Router::build();
+        build.add(Route::new("/a", "GET", 1)).add(Route::new("/{}", "GET", 2)).lazy_patterns();
+        let mut router = build.finish_dynamic();
+        let error = router.add_route(Route::new("/{:ipv6}".repeat(64), "GET", 3)).unwrap_err();
+        assert_eq!(error.index, 2);
+        assert_eq!(router.routes().count(), 2);
+        assert_eq!(router.lookup(&"GET", "/b"), Some((&2, vec!["b"])));
+    }
+}
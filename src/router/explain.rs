@@ -0,0 +1,193 @@
+use super::{RequestInfo, Route, RouteInfo, Router};
+
+#[derive(Debug, Clone, PartialEq)]
+/// How a single route fared in a lookup, as given by [`Router::explain`].
+pub struct Explanation<'r, M> {
+    /// The route.
+    pub route: RouteInfo<'r, M>,
+    /// How far the route got, and why it didn't match, if it didn't.
+    pub outcome: Outcome,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// The step of a lookup at which a route stopped matching.  The steps are
+/// checked in the order they're listed here, and the first that fails is
+/// the outcome.
+pub enum Outcome {
+    /// The path doesn't match the route's pattern.
+    PathMismatch,
+    /// The path matches, but the method doesn't.
+    MethodMismatch,
+    /// The path and method match, but the value of a parameter is outside
+    /// of its range, e.g. `12` for `{:uint(1..=10)}`.
+    ConstraintFailed {
+        /// The index of the parameter.
+        param: usize,
+        /// The value the parameter was given.
+        value: String,
+    },
+    /// A guard on the route (e.g. for its host) rejected the request.
+    GuardFailed {
+        /// The index of the guard, in the order they were added to the
+        /// route.
+        guard: usize,
+    },
+    /// The route doesn't accept the content type of the request.  See
+    /// [`super::Route::consumes`].
+    UnsupportedContentType,
+    /// The route doesn't produce anything the request accepts.  See
+    /// [`super::Route::produces`].
+    NotAcceptable,
+    /// The route matches.
+    Matched,
+}
+
+impl<M: Eq, H> Router<M, H> {
+    /// Explains how the given method and path are looked up, for debugging
+    /// why a request matches the route it does (or none at all).  Each
+    /// route is listed in the order they're evaluated in, along with the
+    /// step at which it stopped matching; the first route that matched is
+    /// the one the lookup takes, unless its content is negotiated (see
+    /// [`Router::lookup_route_with`]).  Like [`Router::lookup`], the path
+    /// **must** be URL decoded.
+    ///
+    /// Fallback methods (see [`super::Build::fallback_method`]) aren't
+    /// followed; explain the lookup for the fallback method to see those.
+    pub fn explain(&self, method: &M, path: &str) -> Vec<Explanation<'_, M>> {
+        self.explain_with(method, path, &())
+    }
+
+    /// Explains the lookup like [`Router::explain`], but also checks the
+    /// guards and content types of each route against the given request
+    /// information, like [`Router::lookup_with`].
+    pub fn explain_with(
+        &self,
+        method: &M,
+        path: &str,
+        request: &dyn RequestInfo,
+    ) -> Vec<Explanation<'_, M>> {
+        let matches = self.pattern_matches(path);
+        self.routes
+            .iter()
+            .enumerate()
+            .map(|(i, route)| {
+                let outcome = if !matches.iter().any(|(matched, _)| *matched == i) {
+                    Outcome::PathMismatch
+                } else if !route.matches_method(method) {
+                    Outcome::MethodMismatch
+                } else {
+                    outcome(route, path, request)
+                };
+                Explanation {
+                    route: RouteInfo::new(route),
+                    outcome,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Checks the rest of the steps for a route whose path and method match,
+/// in the same order as the lookup does.
+fn outcome<M, H>(route: &Route<M, H>, path: &str, request: &dyn RequestInfo) -> Outcome {
+    let caps = match route.pattern.captures(path) {
+        Some(caps) => caps,
+        None => return Outcome::PathMismatch,
+    };
+    let failed = route
+        .constraints
+        .iter()
+        .zip(caps.iter().skip(1))
+        .position(|(constraint, capture)| match (constraint, capture) {
+            (Some(constraint), Some(capture)) => !constraint.allows(capture.as_str()),
+            _ => false,
+        });
+    if let Some(param) = failed {
+        return Outcome::ConstraintFailed {
+            param,
+            value: caps[param + 1].to_string(),
+        };
+    }
+    if let Some(guard) = route.guards.iter().position(|guard| !guard.allows(request)) {
+        return Outcome::GuardFailed { guard };
+    }
+    if !route.consumes_allow(request) {
+        Outcome::UnsupportedContentType
+    } else if route.produces_quality(request) <= 0.0 {
+        Outcome::NotAcceptable
+    } else {
+        Outcome::Matched
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_explain() {
+        let mut build = Router::build();
+        build
+            .add(Route::new("/pages/{:uint(1..=10)}", "GET", 1))
+            .add(Route::new("/pages/{}", "POST", 2))
+            .add(Route::new("/pages/{}", "GET", 3).with_host("example.com"))
+            .add(Route::new("/pages/{}", "GET", 4).produces("text/html"))
+            .add(Route::new("/pages/{}", "GET", 5))
+            .add(Route::new("/users", "GET", 6));
+        let router = build.finish();
+
+        let outcomes = router
+            .explain(&"GET", "/pages/12")
+            .into_iter()
+            .map(|explanation| (explanation.route.path, explanation.outcome))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            outcomes,
+            vec![
+                (
+                    "/pages/{:uint(1..=10)}",
+                    Outcome::ConstraintFailed {
+                        param: 0,
+                        value: "12".to_string()
+                    }
+                ),
+                ("/pages/{}", Outcome::MethodMismatch),
+                ("/pages/{}", Outcome::GuardFailed { guard: 0 }),
+                ("/pages/{}", Outcome::Matched),
+                ("/pages/{}", Outcome::Matched),
+                ("/users", Outcome::PathMismatch),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_explain_with() {
+        struct Json;
+
+        impl RequestInfo for Json {
+            fn header(&self, name: &str) -> Option<&str> {
+                match name {
+                    "content-type" | "accept" => Some("application/json"),
+                    _ => None,
+                }
+            }
+        }
+
+        let mut build = Router::build();
+        build
+            .add(Route::new("/users", "POST", 1).consumes("text/csv"))
+            .add(Route::new("/users", "POST", 2).produces("text/html"))
+            .add(Route::new("/users", "POST", 3).consumes("application/json"));
+        let router = build.finish();
+
+        let outcomes = router
+            .explain_with(&"POST", "/users", &Json)
+            .into_iter()
+            .map(|explanation| explanation.outcome)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            outcomes,
+            vec![Outcome::UnsupportedContentType, Outcome::NotAcceptable, Outcome::Matched]
+        );
+    }
+}
@@ -0,0 +1,72 @@
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::fmt::{Debug, Formatter, Result as FmtResult};
+use std::sync::Arc;
+
+#[derive(Clone, Default)]
+/// Typed metadata attached to a route, e.g. the scopes it requires, or its
+/// rate-limit class.  There can be one value of each type.  These are added
+/// with [`super::Route::with_extension`], and can be read back from the route
+/// a lookup matched (see [`super::Router::lookup_route`]), or from
+/// [`super::RouteInfo`].
+pub struct Extensions(HashMap<TypeId, Arc<dyn Any + Send + Sync>>);
+
+impl Extensions {
+    /// The value of the given type, if there is one.
+    pub fn get<T: Any + Send + Sync>(&self) -> Option<&T> {
+        self.0
+            .get(&TypeId::of::<T>())
+            .and_then(|value| value.downcast_ref())
+    }
+
+    /// Whether there's a value of the given type.
+    pub fn contains<T: Any + Send + Sync>(&self) -> bool {
+        self.0.contains_key(&TypeId::of::<T>())
+    }
+
+    /// The number of values.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Whether there are no values at all.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Sets the value of its type, replacing any value already there.
+    pub(crate) fn insert<T: Any + Send + Sync>(&mut self, value: T) {
+        self.0.insert(TypeId::of::<T>(), Arc::new(value));
+    }
+
+    /// Adds the values of the given extensions whose types aren't here yet,
+    /// so that the values already here take precedence.
+    pub(crate) fn merge(&mut self, other: &Extensions) {
+        for (key, value) in &other.0 {
+            self.0.entry(*key).or_insert_with(|| value.clone());
+        }
+    }
+}
+
+impl Debug for Extensions {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("Extensions").field("len", &self.0.len()).finish()
+    }
+}
+
+impl PartialEq for Extensions {
+    // Extensions can't be compared by value, so they're only equal if
+    // they're the very same values, which is enough for comparing the
+    // `RouteInfo`s of the same router.
+    fn eq(&self, other: &Self) -> bool {
+        self.0.len() == other.0.len()
+            && self.0.iter().all(|(key, value)| {
+                other
+                    .0
+                    .get(key)
+                    .is_some_and(|other| Arc::ptr_eq(value, other))
+            })
+    }
+}
+
+impl Eq for Extensions {}
@@ -0,0 +1,54 @@
+use super::{Extensions, Route};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Information about a route in a router, as given by
+/// [`super::Router::routes`].  This is meant for listing what a router
+/// serves, e.g. for debugging, or for generating documentation.
+pub struct RouteInfo<'r, M> {
+    /// The path the route was created with, e.g. `/users/{id:uint}`.  For
+    /// a route added under a prefix (see [`super::Build::mount`]), this
+    /// includes the prefix.
+    pub path: &'r str,
+    /// The regular expression the route matches paths with.
+    pub pattern: &'r str,
+    /// The methods the route matches, or `None` if it matches any method.
+    pub methods: Option<&'r [M]>,
+    /// The name of the route, if it was given one.
+    pub name: Option<&'r str>,
+    /// The names of the parameters of the route, in order; parameters
+    /// without a name are `None`.
+    pub params: Vec<Option<&'r str>>,
+    /// The priority of the route.
+    pub priority: i32,
+    /// The content types the route accepts; if this is empty, it accepts
+    /// any content type.
+    pub consumes: &'r [String],
+    /// The content types the route responds with, if it declared any.
+    pub produces: &'r [String],
+    /// The number of guards on the route, including the ones that are
+    /// used for its host, scheme, headers, and so on.
+    pub guards: usize,
+    /// Whether the route matches every path under its own, which is the
+    /// case for the default of a mounted router.
+    pub catch_all: bool,
+    /// The metadata attached to the route.
+    pub extensions: &'r Extensions,
+}
+
+impl<'r, M> RouteInfo<'r, M> {
+    pub(super) fn new<H>(route: &'r Route<M, H>) -> Self {
+        RouteInfo {
+            path: route.path(),
+            pattern: route.pattern.as_str(),
+            methods: route.methods(),
+            name: route.name(),
+            params: route.param_names().collect(),
+            priority: route.priority,
+            consumes: &route.consumes,
+            produces: &route.produces,
+            guards: route.guards.len(),
+            catch_all: route.catch_all,
+            extensions: &route.extensions,
+        }
+    }
+}
@@ -0,0 +1,53 @@
+use super::{Route, RouteInfo};
+use std::fmt::{Debug, Formatter, Result as FmtResult};
+use std::sync::Arc;
+
+#[derive(Debug)]
+/// A lookup that matched a route, or that fell back to the default.  This
+/// is passed to the hook given to [`super::Build::on_match`], and is meant
+/// for metrics keyed by the route's path (rather than the path that was
+/// looked up, which would have a key for every user id).
+pub struct Match<'a, M> {
+    /// The method of the request.
+    pub method: &'a M,
+    /// The path that was looked up.
+    pub path: &'a str,
+    /// The route that matched, or `None` if the lookup fell back to the
+    /// default.
+    pub route: Option<RouteInfo<'a, M>>,
+}
+
+type HookFn<M> = dyn Fn(&Match<'_, M>) + Send + Sync + 'static;
+
+/// The hook called when a lookup matches a route, or falls back to the
+/// default.
+pub struct MatchHook<M>(Arc<HookFn<M>>);
+
+impl<M> MatchHook<M> {
+    pub(super) fn new<F>(hook: F) -> Self
+    where
+        F: Fn(&Match<'_, M>) + Send + Sync + 'static,
+    {
+        MatchHook(Arc::new(hook))
+    }
+
+    pub(super) fn call<H>(&self, method: &M, path: &str, route: Option<&Route<M, H>>) {
+        (self.0)(&Match {
+            method,
+            path,
+            route: route.map(RouteInfo::new),
+        })
+    }
+}
+
+impl<M> Clone for MatchHook<M> {
+    fn clone(&self) -> Self {
+        MatchHook(self.0.clone())
+    }
+}
+
+impl<M> Debug for MatchHook<M> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.write_str("MatchHook")
+    }
+}
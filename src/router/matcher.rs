@@ -0,0 +1,459 @@
+use super::normalize::Normalize;
+use super::route::PLACEHOLDER;
+use super::Route;
+use regex::RegexSet;
+use smallvec::SmallVec;
+use std::collections::HashMap;
+use std::fmt::{Debug, Formatter, Result as FmtResult};
+
+/// Routers with more routes than this have their sets split into shards, by
+/// the first segment of the path.  See [`Sets`].
+const SHARD_THRESHOLD: usize = 128;
+
+/// The routes whose pattern matches a path, with the parameters captured
+/// from it.  Only a few routes match any one path, so these are kept on the
+/// stack.
+pub(super) type Found<'p> = SmallVec<[(usize, Vec<&'p str>); 4]>;
+
+#[derive(Debug, Clone)]
+/// How a router finds the routes whose pattern matches a path.  Either way,
+/// the routes are given in the order they're evaluated in, along with the
+/// parameters captured from the path; the methods, constraints, and guards
+/// of the routes are checked afterwards.
+pub(super) enum Matcher {
+    /// The patterns of every route are in sets.
+    Set(Sets),
+    /// The routes whose segments are all literal text or a single string
+    /// parameter (e.g. `/users/{id}/posts`) are in a trie, which is walked a
+    /// segment at a time; the rest (typed parameters, parameters mixed with
+    /// literal text, and catch-all routes) are in a set, like with
+    /// [`Matcher::Set`].  See [`super::Build::trie_matching`].
+    Trie(Trie),
+}
+
+impl Matcher {
+    /// Builds the matcher for the given routes, which must already be in
+    /// the order they're evaluated in.
+    pub(super) fn new<M, H>(
+        routes: &[Route<M, H>],
+        normalize: &Normalize,
+        trie: bool,
+    ) -> Result<Matcher, regex::Error> {
+        if !trie {
+            return Sets::new(routes, (0..routes.len()).collect()).map(Matcher::Set);
+        }
+
+        let mut root = Node::default();
+        let mut others = vec![];
+        for (index, route) in routes.iter().enumerate() {
+            match segments(route, normalize) {
+                Some(segments) => root.insert(&segments, index),
+                None => others.push(index),
+            }
+        }
+        let others = Sets::new(routes, others)?;
+        Ok(Matcher::Trie(Trie { root, others }))
+    }
+
+    /// Whether this is a [`Matcher::Trie`].
+    pub(super) fn is_trie(&self) -> bool {
+        match self {
+            Matcher::Set(_) => false,
+            Matcher::Trie(_) => true,
+        }
+    }
+
+    /// The index of every route whose pattern matches the given path, in
+    /// order, along with the parameters captured from the path.
+    pub(super) fn matches<'p, M, H>(&self, routes: &[Route<M, H>], path: &'p str) -> Found<'p> {
+        let mut found = Found::new();
+        match self {
+            Matcher::Set(sets) => sets.matches(routes, path, &mut found),
+            Matcher::Trie(trie) => {
+                if let Some(rest) = path.strip_prefix('/') {
+                    let parts = rest.split('/').collect::<SmallVec<[_; 8]>>();
+                    trie.root.find(&parts, &mut SmallVec::new(), &mut found);
+                }
+                trie.others.matches(routes, path, &mut found);
+            }
+        }
+        found.sort_by_key(|(index, _)| *index);
+        found
+    }
+}
+
+#[derive(Debug, Clone)]
+/// The patterns of some of the routes of a router, in regex sets.  With
+/// only a few routes, they're all in one set.  Otherwise, the routes whose
+/// path starts with a literal segment (e.g. `/users` in `/users/{id}`) are
+/// split into shards by that segment, so that a lookup only has to check
+/// the shard for the first segment of the path, and the routes that don't
+/// start with one (e.g. `/{lang}/about`).  This keeps each set well under
+/// the regex size limit, and quick to match, for routers with thousands of
+/// routes.
+pub(super) struct Sets {
+    shards: HashMap<String, Shard>,
+    rest: Shard,
+}
+
+#[derive(Debug, Clone)]
+/// A single regex set, for some of the routes.  Routes with the same
+/// pattern share a single pattern in the set.
+struct Shard {
+    /// The indices of the routes with each pattern, in the order of the
+    /// patterns in the set.
+    indices: Vec<SmallVec<[usize; 2]>>,
+    set: RegexSet,
+}
+
+impl Sets {
+    /// Builds the sets for the routes with the given indices, which must be
+    /// in order.
+    fn new<M, H>(routes: &[Route<M, H>], indices: Vec<usize>) -> Result<Sets, regex::Error> {
+        let mut shards = HashMap::<_, Vec<usize>>::new();
+        let mut rest = vec![];
+        for index in indices.iter().copied() {
+            match shard_key(&routes[index]).filter(|_| indices.len() > SHARD_THRESHOLD) {
+                Some(key) => shards.entry(key.to_string()).or_default().push(index),
+                None => rest.push(index),
+            }
+        }
+        Ok(Sets {
+            shards: shards
+                .into_iter()
+                .map(|(key, indices)| Ok((key, Shard::new(routes, indices)?)))
+                .collect::<Result<_, regex::Error>>()?,
+            rest: Shard::new(routes, rest)?,
+        })
+    }
+
+    /// Adds every route whose pattern matches the given path to `found`,
+    /// with the parameters captured from the path.  These aren't in order.
+    fn matches<'p, M, H>(&self, routes: &[Route<M, H>], path: &'p str, found: &mut Found<'p>) {
+        let shard = path
+            .strip_prefix('/')
+            .filter(|_| !self.shards.is_empty())
+            .and_then(|rest| self.shards.get(rest.split('/').next().unwrap_or(rest)));
+        for shard in shard.into_iter().chain(Some(&self.rest)) {
+            for i in shard.set.matches(path).into_iter() {
+                let indices = &shard.indices[i];
+                // The routes have the same pattern, so they capture the same
+                // parameters.
+                if let Some(params) = captures(&routes[indices[0]], path) {
+                    found.extend(indices.iter().map(|&index| (index, params.clone())));
+                }
+            }
+        }
+    }
+}
+
+impl Shard {
+    fn new<M, H>(routes: &[Route<M, H>], indices: Vec<usize>) -> Result<Shard, regex::Error> {
+        let mut patterns = HashMap::<&str, usize>::new();
+        let mut grouped = Vec::<SmallVec<[usize; 2]>>::new();
+        for index in indices {
+            let pattern = routes[index].pattern.as_str();
+            match patterns.get(pattern) {
+                Some(&i) => grouped[i].push(index),
+                None => {
+                    patterns.insert(pattern, grouped.len());
+                    grouped.push(SmallVec::from_slice(&[index]));
+                }
+            }
+        }
+        let set = RegexSet::new(grouped.iter().map(|indices| routes[indices[0]].pattern.as_str()))?;
+        Ok(Shard { indices: grouped, set })
+    }
+}
+
+/// The first segment of the route's path, if it's literal text, which every
+/// path the route matches has to start with.  Only ASCII segments are used,
+/// so that Unicode normalization can't change them.
+fn shard_key<M, H>(route: &Route<M, H>) -> Option<&str> {
+    let first = route.path.strip_prefix('/')?.split('/').next()?;
+    let literal = !first.is_empty()
+        && first.is_ascii()
+        && !first.contains(['{', '}', '%', '?']);
+    Some(first).filter(|_| literal)
+}
+
+#[derive(Debug, Clone)]
+/// The routes of a [`Matcher::Trie`].
+pub(super) struct Trie {
+    root: Node,
+    /// The routes that aren't in the trie.
+    others: Sets,
+}
+
+#[derive(Debug, Clone, Default)]
+/// A node of the trie, for a run of segments of a path.
+struct Node {
+    /// The nodes for each literal segment that can come next.
+    statics: HashMap<String, Node>,
+    /// The node for a string parameter, which matches any segment that
+    /// isn't empty.
+    param: Option<Box<Node>>,
+    /// The routes whose path ends here.
+    routes: Vec<usize>,
+}
+
+/// A single segment of a route that can go in the trie.
+enum Segment<'r> {
+    Static(&'r str),
+    Param,
+}
+
+impl Node {
+    fn insert(&mut self, segments: &[Segment<'_>], index: usize) {
+        match segments.split_first() {
+            None => self.routes.push(index),
+            Some((Segment::Static(text), rest)) => self
+                .statics
+                .entry(text.to_string())
+                .or_default()
+                .insert(rest, index),
+            Some((Segment::Param, rest)) => self.param.get_or_insert_with(Default::default).insert(rest, index),
+        }
+    }
+
+    fn find<'p>(&self, parts: &[&'p str], params: &mut SmallVec<[&'p str; 4]>, found: &mut Found<'p>) {
+        let (part, rest) = match parts.split_first() {
+            Some(split) => split,
+            None => {
+                found.extend(self.routes.iter().map(|&index| (index, params.to_vec())));
+                return;
+            }
+        };
+        if let Some(node) = self.statics.get(*part) {
+            node.find(rest, params, found);
+        }
+        if let Some(node) = self.param.as_ref().filter(|_| !part.is_empty()) {
+            params.push(part);
+            node.find(rest, params, found);
+            params.pop();
+        }
+    }
+}
+
+/// The segments of the given route, if it can go in the trie.  A segment
+/// can if it's all literal text (without escaped braces), or if it's a
+/// single string parameter, e.g. `{}`, `{id}`, or `{id:string}`.
+fn segments<'r, M, H>(route: &'r Route<M, H>, normalize: &Normalize) -> Option<Vec<Segment<'r>>> {
+    if route.catch_all {
+        return None;
+    }
+    // If decoding the path changes it, the segments can't borrow from it;
+    // these are rare enough to leave to the set.
+    let path = route.path.as_ref();
+    if crate::normalize_url(path) != path {
+        return None;
+    }
+    #[cfg(feature = "unicode-normalization")]
+    {
+        if normalize.nfc && !unicode_normalization::is_nfc(path) {
+            return None;
+        }
+    }
+    #[cfg(not(feature = "unicode-normalization"))]
+    let _ = normalize;
+
+    path.split('/')
+        .skip(1)
+        .map(|part| {
+            let mut caps = PLACEHOLDER.captures_iter(part);
+            let cap = match caps.next() {
+                None => return Some(Segment::Static(part)),
+                Some(cap) => cap,
+            };
+            let kind = cap.name("kind").map(|m| m.as_str());
+            let whole = cap.get(0).unwrap().as_str() == part;
+            let string = cap.name("escape").is_none()
+                && cap.name("args").is_none()
+                && kind.is_none_or(|kind| kind == "string");
+            if whole && string && caps.next().is_none() {
+                Some(Segment::Param)
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// The parameters captured by the route's pattern from the given path, if
+/// it matches.  Routes without parameters don't need their captures, so
+/// they're only checked for a match, which doesn't allocate.
+fn captures<'p, M, H>(route: &Route<M, H>, path: &'p str) -> Option<Vec<&'p str>> {
+    if route.params.is_empty() {
+        return route.pattern.is_match(path).then(Vec::new);
+    }
+    let caps = route.pattern.captures(path)?;
+    let mut params = Vec::with_capacity(route.params.len());
+    params.extend(caps.iter().skip(1).map(|m| m.unwrap().as_str()));
+    Some(params)
+}
+
+/// A function that finds a route by `match`ing the method and the path
+/// against the literal paths of the routes, as generated by the `routes!`
+/// macro.  It gives the index of the route in the order the routes were
+/// added to the builder.  See [`super::Build::static_routes`].
+pub type StaticDispatch<M> = fn(&M, &str) -> Option<usize>;
+
+#[derive(Clone)]
+/// The routes that can be found by a [`StaticDispatch`], before the
+/// matcher is tried.
+pub(super) struct Statics<M> {
+    dispatch: StaticDispatch<M>,
+    /// Where each of the routes the function knows about is evaluated, if
+    /// the function can be trusted with it.
+    routes: Vec<Option<usize>>,
+}
+
+impl<M> Statics<M> {
+    /// The function knows about the given number of routes, the first
+    /// ones added to the builder.
+    pub(super) fn new(dispatch: StaticDispatch<M>, len: usize) -> Self {
+        Statics {
+            dispatch,
+            routes: (0..len).map(Some).collect(),
+        }
+    }
+
+    /// Works out where the routes end up, given the order they're evaluated
+    /// in (the index each one was added at, in turn).  The function only
+    /// knows that none of its routes that were added before the one it
+    /// finds matches the path, so it's only trusted with the routes that
+    /// only come after its own, in the order they were added, and that
+    /// don't have anything besides the path and method to check.
+    pub(super) fn resolve<H>(self, order: &[usize], routes: &[Route<M, H>]) -> Self {
+        let mut resolved = vec![None; self.routes.len()];
+        for (at, &index) in order.iter().enumerate() {
+            if index != at || index >= resolved.len() {
+                break;
+            }
+            let route = &routes[index];
+            if route.guards.is_empty() && route.consumes.is_empty() && route.produces.is_empty() {
+                resolved[index] = Some(at);
+            }
+        }
+        Statics {
+            dispatch: self.dispatch,
+            routes: resolved,
+        }
+    }
+
+    /// The index of the route with the given method and literal path, if
+    /// the function finds one it can be trusted with.
+    pub(super) fn find(&self, method: &M, path: &str) -> Option<usize> {
+        *self.routes.get((self.dispatch)(method, path)?)?
+    }
+}
+
+impl<M> Debug for Statics<M> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("Statics").field("routes", &self.routes).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Build, Router};
+    use super::{Matcher, Shard};
+    use crate::Route;
+
+    fn routes(build: &mut Build<&'static str, i32>) {
+        build
+            .add(Route::new("/", "GET", 0))
+            .add(Route::new("/users/{}", "GET", 1))
+            .add(Route::new("/users/new", "GET", 2))
+            .add(Route::new("/users/{id:uint}/posts", "GET", 3))
+            .add(Route::new("/users/{id}/posts", "GET", 4))
+            .add(Route::new("/users/{id:string}/posts/{post}", "POST", 5))
+            .add(Route::new("/report/{id}.{format}", "GET", 6))
+            .add(Route::new("/literal/{{x}}", "GET", 7))
+            .add(Route::new("/users/", "GET", 8))
+            .add(Route::catch_all("/static", 9));
+    }
+
+    #[test]
+    fn test_trie_matches_set() {
+        let mut build = Router::build();
+        routes(&mut build);
+        let set = build.finish();
+        let mut build = Router::build();
+        routes(&mut build);
+        build.trie_matching();
+        let trie = build.finish();
+
+        for (method, path) in &[
+            ("GET", "/"),
+            ("GET", "/users/5"),
+            ("GET", "/users/new"),
+            ("GET", "/users/5/posts"),
+            ("GET", "/users/jane/posts"),
+            ("POST", "/users/jane/posts/7"),
+            ("GET", "/users/jane/posts/7"),
+            ("GET", "/report/5.json"),
+            ("GET", "/literal/{x}"),
+            ("GET", "/users/"),
+            ("GET", "/users//posts"),
+            ("GET", "/static/css/site.css"),
+            ("GET", "/missing"),
+            ("GET", ""),
+        ] {
+            assert_eq!(trie.lookup(method, path), set.lookup(method, path), "{} {}", method, path);
+            assert_eq!(trie.allowed_methods(path), set.allowed_methods(path), "{}", path);
+        }
+        assert_eq!(trie.lookup(&"GET", "/users/new"), Some((&1, vec!["new"])));
+        assert_eq!(trie.lookup(&"GET", "/users/5/posts"), Some((&3, vec!["5"])));
+    }
+
+    #[test]
+    fn test_sharded_sets() {
+        let mut build = Router::build();
+        routes(&mut build);
+        build
+            .add(Route::new("/{lang}/about", "GET", 10))
+            .add(Route::new("/caf%C3%A9/menu", "GET", 11))
+            .add(Route::new("/users", "GET", 12))
+            .add(Route::new("/users", "POST", 13));
+        for i in 0..200 {
+            build.add(Route::new(format!("/resource{}/{{}}", i), "GET", 100 + i));
+        }
+        let router = build.finish();
+        // The indices are in the order the routes are evaluated in, which
+        // puts the catch-all route last.
+        match &router.matcher {
+            Matcher::Set(sets) => {
+                let indices = |shard: &Shard| shard.indices.iter().map(|i| i.to_vec()).collect::<Vec<_>>();
+                assert_eq!(sets.shards.len(), 204);
+                // The two `/users` routes share their pattern.
+                assert_eq!(
+                    indices(&sets.shards["users"]),
+                    vec![vec![1], vec![2], vec![3], vec![4], vec![5], vec![8], vec![11, 12]]
+                );
+                assert_eq!(sets.shards["users"].set.len(), 7);
+                assert_eq!(indices(&sets.rest), vec![vec![0], vec![9], vec![10]]);
+            }
+            Matcher::Trie(_) => unreachable!(),
+        }
+
+        for (path, expected) in &[
+            ("/", Some((&0, vec![]))),
+            ("/users/5/posts", Some((&3, vec!["5"]))),
+            ("/users", Some((&12, vec![]))),
+            ("/users/5", Some((&1, vec!["5"]))),
+            ("/static/css/site.css", Some((&9, vec![]))),
+            ("/static", Some((&9, vec![]))),
+            ("/en/about", Some((&10, vec!["en"]))),
+            ("/users/about", Some((&1, vec!["about"]))),
+            ("/café/menu", Some((&11, vec![]))),
+            ("/resource150/5", Some((&250, vec!["5"]))),
+            ("/resource200/5", None),
+            ("resource150/5", None),
+            ("", None),
+        ] {
+            assert_eq!(router.lookup(&"GET", path), *expected, "{}", path);
+        }
+        assert_eq!(router.lookup(&"POST", "/users"), Some((&13, vec![])));
+    }
+}
@@ -1,10 +1,54 @@
 mod build;
+mod cache;
+mod config;
+mod explain;
+mod dynamic;
+mod extensions;
+mod info;
+#[cfg(feature = "openapi")]
+mod json;
+mod matched;
+mod matcher;
+mod no_match;
+mod normalize;
+#[cfg(feature = "openapi")]
+mod openapi;
+mod param;
+mod pattern;
+mod query;
+mod request;
 mod route;
+mod shared;
+mod table;
+mod url;
+mod validate;
 
-pub use self::build::Build;
-pub use self::route::Route;
-use regex::RegexSet;
-use std::fmt::{Debug, Formatter, Result as FmtResult};
+pub use self::build::{Build, BuildError, Scope};
+pub use self::config::{ConfigError, HandlerRegistry};
+pub use self::dynamic::DynamicRouter;
+pub use self::explain::{Explanation, Outcome};
+pub use self::extensions::Extensions;
+pub use self::info::RouteInfo;
+pub use self::matched::{Match, MatchHook};
+pub use self::matcher::StaticDispatch;
+pub use self::no_match::{Candidate, NoMatch, NoMatchHook};
+pub use self::normalize::{toggle_trailing_slash, NormalizeError};
+#[cfg(feature = "openapi")]
+pub use self::openapi::{ApiOperation, OpenApiError};
+pub use self::param::{FromParam, ParamError, Params, ParseParams, PathParams};
+pub use self::pattern::{PathPattern, PatternError};
+pub use self::query::{Query, QueryError};
+pub use self::request::{Guard, HttpVersion, RequestInfo};
+pub use self::route::{Route, RouteParseError, WarmUp, WarmUpFuture};
+pub use self::shared::SharedRouter;
+pub use self::url::{UrlBuilder, UrlError};
+pub use self::validate::{Conflict, ConflictKind, RouteRef};
+use self::cache::LookupCache;
+use self::matcher::{Found, Matcher, Statics};
+use self::normalize::Normalize;
+use failure::ResultExt;
+use std::borrow::Cow;
+use std::fmt::{Debug, Display, Formatter, Result as FmtResult};
 
 #[derive(Clone)]
 /// The main router.  This contains a set of routes that can be taken, as well
@@ -18,8 +62,37 @@ use std::fmt::{Debug, Formatter, Result as FmtResult};
 /// lookup is an immutable reference to the handler, if one exists.
 pub struct Router<Method, Handler> {
     routes: Vec<Route<Method, Handler>>,
-    set: RegexSet,
+    matcher: Matcher,
+    cache: Option<LookupCache>,
     default: Option<Handler>,
+    no_match: Option<NoMatchHook<Method>>,
+    on_match: Option<MatchHook<Method>>,
+    normalize: Normalize,
+    fallbacks: Vec<(Method, Method)>,
+    auto_options: Option<Method>,
+    redirect_trailing_slash: bool,
+    extensions: Extensions,
+    or: Option<Box<Or<Method, Handler>>>,
+    statics: Option<Statics<Method>>,
+}
+
+#[derive(Clone)]
+/// The router that lookups are retried against when they miss; see
+/// [`Router::or`].
+struct Or<M, H> {
+    router: Router<M, H>,
+    /// Whether a lookup that only missed because of its method is retried.
+    wrong_method: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// The methods allowed for a path, as given by [`Router::allowed_methods`].
+pub enum Allowed<'r, M> {
+    /// A route that matches any method matched the path.
+    Any,
+    /// Only these methods are allowed, in the order their routes are
+    /// evaluated.
+    Only(Vec<&'r M>),
 }
 
 impl<M: Eq, H> Router<M, H> {
@@ -32,35 +105,535 @@ impl<M: Eq, H> Router<M, H> {
     /// This performs the actual lookup.  We take a reference to the method, and
     /// a reference to the path, and return the handler and the url parameters,
     /// if they exist.  Note that the path **must** be URL decoded, and *only*
-    /// contain the path - it **must not** contain any query parameters.
+    /// contain the path - it **must not** contain any query parameters.  If
+    /// the router was built with any normalization options, the path should
+    /// be passed through [`Router::normalize`] first.
+    ///
+    /// Routes with guards (e.g. [`Route::with_host`]) never match here, since
+    /// there's no information about the request to check them against; use
+    /// [`Router::lookup_with`] for those.
     pub fn lookup<'s, 'p>(&'s self, method: &'_ M, path: &'p str) -> Option<(&'s H, Vec<&'p str>)> {
-        self.set
-            // First, we attempt to lookup any of the routes that match.  We
-            // use our regex set to narrow down the routes easily...
-            .matches(path)
-            // Which returns an iterator of indexes...
-            .iter()
-            // So we'll have to lookup the routes in our array.
-            .flat_map(|i| self.routes.get(i))
+        self.lookup_with(method, path, &())
+    }
+
+    /// Performs the lookup like [`Router::lookup`], but also checks the
+    /// guards of each route against the given request information.
+    pub fn lookup_with<'s, 'p>(
+        &'s self,
+        method: &'_ M,
+        path: &'p str,
+        request: &dyn RequestInfo,
+    ) -> Option<(&'s H, Vec<&'p str>)> {
+        self.lookup_route_with(method, path, request)
+            .map(|(route, params)| {
+                self.hit(method, path, route);
+                (&route.handler, params)
+            })
+            .or_else(|| self.miss(method, path))
+    }
+
+    /// Handles a lookup that matched the given route, by letting the hook
+    /// know.
+    pub(crate) fn hit(&self, method: &M, path: &str, route: &Route<M, H>) {
+        if let Some(hook) = &self.on_match {
+            hook.call(method, path, Some(route));
+        }
+    }
+
+    /// Handles a lookup that matched no routes.  We'll let the hooks know,
+    /// and return the default, if it exists.
+    pub(crate) fn miss<'s, 'p>(&'s self, method: &M, path: &str) -> Option<(&'s H, Vec<&'p str>)> {
+        self.no_match(method, path);
+        let default = self.default.as_ref()?;
+        if let Some(hook) = &self.on_match {
+            hook.call::<H>(method, path, None);
+        }
+        Some((default, vec![]))
+    }
+
+    /// Calls the no-match hook, for a lookup that matched no routes, but is
+    /// handled by something other than the default.
+    pub(crate) fn no_match(&self, method: &M, path: &str) {
+        if let Some(hook) = &self.no_match {
+            hook.call(&self.routes, method, path);
+        }
+    }
+
+    /// Looks up the route that matches the given method and path, returning
+    /// the route itself along with the url parameters.  Unlike
+    /// [`Router::lookup`], this never falls back to the default, and doesn't
+    /// call the no-match hook.  Having the route on hand allows parameters
+    /// to be retrieved by name, with [`Route::param_index`].
+    ///
+    /// If no route matches the method, but the router was built with a
+    /// fallback for it (see [`Build::fallback_method`]), the lookup is tried
+    /// again with the fallback method.
+    pub fn lookup_route<'s, 'p>(
+        &'s self,
+        method: &'_ M,
+        path: &'p str,
+    ) -> Option<(&'s Route<M, H>, Vec<&'p str>)> {
+        self.lookup_route_with(method, path, &())
+    }
+
+    /// Looks up the route like [`Router::lookup_route`], but also checks the
+    /// guards of each route against the given request information.
+    ///
+    /// If the first route that matches declares what it produces (see
+    /// [`Route::produces`]), then the content is negotiated: of it and the
+    /// other matching routes that declare what they produce, the one the
+    /// request's `Accept` header prefers is used.
+    pub fn lookup_route_with<'s, 'p>(
+        &'s self,
+        method: &'_ M,
+        path: &'p str,
+        request: &dyn RequestInfo,
+    ) -> Option<(&'s Route<M, H>, Vec<&'p str>)> {
+        self.find_own_route(method, path, request)
+            .map(|(i, params)| (&self.routes[i], params))
+            .or_else(|| self.or_for(path)?.lookup_route_with(method, path, request))
+    }
+
+    /// Looks up the index of the route like [`Router::lookup_route_with`],
+    /// but only among the router's own routes, not its fallback's.
+    fn find_own_route<'p>(&self, method: &M, path: &'p str, request: &dyn RequestInfo) -> Option<(usize, Vec<&'p str>)> {
+        self.find_route(method, path, request).or_else(|| {
+            self.fallbacks
+                .iter()
+                .filter(|(from, _)| from == method)
+                .find_map(|(_, to)| self.find_route(to, path, request))
+        })
+    }
+
+    /// Retries lookups that miss against the given router, before falling
+    /// back to the default, e.g. to layer a router of plugins over a core
+    /// router.  A lookup misses if no route matches the path, or if routes
+    /// match the path, but not the method, so the fallback can add methods
+    /// to a path; see [`Router::or_for_missing_paths`] to only retry the
+    /// former.  Only the fallback's routes are used: its default, hooks,
+    /// and normalization are ignored, as with [`Build::mount`].  If the
+    /// router already has a fallback, the given one is tried after it.  If
+    /// the router is mounted, the fallback's routes are mounted after its
+    /// own.
+    pub fn or(self, fallback: Router<M, H>) -> Self {
+        self.chain(fallback, true)
+    }
+
+    /// Retries lookups against the given router like [`Router::or`], but
+    /// only if no route matches the path at all; a path that this router
+    /// has routes for, but not for the request's method, is answered by
+    /// this router alone (e.g. with a 405 Method Not Allowed).
+    pub fn or_for_missing_paths(self, fallback: Router<M, H>) -> Self {
+        self.chain(fallback, false)
+    }
+
+    fn chain(mut self, router: Router<M, H>, wrong_method: bool) -> Self {
+        let or = match self.or.take() {
+            Some(or) => Or {
+                router: or.router.chain(router, wrong_method),
+                wrong_method: or.wrong_method,
+            },
+            None => Or { router, wrong_method },
+        };
+        self.or = Some(Box::new(or));
+        self
+    }
+
+    /// The router a lookup of the given path that missed should be retried
+    /// against, if any.
+    fn or_for(&self, path: &str) -> Option<&Router<M, H>> {
+        let or = self.or.as_ref()?;
+        if !or.wrong_method && self.matches_path(path) {
+            return None;
+        }
+        Some(&or.router)
+    }
+
+    /// Whether any of the router's own routes matches the given path, with
+    /// any method.
+    fn matches_path(&self, path: &str) -> bool {
+        self.pattern_matches(path)
+            .into_iter()
+            .any(|(i, params)| self.routes[i].allows(&params))
+    }
+
+    /// Looks up the route like [`Router::lookup_route`], but returns its
+    /// index instead, which is its position in [`Router::routes`] (the order
+    /// the routes are evaluated in).  This lets the router be used purely as
+    /// a matcher, e.g. as a `Router<Method, ()>`, with the handlers kept in
+    /// the caller's own table, in the same order.
+    pub fn lookup_index<'p>(&self, method: &M, path: &'p str) -> Option<(usize, Vec<&'p str>)> {
+        self.lookup_index_with(method, path, &())
+    }
+
+    /// Looks up the route's index like [`Router::lookup_index`], but also
+    /// checks the guards of each route against the given request
+    /// information.
+    pub fn lookup_index_with<'p>(
+        &self,
+        method: &M,
+        path: &'p str,
+        request: &dyn RequestInfo,
+    ) -> Option<(usize, Vec<&'p str>)> {
+        if let Some(found) = self.find_own_route(method, path, request) {
+            return Some(found);
+        }
+        // The fallback's routes come after ours.
+        let (i, params) = self.or_for(path)?.lookup_index_with(method, path, request)?;
+        Some((self.routes.len() + i, params))
+    }
+
+    /// The methods that are allowed for the given path, i.e. the methods of
+    /// every route that matches it, including any methods that fall back to
+    /// them, and those of the router's fallback (see [`Router::or`]).  If
+    /// no route matches the path, this returns `None`.  Like
+    /// [`Router::lookup`], the path **must** be URL decoded.
+    pub fn allowed_methods(&self, path: &str) -> Option<Allowed<'_, M>> {
+        let own = self.own_allowed_methods(path);
+        let or = match self.or_for(path) {
+            Some(or) => or.allowed_methods(path),
+            None => return own,
+        };
+        match (own, or) {
+            (own, None) => own,
+            (None, or) => or,
+            (Some(Allowed::Any), _) | (_, Some(Allowed::Any)) => Some(Allowed::Any),
+            (Some(Allowed::Only(mut allowed)), Some(Allowed::Only(more))) => {
+                for method in more {
+                    if !allowed.contains(&method) {
+                        allowed.push(method);
+                    }
+                }
+                Some(Allowed::Only(allowed))
+            }
+        }
+    }
+
+    /// The methods allowed for the given path by the router's own routes.
+    fn own_allowed_methods(&self, path: &str) -> Option<Allowed<'_, M>> {
+        let mut allowed = vec![];
+        let mut matched = false;
+        let routes = self
+            .pattern_matches(path)
+            .into_iter()
+            .map(|(i, params)| (&self.routes[i], params))
+            .filter(|(route, params)| route.allows(params))
+            .map(|(route, _)| route);
+        for route in routes {
+            matched = true;
+            match &route.methods {
+                None => return Some(Allowed::Any),
+                Some(methods) => allowed.extend(methods),
+            }
+        }
+        if !matched {
+            return None;
+        }
+
+        for (from, to) in &self.fallbacks {
+            if allowed.contains(&to) {
+                allowed.push(from);
+            }
+        }
+        let mut seen = vec![];
+        allowed.retain(|method| {
+            let new = !seen.contains(method);
+            seen.push(*method);
+            new
+        });
+        Some(Allowed::Only(allowed))
+    }
+
+    /// Answers the given method automatically, if the router was built with
+    /// [`Build::auto_options`] for it, and no route for the method matches
+    /// the path.  The methods allowed for the path are returned, including
+    /// the automatic method itself; if no route matches the path at all,
+    /// this returns `None`, and the request should be looked up as usual.
+    pub fn auto_options<'s>(&'s self, method: &M, path: &str) -> Option<Allowed<'s, M>> {
+        let options = self.auto_options.as_ref().filter(|options| *options == method)?;
+        if self.lookup_route(method, path).is_some() {
+            return None;
+        }
+        match self.allowed_methods(path)? {
+            Allowed::Any => Some(Allowed::Any),
+            Allowed::Only(mut allowed) => {
+                if !allowed.contains(&options) {
+                    allowed.push(options);
+                }
+                Some(Allowed::Only(allowed))
+            }
+        }
+    }
+
+    /// Whether the lookup for the given method and path should be answered
+    /// with a redirect to the other form of the path (see
+    /// [`toggle_trailing_slash`]), because the router was built with
+    /// [`Build::redirect_trailing_slash`], no route matches the path, and
+    /// one matches the other form.  Like the lookup, the path **must** be
+    /// URL decoded.
+    pub fn redirects_trailing_slash(&self, method: &M, path: &str, request: &dyn RequestInfo) -> bool {
+        if !self.redirect_trailing_slash || self.lookup_route_with(method, path, request).is_some() {
+            return false;
+        }
+        match toggle_trailing_slash(path) {
+            Some(other) => self.lookup_route_with(method, &other, request).is_some(),
+            None => false,
+        }
+    }
+
+    /// The `Location` of the redirect for the given path, if the lookup for
+    /// it is answered with one (see [`Router::redirects_trailing_slash`]):
+    /// the other form of the path, percent-encoded again.  The path should
+    /// be the one that was looked up, i.e. decoded and normalized, and not
+    /// the one from the request, so that the redirect goes to a path that
+    /// matches.  Leading slashes are collapsed into one, so that the
+    /// location can never be a protocol-relative url (like `//evil.com`),
+    /// which would send the client to another host.
+    pub fn redirect_location(&self, path: &str) -> Option<String> {
+        let other = toggle_trailing_slash(path)?;
+        Some(format!("/{}", self.normalize.encode(other.trim_start_matches('/'))))
+    }
+
+    /// Finds the index of the route that matches the given method and path,
+    /// with the url parameters it captures.
+    fn find_route<'p>(&self, method: &M, path: &'p str, request: &dyn RequestInfo) -> Option<(usize, Vec<&'p str>)> {
+        // A route with a literal path that's found by the `routes!` macro's
+        // `match` is the one the matcher would find first.
+        if let Some(index) = self.statics.as_ref().and_then(|statics| statics.find(method, path)) {
+            return Some((index, vec![]));
+        }
+        let mut routes = self
+            .matching_routes(method, path, request)
+            .filter(|(_, route, _)| route.consumes_allow(request))
+            .map(|(i, route, params)| (route.produces_quality(request), i, params))
+            .filter(|(quality, _, _)| *quality > 0.0);
+        // Grab the first route that matched.  If it declares what it
+        // produces, we'll negotiate between it and the other matching routes
+        // that do, using the request's preferences; ties go to the earlier
+        // route.
+        let first = routes.next()?;
+        if self.routes[first.1].produces.is_empty() {
+            return Some((first.1, first.2));
+        }
+        let (_, i, params) = routes
+            .filter(|(_, i, _)| !self.routes[*i].produces.is_empty())
+            .fold(first, |best, next| if next.0 > best.0 { next } else { best });
+        Some((i, params))
+    }
+
+    /// Whether the lookup for the given method and path failed only because
+    /// of the content type of the request - that is, a route would have
+    /// matched, but none of the routes that match accept the request's
+    /// content type (see [`Route::consumes`]).  The hyper `Service` answers
+    /// these with a 415 Unsupported Media Type.
+    pub fn rejects_content_type(&self, method: &M, path: &str, request: &dyn RequestInfo) -> bool {
+        let mut routes = self.matching_routes(method, path, request).peekable();
+        if routes.peek().is_some() {
+            return routes.all(|(_, route, _)| !route.consumes_allow(request));
+        }
+        self.or_for(path)
+            .is_some_and(|or| or.rejects_content_type(method, path, request))
+    }
+
+    fn matching_routes<'s, 'p, 'a>(
+        &'s self,
+        method: &'a M,
+        path: &'p str,
+        request: &'a dyn RequestInfo,
+    ) -> impl Iterator<Item = (usize, &'s Route<M, H>, Vec<&'p str>)> + 'a
+    where
+        's: 'a,
+        'p: 'a,
+    {
+        self
+            // First, we find the routes whose pattern matches the path, with
+            // the url parameters they capture.  This uses either our regex
+            // set, or the trie (see `Build::trie_matching`), to narrow down
+            // the routes easily, unless the path is in the lookup cache...
+            .pattern_matches(path)
+            // Which returns the indexes of the routes, in order...
+            .into_iter()
+            // So we'll have to lookup the routes in our array, keeping the
+            // indexes for those who need them (see `Router::lookup_index`).
+            .map(move |(i, params)| (i, &self.routes[i], params))
             // We then verify that the route has the corresponding method...
-            .filter(|route| method == &route.method)
-            // Then, we use the route's internal pattern to do the lookup.
-            // This serves two purposes: 1. collect the url parameters; and 2.
-            // verify that the route actually matched.
-            .flat_map(|route| {
-                route.pattern.captures(path).map(|caps| {
-                    let caps = caps
-                        .iter()
-                        .skip(1)
-                        .map(|m| m.unwrap().as_str())
-                        .collect::<Vec<_>>();
-                    (&route.handler, caps)
-                })
+            .filter(move |(_, route, _)| route.matches_method(method))
+            // The parameters are then checked against any constraints the
+            // pattern couldn't express, like numeric ranges.  Last of all,
+            // since they can be arbitrarily expensive, the route's guards are
+            // checked against the request.
+            .filter(|(_, route, params)| route.allows(params))
+            .filter(move |(_, route, _)| route.guards_allow(request))
+    }
+
+    /// The routes whose pattern matches the given path, with the parameters
+    /// they capture.  If the router has a lookup cache (see
+    /// [`Build::with_lookup_cache`]), it's used, and filled in.
+    fn pattern_matches<'p>(&self, path: &'p str) -> Found<'p> {
+        let cache = match &self.cache {
+            Some(cache) => cache,
+            None => return self.matcher.matches(&self.routes, path),
+        };
+        if let Some(found) = cache.get(path) {
+            // Entries are only ever cached for this table, but an index that
+            // isn't ours would panic, so it's checked all the same.
+            if found.iter().all(|(i, _)| *i < self.routes.len()) {
+                return found;
+            }
+        }
+        let found = self.matcher.matches(&self.routes, path);
+        cache.insert(path, &found);
+        found
+    }
+
+    /// The routes of the router, in the order they're evaluated in,
+    /// followed by those of its fallback (see [`Router::or`]).
+    pub fn routes(&self) -> impl Iterator<Item = RouteInfo<'_, M>> + '_ {
+        let or = self.or.iter().flat_map(|or| {
+            Box::new(or.router.routes()) as Box<dyn Iterator<Item = RouteInfo<'_, M>>>
+        });
+        self.routes.iter().map(RouteInfo::new).chain(or)
+    }
+
+    /// Builds the url of the route with the given name (see
+    /// [`Route::with_name`]), with the given values for its parameters, in
+    /// the order they appear in its path.  Each value has to match the kind
+    /// of its parameter, e.g. a `{:uint}` parameter can't be given `abc`;
+    /// otherwise, or if the wrong number of values is given, this fails
+    /// with an error that says which.  The values are percent-encoded.
+    pub fn url_for(&self, name: &str, params: &[&dyn Display]) -> Result<String, UrlError> {
+        let route = self.named(name)?;
+        let values = params.iter().map(|param| param.to_string()).collect::<Vec<_>>();
+        url::build_url(route, name, &values)
+    }
+
+    /// Returns a builder for the url of the route with the given name, whose
+    /// parameters are given by name instead of by position.  See
+    /// [`Router::url_for`].
+    pub fn url<'s>(&'s self, name: &'s str) -> Result<UrlBuilder<'s, M, H>, UrlError> {
+        self.named(name).map(|route| UrlBuilder::new(route, name))
+    }
+
+    fn named(&self, name: &str) -> Result<&Route<M, H>, UrlError> {
+        self.routes
+            .iter()
+            .find(|route| route.name() == Some(name))
+            .ok_or_else(|| UrlError::UnknownRoute(name.to_string()))
+    }
+
+    /// Strips the query from the given url, and URL decodes the rest, so
+    /// that it can be given to [`Router::normalize`] and [`Router::lookup`].
+    /// If the router was built with [`Build::keep_encoded_slashes`], then
+    /// `%2F`, `%23`, and `%25` are left encoded, and the parameters that
+    /// come out of the lookup should be passed through
+    /// [`Router::decode_param`].  This only fails if the router was built
+    /// with [`Build::strict_decoding`].
+    pub fn decode(&self, url: &str) -> Result<String, NormalizeError> {
+        self.normalize.decode(url)
+    }
+
+    /// Decodes a parameter from a path that was decoded with
+    /// [`Router::decode`].  Unless the router was built with
+    /// [`Build::keep_encoded_slashes`], the parameter is already decoded,
+    /// and is borrowed as-is.
+    pub fn decode_param<'p>(&self, param: &'p str) -> Cow<'p, str> {
+        self.normalize.decode_param(param)
+    }
+
+    /// Normalizes the given path according to the options the router was
+    /// built with (e.g. [`Build::collapse_slashes`]), so that it can be
+    /// given to [`Router::lookup`].  Like the lookup, the path **must**
+    /// already be URL decoded.  If nothing needs to change, the path is
+    /// borrowed as-is.  Some options can reject a path outright (e.g.
+    /// [`Build::reject_root_escape`]); such a path shouldn't be looked up.
+    pub fn normalize<'p>(&self, path: &'p str) -> Result<Cow<'p, str>, NormalizeError> {
+        self.normalize.apply(path)
+    }
+
+    /// Runs the warm-up callbacks of every route, in order.  This should be
+    /// awaited once, when the application starts, before the router begins
+    /// serving requests.  The first callback that fails stops the warm-up,
+    /// and its error is returned with the path of the failing route attached
+    /// as context.
+    pub async fn warm_up(&self) -> Result<(), failure::Error> {
+        for route in &self.routes {
+            if let Some(warm_up) = &route.warm_up {
+                warm_up
+                    .call()
+                    .await
+                    .with_context(|_| format!("warm-up failed for route {}", route.path))?;
+            }
+        }
+        if let Some(or) = &self.or {
+            Box::pin(or.router.warm_up()).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Creates a copy of this router with every handler replaced by the one
+    /// the given function returns for that route, given its information
+    /// (as in [`Router::routes`]).  The compiled patterns, the regex set,
+    /// and the order of the routes are all reused, so this is much cheaper
+    /// than building the router again - handy for swapping in test doubles
+    /// or per-environment handlers.
+    ///
+    /// The default handler and any warm-up callbacks belong to the old
+    /// handlers, and so are not carried over; use [`Router::set_default`] to
+    /// give the new router a default.
+    pub fn rebind<H2, F>(&self, mut rebind: F) -> Router<M, H2>
+    where
+        M: Clone,
+        F: FnMut(&RouteInfo<'_, M>) -> H2,
+    {
+        self.rebind_with(&mut rebind)
+    }
+
+    fn rebind_with<H2>(&self, rebind: &mut dyn FnMut(&RouteInfo<'_, M>) -> H2) -> Router<M, H2>
+    where
+        M: Clone,
+    {
+        let routes = self
+            .routes
+            .iter()
+            .map(|route| Route {
+                path: route.path.clone(),
+                methods: route.methods.clone(),
+                handler: rebind(&RouteInfo::new(route)),
+                pattern: route.pattern.clone(),
+                params: route.params.clone(),
+                constraints: route.constraints.clone(),
+                warm_up: None,
+                priority: route.priority,
+                guards: route.guards.clone(),
+                consumes: route.consumes.clone(),
+                produces: route.produces.clone(),
+                catch_all: route.catch_all,
+                name: route.name.clone(),
+                extensions: route.extensions.clone(),
             })
-            // Grab the first route that matched.
-            .next()
-            // If no routes matched, we'll return the default, if it exists.
-            .or_else(|| self.default.as_ref().map(|h| (h, vec![])))
+            .collect();
+
+        Router {
+            routes,
+            matcher: self.matcher.clone(),
+            cache: self.cache.clone(),
+            default: None,
+            no_match: self.no_match.clone(),
+            on_match: self.on_match.clone(),
+            normalize: self.normalize,
+            fallbacks: self.fallbacks.clone(),
+            auto_options: self.auto_options.clone(),
+            redirect_trailing_slash: self.redirect_trailing_slash,
+            extensions: self.extensions.clone(),
+            or: self.or.as_ref().map(|or| {
+                Box::new(Or {
+                    router: or.router.rebind_with(rebind),
+                    wrong_method: or.wrong_method,
+                })
+            }),
+            statics: self.statics.clone(),
+        }
     }
 
     /// Sets the default of the router.  This is similar to
@@ -68,6 +641,36 @@ impl<M: Eq, H> Router<M, H> {
     pub fn set_default(&mut self, default: H) {
         self.default = Some(default);
     }
+
+    /// Sets the hook called when a lookup matches no routes.  This is
+    /// similar to [`Build::on_no_match`].
+    pub fn on_no_match<F>(&mut self, hook: F)
+    where
+        F: Fn(&NoMatch<'_, M>) + Send + Sync + 'static,
+    {
+        self.no_match = Some(NoMatchHook::new(hook));
+    }
+
+    /// Settings for the integration with an HTTP library, e.g. the access
+    /// log of the hyper `Service`.
+    #[cfg(feature = "hyper")]
+    pub(crate) fn extensions(&self) -> &Extensions {
+        &self.extensions
+    }
+
+    #[cfg(feature = "hyper")]
+    pub(crate) fn extensions_mut(&mut self) -> &mut Extensions {
+        &mut self.extensions
+    }
+
+    /// Sets the hook called when a lookup matches a route, or falls back
+    /// to the default.  This is similar to [`Build::on_match`].
+    pub fn on_match<F>(&mut self, hook: F)
+    where
+        F: Fn(&Match<'_, M>) + Send + Sync + 'static,
+    {
+        self.on_match = Some(MatchHook::new(hook));
+    }
 }
 
 impl<M: Debug, H: Debug> Debug for Router<M, H> {
@@ -102,7 +705,7 @@ mod tests {
             Get,
             #[allow(dead_code)]
             Post,
-        };
+        }
         let mut build = Router::build();
         build
             .add(Route::new("/some/path", Method::Get, 1))
@@ -134,4 +737,1649 @@ mod tests {
         );
         assert_eq!(router.lookup(&Method::Get, "/soap"), None);
     }
+
+    #[test]
+    fn test_semver_routes() {
+        let mut build = Router::build();
+        build.add(Route::new("/releases/{:semver}/changelog", "GET", 1));
+        let router = build.finish();
+
+        for version in &[
+            "1.2.3",
+            "0.0.0",
+            "1.0.0-rc.1",
+            "1.0.0-alpha-a.b-c",
+            "1.0.0+build.5",
+            "10.20.30-0.3.7+20130313144700",
+        ] {
+            let path = format!("/releases/{}/changelog", version);
+            assert_eq!(router.lookup(&"GET", &path), Some((&1, vec![*version])));
+        }
+
+        for version in &[
+            "1.2", "01.2.3", "1.2.3-", "1.2.3-01", "1.2.3+", "1.2.3.4", "v1.2.3",
+        ] {
+            let path = format!("/releases/{}/changelog", version);
+            assert_eq!(
+                router.lookup(&"GET", &path),
+                None,
+                "{} should not match",
+                version
+            );
+        }
+    }
+
+    #[test]
+    fn test_ip_routes() {
+        let mut build = Router::build();
+        build
+            .add(Route::new("/hosts/{:ipv4}", "GET", 4))
+            .add(Route::new("/hosts/{:ipv6}", "GET", 6));
+        let router = build.finish();
+
+        for address in &["192.168.0.1", "0.0.0.0", "255.255.255.255"] {
+            let path = format!("/hosts/{}", address);
+            assert_eq!(router.lookup(&"GET", &path), Some((&4, vec![*address])));
+        }
+
+        for address in &[
+            "::",
+            "::1",
+            "2001:db8::1",
+            "1:2:3:4:5:6:7:8",
+            "::ffff:192.168.0.1",
+            "FE80::1",
+        ] {
+            let path = format!("/hosts/{}", address);
+            assert_eq!(router.lookup(&"GET", &path), Some((&6, vec![*address])));
+        }
+
+        for address in &[
+            "256.0.0.1",
+            "1.2.3",
+            "01.2.3.4",
+            ":::",
+            "1::2::3",
+            "1:2:3:4:5:6:7:8:9",
+            "example",
+        ] {
+            let path = format!("/hosts/{}", address);
+            assert_eq!(
+                router.lookup(&"GET", &path),
+                None,
+                "{} should not match",
+                address
+            );
+        }
+
+        let decoded = crate::normalize_url("/hosts/2001%3Adb8%3A%3A1");
+        assert_eq!(
+            router.lookup(&"GET", &decoded),
+            Some((&6, vec!["2001:db8::1"]))
+        );
+    }
+
+    #[test]
+    fn test_grpc_method_routes() {
+        let mut build = Router::build();
+        build.add(Route::new("/users.v1.UserService/{:grpc_method}", "POST", 1));
+        let router = build.finish();
+
+        assert_eq!(
+            router.lookup(&"POST", "/users.v1.UserService/GetUser"),
+            Some((&1, vec!["GetUser"]))
+        );
+        assert_eq!(
+            router.lookup(&"POST", "/users.v1.UserService/_list_2"),
+            Some((&1, vec!["_list_2"]))
+        );
+        for path in &[
+            "/users.v1.UserService/2Get",
+            "/users.v1.UserService/Get-User",
+            "/usersXv1.UserService/GetUser",
+        ] {
+            assert_eq!(router.lookup(&"POST", path), None, "{} should not match", path);
+        }
+    }
+
+    #[test]
+    fn test_email_routes() {
+        let mut build = Router::build();
+        build.add(Route::new("/unsubscribe/{:email}", "GET", 1));
+        let router = build.finish();
+
+        for address in &[
+            "someone@example.com",
+            "first.last+tag@mail.example.co.uk",
+            "o'neil@x-y.io",
+        ] {
+            let path = format!("/unsubscribe/{}", address);
+            assert_eq!(router.lookup(&"GET", &path), Some((&1, vec![*address])));
+        }
+
+        for address in &[
+            "someone",
+            "@example.com",
+            "someone@",
+            "someone@localhost",
+            "a@b@example.com",
+            "a@-example.com",
+        ] {
+            let path = format!("/unsubscribe/{}", address);
+            assert_eq!(
+                router.lookup(&"GET", &path),
+                None,
+                "{} should not match",
+                address
+            );
+        }
+    }
+
+    #[test]
+    fn test_one_of_routes() {
+        let mut build = Router::build();
+        build.add(Route::new(
+            "/report/{format:one_of(json|xml|csv)}",
+            "GET",
+            1,
+        ));
+        let router = build.finish();
+
+        assert_eq!(
+            router.lookup(&"GET", "/report/json"),
+            Some((&1, vec!["json"]))
+        );
+        assert_eq!(
+            router.lookup(&"GET", "/report/csv"),
+            Some((&1, vec!["csv"]))
+        );
+        assert_eq!(router.lookup(&"GET", "/report/jsonx"), None);
+        assert_eq!(router.lookup(&"GET", "/report/yaml"), None);
+    }
+
+    #[test]
+    fn test_range_routes() {
+        let mut build = Router::build();
+        build
+            .add(Route::new("/users/{:uint(1..=9999)}", "GET", 1))
+            .add(Route::new("/offset/{:int(-10..10)}", "GET", 2));
+        let router = build.finish();
+
+        assert_eq!(router.lookup(&"GET", "/users/1"), Some((&1, vec!["1"])));
+        assert_eq!(
+            router.lookup(&"GET", "/users/9999"),
+            Some((&1, vec!["9999"]))
+        );
+        assert_eq!(router.lookup(&"GET", "/users/0"), None);
+        assert_eq!(router.lookup(&"GET", "/users/10000"), None);
+        assert_eq!(
+            router.lookup(&"GET", "/offset/-10"),
+            Some((&2, vec!["-10"]))
+        );
+        assert_eq!(router.lookup(&"GET", "/offset/9"), Some((&2, vec!["9"])));
+        assert_eq!(router.lookup(&"GET", "/offset/10"), None);
+    }
+
+    #[test]
+    fn test_extension_routes() {
+        let mut build = Router::build();
+        build
+            .add(Route::new(
+                "/report/{id:uint}.{format:one_of(json|csv)}",
+                "GET",
+                1,
+            ))
+            .add(Route::new("/archive/{name}.tar.gz", "GET", 2))
+            .add(Route::new("/report/{id:uint}", "GET", 3));
+        let router = build.finish();
+
+        assert_eq!(
+            router.lookup(&"GET", "/report/5.json"),
+            Some((&1, vec!["5", "json"]))
+        );
+        assert_eq!(
+            router.lookup(&"GET", "/report/5.csv"),
+            Some((&1, vec!["5", "csv"]))
+        );
+        assert_eq!(router.lookup(&"GET", "/report/5"), Some((&3, vec!["5"])));
+        assert_eq!(router.lookup(&"GET", "/report/5.xml"), None);
+        assert_eq!(router.lookup(&"GET", "/report/x.json"), None);
+        assert_eq!(
+            router.lookup(&"GET", "/archive/v1.0.tar.gz"),
+            Some((&2, vec!["v1.0"]))
+        );
+        assert_eq!(router.lookup(&"GET", "/archive/v1.0.zip"), None);
+    }
+
+    #[test]
+    fn test_mixed_segment_routes() {
+        let mut build = Router::build();
+        build
+            .add(Route::new("/orders/order-{id:uint}", "GET", 1))
+            .add(Route::new("/v{version:uint}/users", "GET", 2))
+            .add(Route::new("/orders/{id}", "GET", 3));
+        let router = build.finish();
+
+        assert_eq!(
+            router.lookup(&"GET", "/orders/order-15"),
+            Some((&1, vec!["15"]))
+        );
+        assert_eq!(
+            router.lookup(&"GET", "/orders/order-x"),
+            Some((&3, vec!["order-x"]))
+        );
+        assert_eq!(router.lookup(&"GET", "/orders/15"), Some((&3, vec!["15"])));
+        assert_eq!(router.lookup(&"GET", "/v2/users"), Some((&2, vec!["2"])));
+        assert_eq!(router.lookup(&"GET", "/v/users"), None);
+        assert_eq!(router.lookup(&"GET", "/2/users"), None);
+    }
+
+    #[test]
+    fn test_multiple_param_segments() {
+        let mut build = Router::build();
+        build
+            .add(Route::new("/range/{from:uint}-{to:uint}", "GET", 1))
+            .add(Route::new(
+                "/at/{lat:int}.{:uint},{lng:int}.{:uint}",
+                "GET",
+                2,
+            ));
+        let router = build.finish();
+
+        assert_eq!(
+            router.lookup(&"GET", "/range/5-10"),
+            Some((&1, vec!["5", "10"]))
+        );
+        assert_eq!(router.lookup(&"GET", "/range/5-"), None);
+        assert_eq!(
+            router.lookup(&"GET", "/at/-33.86,151.20"),
+            Some((&2, vec!["-33", "86", "151", "20"]))
+        );
+
+        let (route, params) = router.lookup_route(&"GET", "/range/5-10").unwrap();
+        assert_eq!(route.param_index("to").map(|i| params[i]), Some("10"));
+        assert_eq!(route.param_index("from").map(|i| params[i]), Some("5"));
+        assert_eq!(route.param_index("missing"), None);
+        assert!(router.lookup_route(&"GET", "/range").is_none());
+    }
+
+    #[test]
+    fn test_escaped_braces() {
+        let mut build = Router::build();
+        build
+            .add(Route::new("/templates/{{name}}", "GET", 1))
+            .add(Route::new("/templates/{{{name}}}/{:uint}", "GET", 2));
+        let router = build.finish();
+
+        assert_eq!(
+            router.lookup(&"GET", "/templates/{name}"),
+            Some((&1, vec![]))
+        );
+        assert_eq!(router.lookup(&"GET", "/templates/name"), None);
+        assert_eq!(
+            router.lookup(&"GET", "/templates/{user}/5"),
+            Some((&2, vec!["user", "5"]))
+        );
+        assert_eq!(router.lookup(&"GET", "/templates/user/5"), None);
+    }
+
+    #[test]
+    fn test_by_specificity() {
+        let mut build = Router::build();
+        build
+            .add(Route::new("/users/{id}", "GET", 1))
+            .add(Route::new("/users/{id:uint}", "GET", 2))
+            .add(Route::new("/users/new", "GET", 3))
+            .add(Route::new("/{}/posts", "GET", 4))
+            .add(Route::new("/users/{id}/posts", "GET", 5))
+            .add(Route::new("/users/{name}", "GET", 6))
+            .by_specificity();
+        let router = build.finish();
+
+        assert_eq!(router.lookup(&"GET", "/users/new"), Some((&3, vec![])));
+        assert_eq!(router.lookup(&"GET", "/users/5"), Some((&2, vec!["5"])));
+        assert_eq!(
+            router.lookup(&"GET", "/users/jane"),
+            Some((&1, vec!["jane"]))
+        );
+        assert_eq!(
+            router.lookup(&"GET", "/users/posts"),
+            Some((&1, vec!["posts"]))
+        );
+        assert_eq!(
+            router.lookup(&"GET", "/users/5/posts"),
+            Some((&5, vec!["5"]))
+        );
+    }
+
+    #[test]
+    fn test_priority() {
+        let mut build = Router::build();
+        build
+            .add(Route::new("/users/{id}", "GET", 1))
+            .add(Route::new("/users/{id:uint}", "GET", 2).with_priority(-1))
+            .add(Route::new("/users/new", "GET", 3))
+            .add(Route::new("/users/{name}", "GET", 4).with_priority(5))
+            .add(Route::new("/users/me", "GET", 5).with_priority(5));
+        let router = build.finish();
+
+        assert_eq!(
+            router.lookup(&"GET", "/users/new"),
+            Some((&4, vec!["new"]))
+        );
+        assert_eq!(router.lookup(&"GET", "/users/5"), Some((&4, vec!["5"])));
+        assert_eq!(
+            router.lookup(&"GET", "/users/me"),
+            Some((&4, vec!["me"]))
+        );
+
+        let mut build = Router::build();
+        build
+            .add(Route::new("/users/{id}", "GET", 1))
+            .add(Route::new("/users/{id:uint}", "GET", 2))
+            .add(Route::new("/users/new", "GET", 3).with_priority(-1))
+            .by_specificity();
+        let router = build.finish();
+
+        assert_eq!(router.lookup(&"GET", "/users/5"), Some((&2, vec!["5"])));
+        assert_eq!(
+            router.lookup(&"GET", "/users/new"),
+            Some((&1, vec!["new"]))
+        );
+    }
+
+    #[test]
+    fn test_collapse_slashes() {
+        let mut build = Router::build();
+        build
+            .add(Route::new("/foo/bar", "GET", 1))
+            .add(Route::new("/foo/{}/baz", "GET", 2))
+            .collapse_slashes();
+        let router = build.finish();
+
+        let path = router.normalize("//foo///bar").unwrap();
+        assert_eq!(router.lookup(&"GET", &path), Some((&1, vec![])));
+        let path = router.normalize("/foo//5//baz").unwrap();
+        assert_eq!(router.lookup(&"GET", &path), Some((&2, vec!["5"])));
+        assert_eq!(router.lookup(&"GET", "/foo//bar"), None);
+    }
+
+    #[test]
+    fn test_redirect_trailing_slash() {
+        let mut build = Router::build();
+        build
+            .add(Route::new("/users", "GET", 1))
+            .add(Route::new("/posts/", "GET", 2))
+            .add(Route::new("/posts", "POST", 3))
+            .add(Route::new("/", "GET", 4))
+            .redirect_trailing_slash();
+        let router = build.finish();
+
+        assert!(router.redirects_trailing_slash(&"GET", "/users/", &()));
+        assert!(router.redirects_trailing_slash(&"GET", "/posts", &()));
+        assert!(router.redirects_trailing_slash(&"POST", "/posts/", &()));
+        assert!(!router.redirects_trailing_slash(&"GET", "/users", &()));
+        assert!(!router.redirects_trailing_slash(&"POST", "/users/", &()));
+        assert!(!router.redirects_trailing_slash(&"GET", "/", &()));
+        assert!(!router.redirects_trailing_slash(&"GET", "/missing/", &()));
+        assert_eq!(router.redirect_location("/users/").as_deref(), Some("/users"));
+        assert_eq!(router.redirect_location("//evil.com/").as_deref(), Some("/evil.com"));
+        assert_eq!(router.redirect_location("/\\evil.com/").as_deref(), Some("/%5Cevil.com"));
+        assert_eq!(router.redirect_location("/a b?/").as_deref(), Some("/a%20b%3F"));
+        assert_eq!(router.redirect_location("/"), None);
+
+        let mut build = Router::build();
+        build.add(Route::new("/users", "GET", 1));
+        let router = build.finish();
+        assert!(!router.redirects_trailing_slash(&"GET", "/users/", &()));
+    }
+
+    #[test]
+    fn test_dot_segments() {
+        let mut build = Router::build();
+        build
+            .add(Route::new("/static/{}", "GET", 1))
+            .add(Route::new("/etc/passwd", "GET", 2))
+            .resolve_dot_segments();
+        let router = build.finish();
+
+        let path = router.normalize("/static/./css/../app.js").unwrap();
+        assert_eq!(router.lookup(&"GET", &path), Some((&1, vec!["app.js"])));
+        let path = router.normalize("/static/../../etc/passwd").unwrap();
+        assert_eq!(router.lookup(&"GET", &path), Some((&2, vec![])));
+
+        let mut build = Router::build();
+        build
+            .add(Route::new("/static/{}", "GET", 1))
+            .reject_root_escape();
+        let router = build.finish();
+
+        let path = router.normalize("/static/css/../app.js").unwrap();
+        assert_eq!(router.lookup(&"GET", &path), Some((&1, vec!["app.js"])));
+        assert_eq!(
+            router.normalize("/static/../../etc/passwd"),
+            Err(NormalizeError::RootEscape)
+        );
+    }
+
+    #[test]
+    fn test_keep_encoded_slashes() {
+        let mut build = Router::build();
+        build
+            .add(Route::new("/files/{}/raw", "GET", 1))
+            .add(Route::new("/files/{}/{}/raw", "GET", 2))
+            .keep_encoded_slashes();
+        let router = build.finish();
+
+        let path = router.decode("/files/docs%2Fa%20b.txt/raw?x=1").unwrap();
+        assert_eq!(path, "/files/docs%2Fa b.txt/raw");
+        let (handler, params) = router.lookup(&"GET", &path).unwrap();
+        assert_eq!(*handler, 1);
+        assert_eq!(router.decode_param(params[0]), "docs/a b.txt");
+
+        let path = router.decode("/files/100%25%23/raw").unwrap();
+        let (_, params) = router.lookup(&"GET", &path).unwrap();
+        assert_eq!(router.decode_param(params[0]), "100%#");
+        let path = router.decode("/files/%252F/raw").unwrap();
+        let (_, params) = router.lookup(&"GET", &path).unwrap();
+        assert_eq!(router.decode_param(params[0]), "%2F");
+
+        let mut build = Router::build();
+        build
+            .add(Route::new("/files/{}/raw", "GET", 1))
+            .add(Route::new("/files/{}/{}/raw", "GET", 2));
+        let router = build.finish();
+
+        let path = router.decode("/files/docs%2Fa.txt/raw").unwrap();
+        assert_eq!(
+            router.lookup(&"GET", &path),
+            Some((&2, vec!["docs", "a.txt"]))
+        );
+        assert!(matches!(router.decode_param("a%2Fb"), Cow::Borrowed("a%2Fb")));
+    }
+
+    #[test]
+    fn test_strict_decoding() {
+        let mut build = Router::build();
+        build.add(Route::new("/{}", "GET", 1)).strict_decoding();
+        let router = build.finish();
+
+        let path = router.decode("/caf%C3%A9").unwrap();
+        assert_eq!(router.lookup(&"GET", &path), Some((&1, vec!["caf\u{e9}"])));
+        assert_eq!(router.decode("/caf%C3"), Err(NormalizeError::InvalidUtf8));
+        assert_eq!(router.decode("/100%"), Err(NormalizeError::MalformedEscape));
+    }
+
+    #[test]
+    #[cfg(feature = "unicode-normalization")]
+    fn test_nfc_normalization() {
+        let mut build = Router::build();
+        build
+            .add(Route::new("/cafe\u{301}/{}", "GET", 1))
+            .add(Route::new(
+                "/na\u{ef}ve/{:one_of(cre\u{300}me|br\u{fb}le\u{301}e)}",
+                "GET",
+                2,
+            ))
+            .nfc_normalization();
+        let router = build.finish();
+
+        let path = router.normalize("/caf\u{e9}/x").unwrap();
+        assert_eq!(router.lookup(&"GET", &path), Some((&1, vec!["x"])));
+        let path = router.normalize("/cafe\u{301}/x").unwrap();
+        assert_eq!(router.lookup(&"GET", &path), Some((&1, vec!["x"])));
+        let path = router.normalize("/nai\u{308}ve/cr\u{e8}me").unwrap();
+        assert_eq!(
+            router.lookup(&"GET", &path),
+            Some((&2, vec!["cr\u{e8}me"]))
+        );
+        let path = router.normalize("/na\u{ef}ve/bru\u{302}le\u{301}e").unwrap();
+        assert_eq!(
+            router.lookup(&"GET", &path),
+            Some((&2, vec!["br\u{fb}l\u{e9}e"]))
+        );
+    }
+
+    #[test]
+    fn test_any_method() {
+        let mut build = Router::build();
+        build
+            .add(Route::new("/proxy/status", "GET", 1))
+            .add(Route::new_any("/proxy/{}", 2))
+            .add(Route::new("/proxy/{}", "POST", 3));
+        let router = build.finish();
+
+        assert_eq!(router.lookup(&"GET", "/proxy/status"), Some((&1, vec![])));
+        assert_eq!(
+            router.lookup(&"POST", "/proxy/status"),
+            Some((&2, vec!["status"]))
+        );
+        assert_eq!(
+            router.lookup(&"DELETE", "/proxy/users"),
+            Some((&2, vec!["users"]))
+        );
+        assert_eq!(router.lookup(&"GET", "/other"), None);
+
+        let (route, _) = router.lookup_route(&"PUT", "/proxy/x").unwrap();
+        assert_eq!(route.method(), None);
+    }
+
+    #[test]
+    fn test_multiple_methods() {
+        let mut build = Router::build();
+        build
+            .add(Route::for_methods("/form", vec!["GET", "POST"], 1))
+            .add(Route::new("/form", "PUT", 2))
+            .add(Route::for_methods("/never", vec![], 3));
+        let router = build.finish();
+
+        assert_eq!(router.lookup(&"GET", "/form"), Some((&1, vec![])));
+        assert_eq!(router.lookup(&"POST", "/form"), Some((&1, vec![])));
+        assert_eq!(router.lookup(&"PUT", "/form"), Some((&2, vec![])));
+        assert_eq!(router.lookup(&"DELETE", "/form"), None);
+        assert_eq!(router.lookup(&"GET", "/never"), None);
+
+        let (route, _) = router.lookup_route(&"POST", "/form").unwrap();
+        assert_eq!(route.method(), Some(&"GET"));
+        assert_eq!(route.methods(), Some(&["GET", "POST"][..]));
+    }
+
+    #[test]
+    fn test_fallback_method() {
+        let mut build = Router::build();
+        build
+            .add(Route::new("/users", "GET", 1))
+            .add(Route::new("/users/{:uint}", "GET", 2))
+            .add(Route::new("/users/{:uint}", "HEAD", 3))
+            .fallback_method("HEAD", "GET");
+        let router = build.finish();
+
+        assert_eq!(router.lookup(&"HEAD", "/users"), Some((&1, vec![])));
+        assert_eq!(router.lookup(&"HEAD", "/users/5"), Some((&3, vec!["5"])));
+        assert_eq!(router.lookup(&"POST", "/users"), None);
+        assert_eq!(router.lookup(&"HEAD", "/posts"), None);
+    }
+
+    #[test]
+    fn test_allowed_methods() {
+        let mut build = Router::build();
+        build
+            .add(Route::new("/users", "GET", 1))
+            .add(Route::for_methods("/users", vec!["POST", "GET"], 2))
+            .add(Route::new("/users/{:uint}", "DELETE", 3))
+            .add(Route::new("/users/{:uint}", "PUT", 4))
+            .add(Route::new("/users/{:uint(..10)}", "PATCH", 5))
+            .add(Route::new_any("/proxy/{}", 6))
+            .add(Route::new("/explicit", "OPTIONS", 7))
+            .fallback_method("HEAD", "GET")
+            .auto_options("OPTIONS");
+        let router = build.finish();
+
+        assert_eq!(
+            router.allowed_methods("/users"),
+            Some(Allowed::Only(vec![&"GET", &"POST", &"HEAD"]))
+        );
+        assert_eq!(
+            router.allowed_methods("/users/50"),
+            Some(Allowed::Only(vec![&"DELETE", &"PUT"]))
+        );
+        assert_eq!(router.allowed_methods("/proxy/x"), Some(Allowed::Any));
+        assert_eq!(router.allowed_methods("/missing"), None);
+
+        assert_eq!(
+            router.auto_options(&"OPTIONS", "/users/5"),
+            Some(Allowed::Only(vec![&"DELETE", &"PUT", &"PATCH", &"OPTIONS"]))
+        );
+        assert_eq!(router.auto_options(&"GET", "/users"), None);
+        assert_eq!(router.auto_options(&"OPTIONS", "/explicit"), None);
+        assert_eq!(router.auto_options(&"OPTIONS", "/proxy/x"), None);
+        assert_eq!(router.auto_options(&"OPTIONS", "/missing"), None);
+    }
+
+    #[test]
+    fn test_host_routes() {
+        struct Host(&'static str);
+
+        impl RequestInfo for Host {
+            fn host(&self) -> Option<&str> {
+                Some(self.0)
+            }
+        }
+
+        let mut build = Router::build();
+        build
+            .host("api.example.com", |build| {
+                build
+                    .add(Route::new("/users", "GET", 1))
+                    .add(Route::new("/users/{:uint}", "GET", 2));
+            })
+            .add(Route::new("/users", "GET", 3).with_host("admin.example.com"))
+            .add(Route::new("/users", "GET", 4));
+        let router = build.finish();
+
+        let api = Host("API.example.com:8080");
+        assert_eq!(router.lookup_with(&"GET", "/users", &api), Some((&1, vec![])));
+        assert_eq!(
+            router.lookup_with(&"GET", "/users/5", &api),
+            Some((&2, vec!["5"]))
+        );
+        let admin = Host("admin.example.com");
+        assert_eq!(router.lookup_with(&"GET", "/users", &admin), Some((&3, vec![])));
+        assert_eq!(router.lookup_with(&"GET", "/users/5", &admin), None);
+        let other = Host("example.com");
+        assert_eq!(router.lookup_with(&"GET", "/users", &other), Some((&4, vec![])));
+        assert_eq!(router.lookup(&"GET", "/users"), Some((&4, vec![])));
+        assert_eq!(router.lookup(&"GET", "/users/5"), None);
+    }
+
+    #[test]
+    fn test_header_guards() {
+        struct Headers(Vec<(&'static str, &'static str)>);
+
+        impl RequestInfo for Headers {
+            fn header(&self, name: &str) -> Option<&str> {
+                self.0
+                    .iter()
+                    .find(|(key, _)| key.eq_ignore_ascii_case(name))
+                    .map(|(_, value)| *value)
+            }
+        }
+
+        let mut build = Router::build();
+        build
+            .add(Route::new("/data", "GET", 1).when_header("X-Api-Key", |key| key == "secret"))
+            .add(
+                Route::new("/data", "GET", 2)
+                    .when_header("X-Api-Key", |_| true)
+                    .when_header("X-Debug", |value| value == "1"),
+            )
+            .add(Route::new("/data", "GET", 3));
+        let router = build.finish();
+
+        let request = Headers(vec![("x-api-key", "secret")]);
+        assert_eq!(router.lookup_with(&"GET", "/data", &request), Some((&1, vec![])));
+        let request = Headers(vec![("X-Api-Key", "other"), ("X-Debug", "1")]);
+        assert_eq!(router.lookup_with(&"GET", "/data", &request), Some((&2, vec![])));
+        let request = Headers(vec![("X-Api-Key", "other")]);
+        assert_eq!(router.lookup_with(&"GET", "/data", &request), Some((&3, vec![])));
+        assert_eq!(router.lookup(&"GET", "/data"), Some((&3, vec![])));
+    }
+
+    #[test]
+    fn test_query_guards() {
+        struct Query(&'static str);
+
+        impl RequestInfo for Query {
+            fn query(&self) -> Option<&str> {
+                Some(self.0)
+            }
+        }
+
+        let mut build = Router::build();
+        build
+            .add(Route::new("/search", "GET", 1).when_query(&[("type", "user")]))
+            .add(
+                Route::new("/search", "GET", 2)
+                    .when_query(&[("type", "repo"), ("sort", "new est")]),
+            )
+            .add(Route::new("/search", "GET", 3));
+        let router = build.finish();
+
+        let query = Query("q=x&type=user");
+        assert_eq!(router.lookup_with(&"GET", "/search", &query), Some((&1, vec![])));
+        let query = Query("sort=new+est&type=repo");
+        assert_eq!(router.lookup_with(&"GET", "/search", &query), Some((&2, vec![])));
+        let query = Query("type=repo");
+        assert_eq!(router.lookup_with(&"GET", "/search", &query), Some((&3, vec![])));
+        let query = Query("type=users");
+        assert_eq!(router.lookup_with(&"GET", "/search", &query), Some((&3, vec![])));
+        assert_eq!(router.lookup(&"GET", "/search"), Some((&3, vec![])));
+    }
+
+    #[test]
+    fn test_consumes() {
+        struct ContentType(Option<&'static str>);
+
+        impl RequestInfo for ContentType {
+            fn header(&self, name: &str) -> Option<&str> {
+                self.0.filter(|_| name.eq_ignore_ascii_case("content-type"))
+            }
+        }
+
+        let mut build = Router::build();
+        build
+            .add(Route::new("/form", "POST", 1).consumes("application/json"))
+            .add(
+                Route::new("/form", "POST", 2)
+                    .consumes("application/x-www-form-urlencoded")
+                    .consumes("multipart/*"),
+            )
+            .add(Route::new("/upload", "POST", 3).consumes("*/*"))
+            .add(Route::new("/other", "POST", 4))
+            .with_default(0);
+        let router = build.finish();
+
+        let json = ContentType(Some("Application/JSON; charset=utf-8"));
+        assert_eq!(router.lookup_with(&"POST", "/form", &json), Some((&1, vec![])));
+        let form = ContentType(Some("application/x-www-form-urlencoded"));
+        assert_eq!(router.lookup_with(&"POST", "/form", &form), Some((&2, vec![])));
+        let multipart = ContentType(Some("multipart/form-data; boundary=x"));
+        assert_eq!(
+            router.lookup_with(&"POST", "/form", &multipart),
+            Some((&2, vec![]))
+        );
+        let xml = ContentType(Some("text/xml"));
+        assert_eq!(router.lookup_with(&"POST", "/form", &xml), Some((&0, vec![])));
+        assert!(router.rejects_content_type(&"POST", "/form", &xml));
+        let none = ContentType(None);
+        assert!(router.rejects_content_type(&"POST", "/form", &none));
+        assert!(router.rejects_content_type(&"POST", "/upload", &none));
+        assert_eq!(router.lookup_with(&"POST", "/upload", &xml), Some((&3, vec![])));
+        assert!(!router.rejects_content_type(&"POST", "/upload", &xml));
+        assert!(!router.rejects_content_type(&"POST", "/other", &xml));
+        assert!(!router.rejects_content_type(&"POST", "/missing", &xml));
+        assert!(!router.rejects_content_type(&"GET", "/form", &xml));
+    }
+
+    #[test]
+    fn test_produces() {
+        struct Accept(Option<&'static str>);
+
+        impl RequestInfo for Accept {
+            fn header(&self, name: &str) -> Option<&str> {
+                self.0.filter(|_| name.eq_ignore_ascii_case("accept"))
+            }
+        }
+
+        let mut build = Router::build();
+        build
+            .add(Route::new("/users/new", "GET", 1))
+            .add(Route::new("/users/{}", "GET", 2).produces("text/html"))
+            .add(
+                Route::new("/users/{}", "GET", 3)
+                    .produces("application/json")
+                    .produces("text/json"),
+            )
+            .add(Route::new("/users/{}", "GET", 4));
+        let router = build.finish();
+
+        let lookup = |accept| router.lookup_with(&"GET", "/users/5", &Accept(accept));
+        assert_eq!(lookup(None), Some((&2, vec!["5"])));
+        assert_eq!(lookup(Some("text/html")), Some((&2, vec!["5"])));
+        assert_eq!(lookup(Some("application/json")), Some((&3, vec!["5"])));
+        assert_eq!(lookup(Some("Text/JSON")), Some((&3, vec!["5"])));
+        assert_eq!(
+            lookup(Some("text/html;q=0.5, application/json;q=0.9")),
+            Some((&3, vec!["5"]))
+        );
+        assert_eq!(
+            lookup(Some("text/html, application/json")),
+            Some((&2, vec!["5"]))
+        );
+        assert_eq!(lookup(Some("text/*, */*;q=0.1")), Some((&2, vec!["5"])));
+        assert_eq!(lookup(Some("image/png")), Some((&4, vec!["5"])));
+        assert_eq!(
+            router.lookup_with(&"GET", "/users/new", &Accept(Some("application/json"))),
+            Some((&1, vec![]))
+        );
+    }
+
+    #[test]
+    fn test_scheme_routes() {
+        struct Scheme(Option<&'static str>, Option<&'static str>);
+
+        impl RequestInfo for Scheme {
+            fn scheme(&self) -> Option<&str> {
+                self.0
+            }
+
+            fn header(&self, name: &str) -> Option<&str> {
+                self.1
+                    .filter(|_| name.eq_ignore_ascii_case("x-forwarded-proto"))
+            }
+        }
+
+        let mut build = Router::build();
+        build
+            .scheme("https", |build| {
+                build.add(Route::new_any("/{}", 1));
+            })
+            .add(Route::new_any("/{}", 2).with_scheme("http"));
+        let router = build.finish();
+
+        let lookup = |scheme, proto| router.lookup_with(&"GET", "/app", &Scheme(scheme, proto));
+        assert_eq!(lookup(Some("https"), None), Some((&1, vec!["app"])));
+        assert_eq!(lookup(Some("http"), None), Some((&2, vec!["app"])));
+        assert_eq!(lookup(None, None), Some((&2, vec!["app"])));
+        assert_eq!(lookup(None, Some("HTTPS")), Some((&1, vec!["app"])));
+        assert_eq!(lookup(Some("https"), Some("http")), Some((&2, vec!["app"])));
+        assert_eq!(lookup(None, Some("https, http")), Some((&1, vec!["app"])));
+    }
+
+    #[test]
+    fn test_version_routes() {
+        struct Version(Option<HttpVersion>);
+
+        impl RequestInfo for Version {
+            fn version(&self) -> Option<HttpVersion> {
+                self.0
+            }
+        }
+
+        let mut build = Router::build();
+        build
+            .add(Route::new("/stream", "GET", 1).with_version(HttpVersion::Http2))
+            .add(Route::new("/stream", "GET", 2));
+        let router = build.finish();
+
+        let lookup = |version| router.lookup_with(&"GET", "/stream", &Version(version));
+        assert_eq!(lookup(Some(HttpVersion::Http2)), Some((&1, vec![])));
+        assert_eq!(lookup(Some(HttpVersion::Http11)), Some((&2, vec![])));
+        assert_eq!(lookup(None), Some((&2, vec![])));
+    }
+
+    #[test]
+    fn test_custom_guards() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        struct Header(&'static str);
+
+        impl RequestInfo for Header {
+            fn header(&self, name: &str) -> Option<&str> {
+                Some(self.0).filter(|_| name == "x-beta")
+            }
+        }
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let mut build = Router::build();
+        build
+            .add(Route::new("/other", "GET", 0).with_guard(|_| true))
+            .add(Route::new("/feed", "GET", 1).with_guard(move |request| {
+                counter.fetch_add(1, Ordering::SeqCst);
+                request.header("x-beta") == Some("yes")
+            }))
+            .add(Route::new("/feed", "GET", 2).with_guard(|_| false))
+            .add(Route::new("/feed", "GET", 3));
+        let router = build.finish();
+
+        assert_eq!(router.lookup_with(&"GET", "/feed", &Header("yes")), Some((&1, vec![])));
+        assert_eq!(router.lookup_with(&"GET", "/feed", &Header("no")), Some((&3, vec![])));
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        assert_eq!(router.lookup_with(&"POST", "/feed", &Header("yes")), None);
+        assert_eq!(router.lookup_with(&"GET", "/other/x", &Header("yes")), None);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_mount() {
+        let mut api = Router::build();
+        api.add(Route::new("/", "GET", 1))
+            .add(Route::new("/users/{id:uint}", "GET", 2))
+            .with_default(3);
+        let mut tenant = Router::build();
+        tenant.add(Route::new("/users/{id}", "GET", 4));
+
+        let mut build = Router::build();
+        build
+            .mount("/api/v1/", api.finish())
+            .mount("/tenants/{tenant}", tenant.finish())
+            .add(Route::new("/api/v1/status", "GET", 5))
+            .with_default(6);
+        let router = build.finish();
+
+        assert_eq!(router.lookup(&"GET", "/api/v1"), Some((&1, vec![])));
+        assert_eq!(router.lookup(&"GET", "/api/v1/users/5"), Some((&2, vec!["5"])));
+        assert_eq!(router.lookup(&"GET", "/api/v1/status"), Some((&5, vec![])));
+        assert_eq!(router.lookup(&"GET", "/api/v1/users/bob"), Some((&3, vec![])));
+        assert_eq!(router.lookup(&"POST", "/api/v1/users/5"), Some((&3, vec![])));
+        assert_eq!(router.lookup(&"GET", "/api/v1/"), Some((&3, vec![])));
+        assert_eq!(router.lookup(&"GET", "/api/v10"), Some((&6, vec![])));
+        assert_eq!(
+            router.lookup(&"GET", "/tenants/acme/users/bob"),
+            Some((&4, vec!["acme", "bob"]))
+        );
+        assert_eq!(router.lookup(&"GET", "/tenants/acme/posts"), Some((&6, vec![])));
+        assert_eq!(router.lookup(&"GET", "/users/5"), Some((&6, vec![])));
+    }
+
+    #[test]
+    fn test_mount_or() {
+        let mut core = Router::build();
+        core.add(Route::new("/users/{id}", "GET", 1)).with_default(2);
+        let mut plugins = Router::build();
+        plugins
+            .add(Route::new("/users/{id}", "DELETE", 3))
+            .add(Route::new("/plugins", "GET", 4))
+            .with_default(5);
+        let mut extra = Router::build();
+        extra.add(Route::new("/extra", "GET", 6));
+        let api = core
+            .finish()
+            .or(plugins.finish())
+            .or_for_missing_paths(extra.finish());
+
+        let mut build = Router::build();
+        build.mount("/api", api);
+        let router = build.finish();
+
+        assert_eq!(router.lookup(&"GET", "/api/users/5"), Some((&1, vec!["5"])));
+        assert_eq!(router.lookup(&"DELETE", "/api/users/5"), Some((&3, vec!["5"])));
+        assert_eq!(router.lookup(&"GET", "/api/plugins"), Some((&4, vec![])));
+        assert_eq!(router.lookup(&"GET", "/api/extra"), Some((&6, vec![])));
+        assert_eq!(router.lookup(&"GET", "/api/missing"), Some((&2, vec![])));
+        assert_eq!(router.routes().count(), 5);
+    }
+
+    #[test]
+    #[should_panic(expected = "the route `/api/{version:v}` has a parameter `{version:v}` with an unknown kind")]
+    fn test_mount_invalid_prefix() {
+        let mut build = Router::build();
+        build.mount("/api/{version:v}", Router::<&str, i32>::build().finish());
+    }
+
+    #[test]
+    #[should_panic(expected = "the route `/admin/{section:sectoin}` has a parameter")]
+    fn test_scope_invalid_prefix() {
+        let mut build = Router::<&str, i32>::build();
+        build.scope("/admin", |admin| {
+            admin.scope("/{section:sectoin}", |_| {});
+        });
+    }
+
+    #[test]
+    fn test_scope() {
+        let mut build = Router::build();
+        build
+            .add(Route::new("/", "GET", 1))
+            .scope("/admin/", |admin| {
+                admin
+                    .add(Route::new("/", "GET", 2))
+                    .add(Route::new("/users/{id:uint}", "GET", 3))
+                    .scope("/reports", |reports| {
+                        assert_eq!(reports.prefix(), "/admin/reports");
+                        reports.add(Route::new("/{name}", "GET", 4));
+                    });
+            })
+            .add(Route::new("/users/{id:uint}", "GET", 5));
+        let router = build.finish();
+
+        assert_eq!(router.lookup(&"GET", "/"), Some((&1, vec![])));
+        assert_eq!(router.lookup(&"GET", "/admin"), Some((&2, vec![])));
+        assert_eq!(router.lookup(&"GET", "/admin/users/7"), Some((&3, vec!["7"])));
+        assert_eq!(router.lookup(&"GET", "/admin/reports/daily"), Some((&4, vec!["daily"])));
+        assert_eq!(router.lookup(&"GET", "/users/7"), Some((&5, vec!["7"])));
+        assert_eq!(router.lookup(&"GET", "/reports/daily"), None);
+    }
+
+    #[test]
+    fn test_scope_extensions() {
+        #[derive(Debug, PartialEq)]
+        struct Class(&'static str);
+
+        let mut build = Router::build();
+        build.scope("/api", |api| {
+            api.with_extension(Class("api"))
+                .add(Route::new("/users", "GET", 1))
+                .add(Route::new("/health", "GET", 2).with_extension(Class("health")))
+                .scope("/admin", |admin| {
+                    admin.add(Route::new("/", "GET", 3));
+                });
+        });
+        let router = build.finish();
+
+        let class = |path| {
+            let (route, _) = router.lookup_route(&"GET", path).unwrap();
+            route.extension::<Class>()
+        };
+        assert_eq!(class("/api/users"), Some(&Class("api")));
+        assert_eq!(class("/api/health"), Some(&Class("health")));
+        assert_eq!(class("/api/admin"), Some(&Class("api")));
+    }
+
+    #[test]
+    fn test_or() {
+        let core = || {
+            let mut build = Router::build();
+            build
+                .add(Route::new("/users", "GET", 1))
+                .add(Route::new("/users/{id:uint}", "GET", 2))
+                .with_default(0);
+            build.finish()
+        };
+        let plugins = || {
+            let mut build = Router::build();
+            build
+                .add(Route::new("/users", "POST", 3))
+                .add(Route::new("/plugins/{name}", "GET", 4))
+                .with_default(5);
+            build.finish()
+        };
+        let mut build = Router::build();
+        build.add(Route::new("/health", "GET", 6));
+        let extra = build.finish();
+
+        let router = core().or(plugins()).or(extra);
+        assert_eq!(router.lookup(&"GET", "/users"), Some((&1, vec![])));
+        assert_eq!(router.lookup(&"POST", "/users"), Some((&3, vec![])));
+        assert_eq!(router.lookup(&"GET", "/plugins/auth"), Some((&4, vec!["auth"])));
+        assert_eq!(router.lookup(&"GET", "/health"), Some((&6, vec![])));
+        assert_eq!(router.lookup(&"GET", "/missing"), Some((&0, vec![])));
+        assert_eq!(router.lookup_index(&"POST", "/users"), Some((2, vec![])));
+        assert_eq!(router.lookup_index(&"GET", "/health"), Some((4, vec![])));
+        assert_eq!(router.routes().count(), 5);
+        assert_eq!(router.allowed_methods("/users"), Some(Allowed::Only(vec![&"GET", &"POST"])));
+
+        let router = core().or_for_missing_paths(plugins());
+        assert_eq!(router.lookup(&"POST", "/users"), Some((&0, vec![])));
+        assert_eq!(router.lookup(&"GET", "/plugins/auth"), Some((&4, vec!["auth"])));
+        assert_eq!(router.allowed_methods("/users"), Some(Allowed::Only(vec![&"GET"])));
+
+        let router = router.rebind(|route| route.path.to_string());
+        let handler = "/plugins/{name}".to_string();
+        assert_eq!(router.lookup(&"GET", "/plugins/auth"), Some((&handler, vec!["auth"])));
+    }
+
+    #[test]
+    fn test_versions() {
+        struct Header(Option<&'static str>);
+
+        impl RequestInfo for Header {
+            fn header(&self, name: &str) -> Option<&str> {
+                self.0.filter(|_| name == "Accept-Version")
+            }
+        }
+
+        let mut build = Router::build();
+        build
+            .default_version(2)
+            .version(1, |v1| {
+                v1.add(Route::new("/users", "GET", 1));
+            })
+            .version(2, |v2| {
+                v2.add(Route::new("/users", "GET", 2))
+                    .add(Route::new("/users/{id:uint}", "GET", 3));
+            });
+        let router = build.finish();
+
+        assert_eq!(router.lookup(&"GET", "/v1/users"), Some((&1, vec![])));
+        assert_eq!(router.lookup(&"GET", "/v1/users/5"), None);
+        assert_eq!(router.lookup(&"GET", "/v2/users"), Some((&2, vec![])));
+        assert_eq!(router.lookup(&"GET", "/v2/users/5"), Some((&3, vec!["5"])));
+        assert_eq!(router.lookup(&"GET", "/users/5"), Some((&3, vec!["5"])));
+
+        let mut build = Router::build();
+        build
+            .version_header("Accept-Version")
+            .default_version(1)
+            .version(1, |v1| {
+                v1.add(Route::new("/users", "GET", 1));
+            })
+            .version(2, |v2| {
+                v2.add(Route::new("/users", "GET", 2));
+            });
+        let router = build.finish();
+
+        let lookup = |version| router.lookup_with(&"GET", "/users", &Header(version)).map(|(handler, _)| *handler);
+        assert_eq!(lookup(Some("1")), Some(1));
+        assert_eq!(lookup(Some("v2")), Some(2));
+        assert_eq!(lookup(Some("3")), None);
+        assert_eq!(lookup(None), Some(1));
+        assert_eq!(router.lookup(&"GET", "/v1/users"), None);
+        assert_eq!(router.lookup(&"GET", "/users"), Some((&1, vec![])));
+
+        // The settings apply to the versions added before them, and keep
+        // the routes in the order they were added.
+        let mut build = Router::build();
+        build
+            .version(1, |v1| {
+                v1.add(Route::new("/users", "GET", 1));
+            })
+            .add(Route::new("/{}", "GET", 4))
+            .version(2, |v2| {
+                v2.add(Route::new("/users", "GET", 2));
+            })
+            .default_version(2);
+        let router = build.clone().finish();
+
+        assert_eq!(router.lookup(&"GET", "/v1/users"), Some((&1, vec![])));
+        assert_eq!(router.lookup(&"GET", "/users"), Some((&4, vec!["users"])));
+        assert_eq!(router.lookup(&"GET", "/v2/users"), Some((&2, vec![])));
+
+        build.version_header("Accept-Version");
+        let router = build.finish();
+
+        let lookup = |version| router.lookup_with(&"GET", "/users", &Header(version)).map(|(handler, _)| *handler);
+        assert_eq!(lookup(Some("1")), Some(1));
+        assert_eq!(lookup(Some("2")), Some(4));
+        assert_eq!(lookup(None), Some(4));
+        assert_eq!(router.lookup(&"GET", "/v2/users"), None);
+    }
+
+    #[test]
+    fn test_scope_guards() {
+        struct Host(&'static str);
+
+        impl RequestInfo for Host {
+            fn host(&self) -> Option<&str> {
+                Some(self.0)
+            }
+        }
+
+        let mut build = Router::build();
+        build
+            .scope("/users", |users| {
+                users
+                    .with_guard(|request| request.host() != Some("blocked.example.com"))
+                    .add(Route::new("/{id:uint}", "GET", 1))
+                    .scope("/{id:uint}/posts", |posts| {
+                        posts
+                            .with_guard(|request| request.host() != Some("old.example.com"))
+                            .add(Route::new("/", "GET", 2))
+                            .add(Route::new("/{post}", "GET", 3).with_host("new.example.com"));
+                    })
+                    .add(Route::new("/{id:uint}/posts", "GET", 4));
+            })
+            .add(Route::new_any("/{}/{}", 5));
+        let router = build.finish();
+
+        let new = Host("new.example.com");
+        let old = Host("old.example.com");
+        let blocked = Host("blocked.example.com");
+        assert_eq!(router.lookup_with(&"GET", "/users/1", &new), Some((&1, vec!["1"])));
+        assert_eq!(router.lookup_with(&"GET", "/users/1", &blocked), Some((&5, vec!["users", "1"])));
+        assert_eq!(router.lookup_with(&"GET", "/users/1/posts", &new), Some((&2, vec!["1"])));
+        assert_eq!(router.lookup_with(&"GET", "/users/1/posts", &old), Some((&4, vec!["1"])));
+        assert_eq!(router.lookup_with(&"GET", "/users/1/posts", &blocked), None);
+        assert_eq!(
+            router.lookup_with(&"GET", "/users/1/posts/hi", &new),
+            Some((&3, vec!["1", "hi"]))
+        );
+        assert_eq!(router.lookup_with(&"GET", "/users/1/posts/hi", &old), None);
+    }
+
+    #[test]
+    fn test_url_for() {
+        let mut build = Router::build();
+        build
+            .add(Route::new("/users/{id:uint}", "GET", 1).with_name("user"))
+            .add(Route::new("/users/{id:uint}/posts/{slug}", "GET", 2).with_name("post"))
+            .mount("/tenants/{tenant}", {
+                let mut tenant = Router::build();
+                tenant.add(Route::new("/", "GET", 3).with_name("tenant"));
+                tenant.finish()
+            });
+        let router = build.finish();
+
+        assert_eq!(router.url_for("user", &[&5]).unwrap(), "/users/5");
+        assert_eq!(router.url_for("post", &[&5, &"hello world"]).unwrap(), "/users/5/posts/hello%20world");
+        assert_eq!(router.url_for("tenant", &[&"acme"]).unwrap(), "/tenants/acme");
+        assert_eq!(router.url_for("missing", &[]), Err(UrlError::UnknownRoute("missing".to_string())));
+        assert_eq!(
+            router.url_for("user", &[&"bob"]).unwrap_err().to_string(),
+            "`bob` is not a valid value for parameter `id` (`{id:uint}`) of route `user`"
+        );
+        assert_eq!(
+            router.url_for("post", &[&5]).unwrap_err().to_string(),
+            "route `post` takes 2 parameter(s), but 1 were given"
+        );
+
+        let url = router.url("post").unwrap().param("slug", "hi").param("id", 7).build();
+        assert_eq!(url.unwrap(), "/users/7/posts/hi");
+        let url = router.url("post").unwrap().param("id", 7).build();
+        assert_eq!(
+            url,
+            Err(UrlError::MissingParam {
+                route: "post".to_string(),
+                param: "slug".to_string()
+            })
+        );
+        let url = router.url("user").unwrap().param("id", 7).param("name", "x").build();
+        assert_eq!(
+            url,
+            Err(UrlError::UnknownParam {
+                route: "user".to_string(),
+                param: "name".to_string()
+            })
+        );
+
+        let built = router.url_for("post", &[&5, &"a/b?"]);
+        assert!(built.is_err());
+        let built = router.url_for("post", &[&5, &"a?b"]).unwrap();
+        assert_eq!(built, "/users/5/posts/a%3Fb");
+        let path = router.decode(&built).unwrap();
+        assert_eq!(router.lookup(&"GET", &path), Some((&2, vec!["5", "a?b"])));
+    }
+
+    #[test]
+    fn test_routes() {
+        let mut build = Router::build();
+        build
+            .add(Route::new("/users/{id:uint}", "GET", 1).with_name("user"))
+            .add(
+                Route::for_methods("/users", vec!["POST", "PUT"], 2)
+                    .with_priority(1)
+                    .with_host("example.com")
+                    .consumes("application/json"),
+            )
+            .mount("/api", {
+                let mut api = Router::build();
+                api.add(Route::new("/{}", "GET", 3)).with_default(4);
+                api.finish()
+            });
+        let router = build.finish();
+        let routes = router.routes().collect::<Vec<_>>();
+
+        assert_eq!(
+            routes[0],
+            RouteInfo {
+                path: "/users",
+                pattern: "^/users$",
+                methods: Some(&["POST", "PUT"][..]),
+                name: None,
+                params: vec![],
+                priority: 1,
+                consumes: &["application/json".to_string()],
+                produces: &[],
+                guards: 1,
+                catch_all: false,
+                extensions: &Extensions::default(),
+            }
+        );
+        assert_eq!(routes[1].name, Some("user"));
+        assert_eq!(routes[1].params, vec![Some("id")]);
+        assert_eq!(routes[2].path, "/api/{}");
+        assert_eq!(routes[2].params, vec![None]);
+        assert_eq!((routes[3].path, routes[3].methods, routes[3].catch_all), ("/api", None, true));
+        assert_eq!(routes.len(), 4);
+    }
+
+    #[test]
+    fn test_extensions() {
+        #[derive(Debug, PartialEq)]
+        struct Scopes(&'static [&'static str]);
+        #[derive(Debug, PartialEq)]
+        struct RateLimit(u32);
+
+        let mut build = Router::build();
+        build
+            .add(
+                Route::new("/admin", "GET", 1)
+                    .with_extension(Scopes(&["admin"]))
+                    .with_extension(RateLimit(10))
+                    .with_extension(RateLimit(5)),
+            )
+            .add(Route::new("/", "GET", 2));
+        let router = build.finish();
+
+        let (route, _) = router.lookup_route(&"GET", "/admin").unwrap();
+        assert_eq!(route.extension::<Scopes>(), Some(&Scopes(&["admin"])));
+        assert_eq!(route.extension::<RateLimit>(), Some(&RateLimit(5)));
+        assert_eq!(route.extensions().len(), 2);
+        let (route, _) = router.lookup_route(&"GET", "/").unwrap();
+        assert_eq!(route.extension::<Scopes>(), None);
+        assert!(route.extensions().is_empty());
+
+        let rebound = router.rebind(|route| route.extensions.len());
+        assert_eq!(rebound.lookup(&"GET", "/admin"), Some((&2, vec![])));
+        let info = rebound.routes().next().unwrap();
+        assert!(info.extensions.contains::<Scopes>());
+        assert_eq!(info.extensions, router.routes().next().unwrap().extensions);
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut build = Router::build();
+        build
+            .add(Route::new("/users/{id}", "GET", 1))
+            .add(Route::new("/users/{id:uint}", "GET", 2))
+            .add(Route::new("/users/{id:uint}", "POST", 3))
+            .add(Route::new("/users/new", "GET", 4))
+            .add(Route::new("/users/new", "GET", 5).with_priority(1));
+        let conflicts = build.validate();
+
+        assert_eq!(
+            conflicts[0],
+            Conflict {
+                route: RouteRef {
+                    index: 1,
+                    path: "/users/{id:uint}",
+                    pattern: r"^/users/(\d+)$",
+                },
+                shadowed_by: RouteRef {
+                    index: 0,
+                    path: "/users/{id}",
+                    pattern: "^/users/([^/]+)$",
+                },
+                kind: ConflictKind::Shadowed,
+            }
+        );
+        assert_eq!(conflicts[1].kind, ConflictKind::Duplicate);
+        assert_eq!(
+            conflicts[1].to_string(),
+            "route #3 (`/users/new`, matching `^/users/new$`) duplicates \
+             route #4 (`/users/new`, matching `^/users/new$`)"
+        );
+        assert_eq!(conflicts.len(), 2);
+
+        let mut build = Router::build();
+        build
+            .add(Route::new("/users/{id}", "GET", 1))
+            .add(Route::new("/users/{id:uint}", "GET", 2))
+            .by_specificity();
+        assert_eq!(build.validate(), vec![]);
+
+        let mut build = Router::build();
+        build
+            .add(Route::for_methods("/users", vec!["GET", "POST"], 1))
+            .add(Route::for_methods("/users", vec!["POST", "GET"], 2))
+            .add(Route::new("/users", "GET", 3))
+            .add(Route::new("/users", "PUT", 4).with_host("example.com"))
+            .add(Route::new("/users", "PUT", 5));
+        let kinds = build
+            .validate()
+            .iter()
+            .map(|conflict| (conflict.route.index, conflict.kind))
+            .collect::<Vec<_>>();
+        assert_eq!(kinds, vec![(1, ConflictKind::Duplicate), (2, ConflictKind::Shadowed)]);
+    }
+
+    #[test]
+    fn test_try_finish() {
+        let mut build = Router::build();
+        build.add(Route::new("/users", "GET", 1));
+        assert!(build.try_finish().is_ok());
+
+        // Each of these routes is fine on its own, but together they're too
+        // large for a single set.
+        let mut build = Router::build();
+        for i in 0..12 {
+            let path = format!("/{}/{{:ipv6}}/{{:ipv6}}/{{:ipv6}}/{{:ipv6}}", i);
+            build.add(Route::new(path, "GET", i));
+        }
+        let error = build.try_finish().unwrap_err();
+        assert!(error.index > 0 && error.index < 12);
+        assert_eq!(error.path, format!("/{}/{{:ipv6}}/{{:ipv6}}/{{:ipv6}}/{{:ipv6}}", error.index));
+        assert!(matches!(error.error, regex::Error::CompiledTooBig(_)));
+        assert!(error.to_string().starts_with(&format!("route #{} (`/{}/", error.index, error.index)));
+    }
+
+    #[test]
+    fn test_lazy_patterns() {
+        let routes = |build: &mut Build<&'static str, i32>| {
+            build
+                .add(Route::new("/users/{id}", "GET", 1))
+                .add(Route::new("/users/{id:uint}/posts", "GET", 2));
+        };
+        let compiled = |router: &Router<&'static str, i32>| {
+            router.routes.iter().map(|route| route.pattern.is_compiled()).collect::<Vec<_>>()
+        };
+
+        let mut build = Router::build();
+        routes(&mut build);
+        assert_eq!(compiled(&build.finish()), vec![true, true]);
+
+        let mut build = Router::build();
+        routes(&mut build);
+        build.lazy_patterns().trie_matching();
+        let router = build.finish();
+        assert_eq!(compiled(&router), vec![false, false]);
+        assert_eq!(router.lookup(&"GET", "/users/jane"), Some((&1, vec!["jane"])));
+        // The first route is in the trie, so it never needs its pattern.
+        assert_eq!(compiled(&router), vec![false, false]);
+        assert_eq!(router.lookup(&"GET", "/users/5/posts"), Some((&2, vec!["5"])));
+        assert_eq!(compiled(&router), vec![false, true]);
+
+        // A pattern that's too large is only found when the router is
+        // built, since creating the route doesn't compile it.
+        let mut build = Router::build();
+        build
+            .add(Route::new("/users", "GET", 1))
+            .add(Route::new("/{:ipv6}".repeat(64), "GET", 2));
+        let error = build.try_finish().unwrap_err();
+        assert_eq!(error.index, 1);
+        assert!(matches!(error.error, regex::Error::CompiledTooBig(_)));
+    }
+
+    #[test]
+    fn test_no_match_hook() {
+        use std::sync::{Arc, Mutex};
+
+        let seen = Arc::new(Mutex::new(vec![]));
+        let log = seen.clone();
+        let mut build = Router::build();
+        build
+            .add(Route::new("/users", "GET", 1))
+            .add(Route::new("/users/{:uint}", "GET", 2))
+            .add(Route::new("/posts/{:uint}", "GET", 3))
+            .with_default(0)
+            .on_no_match(move |miss| {
+                let candidates = miss
+                    .candidates
+                    .iter()
+                    .map(|c| format!("{} {} ({})", c.method.unwrap(), c.path, c.distance))
+                    .collect::<Vec<_>>();
+                log.lock()
+                    .unwrap()
+                    .push((*miss.method, miss.path.to_string(), candidates));
+            });
+        let router = build.finish();
+
+        assert_eq!(router.lookup(&"GET", "/user/5"), Some((&0, vec![])));
+        assert_eq!(router.lookup(&"POST", "/users"), Some((&0, vec![])));
+        assert_eq!(router.lookup(&"GET", "/users/5"), Some((&2, vec!["5"])));
+        assert_eq!(
+            *seen.lock().unwrap(),
+            vec![
+                (
+                    "GET",
+                    "/user/5".to_string(),
+                    vec!["GET /users/{:uint} (1)".to_string()]
+                ),
+                (
+                    "POST",
+                    "/users".to_string(),
+                    vec![
+                        "GET /users (0)".to_string(),
+                        "GET /users/{:uint} (2)".to_string()
+                    ]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_match_hook() {
+        use std::sync::{Arc, Mutex};
+
+        let seen = Arc::new(Mutex::new(vec![]));
+        let log = seen.clone();
+        let mut build = Router::build();
+        build
+            .add(Route::new("/users/{:uint}", "GET", 1))
+            .on_match(move |hit| {
+                let route = hit.route.as_ref().map(|route| route.path.to_string());
+                log.lock().unwrap().push((hit.path.to_string(), route));
+            });
+        let mut router = build.finish();
+
+        assert_eq!(router.lookup(&"GET", "/users/5"), Some((&1, vec!["5"])));
+        assert_eq!(router.lookup(&"GET", "/posts/5"), None);
+        assert!(router.lookup_route(&"GET", "/users/6").is_some());
+        router.set_default(0);
+        assert_eq!(router.lookup(&"GET", "/posts/5"), Some((&0, vec![])));
+        assert_eq!(
+            *seen.lock().unwrap(),
+            vec![
+                ("/users/5".to_string(), Some("/users/{:uint}".to_string())),
+                ("/posts/5".to_string(), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rebind() {
+        let mut build = Router::build();
+        build
+            .add(Route::new("/users", "GET", 1))
+            .add(Route::new("/users/{:uint}", "GET", 2))
+            .with_default(0);
+        let router = build.finish();
+        let rebound = router.rebind(|route| format!("{} {}", route.methods.unwrap()[0], route.path));
+
+        assert_eq!(
+            rebound.lookup(&"GET", "/users/5"),
+            Some((&"GET /users/{:uint}".to_string(), vec!["5"]))
+        );
+        assert_eq!(rebound.lookup(&"GET", "/missing"), None);
+        assert_eq!(router.lookup(&"GET", "/users"), Some((&1, vec![])));
+    }
+
+    #[test]
+    fn test_static_routes() {
+        // The dispatch is trusted, so one that gives the wrong route shows
+        // whether it was used.
+        fn dispatch(method: &&str, path: &str) -> Option<usize> {
+            match (*method, path) {
+                ("GET", "/elsewhere") => Some(0),
+                ("GET", "/guarded") => Some(1),
+                _ => None,
+            }
+        }
+
+        let build = || {
+            let mut build = Router::build();
+            build
+                .add(Route::new("/fast", "GET", 1))
+                .add(Route::new("/guarded", "GET", 2).with_guard(|_| true))
+                .static_routes(dispatch);
+            build
+        };
+
+        let router = build().finish();
+        assert_eq!(router.lookup(&"GET", "/elsewhere"), Some((&1, vec![])));
+        assert_eq!(router.lookup(&"POST", "/elsewhere"), None);
+        assert_eq!(router.lookup_index(&"GET", "/elsewhere"), Some((0, vec![])));
+        assert_eq!(router.rebind(|route| route.path.len()).lookup(&"GET", "/elsewhere"), Some((&5, vec![])));
+        // Routes with guards are left to the matcher.
+        assert_eq!(router.lookup(&"GET", "/guarded"), Some((&2, vec![])));
+
+        // Routes added later don't change anything, unless they come first.
+        let mut later = build();
+        later.add(Route::new("/{}", "GET", 3));
+        assert_eq!(later.finish().lookup(&"GET", "/elsewhere"), Some((&1, vec![])));
+        let mut later = build();
+        later.add(Route::new("/{}", "GET", 3).with_priority(1));
+        assert_eq!(later.finish().lookup(&"GET", "/elsewhere"), Some((&3, vec!["elsewhere"])));
+
+        let mut router = build().finish_dynamic();
+        router.remove_route(&"GET", "/guarded").unwrap();
+        assert_eq!(router.lookup(&"GET", "/elsewhere"), None);
+    }
+
+    #[test]
+    fn test_lookup_index() {
+        let mut build = Router::<&str, ()>::build();
+        build
+            .add(Route::new("/users", "GET", ()))
+            .add(Route::new("/users/{id:uint}", "GET", ()))
+            .add(Route::new("/users/me", "GET", ()).with_priority(1))
+            .fallback_method("HEAD", "GET");
+        let router = build.finish();
+        let paths = router.routes().map(|info| info.path).collect::<Vec<_>>();
+        assert_eq!(paths, vec!["/users/me", "/users", "/users/{id:uint}"]);
+
+        assert_eq!(router.lookup_index(&"GET", "/users/me"), Some((0, vec![])));
+        assert_eq!(router.lookup_index(&"GET", "/users"), Some((1, vec![])));
+        assert_eq!(router.lookup_index(&"GET", "/users/5"), Some((2, vec!["5"])));
+        assert_eq!(router.lookup_index(&"HEAD", "/users/5"), Some((2, vec!["5"])));
+        assert_eq!(router.lookup_index(&"POST", "/users"), None);
+    }
+
+    #[test]
+    fn test_lookup_cache() {
+        let mut build = Router::build();
+        build
+            .add(Route::new("/users/{:uint}", "GET", 1))
+            .add(Route::new("/users/{}", "POST", 2))
+            .add(Route::new("/posts/{}", "GET", 3))
+            .with_lookup_cache(2);
+        let router = build.finish();
+        let clone = router.clone();
+
+        for _ in 0..2 {
+            assert_eq!(router.lookup(&"GET", "/users/5"), Some((&1, vec!["5"])));
+            assert_eq!(router.lookup(&"POST", "/users/5"), Some((&2, vec!["5"])));
+            assert_eq!(router.lookup(&"GET", "/users/jane"), None);
+            assert_eq!(router.lookup(&"GET", "/posts/7"), Some((&3, vec!["7"])));
+            assert_eq!(router.allowed_methods("/users/5"), Some(Allowed::Only(vec![&"GET", &"POST"])));
+        }
+        // The clone shares the cache, which only keeps the last two paths.
+        let cache = format!("{:?}", clone.cache.as_ref().unwrap());
+        assert_eq!(cache, "LookupCache { capacity: 2, len: 2 }");
+        assert_eq!(clone.lookup(&"GET", "/users/5"), Some((&1, vec!["5"])));
+    }
+
+    #[test]
+    fn test_warm_up() {
+        use std::future::Future;
+        use std::pin::Pin;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+        use std::task::{Context, Poll, Wake, Waker};
+        use tokio::prelude::{task, Async};
+        use tokio::runtime::current_thread::Runtime;
+
+        /// Wakes the `futures` 0.1 task that's polling the warm-up.
+        struct Notify(task::Task);
+
+        impl Wake for Notify {
+            fn wake(self: Arc<Self>) {
+                self.0.notify()
+            }
+        }
+
+        /// The warm-up, as a `futures` 0.1 future, so that tokio can run it.
+        struct Compat<F>(Pin<Box<F>>);
+
+        impl<T, E, F: Future<Output = Result<T, E>>> tokio::prelude::Future for Compat<F> {
+            type Item = T;
+            type Error = E;
+
+            fn poll(&mut self) -> Result<Async<T>, E> {
+                let waker = Waker::from(Arc::new(Notify(task::current())));
+                match self.0.as_mut().poll(&mut Context::from_waker(&waker)) {
+                    Poll::Ready(result) => result.map(Async::Ready),
+                    Poll::Pending => Ok(Async::NotReady),
+                }
+            }
+        }
+
+        fn block_on<T, E, F: Future<Output = Result<T, E>>>(future: F) -> Result<T, E> {
+            Runtime::new().unwrap().block_on(Compat(Box::pin(future)))
+        }
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let mut build = Router::build();
+        build
+            .add(Route::new("/one", (), 1).with_warm_up(move || {
+                counter.fetch_add(1, Ordering::SeqCst);
+                async { Ok(()) }
+            }))
+            .add(
+                Route::new("/two", (), 2)
+                    .with_warm_up(|| async { Err(failure::err_msg("pool unavailable")) }),
+            )
+            .add(
+                Route::new("/three", (), 3)
+                    .with_warm_up(|| async { panic!("warm-up should stop at the first failure") }),
+            );
+        let router = build.finish();
+
+        let error = block_on(router.warm_up()).unwrap_err();
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert_eq!(error.to_string(), "warm-up failed for route /two");
+    }
 }
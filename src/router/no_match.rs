@@ -0,0 +1,182 @@
+use super::route::PLACEHOLDER;
+use super::Route;
+use std::fmt::{Debug, Formatter, Result as FmtResult};
+use std::sync::Arc;
+
+/// The most candidates that are reported for a single miss.
+const MAX_CANDIDATES: usize = 3;
+
+#[derive(Debug)]
+/// Information about a lookup that matched none of the routes in the router.
+/// This is passed to the hook given to [`super::Build::on_no_match`], and is
+/// meant for logging "did you mean" diagnostics.
+pub struct NoMatch<'a, M> {
+    /// The method of the request.
+    pub method: &'a M,
+    /// The path that was looked up.
+    pub path: &'a str,
+    /// The routes that came closest to matching, closest first.
+    pub candidates: Vec<Candidate<'a, M>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// A route that came close to matching a path.  The distance is the number
+/// of single-character edits needed to the literal segments of the path to
+/// make it match the route; parameter segments match any non-empty segment.
+/// A candidate with a distance of zero matched the path, but not the method.
+pub struct Candidate<'a, M> {
+    /// The path the route was created with.
+    pub path: &'a str,
+    /// The method of the route, or `None` if it matches any method.  If the
+    /// route matches more than one method, this is the first of them.
+    pub method: Option<&'a M>,
+    /// How far the path was from the route.
+    pub distance: usize,
+}
+
+type HookFn<M> = dyn Fn(&NoMatch<'_, M>) + Send + Sync + 'static;
+
+/// The hook called when a lookup matches none of the routes.
+pub struct NoMatchHook<M>(Arc<HookFn<M>>);
+
+impl<M> NoMatchHook<M> {
+    pub(super) fn new<F>(hook: F) -> Self
+    where
+        F: Fn(&NoMatch<'_, M>) + Send + Sync + 'static,
+    {
+        NoMatchHook(Arc::new(hook))
+    }
+
+    pub(super) fn call<H>(&self, routes: &[Route<M, H>], method: &M, path: &str) {
+        (self.0)(&NoMatch {
+            method,
+            path,
+            candidates: candidates(routes, path),
+        })
+    }
+}
+
+impl<M> Clone for NoMatchHook<M> {
+    fn clone(&self) -> Self {
+        NoMatchHook(self.0.clone())
+    }
+}
+
+impl<M> Debug for NoMatchHook<M> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.write_str("NoMatchHook")
+    }
+}
+
+fn candidates<'r, M, H>(routes: &'r [Route<M, H>], path: &str) -> Vec<Candidate<'r, M>> {
+    let limit = std::cmp::max(2, path.len() / 4);
+    let mut candidates = routes
+        .iter()
+        .map(|route| Candidate {
+            path: route.path.as_ref(),
+            method: route.method(),
+            distance: distance(route.path.as_ref(), path),
+        })
+        .filter(|candidate| candidate.distance <= limit)
+        .collect::<Vec<_>>();
+    // This is a stable sort, so routes at the same distance stay in the
+    // order they were added.
+    candidates.sort_by_key(|candidate| candidate.distance);
+    candidates.truncate(MAX_CANDIDATES);
+    candidates
+}
+
+/// Compares the route's path to the given path, segment by segment.
+/// Segments only present in one of the two count as entirely different.
+fn distance(route: &str, path: &str) -> usize {
+    let mut route = route.split('/').skip(1);
+    let mut path = path.split('/').skip(1);
+    let mut total = 0;
+
+    loop {
+        total += match (route.next(), path.next()) {
+            (Some(expected), Some(given)) => segment_distance(&tokens(expected), given),
+            // Filling in a missing segment takes a slash, plus a character
+            // for each literal character and parameter in it.
+            (Some(expected), None) => tokens(expected).len() + 1,
+            (None, Some(given)) => given.chars().count() + 1,
+            (None, None) => return total,
+        };
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Token {
+    Char(char),
+    Param,
+}
+
+fn tokens(segment: &str) -> Vec<Token> {
+    let mut tokens = vec![];
+    let mut last = 0;
+    for cap in PLACEHOLDER.captures_iter(segment) {
+        let whole = cap.get(0).unwrap();
+        tokens.extend(segment[last..whole.start()].chars().map(Token::Char));
+        match cap.name("escape") {
+            Some(escape) => tokens.extend(escape.as_str().chars().take(1).map(Token::Char)),
+            None => tokens.push(Token::Param),
+        }
+        last = whole.end();
+    }
+    tokens.extend(segment[last..].chars().map(Token::Char));
+    tokens
+}
+
+/// The edit distance between a segment of a route and a segment of a path.
+/// This is the usual Levenshtein distance, except that a parameter in the
+/// route matches any non-empty run of characters for free; leaving one
+/// empty costs a single edit.
+fn segment_distance(expected: &[Token], given: &str) -> usize {
+    let given = given.chars().collect::<Vec<_>>();
+    let mut previous = (0..=given.len()).collect::<Vec<_>>();
+    let mut current = vec![0; given.len() + 1];
+
+    for token in expected {
+        current[0] = previous[0] + 1;
+        for (j, c) in given.iter().enumerate() {
+            current[j + 1] = match *token {
+                Token::Char(expected) => {
+                    let substitution = previous[j] + if expected == *c { 0 } else { 1 };
+                    substitution.min(previous[j + 1] + 1).min(current[j] + 1)
+                }
+                // Either the parameter starts with this character, continues
+                // through it, or is left empty.
+                Token::Param => previous[j].min(current[j]).min(previous[j + 1] + 1),
+            };
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    previous[given.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_distance() {
+        assert_eq!(distance("/users/{:uint}", "/users/5"), 0);
+        assert_eq!(distance("/users/{:uint}", "/user/5"), 1);
+        assert_eq!(distance("/users/{:uint}", "/users/"), 1);
+        assert_eq!(distance("/users", "/users/5"), 2);
+        assert_eq!(distance("/users/{:uint}", "/users"), 2);
+        assert_eq!(distance("/users/new", "/users"), 4);
+        assert_eq!(distance("/users/{}/posts", "/users/5/post"), 1);
+        assert_eq!(distance("/orders/order-{id:uint}", "/orders/order-5"), 0);
+        assert_eq!(distance("/orders/order-{id:uint}", "/orders/ordr-5"), 1);
+        assert_eq!(distance("/orders/order-{id:uint}", "/orders/order-"), 1);
+        assert_eq!(distance("/v{version:uint}/users", "/v2/user"), 1);
+        assert_eq!(distance("/report/{}.{}", "/report/5.json"), 0);
+        assert_eq!(distance("/report/{}.{}", "/report/5json"), 1);
+        assert_eq!(distance("/literal/{{id}}", "/literal/{id}"), 0);
+        assert_eq!(distance("/literal/{{id}}", "/literal/id"), 2);
+        let kitten = "kitten".chars().map(Token::Char).collect::<Vec<_>>();
+        assert_eq!(segment_distance(&kitten, "sitting"), 3);
+    }
+}
@@ -0,0 +1,320 @@
+use lazy_static::lazy_static;
+use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
+use regex::Regex;
+use std::borrow::Cow;
+use std::error::Error;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+lazy_static! {
+    /// The escapes left alone when encoded slashes are kept.
+    static ref RESERVED_ESCAPE: Regex = Regex::new(r"%(?:2[fF]|23|25)").unwrap();
+}
+
+/// The characters that are percent-encoded when a decoded path is put back
+/// into a url, e.g. for a redirect.  A backslash is encoded since browsers
+/// treat it like a slash.
+const PATH: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'\\')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
+
+/// [`PATH`], along with `%`, for paths whose escapes were all decoded.
+const PATH_AND_PERCENT: &AsciiSet = &PATH.add(b'%');
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+/// The normalization done to a path before it's looked up.  Everything here
+/// is opt-in, and is set up through the methods on [`super::Build`]; by
+/// default, paths are matched as they're given.
+pub(super) struct Normalize {
+    /// Whether runs of slashes (e.g. `/foo//bar`) should be collapsed into a
+    /// single slash.
+    pub(super) collapse_slashes: bool,
+    /// Whether dot segments (`.` and `..`) should be resolved.
+    pub(super) resolve_dot_segments: bool,
+    /// Whether a path with a `..` that would go above the root should be
+    /// rejected, instead of stopping at the root.
+    pub(super) reject_root_escape: bool,
+    /// Whether encoded slashes (and hashes) should be left encoded when the
+    /// path is decoded, so that they can be captured by a parameter.
+    pub(super) keep_encoded_slashes: bool,
+    /// Whether malformed percent-encoding should be rejected, instead of
+    /// being passed through (or replaced, if it isn't valid UTF-8).
+    pub(super) strict_decoding: bool,
+    /// Whether the path should be put in Unicode Normalization Form C.
+    #[cfg(feature = "unicode-normalization")]
+    pub(super) nfc: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// The reason a path was rejected by [`super::Router::normalize`].
+pub enum NormalizeError {
+    /// The path has a `..` segment that would go above the root, e.g.
+    /// `/static/../../etc/passwd`.
+    RootEscape,
+    /// The path has a `%` that isn't followed by two hex digits.
+    MalformedEscape,
+    /// The path doesn't decode to valid UTF-8.
+    InvalidUtf8,
+}
+
+impl Display for NormalizeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            NormalizeError::RootEscape => f.write_str("path escapes the root"),
+            NormalizeError::MalformedEscape => f.write_str("path has a malformed percent-escape"),
+            NormalizeError::InvalidUtf8 => f.write_str("path is not valid utf-8"),
+        }
+    }
+}
+
+impl Error for NormalizeError {}
+
+impl Normalize {
+    /// Strips the query from the url, and percent-decodes the rest.  If
+    /// encoded slashes are kept, then `%2F`, `%23`, and `%25` are left as
+    /// they are, so that the path can be matched without them being mistaken
+    /// for the real thing; the parameters captured from the path can then be
+    /// decoded with [`Normalize::decode_param`].
+    pub(super) fn decode(&self, url: &str) -> Result<String, NormalizeError> {
+        let url = url.split_terminator('?').next().unwrap_or(url);
+        if self.strict_decoding && has_malformed_escape(url) {
+            return Err(NormalizeError::MalformedEscape);
+        }
+        if !self.keep_encoded_slashes {
+            return self.decode_part(url).map(Cow::into_owned);
+        }
+
+        let mut decoded = String::with_capacity(url.len());
+        let mut last = 0;
+        for escape in RESERVED_ESCAPE.find_iter(url) {
+            decoded.push_str(&self.decode_part(&url[last..escape.start()])?);
+            decoded.push_str(escape.as_str());
+            last = escape.end();
+        }
+        decoded.push_str(&self.decode_part(&url[last..])?);
+        Ok(decoded)
+    }
+
+    /// Decodes a parameter captured from a path decoded by
+    /// [`Normalize::decode`].  The only escapes left in the parameter are the
+    /// ones that were kept, all of which are ASCII, so this can't fail.
+    pub(super) fn decode_param<'p>(&self, param: &'p str) -> Cow<'p, str> {
+        if self.keep_encoded_slashes {
+            percent_encoding::percent_decode(param.as_bytes()).decode_utf8_lossy()
+        } else {
+            Cow::Borrowed(param)
+        }
+    }
+
+    /// Percent-encodes a path decoded by [`Normalize::decode`], so that it
+    /// can be put back into a url.  The escapes that were kept when the path
+    /// was decoded are left as they are.
+    pub(super) fn encode(&self, path: &str) -> String {
+        if self.keep_encoded_slashes {
+            utf8_percent_encode(path, PATH).to_string()
+        } else {
+            utf8_percent_encode(path, PATH_AND_PERCENT).to_string()
+        }
+    }
+
+    fn decode_part<'p>(&self, part: &'p str) -> Result<Cow<'p, str>, NormalizeError> {
+        let decoded = percent_encoding::percent_decode(part.as_bytes());
+        if self.strict_decoding {
+            decoded.decode_utf8().map_err(|_| NormalizeError::InvalidUtf8)
+        } else {
+            Ok(decoded.decode_utf8_lossy())
+        }
+    }
+
+    /// Normalizes the given path.  If nothing needs to change, the path is
+    /// borrowed as-is.
+    pub(super) fn apply<'p>(&self, path: &'p str) -> Result<Cow<'p, str>, NormalizeError> {
+        let mut path = Cow::Borrowed(path);
+        if self.collapse_slashes && path.contains("//") {
+            path = Cow::Owned(collapse_slashes(&path));
+        }
+        if (self.resolve_dot_segments || self.reject_root_escape)
+            && path.split('/').any(|part| part == "." || part == "..")
+        {
+            path = Cow::Owned(resolve_dot_segments(&path, self.reject_root_escape)?);
+        }
+        #[cfg(feature = "unicode-normalization")]
+        {
+            use unicode_normalization::{is_nfc, UnicodeNormalization};
+            if self.nfc && !is_nfc(&path) {
+                path = Cow::Owned(path.nfc().collect());
+            }
+        }
+        Ok(path)
+    }
+}
+
+/// The other form of the given path: with its trailing slash removed if
+/// it has one, or with one added if it doesn't.  The root has no other
+/// form.  See [`super::Build::redirect_trailing_slash`].
+pub fn toggle_trailing_slash(path: &str) -> Option<String> {
+    if path.is_empty() || path == "/" {
+        None
+    } else if let Some(trimmed) = path.strip_suffix('/') {
+        Some(trimmed.to_string())
+    } else {
+        Some(format!("{}/", path))
+    }
+}
+
+fn has_malformed_escape(url: &str) -> bool {
+    let bytes = url.as_bytes();
+    bytes.iter().enumerate().any(|(i, &b)| {
+        b == b'%'
+            && !(bytes.get(i + 1).is_some_and(u8::is_ascii_hexdigit)
+                && bytes.get(i + 2).is_some_and(u8::is_ascii_hexdigit))
+    })
+}
+
+fn collapse_slashes(path: &str) -> String {
+    let mut collapsed = String::with_capacity(path.len());
+    for c in path.chars() {
+        if c != '/' || !collapsed.ends_with('/') {
+            collapsed.push(c);
+        }
+    }
+    collapsed
+}
+
+/// Resolves the `.` and `..` segments of a path, like a filesystem would.
+/// A `..` at the root stays at the root, unless `reject` is set, in which
+/// case the path is rejected.
+fn resolve_dot_segments(path: &str, reject: bool) -> Result<String, NormalizeError> {
+    let mut parts = path.split('/').skip(1).peekable();
+    let mut resolved = vec![];
+    while let Some(part) = parts.next() {
+        match part {
+            "." => {}
+            ".." => {
+                if resolved.pop().is_none() && reject {
+                    return Err(NormalizeError::RootEscape);
+                }
+            }
+            part => resolved.push(part),
+        }
+        // A trailing dot segment still refers to a directory, so we keep the
+        // trailing slash.
+        if parts.peek().is_none() && (part == "." || part == "..") {
+            resolved.push("");
+        }
+    }
+
+    Ok(resolved.iter().fold(String::new(), |mut acc, part| {
+        acc.push('/');
+        acc.push_str(part);
+        acc
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode() {
+        let normalize = Normalize::default();
+        assert_eq!(normalize.decode("/a%20b/c?d=%20").unwrap(), "/a b/c");
+        assert_eq!(normalize.decode("/a%2Fb").unwrap(), "/a/b");
+        assert_eq!(normalize.decode("/100%").unwrap(), "/100%");
+        assert_eq!(normalize.decode("/%FF").unwrap(), "/\u{FFFD}");
+
+        let normalize = Normalize {
+            keep_encoded_slashes: true,
+            ..Normalize::default()
+        };
+        assert_eq!(normalize.decode("/a%2fb%20c%23%25").unwrap(), "/a%2fb c%23%25");
+        assert_eq!(normalize.decode_param("a%2fb%23%25"), "a/b#%");
+
+        let normalize = Normalize {
+            strict_decoding: true,
+            ..Normalize::default()
+        };
+        assert_eq!(normalize.decode("/a%20b").unwrap(), "/a b");
+        assert_eq!(normalize.decode("/caf%C3%A9").unwrap(), "/caf\u{e9}");
+        assert_eq!(normalize.decode("/100%"), Err(NormalizeError::MalformedEscape));
+        assert_eq!(normalize.decode("/%zz"), Err(NormalizeError::MalformedEscape));
+        assert_eq!(normalize.decode("/%4"), Err(NormalizeError::MalformedEscape));
+        assert_eq!(normalize.decode("/%FF"), Err(NormalizeError::InvalidUtf8));
+        assert_eq!(normalize.decode("/%C3"), Err(NormalizeError::InvalidUtf8));
+        assert_eq!(normalize.decode("/ok?q=%zz").unwrap(), "/ok");
+    }
+
+    #[test]
+    #[cfg(feature = "unicode-normalization")]
+    fn test_nfc() {
+        let normalize = Normalize {
+            nfc: true,
+            ..Normalize::default()
+        };
+        assert_eq!(normalize.apply("/cafe\u{301}").unwrap(), "/caf\u{e9}");
+        assert!(matches!(normalize.apply("/caf\u{e9}"), Ok(Cow::Borrowed(_))));
+        assert_eq!(
+            Normalize::default().apply("/cafe\u{301}").unwrap(),
+            "/cafe\u{301}"
+        );
+    }
+
+    #[test]
+    fn test_collapse_slashes() {
+        let normalize = Normalize {
+            collapse_slashes: true,
+            ..Normalize::default()
+        };
+        assert_eq!(normalize.apply("/foo//bar").unwrap(), "/foo/bar");
+        assert_eq!(normalize.apply("//foo///bar//").unwrap(), "/foo/bar/");
+        assert!(matches!(normalize.apply("/foo/bar"), Ok(Cow::Borrowed(_))));
+        assert_eq!(
+            Normalize::default().apply("/foo//bar").unwrap(),
+            "/foo//bar"
+        );
+    }
+
+    #[test]
+    fn test_resolve_dot_segments() {
+        let normalize = Normalize {
+            resolve_dot_segments: true,
+            ..Normalize::default()
+        };
+        assert_eq!(normalize.apply("/foo/./bar").unwrap(), "/foo/bar");
+        assert_eq!(normalize.apply("/foo/../bar").unwrap(), "/bar");
+        assert_eq!(normalize.apply("/foo/bar/..").unwrap(), "/foo/");
+        assert_eq!(normalize.apply("/foo/.").unwrap(), "/foo/");
+        assert_eq!(normalize.apply("/..").unwrap(), "/");
+        assert_eq!(normalize.apply("/../../etc").unwrap(), "/etc");
+        assert_eq!(normalize.apply("/foo/..bar/.baz").unwrap(), "/foo/..bar/.baz");
+        assert!(matches!(normalize.apply("/foo/bar"), Ok(Cow::Borrowed(_))));
+
+        let normalize = Normalize {
+            reject_root_escape: true,
+            ..Normalize::default()
+        };
+        assert_eq!(normalize.apply("/foo/../bar").unwrap(), "/bar");
+        assert_eq!(normalize.apply("/foo/.."), Ok(Cow::Borrowed("/")));
+        assert_eq!(
+            normalize.apply("/foo/../../etc"),
+            Err(NormalizeError::RootEscape)
+        );
+        assert_eq!(normalize.apply("/.."), Err(NormalizeError::RootEscape));
+    }
+
+    #[test]
+    fn test_toggle_trailing_slash() {
+        assert_eq!(toggle_trailing_slash("/users/"), Some("/users".to_string()));
+        assert_eq!(toggle_trailing_slash("/users"), Some("/users/".to_string()));
+        assert_eq!(toggle_trailing_slash("/users//"), Some("/users/".to_string()));
+        assert_eq!(toggle_trailing_slash("/"), None);
+        assert_eq!(toggle_trailing_slash(""), None);
+    }
+}
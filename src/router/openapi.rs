@@ -0,0 +1,675 @@
+use super::json::Json;
+use super::route::{kind_pattern, Constraint, PLACEHOLDER};
+use super::{Build, HandlerRegistry, Route, RouteParseError, Router};
+use lazy_static::lazy_static;
+use regex::{Captures, Regex};
+use std::error::Error;
+use std::fmt::{Display, Formatter, Result as FmtResult, Write};
+use std::str::FromStr;
+
+/// The methods that an OpenAPI path item can have operations for.
+const METHODS: [&str; 8] = [
+    "get", "put", "post", "delete", "options", "head", "patch", "trace",
+];
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+/// Documentation for a route, for the OpenAPI document built by
+/// [`Router::openapi`].  This is attached to a route with
+/// [`Route::with_extension`], e.g.
+/// `route.with_extension(ApiOperation::new().with_summary("Lists users"))`.
+pub struct ApiOperation {
+    /// A short summary of what the route does.
+    pub summary: Option<String>,
+    /// A longer description of the route, which may use CommonMark.
+    pub description: Option<String>,
+    /// The tags the route is grouped under.
+    pub tags: Vec<String>,
+}
+
+impl ApiOperation {
+    /// Creates empty documentation for a route.
+    pub fn new() -> Self {
+        ApiOperation::default()
+    }
+
+    /// Sets the summary of the route.
+    pub fn with_summary(mut self, summary: &str) -> Self {
+        self.summary = Some(summary.to_string());
+        self
+    }
+
+    /// Sets the description of the route.
+    pub fn with_description(mut self, description: &str) -> Self {
+        self.description = Some(description.to_string());
+        self
+    }
+
+    /// Adds a tag to the route.
+    pub fn with_tag(mut self, tag: &str) -> Self {
+        self.tags.push(tag.to_string());
+        self
+    }
+}
+
+impl<M: Eq + Display, H> Router<M, H> {
+    /// Builds an OpenAPI 3 document, as JSON, that describes the routes of
+    /// the router.  Each route is an operation under its path and method,
+    /// with its path parameters typed by their kinds (e.g. `{id:uint}` is
+    /// a non-negative integer), and documented by the [`ApiOperation`]
+    /// attached to it, if there is one.  A named route uses its name as
+    /// the operation's id.
+    ///
+    /// OpenAPI can't express some routes, so they're left out: routes that
+    /// match any method (or only methods OpenAPI doesn't have), catch-all
+    /// routes, and routes with a literal brace in their path.  If more than
+    /// one route has the same path and method, only the first, which is
+    /// the one that matches, is listed.
+    pub fn openapi(&self, title: &str, version: &str) -> String {
+        let mut paths: Vec<(String, Vec<(&'static str, String)>)> = vec![];
+        for route in &self.routes {
+            let (path, parameters) = match template(route) {
+                Some(template) => template,
+                None => continue,
+            };
+            let index = match paths.iter().position(|(existing, _)| *existing == path) {
+                Some(index) => index,
+                None => {
+                    paths.push((path, vec![]));
+                    paths.len() - 1
+                }
+            };
+            let operations = &mut paths[index].1;
+            for method in route.methods.iter().flatten() {
+                let method = method.to_string().to_ascii_lowercase();
+                let method = match METHODS.iter().find(|known| **known == method) {
+                    Some(method) => *method,
+                    None => continue,
+                };
+                if operations.iter().all(|(existing, _)| *existing != method) {
+                    operations.push((method, operation(route, &parameters)));
+                }
+            }
+        }
+
+        let mut doc = String::new();
+        write!(
+            doc,
+            r#"{{"openapi":"3.0.3","info":{{"title":{},"version":{}}},"paths":{{"#,
+            string(title),
+            string(version)
+        )
+        .unwrap();
+        let paths = paths.iter().filter(|(_, operations)| !operations.is_empty());
+        for (i, (path, operations)) in paths.enumerate() {
+            if i > 0 {
+                doc.push(',');
+            }
+            write!(doc, "{}:{{", string(path)).unwrap();
+            for (j, (method, operation)) in operations.iter().enumerate() {
+                if j > 0 {
+                    doc.push(',');
+                }
+                write!(doc, "{}:{}", string(method), operation).unwrap();
+            }
+            doc.push('}');
+        }
+        doc.push_str("}}");
+        doc
+    }
+}
+
+/// Converts the path of the route to an OpenAPI path template, returning it
+/// along with the parameters of the route, as JSON.  Parameters without a
+/// name are named by their position, e.g. `param0`.
+fn template<M, H>(route: &Route<M, H>) -> Option<(String, Vec<String>)> {
+    if route.catch_all {
+        return None;
+    }
+    let path = crate::normalize_url(route.path());
+    let mut template = String::with_capacity(path.len());
+    let mut parameters = vec![];
+    for (i, part) in path.split('/').enumerate() {
+        if i > 0 {
+            template.push('/');
+        }
+        let mut last = 0;
+        for cap in PLACEHOLDER.captures_iter(part) {
+            let whole = cap.get(0).unwrap();
+            if cap.name("escape").is_some() {
+                return None;
+            }
+            template.push_str(&part[last..whole.start()]);
+            last = whole.end();
+
+            let index = parameters.len();
+            let name = match &route.params[index] {
+                Some(name) => name.clone(),
+                None => format!("param{}", index),
+            };
+            let kind = cap.name("kind").map(|m| m.as_str()).unwrap_or("string");
+            let args = cap.name("args").map(|m| m.as_str());
+            write!(template, "{{{}}}", name).unwrap();
+            parameters.push(format!(
+                r#"{{"name":{},"in":"path","required":true,"schema":{}}}"#,
+                string(&name),
+                schema(kind, args, route.constraints[index])
+            ));
+        }
+        template.push_str(&part[last..]);
+    }
+    Some((template, parameters))
+}
+
+fn operation<M, H>(route: &Route<M, H>, parameters: &[String]) -> String {
+    let mut operation = String::from("{");
+    if let Some(name) = route.name() {
+        write!(operation, r#""operationId":{},"#, string(name)).unwrap();
+    }
+    if let Some(doc) = route.extension::<ApiOperation>() {
+        if let Some(summary) = &doc.summary {
+            write!(operation, r#""summary":{},"#, string(summary)).unwrap();
+        }
+        if let Some(description) = &doc.description {
+            write!(operation, r#""description":{},"#, string(description)).unwrap();
+        }
+        if !doc.tags.is_empty() {
+            let tags = list(doc.tags.iter().map(|tag| string(tag)));
+            write!(operation, r#""tags":{},"#, tags).unwrap();
+        }
+    }
+    if !parameters.is_empty() {
+        write!(operation, r#""parameters":{},"#, list(parameters.iter().cloned())).unwrap();
+    }
+    if !route.consumes.is_empty() {
+        write!(
+            operation,
+            r#""requestBody":{{"required":true,"content":{}}},"#,
+            content(&route.consumes)
+        )
+        .unwrap();
+    }
+    operation.push_str(r#""responses":{"default":{"description":"The response.""#);
+    if !route.produces.is_empty() {
+        write!(operation, r#","content":{}"#, content(&route.produces)).unwrap();
+    }
+    operation.push_str("}}}");
+    operation
+}
+
+/// The schema of a parameter with the given kind.
+fn schema(kind: &str, args: Option<&str>, constraint: Option<Constraint>) -> String {
+    match kind {
+        "string" => r#"{"type":"string"}"#.to_string(),
+        "int" | "uint" => {
+            let (mut min, max) = match constraint {
+                Some(Constraint::Range(min, max)) => (min, max),
+                None => (None, None),
+            };
+            if kind == "uint" {
+                min = Some(min.map_or(0, |min| std::cmp::max(min, 0)));
+            }
+            let mut schema = String::from(r#"{"type":"integer""#);
+            if let Some(min) = min {
+                write!(schema, r#","minimum":{}"#, min).unwrap();
+            }
+            if let Some(max) = max {
+                write!(schema, r#","maximum":{}"#, max).unwrap();
+            }
+            schema.push('}');
+            schema
+        }
+        "uuid" | "ipv4" | "ipv6" | "email" => {
+            format!(r#"{{"type":"string","format":{}}}"#, string(kind))
+        }
+        "one_of" => {
+            let choices = args
+                .unwrap_or_default()
+                .split('|')
+                .map(|choice| string(choice.trim()));
+            format!(r#"{{"type":"string","enum":{}}}"#, list(choices))
+        }
+        _ => {
+            // The route was parsed, so the kind is known to exist.
+            let (pattern, _) = kind_pattern(kind, args).unwrap();
+            format!(
+                r#"{{"type":"string","pattern":{}}}"#,
+                string(&format!("^{}$", pattern))
+            )
+        }
+    }
+}
+
+fn content(media_types: &[String]) -> String {
+    let entries = media_types
+        .iter()
+        .map(|media_type| format!("{}:{{}}", string(media_type)))
+        .collect::<Vec<_>>();
+    format!("{{{}}}", entries.join(","))
+}
+
+fn list<I: Iterator<Item = String>>(items: I) -> String {
+    format!("[{}]", items.collect::<Vec<_>>().join(","))
+}
+
+/// Encodes the given text as a JSON string.
+fn string(text: &str) -> String {
+    let mut json = String::with_capacity(text.len() + 2);
+    json.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => json.push_str(r#"\""#),
+            '\\' => json.push_str(r"\\"),
+            '\n' => json.push_str(r"\n"),
+            '\r' => json.push_str(r"\r"),
+            '\t' => json.push_str(r"\t"),
+            c if (c as u32) < 0x20 => write!(json, r"\u{:04x}", c as u32).unwrap(),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// The reason the routes of an OpenAPI document couldn't be loaded by
+/// [`Build::load_openapi`].
+pub enum OpenApiError {
+    /// The document isn't valid JSON; this is the byte offset of the first
+    /// problem.
+    Syntax(usize),
+    /// An operation has no `operationId`, so there's no handler to bind to
+    /// it.
+    MissingOperationId {
+        /// The path of the operation.
+        path: String,
+        /// The method of the operation, e.g. `get`.
+        method: String,
+    },
+    /// No handler is registered for an operation.
+    UnknownHandler {
+        /// The `operationId` of the operation.
+        operation_id: String,
+    },
+    /// A method couldn't be parsed.
+    InvalidMethod {
+        /// The path of the operation.
+        path: String,
+        /// The method of the operation, e.g. `get`.
+        method: String,
+    },
+    /// The path of an operation couldn't be parsed, once its parameters
+    /// were given kinds.
+    Route {
+        /// The path of the operation, as the document gives it.
+        path: String,
+        /// The error from parsing the path.
+        error: RouteParseError,
+    },
+}
+
+impl Display for OpenApiError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            OpenApiError::Syntax(offset) => {
+                write!(f, "the document isn't valid JSON (at byte {})", offset)
+            }
+            OpenApiError::MissingOperationId { path, method } => {
+                write!(f, "the operation `{} {}` has no operationId", method, path)
+            }
+            OpenApiError::UnknownHandler { operation_id } => {
+                write!(f, "no handler is registered for the operation `{}`", operation_id)
+            }
+            OpenApiError::InvalidMethod { path, method } => {
+                write!(f, "the method of the operation `{} {}` is not valid", method, path)
+            }
+            OpenApiError::Route { path, error } => {
+                write!(f, "the path `{}` couldn't be used: {}", path, error)
+            }
+        }
+    }
+}
+
+impl Error for OpenApiError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            OpenApiError::Route { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl<M: FromStr, H> Build<M, H> {
+    /// Adds a route for each operation of the given OpenAPI 3 document (as
+    /// JSON), for applications whose API is written spec-first.  Each
+    /// operation is bound to the handler registered under its
+    /// `operationId`, which is also used as the route's name.  The path
+    /// parameters are given kinds from their schemas, e.g. an `integer`
+    /// with a `minimum` of `0` is a `{:uint}`, and a `string` with an
+    /// `enum` is a `{:one_of(..)}`; anything else is a `{:string}`.  The
+    /// summary, description, and tags of the operation are attached to
+    /// the route as an [`ApiOperation`].
+    ///
+    /// Every operation has to have an `operationId` with a registered
+    /// handler; if any don't, or anything else is wrong with the document,
+    /// none of its routes are added.
+    pub fn load_openapi(
+        &mut self,
+        spec: &str,
+        registry: &HandlerRegistry<H>,
+    ) -> Result<&mut Self, OpenApiError> {
+        let spec = Json::parse(spec).map_err(OpenApiError::Syntax)?;
+        let mut routes = vec![];
+        for (path, item) in spec.get("paths").map_or(&[][..], Json::members) {
+            let shared = item.get("parameters");
+            for (method, operation) in item.members() {
+                if !METHODS.contains(&method.as_str()) {
+                    continue;
+                }
+                let operation_id = operation
+                    .get("operationId")
+                    .and_then(Json::as_str)
+                    .ok_or_else(|| OpenApiError::MissingOperationId {
+                        path: path.clone(),
+                        method: method.clone(),
+                    })?;
+                let handler = registry.make(operation_id).ok_or_else(|| {
+                    OpenApiError::UnknownHandler {
+                        operation_id: operation_id.to_string(),
+                    }
+                })?;
+                let parsed = method.to_ascii_uppercase().parse().map_err(|_| {
+                    OpenApiError::InvalidMethod {
+                        path: path.clone(),
+                        method: method.clone(),
+                    }
+                })?;
+
+                let params = [shared, operation.get("parameters")];
+                let template = route_path(path, &params);
+                let route = Route::try_from_parts(template, Some(vec![parsed]), handler)
+                    .map_err(|error| OpenApiError::Route {
+                        path: path.clone(),
+                        error,
+                    })?
+                    .with_name(operation_id);
+                let doc = ApiOperation {
+                    summary: operation.get("summary").and_then(Json::as_str).map(str::to_string),
+                    description: operation
+                        .get("description")
+                        .and_then(Json::as_str)
+                        .map(str::to_string),
+                    tags: operation
+                        .get("tags")
+                        .map_or(&[][..], Json::items)
+                        .iter()
+                        .filter_map(Json::as_str)
+                        .map(str::to_string)
+                        .collect(),
+                };
+                routes.push(if doc == ApiOperation::default() {
+                    route
+                } else {
+                    route.with_extension(doc)
+                });
+            }
+        }
+        for route in routes {
+            self.add(route);
+        }
+        Ok(self)
+    }
+}
+
+lazy_static! {
+    static ref TEMPLATE: Regex = Regex::new(r"\{([^{}/]+)\}").unwrap();
+    static ref IDENTIFIER: Regex = Regex::new(r"^[a-zA-Z_]\w*$").unwrap();
+}
+
+/// Converts an OpenAPI path template to a route's path, giving each
+/// parameter the kind that its schema best fits.  The later of the given
+/// parameter lists takes precedence, as an operation's parameters do over
+/// its path's.
+fn route_path(path: &str, params: &[Option<&Json>]) -> String {
+    TEMPLATE
+        .replace_all(path, |cap: &Captures<'_>| {
+            let name = &cap[1];
+            let schema = params
+                .iter()
+                .rev()
+                .flat_map(|params| params.map_or(&[][..], Json::items))
+                .find(|param| {
+                    param.get("in").and_then(Json::as_str) == Some("path")
+                        && param.get("name").and_then(Json::as_str) == Some(name)
+                })
+                .and_then(|param| param.get("schema"));
+            // Names that can't be used for a route's parameter are left
+            // out, so the parameter is only known by its position.
+            let name = if IDENTIFIER.is_match(name) { name } else { "" };
+            match schema.and_then(kind) {
+                Some(kind) => format!("{{{}:{}}}", name, kind),
+                None => format!("{{{}}}", name),
+            }
+        })
+        .into_owned()
+}
+
+/// The kind of a parameter with the given schema, if it's narrower than a
+/// string.
+fn kind(schema: &Json) -> Option<String> {
+    if let Some(choices) = schema.get("enum") {
+        let choices = choices
+            .items()
+            .iter()
+            .map(Json::as_str)
+            .collect::<Option<Vec<_>>>()?;
+        let valid = |choice: &&str| {
+            !choice.is_empty() && !choice.contains(['|', '/', ')'])
+        };
+        if choices.is_empty() || !choices.iter().all(valid) {
+            return None;
+        }
+        return Some(format!("one_of({})", choices.join("|")));
+    }
+
+    match (
+        schema.get("type").and_then(Json::as_str),
+        schema.get("format").and_then(Json::as_str),
+    ) {
+        (Some("integer"), _) => {
+            let bound = |key: &str, exclusive: &str, step: f64| {
+                let value = match schema.get(exclusive) {
+                    // OpenAPI 3.1 gives the exclusive bound as a number,
+                    // and 3.0 as a flag on the inclusive one.
+                    Some(Json::Number(value)) => Some(value + step),
+                    Some(Json::Bool(true)) => {
+                        schema.get(key).and_then(Json::as_f64).map(|value| value + step)
+                    }
+                    _ => schema.get(key).and_then(Json::as_f64),
+                };
+                value.map(|value| if step > 0.0 { value.ceil() } else { value.floor() } as i128)
+            };
+            let min = bound("minimum", "exclusiveMinimum", 1.0);
+            let max = bound("maximum", "exclusiveMaximum", -1.0);
+            let kind = if min.is_some_and(|min| min >= 0) { "uint" } else { "int" };
+            let min = min.filter(|min| kind == "int" || *min > 0);
+            Some(match (min, max) {
+                (None, None) => kind.to_string(),
+                (Some(min), None) => format!("{}({}..)", kind, min),
+                (None, Some(max)) => format!("{}(..={})", kind, max),
+                (Some(min), Some(max)) => format!("{}({}..={})", kind, min, max),
+            })
+        }
+        (Some("string"), Some(format @ "uuid"))
+        | (Some("string"), Some(format @ "ipv4"))
+        | (Some("string"), Some(format @ "ipv6"))
+        | (Some("string"), Some(format @ "email")) => Some(format.to_string()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_openapi() {
+        let mut build = Router::build();
+        build
+            .add(
+                Route::new("/users", "GET", ())
+                    .with_name("listUsers")
+                    .with_extension(
+                        ApiOperation::new()
+                            .with_summary("Lists \"all\" users")
+                            .with_tag("users"),
+                    ),
+            )
+            .add(Route::new("/users", "POST", ()).consumes("application/json"))
+            .add(Route::new("/users/{id:uint}", "GET", ()).produces("application/json"))
+            .add(Route::new("/users/{id:uint}", "GET", ()))
+            .add(Route::new("/pages/{:int(-5..=5)}.{format:one_of(json|xml)}", "GET", ()))
+            .add(Route::new("/literal/{{id}}", "GET", ()))
+            .add(Route::new("/versions/{:semver}", "BREW", ()))
+            .add(Route::new_any("/health", ()));
+        let router = build.finish();
+
+        assert_eq!(
+            router.openapi("Users", "1.0"),
+            concat!(
+                r#"{"openapi":"3.0.3","info":{"title":"Users","version":"1.0"},"paths":{"#,
+                r#""/users":{"#,
+                r#""get":{"operationId":"listUsers","summary":"Lists \"all\" users","tags":["users"],"#,
+                r#""responses":{"default":{"description":"The response."}}},"#,
+                r#""post":{"requestBody":{"required":true,"content":{"application/json":{}}},"#,
+                r#""responses":{"default":{"description":"The response."}}}},"#,
+                r#""/users/{id}":{"get":{"#,
+                r#""parameters":[{"name":"id","in":"path","required":true,"schema":{"type":"integer","minimum":0}}],"#,
+                r#""responses":{"default":{"description":"The response.","content":{"application/json":{}}}}}},"#,
+                r#""/pages/{param0}.{format}":{"get":{"parameters":["#,
+                r#"{"name":"param0","in":"path","required":true,"schema":{"type":"integer","minimum":-5,"maximum":5}},"#,
+                r#"{"name":"format","in":"path","required":true,"schema":{"type":"string","enum":["json","xml"]}}],"#,
+                r#""responses":{"default":{"description":"The response."}}}}"#,
+                r#"}}"#,
+            )
+        );
+    }
+
+    #[test]
+    fn test_schema() {
+        assert_eq!(
+            schema("uint", None, Some(Constraint::Range(Some(-3), None))),
+            r#"{"type":"integer","minimum":0}"#
+        );
+        assert_eq!(schema("uuid", None, None), r#"{"type":"string","format":"uuid"}"#);
+        assert_eq!(
+            schema("b64", Some("4"), None),
+            r#"{"type":"string","pattern":"^([A-Za-z0-9_-]{4,4}={0,2})$"}"#
+        );
+        assert_eq!(string("a\u{1}\n"), r#""a\u0001\n""#);
+    }
+
+    #[test]
+    fn test_load_openapi() {
+        let spec = r#"{
+            "openapi": "3.0.3",
+            "info": {"title": "Users", "version": "1.0"},
+            "paths": {
+                "/users/{user-id}/posts/{post}": {
+                    "parameters": [
+                        {"name": "user-id", "in": "path", "schema": {"type": "integer", "minimum": 1}},
+                        {"name": "post", "in": "path", "schema": {"type": "string"}}
+                    ],
+                    "summary": "A user's post",
+                    "get": {
+                        "operationId": "showPost",
+                        "summary": "Shows a post",
+                        "tags": ["posts"],
+                        "parameters": [
+                            {"name": "post", "in": "path", "schema": {"type": "string", "format": "uuid"}},
+                            {"name": "user-id", "in": "query", "schema": {"type": "string"}}
+                        ]
+                    },
+                    "delete": {"operationId": "deletePost"}
+                },
+                "/reports/{format}": {
+                    "get": {
+                        "operationId": "report",
+                        "parameters": [
+                            {"name": "format", "in": "path", "schema": {"enum": ["json", "csv"]}},
+                            {"name": "limit", "in": "query", "schema": {"type": "integer"}}
+                        ]
+                    }
+                }
+            }
+        }"#;
+        let mut registry = HandlerRegistry::new();
+        registry
+            .register("showPost", 1)
+            .register("deletePost", 2)
+            .register("report", 3);
+        let mut build = Router::<String, i32>::build();
+        build.load_openapi(spec, &registry).unwrap();
+        let router = build.finish();
+
+        let routes = router
+            .routes()
+            .map(|info| (info.path.to_string(), info.methods.unwrap()[0].clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            routes,
+            vec![
+                ("/users/{:uint(1..)}/posts/{post:uuid}".to_string(), "GET".to_string()),
+                ("/users/{:uint(1..)}/posts/{post}".to_string(), "DELETE".to_string()),
+                ("/reports/{format:one_of(json|csv)}".to_string(), "GET".to_string()),
+            ]
+        );
+        let (route, _) = router
+            .lookup_route(&"DELETE".to_string(), "/users/5/posts/first")
+            .unwrap();
+        assert_eq!(route.handler(), &2);
+        assert_eq!(route.name(), Some("deletePost"));
+        assert!(route.extension::<ApiOperation>().is_none());
+        assert_eq!(
+            router.url_for("showPost", &[&5, &"0b7f5a5e-2d4b-4d0b-9a3c-3f2b1c0d9e8f"]).unwrap(),
+            "/users/5/posts/0b7f5a5e-2d4b-4d0b-9a3c-3f2b1c0d9e8f"
+        );
+        let doc = router.named("showPost").unwrap().extension::<ApiOperation>().unwrap();
+        assert_eq!(doc, &ApiOperation::new().with_summary("Shows a post").with_tag("posts"));
+
+        // What's exported can be loaded again.
+        let mut build = Router::<String, i32>::build();
+        build.load_openapi(&router.openapi("Users", "1.0"), &registry).unwrap();
+        let reloaded = build.finish();
+        assert_eq!(
+            reloaded.routes().map(|info| info.pattern).collect::<Vec<_>>(),
+            router.routes().map(|info| info.pattern).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_load_openapi_errors() {
+        fn load(spec: &str) -> OpenApiError {
+            let mut registry = HandlerRegistry::new();
+            registry.register("home", ());
+            let mut build = Router::<String, ()>::build();
+            let error = build.load_openapi(spec, &registry).unwrap_err();
+            assert!(build.finish().routes().next().is_none());
+            error
+        }
+
+        assert_eq!(load(r#"{"paths": {"#), OpenApiError::Syntax(11));
+        assert_eq!(
+            load(r#"{"paths": {"/": {"get": {"operationId": "home"}, "post": {}}}}"#),
+            OpenApiError::MissingOperationId {
+                path: "/".to_string(),
+                method: "post".to_string()
+            }
+        );
+        assert_eq!(
+            load(r#"{"paths": {"/": {"get": {"operationId": "away"}}}}"#),
+            OpenApiError::UnknownHandler {
+                operation_id: "away".to_string()
+            }
+        );
+    }
+}
@@ -0,0 +1,280 @@
+use super::Route;
+use std::borrow::Cow;
+use std::error::Error;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::str::FromStr;
+
+/// The parameters of the path that are given to a handler, URL decoded.
+/// Each one borrows from the path of the request, unless it had to be
+/// decoded, so that they aren't copied for every request; a handler that
+/// keeps one past its call (e.g. in the future it returns) copies it, with
+/// `to_string`.  These work like a list of strings, e.g. `params[0]`,
+/// `params.join("/")`, or `params.parse::<u64>(0)`.
+pub type Params<'p> = [Cow<'p, str>];
+
+/// A type that the value of a parameter can be converted into, with
+/// [`ParseParams::parse`].  This is implemented for every type that
+/// implements [`FromStr`] (with an error that can be displayed), which
+/// covers the integers, `String`, IP addresses, and types from other crates
+/// like `Uuid`; it can be implemented for other types, e.g. a slug that's
+/// looked up differently.
+pub trait FromParam: Sized {
+    /// The error returned when the value isn't valid.
+    type Error: Display;
+
+    /// Converts the value of a parameter, which is URL decoded.
+    fn from_param(param: &str) -> Result<Self, Self::Error>;
+}
+
+impl<T> FromParam for T
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    type Error = T::Err;
+
+    fn from_param(param: &str) -> Result<Self, Self::Error> {
+        param.parse()
+    }
+}
+
+/// Converts the parameters of a lookup (or the ones given to a handler)
+/// by their index, e.g. `let id: u64 = params.parse(0)?;`.  To convert a
+/// parameter by its name, get its index with [`super::Route::param_index`].
+pub trait ParseParams {
+    /// Converts the parameter at the given index into the given type.
+    fn parse<T: FromParam>(&self, index: usize) -> Result<T, ParamError>;
+}
+
+impl<S: AsRef<str>> ParseParams for [S] {
+    fn parse<T: FromParam>(&self, index: usize) -> Result<T, ParamError> {
+        let value = self
+            .get(index)
+            .ok_or(ParamError::Missing { index })?
+            .as_ref();
+        T::from_param(value).map_err(|error| ParamError::Invalid {
+            index,
+            value: value.to_string(),
+            message: error.to_string(),
+        })
+    }
+}
+
+/// A struct whose fields are taken from the parameters of a route, by
+/// their names, e.g. `struct UserPath { id: u64, post: Uuid }` for
+/// `/users/{id:uint}/posts/{post:uuid}`.  With the `derive` feature, this
+/// can be derived with `#[derive(PathParams)]`, for structs with named
+/// fields whose types implement [`FromParam`].
+pub trait PathParams: Sized {
+    /// The names of the parameters that the struct is made from.
+    const NAMES: &'static [&'static str];
+
+    /// Converts the parameters into the struct.  The indices are those of
+    /// the parameters with the names in [`PathParams::NAMES`], in the same
+    /// order; see [`Route::param_indices`].
+    fn from_params<S: AsRef<str>>(indices: &[usize], params: &[S]) -> Result<Self, ParamError>;
+}
+
+impl<M, H> Route<M, H> {
+    /// The indices of the parameters that the given struct is made from,
+    /// or an error if the route doesn't have one of them.  This can be
+    /// worked out once for a route, rather than for every lookup.
+    pub fn param_indices<P: PathParams>(&self) -> Result<Vec<usize>, ParamError> {
+        P::NAMES
+            .iter()
+            .map(|name| {
+                self.param_index(name).ok_or_else(|| ParamError::UnknownName {
+                    name: (*name).to_string(),
+                })
+            })
+            .collect()
+    }
+
+    /// Converts the parameters of a lookup that matched this route into
+    /// the given struct.
+    pub fn path_params<P: PathParams, S: AsRef<str>>(&self, params: &[S]) -> Result<P, ParamError> {
+        P::from_params(&self.param_indices::<P>()?, params)
+    }
+
+    /// Creates a route like [`Route::new`], whose handler is made from the
+    /// indices of the parameters that the given struct is made from (see
+    /// [`Route::param_indices`]).  This panics if the path has no parameter
+    /// for one of the fields of the struct.
+    #[cfg(feature = "hyper")]
+    pub(crate) fn with_path_params<P, T, F>(path: P, method: M, handler: F) -> Route<M, H>
+    where
+        P: Into<Cow<'static, str>>,
+        T: PathParams,
+        F: FnOnce(Vec<usize>) -> H,
+    {
+        Route::from_parts_with(path, Some(vec![method]), |route| {
+            match route.param_indices::<T>() {
+                Ok(indices) => handler(indices),
+                Err(error) => panic!("{}: {}", route.path(), error),
+            }
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// The reason a parameter couldn't be converted by [`ParseParams::parse`],
+/// or into a [`PathParams`] struct.
+/// The hyper `Service` answers a handler that fails with an `Invalid`
+/// parameter with a 404 Not Found, since the path doesn't name anything
+/// that exists.
+pub enum ParamError {
+    /// There's no parameter at the index; the route has fewer parameters.
+    Missing {
+        /// The index of the parameter.
+        index: usize,
+    },
+    /// The value of the parameter isn't valid for the type, e.g. `abc` for
+    /// a `u64`.
+    Invalid {
+        /// The index of the parameter.
+        index: usize,
+        /// The value of the parameter.
+        value: String,
+        /// The error from converting the value, as a string.
+        message: String,
+    },
+    /// The route has no parameter with the name of a field of a
+    /// [`PathParams`] struct.
+    UnknownName {
+        /// The name of the field.
+        name: String,
+    },
+}
+
+impl Display for ParamError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            ParamError::Missing { index } => write!(f, "there's no parameter {}", index),
+            ParamError::Invalid {
+                index,
+                value,
+                message,
+            } => write!(f, "the parameter {} (`{}`) is invalid: {}", index, value, message),
+            ParamError::UnknownName { name } => {
+                write!(f, "the route has no parameter named `{}`", name)
+            }
+        }
+    }
+}
+
+impl Error for ParamError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    #[derive(Debug, PartialEq)]
+    struct Slug(String);
+
+    impl FromParam for Slug {
+        type Error = &'static str;
+
+        fn from_param(param: &str) -> Result<Self, Self::Error> {
+            if param.chars().all(|c| c.is_ascii_lowercase() || c == '-') {
+                Ok(Slug(param.to_string()))
+            } else {
+                Err("not a slug")
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        let params = ["12", "hello-world", "10.0.0.1"]
+            .iter()
+            .map(|param| param.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(params.parse::<u64>(0), Ok(12));
+        assert_eq!(params.parse::<i8>(0), Ok(12));
+        assert_eq!(params.parse::<String>(1), Ok("hello-world".to_string()));
+        assert_eq!(params.parse::<Slug>(1), Ok(Slug("hello-world".to_string())));
+        assert_eq!(params.parse::<Ipv4Addr>(2), Ok(Ipv4Addr::new(10, 0, 0, 1)));
+        assert_eq!(params.parse::<u64>(3), Err(ParamError::Missing { index: 3 }));
+        assert_eq!(
+            params.parse::<u64>(1),
+            Err(ParamError::Invalid {
+                index: 1,
+                value: "hello-world".to_string(),
+                message: "invalid digit found in string".to_string(),
+            })
+        );
+        assert_eq!(
+            ["Hello"].parse::<Slug>(0).unwrap_err().to_string(),
+            "the parameter 0 (`Hello`) is invalid: not a slug"
+        );
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct PostPath {
+        user: Slug,
+        id: u64,
+    }
+
+    impl PathParams for PostPath {
+        const NAMES: &'static [&'static str] = &["user", "id"];
+
+        fn from_params<S: AsRef<str>>(indices: &[usize], params: &[S]) -> Result<Self, ParamError> {
+            Ok(PostPath {
+                user: params.parse(indices[0])?,
+                id: params.parse(indices[1])?,
+            })
+        }
+    }
+
+    #[test]
+    fn test_path_params() {
+        let route = Route::new("/posts/{id:uint}/by/{user}", "GET", ());
+        assert_eq!(route.param_indices::<PostPath>(), Ok(vec![1, 0]));
+        assert_eq!(
+            route.path_params(&["12", "jane-doe"]),
+            Ok(PostPath {
+                user: Slug("jane-doe".to_string()),
+                id: 12
+            })
+        );
+        assert_eq!(
+            route.path_params::<PostPath, _>(&["12", "Jane"]),
+            Err(ParamError::Invalid {
+                index: 1,
+                value: "Jane".to_string(),
+                message: "not a slug".to_string(),
+            })
+        );
+
+        let route = Route::new("/posts/{id:uint}", "GET", ());
+        assert_eq!(
+            route.param_indices::<PostPath>(),
+            Err(ParamError::UnknownName {
+                name: "user".to_string()
+            })
+        );
+    }
+
+    #[cfg(feature = "derive")]
+    #[test]
+    fn test_derive() {
+        use std::net::IpAddr;
+
+        #[derive(Debug, PartialEq, crate::PathParams)]
+        struct HostPath {
+            r#type: String,
+            ip: IpAddr,
+        }
+
+        let route = Route::new("/hosts/{ip:ipv4}/{type}", "GET", ());
+        assert_eq!(HostPath::NAMES, &["type", "ip"]);
+        assert_eq!(
+            route.path_params(&["10.0.0.1", "web"]),
+            Ok(HostPath {
+                r#type: "web".to_string(),
+                ip: "10.0.0.1".parse().unwrap()
+            })
+        );
+    }
+}
@@ -0,0 +1,317 @@
+use super::route::{kind_pattern, parse, Constraint, Pattern, RouteParseError, PLACEHOLDER};
+use std::error::Error;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::str::FromStr;
+
+#[derive(Debug, Clone)]
+/// A route's path, parsed on its own, without a method or a handler.  This
+/// is stricter than [`super::Route::new`]: a brace that doesn't open or
+/// close a parameter (and isn't doubled) is an error here, where a route
+/// would match it as literal text.  This makes it suitable for checking
+/// paths given by users (or fuzzers) before they're made into routes.
+pub struct PathPattern {
+    path: String,
+    pattern: Pattern,
+    params: Vec<Option<String>>,
+    constraints: Vec<Option<Constraint>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// The reason a path couldn't be parsed by [`PathPattern::parse`].  The
+/// offsets are in bytes, into the URL decoded path.
+pub enum PatternError {
+    /// The path doesn't start with a `/`.
+    MissingLeadingSlash {
+        /// The path, URL decoded.
+        path: String,
+    },
+    /// A `{` that's never closed, or a `}` that was never opened.
+    UnmatchedBrace {
+        /// The path, URL decoded.
+        path: String,
+        /// Where the brace is.
+        offset: usize,
+    },
+    /// A parameter that isn't written like one, e.g. `{1d}` or `{id:}`.
+    InvalidParam {
+        /// The path, URL decoded.
+        path: String,
+        /// The parameter, as it's written in the path.
+        param: String,
+        /// Where the parameter starts.
+        offset: usize,
+    },
+    /// A parameter has a kind that doesn't exist, e.g. `{:unit}`, or that
+    /// doesn't take the arguments it was given, e.g. `{:uint(5..1)}`.
+    UnknownKind {
+        /// The path, URL decoded.
+        path: String,
+        /// The parameter, as it's written in the path.
+        param: String,
+        /// Where the parameter starts.
+        offset: usize,
+    },
+    /// The pattern generated for the path couldn't be compiled.
+    Regex {
+        /// The path, URL decoded.
+        path: String,
+        /// The error from compiling the pattern.
+        error: regex::Error,
+    },
+}
+
+impl PatternError {
+    /// The path that couldn't be parsed, URL decoded.
+    pub fn path(&self) -> &str {
+        match self {
+            PatternError::MissingLeadingSlash { path }
+            | PatternError::UnmatchedBrace { path, .. }
+            | PatternError::InvalidParam { path, .. }
+            | PatternError::UnknownKind { path, .. }
+            | PatternError::Regex { path, .. } => path,
+        }
+    }
+
+    /// Where in the path the error is, if it's at a particular place.
+    pub fn offset(&self) -> Option<usize> {
+        match *self {
+            PatternError::UnmatchedBrace { offset, .. }
+            | PatternError::InvalidParam { offset, .. }
+            | PatternError::UnknownKind { offset, .. } => Some(offset),
+            PatternError::MissingLeadingSlash { .. } | PatternError::Regex { .. } => None,
+        }
+    }
+}
+
+impl Display for PatternError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            PatternError::MissingLeadingSlash { path } => {
+                write!(f, "the path `{}` doesn't start with `/`", path)
+            }
+            PatternError::UnmatchedBrace { path, offset } => {
+                write!(f, "the path `{}` has an unmatched brace at {}", path, offset)
+            }
+            PatternError::InvalidParam { path, param, offset } => write!(
+                f,
+                "the path `{}` has an invalid parameter `{}` at {}",
+                path, param, offset
+            ),
+            PatternError::UnknownKind { path, param, offset } => write!(
+                f,
+                "the path `{}` has a parameter `{}` at {} with an unknown kind, or invalid arguments",
+                path, param, offset
+            ),
+            PatternError::Regex { path, error } => {
+                write!(f, "the path `{}` couldn't be compiled: {}", path, error)
+            }
+        }
+    }
+}
+
+impl Error for PatternError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            PatternError::Regex { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl PathPattern {
+    /// Parses the given path, the same way as the path of a route, and
+    /// compiles its pattern.  This never panics, whatever it's given.
+    pub fn parse(path: &str) -> Result<PathPattern, PatternError> {
+        let path = crate::normalize_url(path);
+        if !path.starts_with('/') {
+            return Err(PatternError::MissingLeadingSlash { path });
+        }
+        check(&path)?;
+        let (pattern, params, constraints) = match parse(&path, false) {
+            Ok(parsed) => parsed,
+            Err(RouteParseError::Regex { error, .. }) => return Err(PatternError::Regex { path, error }),
+            Err(RouteParseError::UnknownKind { param, .. }) => {
+                let offset = path.find(&param).unwrap_or(0);
+                return Err(PatternError::UnknownKind { path, param, offset });
+            }
+        };
+        if let Err(error) = pattern.compile() {
+            return Err(PatternError::Regex { path, error });
+        }
+        Ok(PathPattern {
+            path,
+            pattern,
+            params,
+            constraints,
+        })
+    }
+
+    /// The path, URL decoded.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// The source of the regex the path is matched with.
+    pub fn as_str(&self) -> &str {
+        self.pattern.as_str()
+    }
+
+    /// The names of the parameters, in the order they appear in the path;
+    /// `None` for a parameter without a name.
+    pub fn param_names(&self) -> impl Iterator<Item = Option<&str>> + '_ {
+        self.params.iter().map(|name| name.as_deref())
+    }
+
+    /// Whether the given (decoded) path matches the pattern.
+    pub fn is_match(&self, path: &str) -> bool {
+        self.captures(path).is_some()
+    }
+
+    /// The parameters captured from the given (decoded) path, if it
+    /// matches the pattern, and the parameters are within their ranges.
+    pub fn captures<'p>(&self, path: &'p str) -> Option<Vec<&'p str>> {
+        let captures = self.pattern.captures(path)?;
+        let params = captures
+            .iter()
+            .skip(1)
+            .map(|m| m.map_or("", |m| m.as_str()))
+            .collect::<Vec<_>>();
+        let allowed = self
+            .constraints
+            .iter()
+            .zip(&params)
+            .all(|(constraint, param)| constraint.is_none_or(|constraint| constraint.allows(param)));
+        if allowed {
+            Some(params)
+        } else {
+            None
+        }
+    }
+}
+
+impl FromStr for PathPattern {
+    type Err = PatternError;
+
+    fn from_str(path: &str) -> Result<PathPattern, PatternError> {
+        PathPattern::parse(path)
+    }
+}
+
+impl Display for PathPattern {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.write_str(&self.path)
+    }
+}
+
+/// Checks the braces and parameters of the (decoded) path, which the route
+/// parser would otherwise pass over as literal text.
+fn check(path: &str) -> Result<(), PatternError> {
+    let error = |offset: usize, param: Option<&str>| match param {
+        Some(param) => PatternError::InvalidParam {
+            path: path.to_string(),
+            param: param.to_string(),
+            offset,
+        },
+        None => PatternError::UnmatchedBrace {
+            path: path.to_string(),
+            offset,
+        },
+    };
+
+    let mut offset = 0;
+    while let Some(index) = path[offset..].find(['{', '}']) {
+        let start = offset + index;
+        let rest = &path[start..];
+        if rest.starts_with("{{") || rest.starts_with("}}") {
+            offset = start + 2;
+            continue;
+        }
+        if rest.starts_with('}') {
+            return Err(error(start, None));
+        }
+        let end = match rest.find('}') {
+            Some(end) => end + 1,
+            None => return Err(error(start, None)),
+        };
+        let param = &rest[..end];
+        // Routes are split into segments before their parameters are
+        // found, so a parameter can't have a slash in it.
+        let cap = match PLACEHOLDER.captures(param) {
+            Some(cap) if cap.get(0).unwrap().as_str() == param && !param.contains('/') => cap,
+            _ => return Err(error(start, Some(param))),
+        };
+        let kind = cap.name("kind").map(|m| m.as_str()).unwrap_or("string");
+        if kind_pattern(kind, cap.name("args").map(|m| m.as_str())).is_none() {
+            return Err(PatternError::UnknownKind {
+                path: path.to_string(),
+                param: param.to_string(),
+                offset: start,
+            });
+        }
+        offset = start + end;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_path_pattern() {
+        let pattern = PathPattern::parse("/users/{id:uint(1..=100)}/posts.{format}").unwrap();
+        assert_eq!(pattern.path(), "/users/{id:uint(1..=100)}/posts.{format}");
+        assert_eq!(pattern.as_str(), r"^/users/(\d+)/posts\.([^/]+)$");
+        assert_eq!(pattern.param_names().collect::<Vec<_>>(), vec![Some("id"), Some("format")]);
+        assert_eq!(pattern.captures("/users/5/posts.json"), Some(vec!["5", "json"]));
+        assert!(!pattern.is_match("/users/500/posts.json"));
+        assert!(!pattern.is_match("/users/5"));
+
+        let pattern = "/{{literal}}/{}".parse::<PathPattern>().unwrap();
+        assert_eq!(pattern.param_names().collect::<Vec<_>>(), vec![None]);
+        assert!(pattern.is_match("/{literal}/x"));
+        assert_eq!(pattern.to_string(), "/{{literal}}/{}");
+    }
+
+    #[test]
+    fn test_path_pattern_errors() {
+        let path = |path: &str| path.to_string();
+        for (given, expected) in [
+            ("users", PatternError::MissingLeadingSlash { path: path("users") }),
+            ("/users/{id", PatternError::UnmatchedBrace { path: path("/users/{id"), offset: 7 }),
+            ("/users/{}}", PatternError::UnmatchedBrace { path: path("/users/{}}"), offset: 9 }),
+            ("/users/id}", PatternError::UnmatchedBrace { path: path("/users/id}"), offset: 9 }),
+            (
+                "/users/{1d}",
+                PatternError::InvalidParam { path: path("/users/{1d}"), param: path("{1d}"), offset: 7 },
+            ),
+            (
+                "/a/{:one_of(b/c)}",
+                PatternError::InvalidParam {
+                    path: path("/a/{:one_of(b/c)}"),
+                    param: path("{:one_of(b/c)}"),
+                    offset: 3,
+                },
+            ),
+            (
+                "/a/{x}/{id:unit}",
+                PatternError::UnknownKind {
+                    path: path("/a/{x}/{id:unit}"),
+                    param: path("{id:unit}"),
+                    offset: 7,
+                },
+            ),
+        ] {
+            assert_eq!(PathPattern::parse(given).unwrap_err(), expected, "{}", given);
+        }
+
+        let error = PathPattern::parse(&"/{:ipv6}".repeat(64)).unwrap_err();
+        assert!(matches!(error, PatternError::Regex { error: regex::Error::CompiledTooBig(_), .. }));
+        assert_eq!(error.offset(), None);
+
+        // Arbitrary input is rejected, rather than panicking.
+        for given in &["", "/{", "/}", "/é{", "/{é}", "/{{{", "/{:int(", "/%7B"] {
+            assert!(PathPattern::parse(given).is_err(), "{}", given);
+        }
+    }
+}
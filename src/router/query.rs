@@ -0,0 +1,136 @@
+use super::param::FromParam;
+use super::request::{query_pairs, RequestInfo};
+use std::error::Error;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+/// The decoded names and values of a query string, in the order they were
+/// given, e.g. `type=user&page=2`.  A name can be given more than once,
+/// e.g. `tag=a&tag=b`; [`Query::get`] gives the first of its values, and
+/// [`Query::get_all`] gives all of them.
+pub struct Query(Vec<(String, String)>);
+
+impl Query {
+    /// Parses the given query string, without the leading `?`.  A `+` is
+    /// decoded as a space, and a name without a value (e.g. `debug` in
+    /// `debug&page=2`) has an empty value.
+    pub fn new(query: &str) -> Self {
+        Query(
+            query_pairs(query)
+                .map(|(name, value)| (name.into_owned(), value.into_owned()))
+                .collect(),
+        )
+    }
+
+    /// Parses the query string of the given request, which is empty if
+    /// the request doesn't have one.
+    pub fn from_request(request: &dyn RequestInfo) -> Self {
+        request.query().map(Query::new).unwrap_or_default()
+    }
+
+    /// The first value of the given name, if it was given.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Every value of the given name, in the order they were given.
+    pub fn get_all<'q>(&'q self, name: &'q str) -> impl Iterator<Item = &'q str> + 'q {
+        self.0
+            .iter()
+            .filter(move |(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Converts the first value of the given name into the given type, e.g.
+    /// `let page: Option<u32> = query.parse("page")?;`.  This is `None`
+    /// if the name wasn't given.
+    pub fn parse<T: FromParam>(&self, name: &str) -> Result<Option<T>, QueryError> {
+        let value = match self.get(name) {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+        T::from_param(value)
+            .map(Some)
+            .map_err(|error| QueryError {
+                name: name.to_string(),
+                value: value.to_string(),
+                message: error.to_string(),
+            })
+    }
+
+    /// Whether the given name was given, with or without a value.
+    pub fn contains(&self, name: &str) -> bool {
+        self.0.iter().any(|(key, _)| key == name)
+    }
+
+    /// The names and values, in the order they were given.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.0.iter().map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+
+    /// The number of names and values.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Whether the query is empty.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// The reason a value of a query couldn't be converted by [`Query::parse`].
+/// The hyper `Service` answers a handler that fails with this with a 400
+/// Bad Request.
+pub struct QueryError {
+    /// The name the value was given for.
+    pub name: String,
+    /// The value.
+    pub value: String,
+    /// The error from converting the value, as a string.
+    pub message: String,
+}
+
+impl Display for QueryError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(
+            f,
+            "the query value `{}` for `{}` is invalid: {}",
+            self.value, self.name, self.message
+        )
+    }
+}
+
+impl Error for QueryError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_query() {
+        let query = Query::new("tag=a&q=hello+w%C3%B6rld&tag=b&debug&page=2&&");
+        assert_eq!(query.len(), 5);
+        assert_eq!(query.get("q"), Some("hello wörld"));
+        assert_eq!(query.get("tag"), Some("a"));
+        assert_eq!(query.get_all("tag").collect::<Vec<_>>(), vec!["a", "b"]);
+        assert_eq!(query.get("debug"), Some(""));
+        assert!(query.contains("debug"));
+        assert!(!query.contains("missing"));
+        assert_eq!(query.parse::<u32>("page"), Ok(Some(2)));
+        assert_eq!(query.parse::<u32>("missing"), Ok(None));
+        assert_eq!(
+            query.parse::<u32>("q"),
+            Err(QueryError {
+                name: "q".to_string(),
+                value: "hello wörld".to_string(),
+                message: "invalid digit found in string".to_string(),
+            })
+        );
+        assert!(Query::from_request(&()).is_empty());
+    }
+}
@@ -0,0 +1,205 @@
+use std::borrow::Cow;
+use std::fmt::{Debug, Formatter, Result as FmtResult};
+use std::sync::Arc;
+
+/// Information about a request, beyond its method and path, that routes can
+/// be guarded on.  This is given to [`super::Router::lookup_with`]; the hyper
+/// integration implements it for hyper's requests.  Everything is optional,
+/// and a guard that needs information that isn't there doesn't match.  `()`
+/// can be used when there's no information at all, which is what
+/// [`super::Router::lookup`] does.
+pub trait RequestInfo {
+    /// The host the request was made to, e.g. `api.example.com`, possibly
+    /// with a port.
+    fn host(&self) -> Option<&str> {
+        None
+    }
+
+    /// The value of the header with the given name, if the request has it.
+    /// Header names are case-insensitive.  If the header was given more than
+    /// once, this is the first of them.
+    fn header(&self, name: &str) -> Option<&str> {
+        let _ = name;
+        None
+    }
+
+    /// The query string of the request, without the leading `?`, e.g.
+    /// `type=user&page=2`.  This should be left encoded.
+    fn query(&self) -> Option<&str> {
+        None
+    }
+
+    /// The scheme the request was made with, e.g. `https`, if it's known.
+    fn scheme(&self) -> Option<&str> {
+        None
+    }
+
+    /// The HTTP version of the request, if it's known.
+    fn version(&self) -> Option<HttpVersion> {
+        None
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+/// The version of HTTP a request was made with.
+pub enum HttpVersion {
+    /// HTTP/0.9.
+    Http09,
+    /// HTTP/1.0.
+    Http10,
+    /// HTTP/1.1.
+    Http11,
+    /// HTTP/2.
+    Http2,
+    /// HTTP/3.
+    Http3,
+}
+
+impl RequestInfo for () {}
+
+type GuardFn = dyn Fn(&dyn RequestInfo) -> bool + Send + Sync + 'static;
+
+#[derive(Clone)]
+/// A condition on a request that has to hold for a route to match it.  If
+/// it doesn't, the lookup moves on to the next route, as if the path hadn't
+/// matched.
+pub struct Guard(Arc<GuardFn>);
+
+impl Guard {
+    pub(super) fn new<F>(guard: F) -> Self
+    where
+        F: Fn(&dyn RequestInfo) -> bool + Send + Sync + 'static,
+    {
+        Guard(Arc::new(guard))
+    }
+
+    pub(super) fn allows(&self, request: &dyn RequestInfo) -> bool {
+        (self.0)(request)
+    }
+}
+
+impl Debug for Guard {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.write_str("Guard")
+    }
+}
+
+/// Strips the port from a host, if it has one.  IPv6 hosts are given in
+/// brackets, e.g. `[::1]:8080`, so their colons are left alone.
+pub(super) fn strip_port(host: &str) -> &str {
+    if host.starts_with('[') {
+        host.find(']').map(|end| &host[..=end]).unwrap_or(host)
+    } else {
+        host.split(':').next().unwrap_or(host)
+    }
+}
+
+/// Splits a query string into its decoded names and values.  A `+` is
+/// decoded as a space, and a name without a value (e.g. `debug` in
+/// `debug&page=2`) has an empty value.
+pub(super) fn query_pairs(query: &str) -> impl Iterator<Item = (Cow<'_, str>, Cow<'_, str>)> {
+    fn decode(part: &str) -> Cow<'_, str> {
+        if part.contains('+') {
+            let part = part.replace('+', " ");
+            Cow::Owned(decode(&part).into_owned())
+        } else {
+            percent_encoding::percent_decode(part.as_bytes()).decode_utf8_lossy()
+        }
+    }
+
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let mut split = pair.splitn(2, '=');
+            let name = split.next().unwrap_or("");
+            let value = split.next().unwrap_or("");
+            (decode(name), decode(value))
+        })
+}
+
+/// How much the given `Accept` header prefers the given media type, from
+/// `0.0` (not at all) to `1.0`.  The most specific range in the header that
+/// matches the type decides its quality, so that `text/html` in
+/// `text/*;q=0.5, text/html` has a quality of `1.0`.  Without an `Accept`
+/// header, every type is accepted.  Both the header and the type should
+/// already be lowercase.
+pub(super) fn accept_quality(accept: Option<&str>, media_type: &str) -> f32 {
+    let accept = match accept {
+        Some(accept) => accept,
+        None => return 1.0,
+    };
+    let (kind, _) = media_type.split_once('/').unwrap_or((media_type, ""));
+
+    accept
+        .split(',')
+        .filter_map(|range| {
+            let mut parts = range.split(';').map(str::trim);
+            let range = parts.next()?;
+            let mut quality = 1.0;
+            for param in parts {
+                if let Some(value) = param.strip_prefix("q=") {
+                    quality = value.parse::<f32>().ok().filter(|q| (0.0..=1.0).contains(q))?;
+                }
+            }
+            // How specific the range is; the most specific one wins.
+            let specificity = if range == media_type {
+                2
+            } else if range.strip_suffix("/*") == Some(kind) {
+                1
+            } else if range == "*/*" {
+                0
+            } else {
+                return None;
+            };
+            Some((specificity, quality))
+        })
+        .fold(None, |best: Option<(u8, f32)>, (specificity, quality)| match best {
+            Some(best) if best.0 >= specificity => Some(best),
+            _ => Some((specificity, quality)),
+        })
+        .map_or(0.0, |(_, quality)| quality)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_port() {
+        assert_eq!(strip_port("example.com"), "example.com");
+        assert_eq!(strip_port("example.com:8080"), "example.com");
+        assert_eq!(strip_port("[::1]:8080"), "[::1]");
+        assert_eq!(strip_port("[::1]"), "[::1]");
+    }
+
+    #[test]
+    fn test_accept_quality() {
+        assert_eq!(accept_quality(None, "text/html"), 1.0);
+        assert_eq!(accept_quality(Some("text/html"), "text/html"), 1.0);
+        assert_eq!(accept_quality(Some("text/html"), "application/json"), 0.0);
+        let accept = Some("text/*;q=0.5, text/html, application/json;q=0.8, */*;q=0.1");
+        assert_eq!(accept_quality(accept, "text/html"), 1.0);
+        assert_eq!(accept_quality(accept, "text/plain"), 0.5);
+        assert_eq!(accept_quality(accept, "application/json"), 0.8);
+        assert_eq!(accept_quality(accept, "image/png"), 0.1);
+        assert_eq!(accept_quality(Some("application/json;q=0"), "application/json"), 0.0);
+        assert_eq!(accept_quality(Some("application/json;q=2"), "application/json"), 0.0);
+    }
+
+    #[test]
+    fn test_query_pairs() {
+        let pairs = query_pairs("type=user&q=a+b%26c&debug&&empty=")
+            .map(|(name, value)| (name.into_owned(), value.into_owned()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            pairs,
+            vec![
+                ("type".to_string(), "user".to_string()),
+                ("q".to_string(), "a b&c".to_string()),
+                ("debug".to_string(), String::new()),
+                ("empty".to_string(), String::new()),
+            ]
+        );
+    }
+}
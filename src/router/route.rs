@@ -1,10 +1,27 @@
 use lazy_static::lazy_static;
 use phf::{phf_map, Map};
+use super::extensions::Extensions;
+use super::request::{accept_quality, query_pairs, strip_port, Guard, HttpVersion, RequestInfo};
 use regex::Regex;
+use std::any::Any;
 use std::borrow::Cow;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::{Debug, Display, Formatter, Result as FmtResult};
+use std::future::Future;
+use std::ops::Deref;
+use std::pin::Pin;
+use std::sync::{Arc, OnceLock};
+
+/// A single parameter in a path, e.g. `{id:uint}` or `{:b64(22)}`.  A
+/// segment of a path can contain any number of these, mixed with literal
+/// text.  A doubled brace (`{{` or `}}`) is matched as an escape instead,
+/// and stands for a literal brace.
+const PLACEHOLDER_PATTERN: &str =
+    r"(?P<escape>\{\{|\}\})|\{(?P<name>[a-zA-Z_]\w*)?(?::(?P<kind>[a-zA-Z]\w*)(?:\((?P<args>[^)]*)\))?)?\}";
 
 lazy_static! {
-    static ref SEGMENT_MATCH: Regex = Regex::new(r"^\{(?::(?P<kind>[a-zA-Z]\w*))?\}$").unwrap();
+    pub(super) static ref PLACEHOLDER: Regex = Regex::new(PLACEHOLDER_PATTERN).unwrap();
 }
 
 static MATCH_KINDS: Map<&'static str, &'static str> = phf_map! {
@@ -12,6 +29,11 @@ static MATCH_KINDS: Map<&'static str, &'static str> = phf_map! {
     "int" => r"([-+]?\d+)",
     "uint" => r"(\d+)",
     "uuid" => r"([a-fA-F0-9]{8}-[a-fA-F0-9]{4}-[a-fA-F0-9]{4}-[a-fA-F0-9]{4}-[a-fA-F0-9]{12})",
+    "semver" => r"((?:0|[1-9]\d*)\.(?:0|[1-9]\d*)\.(?:0|[1-9]\d*)(?:-(?:0|[1-9]\d*|\d*[a-zA-Z-][0-9a-zA-Z-]*)(?:\.(?:0|[1-9]\d*|\d*[a-zA-Z-][0-9a-zA-Z-]*))*)?(?:\+[0-9a-zA-Z-]+(?:\.[0-9a-zA-Z-]+)*)?)",
+    "ipv4" => r"((?:25[0-5]|2[0-4]\d|1\d\d|[1-9]?\d)(?:\.(?:25[0-5]|2[0-4]\d|1\d\d|[1-9]?\d)){3})",
+    "ipv6" => r"((?:(?:[0-9a-fA-F]{1,4}:){7}[0-9a-fA-F]{1,4}|(?:[0-9a-fA-F]{1,4}:){6}(?:25[0-5]|2[0-4]\d|1\d\d|[1-9]?\d)(?:\.(?:25[0-5]|2[0-4]\d|1\d\d|[1-9]?\d)){3}|(?:[0-9a-fA-F]{1,4}:){1,7}:|(?:[0-9a-fA-F]{1,4}:){1,6}:[0-9a-fA-F]{1,4}|(?:[0-9a-fA-F]{1,4}:){1,5}(?::[0-9a-fA-F]{1,4}){1,2}|(?:[0-9a-fA-F]{1,4}:){1,4}(?::[0-9a-fA-F]{1,4}){1,3}|(?:[0-9a-fA-F]{1,4}:){1,3}(?::[0-9a-fA-F]{1,4}){1,4}|(?:[0-9a-fA-F]{1,4}:){1,2}(?::[0-9a-fA-F]{1,4}){1,5}|[0-9a-fA-F]{1,4}:(?::[0-9a-fA-F]{1,4}){1,6}|(?:[0-9a-fA-F]{1,4}:){1,4}:(?:25[0-5]|2[0-4]\d|1\d\d|[1-9]?\d)(?:\.(?:25[0-5]|2[0-4]\d|1\d\d|[1-9]?\d)){3}|::(?:[fF]{4}(?::0{1,4})?:)?(?:25[0-5]|2[0-4]\d|1\d\d|[1-9]?\d)(?:\.(?:25[0-5]|2[0-4]\d|1\d\d|[1-9]?\d)){3}|:(?:(?::[0-9a-fA-F]{1,4}){1,7}|:)))",
+    "email" => r"([a-zA-Z0-9.!#$%&'*+=?^_`|~-]+@[a-zA-Z0-9](?:[a-zA-Z0-9-]{0,61}[a-zA-Z0-9])?(?:\.[a-zA-Z0-9](?:[a-zA-Z0-9-]{0,61}[a-zA-Z0-9])?)+)",
+    "grpc_method" => r"([A-Za-z_][A-Za-z0-9_]*)",
 };
 
 #[derive(Debug, Clone)]
@@ -20,9 +42,41 @@ static MATCH_KINDS: Map<&'static str, &'static str> = phf_map! {
 /// it.
 pub struct Route<M, H> {
     pub(super) path: Cow<'static, str>,
-    pub(super) method: M,
+    pub(super) methods: Option<Vec<M>>,
     pub(super) handler: H,
-    pub(super) pattern: Regex,
+    pub(super) pattern: Pattern,
+    pub(super) params: Vec<Option<String>>,
+    pub(super) constraints: Vec<Option<Constraint>>,
+    pub(super) warm_up: Option<WarmUp>,
+    pub(super) priority: i32,
+    pub(super) guards: Vec<Guard>,
+    pub(super) consumes: Vec<String>,
+    pub(super) produces: Vec<String>,
+    pub(super) catch_all: bool,
+    pub(super) name: Option<String>,
+    pub(super) extensions: Extensions,
+}
+
+/// The future returned by a warm-up callback.
+pub type WarmUpFuture = Pin<Box<dyn Future<Output = Result<(), failure::Error>> + Send + 'static>>;
+
+#[derive(Clone)]
+/// A warm-up callback attached to a route.  This is called by
+/// [`super::Router::warm_up`], and is meant for work that should be done once
+/// before the route starts serving requests - priming caches, opening pools,
+/// compiling templates, and so on.
+pub struct WarmUp(Arc<dyn Fn() -> WarmUpFuture + Send + Sync + 'static>);
+
+impl WarmUp {
+    pub(super) fn call(&self) -> WarmUpFuture {
+        (self.0)()
+    }
+}
+
+impl Debug for WarmUp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.write_str("WarmUp")
+    }
 }
 
 impl<M, H> Route<M, H> {
@@ -32,47 +86,828 @@ impl<M, H> Route<M, H> {
     where
         P: Into<Cow<'static, str>>,
     {
-        let path = path.into();
-        let compile = parse(path.as_ref());
+        Route::from_parts(path, Some(vec![method]), handler)
+    }
+
+    /// Creates a new route that matches any method, with the given path and
+    /// handler.  This is useful for catch-all proxies, or endpoints that
+    /// handle the method themselves.
+    pub fn new_any<P>(path: P, handler: H) -> Route<M, H>
+    where
+        P: Into<Cow<'static, str>>,
+    {
+        Route::from_parts(path, None, handler)
+    }
+
+    /// Creates a new route that matches any of the given methods, with the
+    /// given path and handler.  This lets a single handler be used for
+    /// several methods, even if it can't be cloned.
+    pub fn for_methods<P, I>(path: P, methods: I, handler: H) -> Route<M, H>
+    where
+        P: Into<Cow<'static, str>>,
+        I: IntoIterator<Item = M>,
+    {
+        Route::from_parts(path, Some(methods.into_iter().collect()), handler)
+    }
+
+    /// Creates a new route with the given information, like [`Route::new`],
+    /// but returns an error instead of panicking if the path can't be
+    /// parsed.  This is meant for routes that come from configuration, or
+    /// other input that can't be trusted to be valid.
+    pub fn try_new<P>(path: P, method: M, handler: H) -> Result<Route<M, H>, RouteParseError>
+    where
+        P: Into<Cow<'static, str>>,
+    {
+        Route::try_from_parts(path, Some(vec![method]), handler)
+    }
+
+    fn from_parts<P>(path: P, methods: Option<Vec<M>>, handler: H) -> Route<M, H>
+    where
+        P: Into<Cow<'static, str>>,
+    {
+        Route::from_parts_with(path, methods, |_| handler)
+    }
+
+    /// Creates the route like [`Route::from_parts`], but with a handler
+    /// that's made from the route once its path is parsed, e.g. one that
+    /// needs to know about the route's parameters.
+    pub(super) fn from_parts_with<P, F>(path: P, methods: Option<Vec<M>>, handler: F) -> Route<M, H>
+    where
+        P: Into<Cow<'static, str>>,
+        F: FnOnce(&Route<M, ()>) -> H,
+    {
+        let route = match Route::parse_parts(path, methods, ()) {
+            Ok(route) => route,
+            Err(error) => panic!("{}", error),
+        };
+        let handler = handler(&route);
         Route {
+            path: route.path,
+            methods: route.methods,
+            handler,
+            pattern: route.pattern,
+            params: route.params,
+            constraints: route.constraints,
+            warm_up: route.warm_up,
+            priority: route.priority,
+            guards: route.guards,
+            consumes: route.consumes,
+            produces: route.produces,
+            catch_all: route.catch_all,
+            name: route.name,
+            extensions: route.extensions,
+        }
+    }
+
+    /// Parses the route like [`Route::from_parts`], but also compiles its
+    /// pattern right away, so that a pattern that's over the regex size
+    /// limit is reported here, instead of when the router is built.
+    pub(super) fn try_from_parts<P>(
+        path: P,
+        methods: Option<Vec<M>>,
+        handler: H,
+    ) -> Result<Route<M, H>, RouteParseError>
+    where
+        P: Into<Cow<'static, str>>,
+    {
+        let route = Route::parse_parts(path, methods, handler)?;
+        if let Err(error) = route.pattern.compile() {
+            let path = crate::normalize_url(route.path.as_ref()).to_string();
+            return Err(RouteParseError::Regex { path, error });
+        }
+        Ok(route)
+    }
+
+    fn parse_parts<P>(
+        path: P,
+        methods: Option<Vec<M>>,
+        handler: H,
+    ) -> Result<Route<M, H>, RouteParseError>
+    where
+        P: Into<Cow<'static, str>>,
+    {
+        let path = path.into();
+        let (pattern, params, constraints) = parse(&crate::normalize_url(path.as_ref()), false)?;
+        Ok(Route {
             path,
-            method,
+            methods,
             handler,
-            pattern: compile,
+            pattern,
+            params,
+            constraints,
+            warm_up: None,
+            priority: 0,
+            guards: vec![],
+            consumes: vec![],
+            produces: vec![],
+            catch_all: false,
+            name: None,
+            extensions: Extensions::default(),
+        })
+    }
+
+    /// Creates a route that matches the given prefix, and every path under
+    /// it, with any method.  It has the lowest possible priority, so that
+    /// every other route is tried first.  This is used for the defaults of
+    /// mounted routers; see [`super::Build::mount`].
+    pub(super) fn catch_all(prefix: &str, handler: H) -> Route<M, H> {
+        let mut route = Route::new_any(prefix.to_string(), handler);
+        route.catch_all = true;
+        route.reparse();
+        route.with_priority(i32::MIN)
+    }
+
+    /// Moves the route under the given prefix, e.g. `/users/{id}` under
+    /// `/api/v1` becomes `/api/v1/users/{id}`.  A route for `/` becomes the
+    /// prefix itself.  The parameters of the prefix come before the route's
+    /// own.  The prefix should already have been checked with
+    /// [`check_prefix`].
+    pub(super) fn with_prefix(mut self, prefix: &str) -> Self {
+        let prefix = prefix.trim_end_matches('/');
+        self.path = match self.path.as_ref() {
+            "/" if !prefix.is_empty() => Cow::Owned(prefix.to_string()),
+            path => Cow::Owned(format!("{}{}", prefix, path)),
+        };
+        self.reparse();
+        self
+    }
+
+    fn reparse(&mut self) {
+        let (pattern, params, constraints) =
+            match parse(&crate::normalize_url(self.path.as_ref()), self.catch_all) {
+                Ok(parsed) => parsed,
+                Err(error) => panic!("{}", error),
+            };
+        self.pattern = pattern;
+        self.params = params;
+        self.constraints = constraints;
+    }
+
+    /// The path the route was created with.
+    pub fn path(&self) -> &str {
+        self.path.as_ref()
+    }
+
+    /// The name of the route, if it was given one.  See [`Route::with_name`].
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Names the route, so that its url can be built with
+    /// [`super::Router::url_for`] (or [`super::Router::url`]).  If more than
+    /// one route has the same name, the first of them (in the order they're
+    /// evaluated) is used.
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = Some(name.to_string());
+        self
+    }
+
+    /// The metadata attached to the route.  See [`Route::with_extension`].
+    pub fn extensions(&self) -> &Extensions {
+        &self.extensions
+    }
+
+    /// The metadata of the given type attached to the route, if there is
+    /// any.  See [`Route::with_extension`].
+    pub fn extension<T: Any + Send + Sync>(&self) -> Option<&T> {
+        self.extensions.get()
+    }
+
+    /// Attaches the given value to the route as metadata, e.g. the scopes
+    /// the route requires, or its rate-limit class, so that middleware (or
+    /// a documentation generator) can read it back from the route that a
+    /// lookup matched.  The route holds one value of each type; a second
+    /// value of the same type replaces the first.  The router itself
+    /// ignores these.
+    pub fn with_extension<T: Any + Send + Sync>(mut self, value: T) -> Self {
+        self.extensions.insert(value);
+        self
+    }
+
+    /// The method the route matches, or `None` if it matches any method.  If
+    /// the route matches more than one method, this is the first of them;
+    /// see [`Route::methods`].
+    pub fn method(&self) -> Option<&M> {
+        self.methods.as_ref().and_then(|methods| methods.first())
+    }
+
+    /// The methods the route matches, or `None` if it matches any method.
+    pub fn methods(&self) -> Option<&[M]> {
+        self.methods.as_deref()
+    }
+
+    /// The handler of the route.
+    pub fn handler(&self) -> &H {
+        &self.handler
+    }
+
+    /// Replaces the handler of the route, e.g. with one that needs to know
+    /// about the route's parameters (see [`Route::param_indices`]).
+    pub fn with_handler(mut self, handler: H) -> Self {
+        self.handler = handler;
+        self
+    }
+
+    /// The names of the parameters in the route's path, in the order they're
+    /// given to the handler.  Parameters without a name (e.g. `{:uint}`) are
+    /// `None`.
+    pub fn param_names(&self) -> impl Iterator<Item = Option<&str>> + '_ {
+        self.params.iter().map(|name| name.as_deref())
+    }
+
+    /// The index of the parameter with the given name, in the list of
+    /// parameters given to the handler.
+    pub fn param_index(&self, name: &str) -> Option<usize> {
+        self.params
+            .iter()
+            .position(|param| param.as_deref() == Some(name))
+    }
+
+    /// The priority of the route.  See [`Route::with_priority`].
+    pub fn priority(&self) -> i32 {
+        self.priority
+    }
+
+    /// Sets the priority of the route, which is `0` by default.  When more
+    /// than one route matches a path, the one with the highest priority is
+    /// used; routes with the same priority fall back to the usual order.
+    /// This allows overrides to be added without having to carefully order
+    /// the whole table.
+    pub fn with_priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Adds a guard to the route, which has to allow a request for the route
+    /// to match it.  If it doesn't, the lookup moves on to the next route.
+    /// This can be given more than once, in which case every guard has to
+    /// allow the request; the other guards (like [`Route::with_host`]) are
+    /// built on this.
+    ///
+    /// Guards are only called once the method and path of the route have
+    /// matched, so they can be fairly expensive.  They're only checked by
+    /// [`super::Router::lookup_with`] (and the hyper `Service`), since they
+    /// need information about the request.
+    pub fn with_guard<F>(mut self, guard: F) -> Self
+    where
+        F: Fn(&dyn RequestInfo) -> bool + Send + Sync + 'static,
+    {
+        self.guards.push(Guard::new(guard));
+        self
+    }
+
+    /// Only matches requests made to the given host, e.g.
+    /// `api.example.com`.  The port of the request's host is ignored, and
+    /// hosts are compared case-insensitively.
+    pub fn with_host(self, host: &str) -> Self {
+        let host = host.to_ascii_lowercase();
+        self.with_guard(move |request| {
+            request
+                .host()
+                .is_some_and(|given| strip_port(given).eq_ignore_ascii_case(&host))
+        })
+    }
+
+    /// Only matches requests made with the given scheme, e.g. `https`.  The
+    /// `X-Forwarded-Proto` header is honored, since the scheme usually isn't
+    /// known behind a proxy; otherwise, the request's own scheme is used,
+    /// and if that isn't known either, the request is assumed to be `http`.
+    /// Since clients can set the header themselves, this shouldn't be used
+    /// as a security check unless a proxy in front of the router sets it.
+    pub fn with_scheme(self, scheme: &str) -> Self {
+        let scheme = scheme.to_ascii_lowercase();
+        self.with_guard(move |request| {
+            let given = request
+                .header("x-forwarded-proto")
+                .and_then(|proto| proto.split(',').next())
+                .map(str::trim)
+                .or_else(|| request.scheme())
+                .unwrap_or("http");
+            given.eq_ignore_ascii_case(&scheme)
+        })
+    }
+
+    /// Only matches requests made with the given version of HTTP, e.g. for
+    /// an endpoint that only streams over HTTP/2.  Requests whose version
+    /// isn't known don't match.
+    pub fn with_version(self, version: HttpVersion) -> Self {
+        self.with_guard(move |request| request.version() == Some(version))
+    }
+
+    /// Only matches requests with the given header, whose value satisfies
+    /// the given predicate.  Requests without the header don't match.
+    pub fn when_header<F>(self, name: &'static str, predicate: F) -> Self
+    where
+        F: Fn(&str) -> bool + Send + Sync + 'static,
+    {
+        self.with_guard(move |request| request.header(name).is_some_and(&predicate))
+    }
+
+    /// Only matches requests whose query has every one of the given name and
+    /// value pairs, e.g. `&[("type", "user")]` matches `?type=user&page=2`.
+    /// The query is decoded before it's compared.
+    pub fn when_query(self, pairs: &[(&str, &str)]) -> Self {
+        let pairs = pairs
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect::<Vec<_>>();
+        self.with_guard(move |request| {
+            request.query().is_some_and(|query| {
+                pairs.iter().all(|(name, value)| {
+                    query_pairs(query).any(|pair| pair.0 == *name && pair.1 == *value)
+                })
+            })
+        })
+    }
+
+    /// Only matches requests with the given content type, e.g.
+    /// `application/json`; this can be given more than once to accept any
+    /// of several types.  A wildcard can be used for the subtype, or the
+    /// whole type (`text/*` or `*/*`).  Parameters of the request's content
+    /// type (like `charset`) are ignored, and requests without a content
+    /// type don't match.
+    pub fn consumes(mut self, media_type: &str) -> Self {
+        self.consumes.push(media_type.trim().to_ascii_lowercase());
+        self
+    }
+
+    /// Whether the route accepts the content type of the given request.
+    pub(super) fn consumes_allow(&self, request: &dyn RequestInfo) -> bool {
+        if self.consumes.is_empty() {
+            return true;
+        }
+        let given = match request.header("content-type") {
+            Some(given) => given.split(';').next().unwrap_or("").trim().to_ascii_lowercase(),
+            None => return false,
+        };
+        let (kind, _) = given.split_once('/').unwrap_or((&given, ""));
+        self.consumes.iter().any(|media_type| match media_type.as_str() {
+            "*/*" => true,
+            media_type => {
+                *media_type == given
+                    || media_type
+                        .strip_suffix("/*")
+                        .is_some_and(|expected| expected == kind)
+            }
+        })
+    }
+
+    /// Declares that the route responds with the given content type, e.g.
+    /// `application/json`; this can be given more than once.  Such a route
+    /// only matches requests whose `Accept` header accepts one of its types,
+    /// and when several of these routes match a request, the one whose type
+    /// the request prefers (by its q-values) is used.  See
+    /// [`super::Router::lookup_route_with`].
+    pub fn produces(mut self, media_type: &str) -> Self {
+        self.produces.push(media_type.trim().to_ascii_lowercase());
+        self
+    }
+
+    /// How much the given request prefers the content the route produces,
+    /// from `0.0` to `1.0`.  Routes that don't declare what they produce
+    /// are always given `1.0`.
+    pub(super) fn produces_quality(&self, request: &dyn RequestInfo) -> f32 {
+        if self.produces.is_empty() {
+            return 1.0;
         }
+        let accept = request.header("accept").map(str::to_ascii_lowercase);
+        self.produces
+            .iter()
+            .map(|media_type| accept_quality(accept.as_deref(), media_type))
+            .fold(0.0, f32::max)
     }
+
+    /// Whether every guard of the route allows the given request.
+    pub(super) fn guards_allow(&self, request: &dyn RequestInfo) -> bool {
+        self.guards.iter().all(|guard| guard.allows(request))
+    }
+
+    /// Attaches a warm-up callback to the route.  The callback is invoked
+    /// when [`super::Router::warm_up`] is awaited; if it fails, the error is
+    /// reported by the router along with the path of this route.
+    pub fn with_warm_up<F, R>(mut self, warm_up: F) -> Self
+    where
+        F: Fn() -> R + Send + Sync + 'static,
+        R: Future<Output = Result<(), failure::Error>> + Send + 'static,
+    {
+        self.warm_up = Some(WarmUp(Arc::new(move || Box::pin(warm_up()))));
+        self
+    }
+}
+
+impl<M: Eq, H> Route<M, H> {
+    /// Whether the route matches the given method.
+    pub(super) fn matches_method(&self, method: &M) -> bool {
+        self.methods
+            .as_ref()
+            .is_none_or(|methods| methods.contains(method))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// The reason a path couldn't be parsed by [`Route::try_new`].
+pub enum RouteParseError {
+    /// The pattern generated for the path couldn't be compiled, e.g.
+    /// because it has so many parameters that it's over the regex size
+    /// limit.
+    Regex {
+        /// The path, URL decoded.
+        path: String,
+        /// The error from compiling the pattern.
+        error: regex::Error,
+    },
+    /// A parameter has a kind that doesn't exist, e.g. `{:unit}`, or that
+    /// doesn't take the arguments it was given, e.g. `{:uint(5..1)}`.
+    UnknownKind {
+        /// The path, URL decoded.
+        path: String,
+        /// The parameter, as it's written in the path.
+        param: String,
+    },
 }
 
-fn parse(path: &str) -> Regex {
-    let normalized = crate::normalize_url(path);
-    let split = normalized.split("/").skip(1);
-    let mut pattern = split
-        .map(|part| {
-            if let Some(cap) = SEGMENT_MATCH.captures(part) {
-                let name = cap.name("kind").map(|m| m.as_str()).unwrap_or("string");
-                Cow::Borrowed(MATCH_KINDS.get(name).map(|v| *v).unwrap_or(r"([^/]*)"))
-            } else {
-                Cow::Owned(regex::escape(part))
+impl Display for RouteParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            RouteParseError::Regex { path, error } => {
+                write!(f, "the route `{}` couldn't be compiled: {}", path, error)
             }
-        })
-        .fold(String::from("^"), |mut acc, el| {
-            acc.push('/');
-            acc.push_str(el.as_ref());
-            acc
-        });
+            RouteParseError::UnknownKind { path, param } => write!(
+                f,
+                "the route `{}` has a parameter `{}` with an unknown kind, or invalid arguments",
+                path, param
+            ),
+        }
+    }
+}
+
+impl Error for RouteParseError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            RouteParseError::Regex { error, .. } => Some(error),
+            RouteParseError::UnknownKind { .. } => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// A check on the value of a parameter that can't be expressed in its
+/// pattern, and so is done after the pattern matches.
+pub(super) enum Constraint {
+    /// The parameter must be a number within the given inclusive bounds.
+    Range(Option<i128>, Option<i128>),
+}
+
+impl Constraint {
+    pub(super) fn allows(&self, value: &str) -> bool {
+        match *self {
+            Constraint::Range(min, max) => value.parse::<i128>().ok().is_some_and(|value| {
+                min.is_none_or(|min| value >= min) && max.is_none_or(|max| value <= max)
+            }),
+        }
+    }
+}
+
+/// How specific a single segment of a route is.  More specific segments
+/// sort first, so a literal segment beats a typed parameter, which beats a
+/// string parameter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(super) enum Specificity {
+    /// The segment is entirely literal text.
+    Static,
+    /// The segment only has parameters with a kind narrower than `string`.
+    Typed,
+    /// The segment has at least one `string` parameter.
+    String,
+}
+
+impl<M, H> Route<M, H> {
+    /// The specificity of each segment of the route, in order.  Comparing
+    /// these segment by segment tells which of two routes is more specific.
+    pub(super) fn specificity(&self) -> Vec<Specificity> {
+        crate::normalize_url(self.path.as_ref())
+            .split('/')
+            .skip(1)
+            .map(|part| {
+                PLACEHOLDER
+                    .captures_iter(part)
+                    .filter(|cap| cap.name("escape").is_none())
+                    .map(|cap| match cap.name("kind").map(|m| m.as_str()) {
+                        None | Some("string") => Specificity::String,
+                        Some(_) => Specificity::Typed,
+                    })
+                    .max()
+                    .unwrap_or(Specificity::Static)
+            })
+            .collect()
+    }
+
+    /// Re-parses the route's path in Unicode Normalization Form C, so that
+    /// it matches paths that were normalized the same way.  The parameters
+    /// don't change, so only the pattern is replaced.
+    #[cfg(feature = "unicode-normalization")]
+    pub(super) fn normalize_nfc(&mut self) {
+        use unicode_normalization::{is_nfc, UnicodeNormalization};
+
+        let path = crate::normalize_url(self.path.as_ref());
+        if !is_nfc(&path) {
+            self.pattern = parse(&path.nfc().collect::<String>(), self.catch_all).unwrap().0;
+        }
+    }
 
+    /// Checks the parameters captured by the route's pattern against the
+    /// constraints of the route.
+    pub(super) fn allows(&self, params: &[&str]) -> bool {
+        self.constraints
+            .iter()
+            .zip(params)
+            .all(|(constraint, param)| match constraint {
+                Some(constraint) => constraint.allows(param),
+                None => true,
+            })
+    }
+}
+
+/// The pattern of a parsed path, along with the names of its parameters,
+/// and their constraints.
+pub(super) type Parsed = (Pattern, Vec<Option<String>>, Vec<Option<Constraint>>);
+
+#[derive(Debug, Clone)]
+/// The regex a route's path is matched with.  Compiling it is most of the
+/// cost of creating a route, so it's only done when it's first needed: by
+/// [`super::Build::finish`], unless the router is built with
+/// [`super::Build::lazy_patterns`], or by the first lookup that matches the
+/// route otherwise.  It dereferences to the compiled [`Regex`].
+///
+/// Clones share the regex, so it's only compiled once between them; routes
+/// with the same pattern (e.g. `GET` and `PUT` routes for the same path)
+/// are made to share one by [`intern`].
+pub(super) struct Pattern(Arc<PatternInner>);
+
+#[derive(Debug)]
+struct PatternInner {
+    source: String,
+    regex: OnceLock<Regex>,
+}
+
+impl Pattern {
+    fn new(source: String) -> Self {
+        Pattern(Arc::new(PatternInner {
+            source,
+            regex: OnceLock::new(),
+        }))
+    }
+
+    /// The source of the regex, which doesn't compile it.
+    pub(super) fn as_str(&self) -> &str {
+        &self.0.source
+    }
+
+    /// Compiles the regex, if it hasn't been already.
+    pub(super) fn compile(&self) -> Result<&Regex, regex::Error> {
+        if let Some(regex) = self.0.regex.get() {
+            return Ok(regex);
+        }
+        let regex = Regex::new(&self.0.source)?;
+        Ok(self.0.regex.get_or_init(|| regex))
+    }
+
+    #[cfg(test)]
+    pub(super) fn is_compiled(&self) -> bool {
+        self.0.regex.get().is_some()
+    }
+}
+
+/// Makes every route with the same pattern as an earlier one share the
+/// earlier one's, so that each distinct pattern is only compiled, and kept
+/// in memory, once.
+pub(super) fn intern<M, H>(routes: &mut [Route<M, H>]) {
+    let mut seen = HashMap::<String, Pattern>::new();
+    for route in routes {
+        match seen.get(route.pattern.as_str()) {
+            Some(pattern) => route.pattern = pattern.clone(),
+            None => {
+                seen.insert(route.pattern.as_str().to_string(), route.pattern.clone());
+            }
+        }
+    }
+}
+
+impl Deref for Pattern {
+    type Target = Regex;
+
+    /// The compiled regex.  This panics if it can't be compiled, which is
+    /// only possible if the router was built with
+    /// [`super::Build::lazy_patterns`], since the pattern is checked when
+    /// it's built otherwise.
+    fn deref(&self) -> &Regex {
+        match self.compile() {
+            Ok(regex) => regex,
+            Err(error) => panic!("the pattern `{}` couldn't be compiled: {}", self.as_str(), error),
+        }
+    }
+}
+
+/// Checks that the given prefix, for [`Route::with_prefix`], can be parsed,
+/// so that a prefix with e.g. an unknown kind is reported once, with the
+/// prefix, instead of by the first route moved under it.
+///
+/// # Panics
+///
+/// This panics with the [`RouteParseError`] if the prefix can't be parsed.
+pub(super) fn check_prefix(prefix: &str) {
+    if let Err(error) = parse(&crate::normalize_url(prefix), false) {
+        panic!("{}", error);
+    }
+}
+
+/// Parses the given path, which must already be URL decoded.  If `catch_all`
+/// is set, the pattern also matches every path under the given one.
+pub(super) fn parse(path: &str, catch_all: bool) -> Result<Parsed, RouteParseError> {
+    let mut params = vec![];
+    let mut constraints = vec![];
+    let mut pattern = String::from("^");
+    for part in path.split("/").skip(1) {
+        pattern.push('/');
+        // A segment can mix literal text and parameters, e.g.
+        // `{id:uint}.{format}`, so we scan it for parameters, and escape
+        // everything in between them.
+        let mut last = 0;
+        for cap in PLACEHOLDER.captures_iter(part) {
+            let whole = cap.get(0).unwrap();
+            pattern.push_str(&regex::escape(&part[last..whole.start()]));
+            last = whole.end();
+            if let Some(escape) = cap.name("escape") {
+                pattern.push_str(&regex::escape(&escape.as_str()[..1]));
+                continue;
+            }
+            let name = cap.name("kind").map(|m| m.as_str()).unwrap_or("string");
+            let args = cap.name("args").map(|m| m.as_str());
+            let (kind, constraint) =
+                kind_pattern(name, args).ok_or_else(|| RouteParseError::UnknownKind {
+                    path: path.to_string(),
+                    param: whole.as_str().to_string(),
+                })?;
+            pattern.push_str(&kind);
+            params.push(cap.name("name").map(|m| m.as_str().to_string()));
+            constraints.push(constraint);
+        }
+        pattern.push_str(&regex::escape(&part[last..]));
+    }
+
+    if catch_all {
+        let trimmed = pattern.trim_end_matches('/').len();
+        pattern.truncate(trimmed);
+        pattern.push_str("(?:/.*)?");
+    }
     pattern.push('$');
-    Regex::new(&pattern).unwrap()
+    Ok((Pattern::new(pattern), params, constraints))
+}
+
+/// Looks up the pattern for the given kind.  Most kinds take no arguments,
+/// and are just looked up in [`MATCH_KINDS`]; the rest build their pattern
+/// from their arguments, and may need to check the value after it matches.
+/// If the kind doesn't exist, or the arguments don't make sense for it, this
+/// returns `None`.
+pub(super) fn kind_pattern(name: &str, args: Option<&str>) -> Option<(Cow<'static, str>, Option<Constraint>)> {
+    match (name, args) {
+        ("b64", None) => Some((Cow::Borrowed(r"([A-Za-z0-9_-]+={0,2})"), None)),
+        ("b64", Some(args)) => {
+            let (min, max) = parse_range(args)?;
+            let min = std::cmp::max(min.unwrap_or(1), 1);
+            let max = match max {
+                Some(max) if max < min => return None,
+                Some(max) => max.to_string(),
+                None => String::new(),
+            };
+            let pattern = format!("([A-Za-z0-9_-]{{{},{}}}={{0,2}})", min, max);
+            Some((Cow::Owned(pattern), None))
+        }
+        ("one_of", Some(args)) => {
+            let choices = args.split('|').map(str::trim).collect::<Vec<_>>();
+            if choices
+                .iter()
+                .any(|choice| choice.is_empty() || choice.contains('/'))
+            {
+                return None;
+            }
+            let choices = choices.into_iter().map(regex::escape).collect::<Vec<_>>();
+            Some((Cow::Owned(format!("({})", choices.join("|"))), None))
+        }
+        (kind @ "int", Some(args)) | (kind @ "uint", Some(args)) => {
+            let (min, max) = parse_range(args)?;
+            if let (Some(min), Some(max)) = (min, max) {
+                if max < min {
+                    return None;
+                }
+            }
+            let pattern = MATCH_KINDS.get(kind).copied()?;
+            Some((Cow::Borrowed(pattern), Some(Constraint::Range(min, max))))
+        }
+        (name, None) => MATCH_KINDS
+            .get(name)
+            .copied()
+            .map(|pattern| (Cow::Borrowed(pattern), None)),
+        _ => None,
+    }
+}
+
+/// Parses a range argument, returning the inclusive bounds of the range.  We
+/// accept a single number (`5`), or any of Rust's range forms (`1..5`,
+/// `1..=5`, `1..`, `..5`, `..=5`).
+fn parse_range(args: &str) -> Option<(Option<i128>, Option<i128>)> {
+    fn bound(value: &str) -> Option<Option<i128>> {
+        match value.trim() {
+            "" => Some(None),
+            value => value.parse().ok().map(Some),
+        }
+    }
+
+    let args = args.trim();
+    if let Some(index) = args.find("..=") {
+        let min = bound(&args[..index])?;
+        let max = bound(&args[index + 3..])??;
+        Some((min, Some(max)))
+    } else if let Some(index) = args.find("..") {
+        let min = bound(&args[..index])?;
+        let max = match bound(&args[index + 2..])? {
+            Some(max) => Some(max.checked_sub(1)?),
+            None => None,
+        };
+        Some((min, max))
+    } else {
+        let exact = args.parse().ok()?;
+        Some((Some(exact), Some(exact)))
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_route_catch_all() {
+        assert_eq!(parse("/api/{}", true).unwrap().0.as_str(), r"^/api/([^/]+)(?:/.*)?$");
+        assert_eq!(parse("/api/", true).unwrap().0.as_str(), r"^/api(?:/.*)?$");
+        assert_eq!(parse("/", true).unwrap().0.as_str(), r"^(?:/.*)?$");
+
+        let route = Route::<(), ()>::new("/{id}", (), ()).with_prefix("/users/");
+        assert_eq!(route.path(), "/users/{id}");
+        assert_eq!(route.pattern.as_str(), r"^/users/([^/]+)$");
+        assert_eq!(Route::<(), ()>::new("/", (), ()).with_prefix("/users").path(), "/users");
+        assert_eq!(Route::<(), ()>::new("/", (), ()).with_prefix("/").path(), "/");
+    }
+
+    #[test]
+    fn test_intern() {
+        let mut routes = vec![
+            Route::new("/users/{id:uint}", "GET", ()),
+            Route::new("/users/{}", "GET", ()),
+            Route::new("/users/{user:uint}", "PUT", ()),
+        ];
+        intern(&mut routes);
+        assert!(Arc::ptr_eq(&routes[0].pattern.0, &routes[2].pattern.0));
+        assert!(!Arc::ptr_eq(&routes[0].pattern.0, &routes[1].pattern.0));
+
+        routes[2].pattern.compile().unwrap();
+        assert!(routes[0].pattern.is_compiled());
+        assert!(!routes[1].pattern.is_compiled());
+    }
+
+    #[test]
+    fn test_route_try_new() {
+        let route = Route::try_new("/users/{id:uint}", (), ()).unwrap();
+        assert_eq!(route.pattern.as_str(), r"^/users/(\d+)$");
+
+        let path = "/{:ipv6}".repeat(64);
+        let error = Route::try_new(path.clone(), (), ()).unwrap_err();
+        assert!(matches!(
+            &error,
+            RouteParseError::Regex { path: given, error: regex::Error::CompiledTooBig(_) } if *given == path
+        ));
+        assert!(error.to_string().starts_with("the route `/{:ipv6}/{:ipv6}"));
+
+        for (path, param) in [
+            ("/users/{id:unit}", "{id:unit}"),
+            ("/users/{:uint(5..1)}", "{:uint(5..1)}"),
+            ("/report/{:one_of(a||c)}", "{:one_of(a||c)}"),
+            ("/report/{:one_of}", "{:one_of}"),
+            ("/confirm/{:b64(x)}.{}", "{:b64(x)}"),
+        ] {
+            assert_eq!(
+                Route::try_new(path, (), ()).unwrap_err(),
+                RouteParseError::UnknownKind {
+                    path: path.to_string(),
+                    param: param.to_string(),
+                }
+            );
+        }
+    }
+
     #[test]
     fn test_route_parse() {
         fn assert_path(given: &str, expected: &str) {
-            assert_eq!(parse(given).as_str(), expected)
+            assert_eq!(parse(given, false).unwrap().0.as_str(), expected)
         }
         assert_path("/some/path", r"^/some/path$");
         assert_path("/some/{:string}", r"^/some/([^/]+)$");
@@ -82,5 +917,107 @@ mod tests {
             "/some/{:uuid}",
             r"^/some/([a-fA-F0-9]{8}-[a-fA-F0-9]{4}-[a-fA-F0-9]{4}-[a-fA-F0-9]{4}-[a-fA-F0-9]{12})$",
         );
+        assert_path(
+            "/report/{format:one_of(json|xml|csv)}",
+            r"^/report/(json|xml|csv)$",
+        );
+        assert_path("/report/{:one_of(a.b | c)}", r"^/report/(a\.b|c)$");
+        assert_path("/users/{id}", r"^/users/([^/]+)$");
+        assert_path("/users/{id:uint}", r"^/users/(\d+)$");
+        assert_path("/confirm/{:b64}", r"^/confirm/([A-Za-z0-9_-]+={0,2})$");
+        assert_path(
+            "/confirm/{:b64(22)}",
+            r"^/confirm/([A-Za-z0-9_-]{22,22}={0,2})$",
+        );
+        assert_path(
+            "/confirm/{:b64(16..=64)}",
+            r"^/confirm/([A-Za-z0-9_-]{16,64}={0,2})$",
+        );
+        assert_path(
+            "/confirm/{:b64(16..)}",
+            r"^/confirm/([A-Za-z0-9_-]{16,}={0,2})$",
+        );
+        assert_path(
+            "/confirm/{:b64(..8)}",
+            r"^/confirm/([A-Za-z0-9_-]{1,7}={0,2})$",
+        );
+        assert_path("/literal/{{}}", r"^/literal/\{\}$");
+        assert_path("/literal/{{id}}", r"^/literal/\{id\}$");
+        assert_path("/literal/{{{id:uint}}}", r"^/literal/\{(\d+)\}$");
+        assert_path("/literal/{{{{", r"^/literal/\{\{$");
+    }
+
+    #[test]
+    fn test_specificity() {
+        use Specificity::*;
+        fn specificity(path: &'static str) -> Vec<Specificity> {
+            Route::new(path, (), ()).specificity()
+        }
+        assert_eq!(specificity("/users/new"), vec![Static, Static]);
+        assert_eq!(specificity("/users/{id:uint}"), vec![Static, Typed]);
+        assert_eq!(specificity("/users/{id}"), vec![Static, String]);
+        assert_eq!(specificity("/users/{{id}}"), vec![Static, Static]);
+        assert_eq!(specificity("/{:uint}-{}/x"), vec![String, Static]);
+        assert!(specificity("/users/new") < specificity("/users/{id:uint}"));
+        assert!(specificity("/users/{id:uint}") < specificity("/users/{id}"));
+    }
+
+    #[test]
+    fn test_parse_range() {
+        assert_eq!(parse_range("5"), Some((Some(5), Some(5))));
+        assert_eq!(parse_range("1..5"), Some((Some(1), Some(4))));
+        assert_eq!(parse_range("1..=5"), Some((Some(1), Some(5))));
+        assert_eq!(parse_range("1.."), Some((Some(1), None)));
+        assert_eq!(parse_range("..=5"), Some((None, Some(5))));
+        assert_eq!(parse_range("-10..=-5"), Some((Some(-10), Some(-5))));
+        assert_eq!(parse_range("..0"), Some((None, Some(-1))));
+        assert_eq!(parse_range("..="), None);
+        assert_eq!(parse_range("a..b"), None);
+        assert_eq!(parse_range(""), None);
+    }
+
+    #[cfg(all(feature = "derive", feature = "http"))]
+    #[test]
+    fn test_routes_macro_kinds() {
+        // The `routes!` macro checks paths with its own copy of the parser,
+        // so every kind, and every form of arguments, goes through it here;
+        // one it doesn't know about is a compile error.
+        let build: crate::http::Build<(), ()> = crate::routes! {
+            GET "/string/{:string}" => |_, _| (),
+            GET "/int/{:int}" => |_, _| (),
+            GET "/uint/{:uint}" => |_, _| (),
+            GET "/uuid/{:uuid}" => |_, _| (),
+            GET "/semver/{:semver}" => |_, _| (),
+            GET "/ipv4/{:ipv4}" => |_, _| (),
+            GET "/ipv6/{:ipv6}" => |_, _| (),
+            GET "/email/{:email}" => |_, _| (),
+            GET "/grpc_method/{:grpc_method}" => |_, _| (),
+            GET "/b64/{:b64}" => |_, _| (),
+            GET "/ranges/{:int(-5..5)}/{:int(..=5)}/{:uint(1..)}/{:uint(7)}" => |_, _| (),
+            GET "/b64s/{:b64(22)}/{:b64(..=8)}/{:b64(4..)}" => |_, _| (),
+            GET "/one_of/{:one_of(json|csv)}" => |_, _| (),
+        };
+        let router = build.finish();
+
+        let kinds = router
+            .routes()
+            .flat_map(|info| PLACEHOLDER.captures_iter(info.path).collect::<Vec<_>>())
+            .filter_map(|cap| cap.name("kind").map(|kind| kind.as_str()))
+            .collect::<Vec<_>>();
+        for kind in MATCH_KINDS.keys() {
+            assert!(kinds.contains(kind), "`{}` isn't given to the macro", kind);
+        }
+
+        let lookup = |path| router.lookup(&::http::Method::GET, path).map(|(_, params)| params);
+        assert_eq!(lookup("/ranges/-5/5/1/7"), Some(vec!["-5", "5", "1", "7"]));
+        assert_eq!(lookup("/ranges/5/5/1/7"), None);
+        assert_eq!(lookup("/ranges/4/6/1/7"), None);
+        assert_eq!(lookup("/ranges/4/5/0/7"), None);
+        assert_eq!(lookup("/ranges/4/5/1/8"), None);
+        assert_eq!(lookup("/b64s/abcdefghijklmnopqrstuv/abcdefgh/abcd"), Some(vec!["abcdefghijklmnopqrstuv", "abcdefgh", "abcd"]));
+        assert_eq!(lookup("/b64s/abcdefghijklmnopqrstuv/abcdefghi/abcd"), None);
+        assert_eq!(lookup("/b64s/abcdefghijklmnopqrstuv/abcdefgh/abc"), None);
+        assert_eq!(lookup("/one_of/csv"), Some(vec!["csv"]));
+        assert_eq!(lookup("/one_of/xml"), None);
     }
 }
@@ -0,0 +1,86 @@
+use super::Router;
+use std::fmt::{Debug, Formatter, Result as FmtResult};
+use std::sync::{Arc, PoisonError, RwLock};
+
+/// A router that's shared between threads (or connections), and that can
+/// be replaced while it's serving requests, e.g. when the routes are loaded
+/// from a configuration file that's reloaded without a restart.  Cloning it
+/// is cheap, and every clone sees the same router.
+///
+/// Replacing the router is atomic: each lookup is done against either the
+/// old router or the new one, never a mix of the two.  Lookups that are
+/// already in progress keep using the router they started with.
+pub struct SharedRouter<M, H> {
+    current: Arc<RwLock<Arc<Router<M, H>>>>,
+}
+
+impl<M, H> SharedRouter<M, H> {
+    /// Creates a shared router, starting with the given router.
+    pub fn new(router: Router<M, H>) -> Self {
+        SharedRouter {
+            current: Arc::new(RwLock::new(Arc::new(router))),
+        }
+    }
+
+    /// The current router.  This can be held on to for as long as needed;
+    /// it isn't affected by the router being replaced.
+    pub fn load(&self) -> Arc<Router<M, H>> {
+        // The lock is only ever held to clone or replace the `Arc`, neither
+        // of which can panic, so a poisoned lock is still consistent.
+        self.current
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Replaces the router with the given one, returning the previous
+    /// router.  Every lookup made after this uses the new router.
+    pub fn store(&self, router: Router<M, H>) -> Arc<Router<M, H>> {
+        let mut current = self.current.write().unwrap_or_else(PoisonError::into_inner);
+        std::mem::replace(&mut *current, Arc::new(router))
+    }
+}
+
+impl<M, H> Clone for SharedRouter<M, H> {
+    fn clone(&self) -> Self {
+        SharedRouter {
+            current: self.current.clone(),
+        }
+    }
+}
+
+impl<M, H> From<Router<M, H>> for SharedRouter<M, H> {
+    fn from(router: Router<M, H>) -> Self {
+        SharedRouter::new(router)
+    }
+}
+
+impl<M: Debug, H: Debug> Debug for SharedRouter<M, H> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_tuple("SharedRouter").field(&self.load()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Route;
+
+    #[test]
+    fn test_store() {
+        let mut build = Router::build();
+        build.add(Route::new("/one", "GET", 1));
+        let shared = SharedRouter::new(build.finish());
+        let clone = shared.clone();
+        let old = clone.load();
+
+        let mut build = Router::build();
+        build.add(Route::new("/two", "GET", 2));
+        let previous = shared.store(build.finish());
+
+        assert!(Arc::ptr_eq(&previous, &old));
+        assert_eq!(old.lookup(&"GET", "/one"), Some((&1, vec![])));
+        assert_eq!(clone.load().lookup(&"GET", "/one"), None);
+        assert_eq!(clone.load().lookup(&"GET", "/two"), Some((&2, vec![])));
+    }
+}
@@ -0,0 +1,199 @@
+use super::{RouteInfo, Router};
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+const HEADER: [&str; 4] = ["METHOD", "PATH", "NAME", "NOTES"];
+
+/// Lists the routes of the router as a table, in the order they're
+/// evaluated in, with a column each for the methods, path, name, and notes
+/// (priority, guards, content types, and so on) of each route.  The default
+/// handler, if there is one, is listed last.  This is meant to be printed
+/// at startup, to check what the router actually serves.
+impl<M: Display, H> Display for Router<M, H> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        let mut rows = vec![HEADER.map(String::from)];
+        rows.extend(self.routes.iter().map(|route| row(&RouteInfo::new(route))));
+        if self.default.is_some() {
+            rows.push(["*".into(), "*".into(), String::new(), "default".into()]);
+        }
+
+        let mut widths = [0; 4];
+        for row in &rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = std::cmp::max(*width, cell.chars().count());
+            }
+        }
+        for row in &rows {
+            let mut line = String::new();
+            for (i, (cell, width)) in row.iter().zip(&widths).enumerate() {
+                if i > 0 {
+                    line.push_str("  ");
+                }
+                line.push_str(cell);
+                line.extend(std::iter::repeat_n(' ', width - cell.chars().count()));
+            }
+            writeln!(f, "{}", line.trim_end())?;
+        }
+        Ok(())
+    }
+}
+
+impl<M: Display, H> Router<M, H> {
+    /// Dumps the routes of the router as text that only changes when the
+    /// routing does, for snapshot tests (e.g. with `insta`).  Unlike the
+    /// table the router displays as, nothing is aligned, so a new route
+    /// doesn't change the lines of the others.  Each route gets a line with
+    /// its methods and path, followed by a line for each of its name,
+    /// pattern, parameters, priority, guards, content types, and extensions
+    /// that it has.  The routes are listed in the order they're evaluated
+    /// in, which only depends on the order they were added in and their
+    /// priorities, so a change in which route wins shows up as well.  The
+    /// default handler, if there is one, is listed last.
+    pub fn snapshot(&self) -> String {
+        let mut snapshot = String::new();
+        for route in &self.routes {
+            let info = RouteInfo::new(route);
+            snapshot.push_str(&format!("{} {}\n", methods(&info), info.path));
+            if let Some(name) = info.name {
+                snapshot.push_str(&format!("  name: {}\n", name));
+            }
+            snapshot.push_str(&format!("  pattern: {}\n", info.pattern));
+            if !info.params.is_empty() {
+                let params = info
+                    .params
+                    .iter()
+                    .map(|param| param.unwrap_or("_"))
+                    .collect::<Vec<_>>();
+                snapshot.push_str(&format!("  params: {}\n", params.join(", ")));
+            }
+            if info.catch_all {
+                snapshot.push_str("  catch-all\n");
+            } else if info.priority != 0 {
+                snapshot.push_str(&format!("  priority: {}\n", info.priority));
+            }
+            if info.guards > 0 {
+                snapshot.push_str(&format!("  guards: {}\n", info.guards));
+            }
+            if !info.consumes.is_empty() {
+                snapshot.push_str(&format!("  consumes: {}\n", info.consumes.join(", ")));
+            }
+            if !info.produces.is_empty() {
+                snapshot.push_str(&format!("  produces: {}\n", info.produces.join(", ")));
+            }
+            if !info.extensions.is_empty() {
+                snapshot.push_str(&format!("  extensions: {}\n", info.extensions.len()));
+            }
+        }
+        if self.default.is_some() {
+            snapshot.push_str("* *\n  default\n");
+        }
+        snapshot
+    }
+}
+
+fn methods<M: Display>(info: &RouteInfo<'_, M>) -> String {
+    match info.methods {
+        None => "*".to_string(),
+        Some(methods) => methods
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(","),
+    }
+}
+
+fn row<M: Display>(info: &RouteInfo<'_, M>) -> [String; 4] {
+    let methods = methods(info);
+
+    let mut notes = vec![];
+    if info.catch_all {
+        notes.push("catch-all".to_string());
+    }
+    if info.priority != 0 && !info.catch_all {
+        notes.push(format!("priority {}", info.priority));
+    }
+    match info.guards {
+        0 => {}
+        1 => notes.push("1 guard".to_string()),
+        guards => notes.push(format!("{} guards", guards)),
+    }
+    if !info.consumes.is_empty() {
+        notes.push(format!("consumes {}", info.consumes.join(",")));
+    }
+    if !info.produces.is_empty() {
+        notes.push(format!("produces {}", info.produces.join(",")));
+    }
+
+    [
+        methods,
+        info.path.to_string(),
+        info.name.unwrap_or_default().to_string(),
+        notes.join("; "),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Route;
+
+    #[test]
+    fn test_display() {
+        let mut build = Router::build();
+        build
+            .add(Route::new("/users", "GET", ()).with_name("users"))
+            .add(Route::new("/users/{id:uint}", "GET", ()).with_priority(1))
+            .add(
+                Route::new("/users", "POST", ())
+                    .with_host("api.example.com")
+                    .consumes("application/json"),
+            )
+            .add(Route::new_any("/health", ()))
+            .with_default(());
+        let router = build.finish();
+
+        assert_eq!(
+            router.to_string(),
+            "METHOD  PATH              NAME   NOTES\n\
+             GET     /users/{id:uint}         priority 1\n\
+             GET     /users            users\n\
+             POST    /users                   1 guard; consumes application/json\n\
+             *       /health\n\
+             *       *                        default\n"
+        );
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let mut build = Router::build();
+        build
+            .add(Route::new("/users", "GET", ()).with_name("users"))
+            .add(Route::new("/users/{id:uint}", "GET", ()).with_priority(1))
+            .add(
+                Route::for_methods("/users/{}/posts/{post}", vec!["POST", "PUT"], ())
+                    .with_host("api.example.com")
+                    .consumes("application/json")
+                    .with_extension(5u8),
+            )
+            .with_default(());
+        let router = build.finish();
+
+        assert_eq!(
+            router.snapshot(),
+            "GET /users/{id:uint}\n  \
+               pattern: ^/users/(\\d+)$\n  \
+               params: id\n  \
+               priority: 1\n\
+             GET /users\n  \
+               name: users\n  \
+               pattern: ^/users$\n\
+             POST,PUT /users/{}/posts/{post}\n  \
+               pattern: ^/users/([^/]+)/posts/([^/]+)$\n  \
+               params: _, post\n  \
+               guards: 1\n  \
+               consumes: application/json\n  \
+               extensions: 1\n\
+             * *\n  \
+               default\n"
+        );
+    }
+}
@@ -0,0 +1,273 @@
+use super::route::{kind_pattern, PLACEHOLDER};
+use super::Route;
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use regex::{Captures, Regex};
+use std::error::Error;
+use std::fmt::{Debug, Display, Formatter, Result as FmtResult};
+
+/// The characters that are percent-encoded in a segment of a url; this is
+/// everything but the unreserved characters.
+const SEGMENT: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// The reason a url couldn't be built by [`super::Router::url_for`] or
+/// [`UrlBuilder::build`].
+pub enum UrlError {
+    /// No route has the given name.
+    UnknownRoute(String),
+    /// The wrong number of parameters was given for the route.
+    WrongArity {
+        /// The name of the route.
+        route: String,
+        /// The number of parameters the route has.
+        expected: usize,
+        /// The number of parameters that were given.
+        given: usize,
+    },
+    /// The route has no parameter with the given name.
+    UnknownParam {
+        /// The name of the route.
+        route: String,
+        /// The name of the parameter.
+        param: String,
+    },
+    /// No value was given for a parameter of the route.
+    MissingParam {
+        /// The name of the route.
+        route: String,
+        /// The name of the parameter, or its position (e.g. `#0`) if it
+        /// doesn't have one.
+        param: String,
+    },
+    /// The value given for a parameter doesn't match its kind, e.g. `abc`
+    /// for `{id:uint}`.
+    InvalidParam {
+        /// The name of the route.
+        route: String,
+        /// The name of the parameter, or its position (e.g. `#0`) if it
+        /// doesn't have one.
+        param: String,
+        /// The parameter as it's written in the route, e.g. `{id:uint}`.
+        kind: String,
+        /// The value that was given.
+        value: String,
+    },
+}
+
+impl Display for UrlError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            UrlError::UnknownRoute(route) => write!(f, "no route is named `{}`", route),
+            UrlError::WrongArity {
+                route,
+                expected,
+                given,
+            } => write!(
+                f,
+                "route `{}` takes {} parameter(s), but {} were given",
+                route, expected, given
+            ),
+            UrlError::UnknownParam { route, param } => {
+                write!(f, "route `{}` has no parameter `{}`", route, param)
+            }
+            UrlError::MissingParam { route, param } => {
+                write!(f, "no value was given for parameter `{}` of route `{}`", param, route)
+            }
+            UrlError::InvalidParam {
+                route,
+                param,
+                kind,
+                value,
+            } => write!(
+                f,
+                "`{}` is not a valid value for parameter `{}` (`{}`) of route `{}`",
+                value, param, kind, route
+            ),
+        }
+    }
+}
+
+impl Error for UrlError {}
+
+/// A builder for the url of a named route, created by
+/// [`super::Router::url`].  Parameters are given by name, in any order, and
+/// each value is checked against the kind of its parameter when the url is
+/// built.
+pub struct UrlBuilder<'r, M, H> {
+    route: &'r Route<M, H>,
+    name: &'r str,
+    values: Vec<Option<String>>,
+    error: Option<UrlError>,
+}
+
+impl<'r, M, H> UrlBuilder<'r, M, H> {
+    pub(super) fn new(route: &'r Route<M, H>, name: &'r str) -> Self {
+        UrlBuilder {
+            route,
+            name,
+            values: vec![None; route.params.len()],
+            error: None,
+        }
+    }
+
+    /// Sets the value of the parameter with the given name.  If the route
+    /// has no such parameter, building the url fails with
+    /// [`UrlError::UnknownParam`].
+    pub fn param<V: Display>(mut self, param: &str, value: V) -> Self {
+        match self.route.param_index(param) {
+            Some(index) => self.values[index] = Some(value.to_string()),
+            None if self.error.is_none() => {
+                self.error = Some(UrlError::UnknownParam {
+                    route: self.name.to_string(),
+                    param: param.to_string(),
+                });
+            }
+            None => {}
+        }
+        self
+    }
+
+    /// Builds the url, checking that every parameter was given a value that
+    /// matches its kind.
+    pub fn build(self) -> Result<String, UrlError> {
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+        let (route, name) = (self.route, self.name);
+        let values = self
+            .values
+            .into_iter()
+            .enumerate()
+            .map(|(index, value)| {
+                value.ok_or_else(|| UrlError::MissingParam {
+                    route: name.to_string(),
+                    param: param_label(route, index),
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        build_url(route, name, &values)
+    }
+}
+
+impl<M, H> Debug for UrlBuilder<'_, M, H> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("UrlBuilder")
+            .field("route", &self.name)
+            .field("values", &self.values)
+            .finish()
+    }
+}
+
+/// Builds the url of the given route, with the given values for its
+/// parameters, in order.  Each value is checked against the kind of its
+/// parameter, and percent-encoded.
+pub(super) fn build_url<M, H>(
+    route: &Route<M, H>,
+    name: &str,
+    values: &[String],
+) -> Result<String, UrlError> {
+    if values.len() != route.params.len() {
+        return Err(UrlError::WrongArity {
+            route: name.to_string(),
+            expected: route.params.len(),
+            given: values.len(),
+        });
+    }
+
+    let path = crate::normalize_url(route.path());
+    let mut url = String::with_capacity(path.len());
+    let mut values = values.iter().enumerate();
+    // This walks the path the same way it's parsed, so that the values line
+    // up with the parameters.
+    for (i, part) in path.split('/').enumerate() {
+        if i > 0 {
+            url.push('/');
+        }
+        let mut last = 0;
+        for cap in PLACEHOLDER.captures_iter(part) {
+            let whole = cap.get(0).unwrap();
+            url.extend(utf8_percent_encode(&part[last..whole.start()], SEGMENT));
+            last = whole.end();
+            if let Some(escape) = cap.name("escape") {
+                url.extend(utf8_percent_encode(&escape.as_str()[..1], SEGMENT));
+                continue;
+            }
+            let (index, value) = values.next().unwrap();
+            if !kind_allows(&cap, value) {
+                return Err(UrlError::InvalidParam {
+                    route: name.to_string(),
+                    param: param_label(route, index),
+                    kind: whole.as_str().to_string(),
+                    value: value.clone(),
+                });
+            }
+            url.extend(utf8_percent_encode(value, SEGMENT));
+        }
+        url.extend(utf8_percent_encode(&part[last..], SEGMENT));
+    }
+    Ok(url)
+}
+
+/// Whether the given value matches the kind of the given parameter, so that
+/// the url built with it would match the route.
+fn kind_allows(cap: &Captures<'_>, value: &str) -> bool {
+    let name = cap.name("kind").map(|m| m.as_str()).unwrap_or("string");
+    let args = cap.name("args").map(|m| m.as_str());
+    // The route was parsed, so the kind is known to exist.
+    let (pattern, constraint) = kind_pattern(name, args).unwrap();
+    let pattern = Regex::new(&format!("^{}$", pattern)).unwrap();
+    pattern.is_match(value) && constraint.is_none_or(|constraint| constraint.allows(value))
+}
+
+fn param_label<M, H>(route: &Route<M, H>, index: usize) -> String {
+    match &route.params[index] {
+        Some(name) => name.clone(),
+        None => format!("#{}", index),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build(path: &'static str, values: &[&str]) -> Result<String, UrlError> {
+        let route = Route::new(path, (), ());
+        let values = values.iter().map(|value| value.to_string()).collect::<Vec<_>>();
+        build_url(&route, "test", &values)
+    }
+
+    #[test]
+    fn test_build_url() {
+        assert_eq!(build("/", &[]).unwrap(), "/");
+        assert_eq!(build("/users/{id:uint}", &["5"]).unwrap(), "/users/5");
+        assert_eq!(
+            build("/files/{}/{name}.{ext:one_of(md|txt)}", &["a b", "read me", "md"]).unwrap(),
+            "/files/a%20b/read%20me.md"
+        );
+        assert_eq!(build("/caf%C3%A9/{}", &["\u{e9}"]).unwrap(), "/caf%C3%A9/%C3%A9");
+        assert_eq!(build("/templates/{{{name}}}", &["x"]).unwrap(), "/templates/%7Bx%7D");
+        assert_eq!(
+            build("/users/{id:uint}", &[]),
+            Err(UrlError::WrongArity {
+                route: "test".to_string(),
+                expected: 1,
+                given: 0
+            })
+        );
+        assert_eq!(
+            build("/users/{id:uint}", &["-5"]),
+            Err(UrlError::InvalidParam {
+                route: "test".to_string(),
+                param: "id".to_string(),
+                kind: "{id:uint}".to_string(),
+                value: "-5".to_string(),
+            })
+        );
+        assert!(build("/pages/{:uint(1..=10)}", &["11"]).is_err());
+        assert!(build("/pages/{:uint(1..=10)}", &["10"]).is_ok());
+    }
+}
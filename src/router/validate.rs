@@ -0,0 +1,255 @@
+use super::route::{kind_pattern, PLACEHOLDER};
+use super::Route;
+use regex::Regex;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// A route that can never match, because a route that's evaluated before it
+/// always matches first.  These are found by [`super::Build::validate`].
+pub struct Conflict<'b> {
+    /// The route that can never match.
+    pub route: RouteRef<'b>,
+    /// The route that matches first.
+    pub shadowed_by: RouteRef<'b>,
+    /// Whether the route is a duplicate of the one that matches first, or
+    /// is just shadowed by it.
+    pub kind: ConflictKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// The kind of a [`Conflict`].
+pub enum ConflictKind {
+    /// The route that matches first matches every path the route does.
+    Shadowed,
+    /// The route that matches first has the same pattern and methods, e.g.
+    /// because the same route was added twice.
+    Duplicate,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// A route in a builder, as reported by a [`Conflict`].
+pub struct RouteRef<'b> {
+    /// The index of the route, in the order routes were added to the
+    /// builder.
+    pub index: usize,
+    /// The path the route was created with.
+    pub path: &'b str,
+    /// The regular expression the route matches paths with.
+    pub pattern: &'b str,
+}
+
+impl Display for Conflict<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        let verb = match self.kind {
+            ConflictKind::Shadowed => "is shadowed by",
+            ConflictKind::Duplicate => "duplicates",
+        };
+        write!(
+            f,
+            "route #{} ({}) {} route #{} ({})",
+            self.route.index, self.route, verb, self.shadowed_by.index, self.shadowed_by
+        )
+    }
+}
+
+impl Display for RouteRef<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "`{}`, matching `{}`", self.path, self.pattern)
+    }
+}
+
+impl<'b> RouteRef<'b> {
+    pub(super) fn new<M, H>(index: usize, route: &'b Route<M, H>) -> Self {
+        RouteRef {
+            index,
+            path: route.path(),
+            pattern: route.pattern.as_str(),
+        }
+    }
+}
+
+/// Whether every request matched by `later` is matched by `earlier` first,
+/// so that `later` can never match.  This errs on the side of caution: it
+/// only says so when it's sure, so some shadowed routes go unreported.
+pub(super) fn shadows<M: Eq, H>(earlier: &Route<M, H>, later: &Route<M, H>) -> bool {
+    // A route with guards, or that negotiates its content type, can let a
+    // request through to the routes after it.
+    if !earlier.guards.is_empty() || !earlier.consumes.is_empty() || !earlier.produces.is_empty() {
+        return false;
+    }
+    let methods = match (&earlier.methods, &later.methods) {
+        (None, _) => true,
+        (Some(_), None) => false,
+        (Some(earlier), Some(later)) => later.iter().all(|method| earlier.contains(method)),
+    };
+    if !methods {
+        return false;
+    }
+
+    let earlier_segments = segments(earlier);
+    let later_segments = segments(later);
+    let lengths = if earlier.catch_all {
+        later_segments.len() >= earlier_segments.len()
+    } else {
+        !later.catch_all && later_segments.len() == earlier_segments.len()
+    };
+    lengths
+        && earlier_segments
+            .iter()
+            .zip(&later_segments)
+            .all(|(earlier, later)| earlier.covers(later))
+}
+
+/// Whether the two routes have the same pattern and methods, so that the
+/// later one is a duplicate of the earlier.
+pub(super) fn duplicates<M: Eq, H>(earlier: &Route<M, H>, later: &Route<M, H>) -> bool {
+    let methods = match (&earlier.methods, &later.methods) {
+        (None, None) => true,
+        (Some(earlier), Some(later)) => {
+            earlier.iter().all(|method| later.contains(method))
+                && later.iter().all(|method| earlier.contains(method))
+        }
+        _ => false,
+    };
+    methods && earlier.pattern.as_str() == later.pattern.as_str()
+}
+
+/// A single segment of a route's path, parsed just enough to tell whether
+/// one segment matches everything another does.
+#[derive(Debug)]
+struct Segment {
+    /// The segment, with the names of its parameters taken out, so that two
+    /// segments that match the same paths are equal.
+    canonical: String,
+    shape: Shape,
+}
+
+#[derive(Debug)]
+enum Shape {
+    /// The segment is entirely literal text; this is the text, with its
+    /// escapes resolved.
+    Literal(String),
+    /// The segment is a single parameter.
+    Param { kind: String, args: Option<String> },
+    /// The segment mixes parameters with literal text, or has more than one
+    /// parameter.
+    Mixed,
+}
+
+impl Segment {
+    /// Whether this segment matches every segment that the other one does.
+    fn covers(&self, other: &Segment) -> bool {
+        if self.canonical == other.canonical {
+            return true;
+        }
+        let (kind, args) = match &self.shape {
+            Shape::Param { kind, args } => (kind.as_str(), args.as_deref()),
+            _ => return false,
+        };
+        // The route was parsed, so the kind is known to exist.
+        let (pattern, constraint) = kind_pattern(kind, args).unwrap();
+        match &other.shape {
+            Shape::Literal(text) => {
+                Regex::new(&format!("^{}$", pattern)).unwrap().is_match(text)
+                    && constraint.is_none_or(|constraint| constraint.allows(text))
+            }
+            Shape::Param { kind: other_kind, .. } => {
+                args.is_none()
+                    && (kind == "string" || kind == other_kind || (kind == "int" && other_kind == "uint"))
+            }
+            // Mixed segments always have some literal text (or more than one
+            // parameter), and never a slash.
+            Shape::Mixed => kind == "string",
+        }
+    }
+}
+
+fn segments<M, H>(route: &Route<M, H>) -> Vec<Segment> {
+    let mut segments = crate::normalize_url(route.path())
+        .split('/')
+        .skip(1)
+        .map(|part| {
+            // Escapes are kept as they are in the canonical form, so that
+            // they can't be mistaken for parameters.
+            let mut canonical = String::new();
+            let mut text = String::new();
+            let mut params = vec![];
+            let mut last = 0;
+            for cap in PLACEHOLDER.captures_iter(part) {
+                let whole = cap.get(0).unwrap();
+                canonical.push_str(&part[last..whole.start()]);
+                text.push_str(&part[last..whole.start()]);
+                last = whole.end();
+                if let Some(escape) = cap.name("escape") {
+                    canonical.push_str(escape.as_str());
+                    text.push_str(&escape.as_str()[..1]);
+                    continue;
+                }
+                let kind = cap.name("kind").map_or("string", |m| m.as_str());
+                let args = cap.name("args").map(|m| m.as_str());
+                canonical.push_str("{:");
+                canonical.push_str(kind);
+                if let Some(args) = args {
+                    canonical.push('(');
+                    canonical.push_str(args);
+                    canonical.push(')');
+                }
+                canonical.push('}');
+                params.push((whole.as_str() == part, kind.to_string(), args.map(str::to_string)));
+            }
+            canonical.push_str(&part[last..]);
+            text.push_str(&part[last..]);
+
+            let shape = match params.pop() {
+                None => Shape::Literal(text),
+                Some((true, kind, args)) => Shape::Param { kind, args },
+                Some(_) => Shape::Mixed,
+            };
+            Segment { canonical, shape }
+        })
+        .collect::<Vec<_>>();
+    // The prefix of a catch-all route matches the same way with or without
+    // a trailing slash, and the root has no segments at all.
+    if route.catch_all && segments.last().is_some_and(|segment| segment.canonical.is_empty()) {
+        segments.pop();
+    }
+    segments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get(path: &'static str) -> Route<&'static str, ()> {
+        Route::new(path, "GET", ())
+    }
+
+    #[test]
+    fn test_shadows() {
+        assert!(shadows(&get("/users/{}"), &get("/users/{id:uint}")));
+        assert!(shadows(&get("/users/{id}"), &get("/users/{:string}")));
+        assert!(shadows(&get("/users/{}"), &get("/users/new")));
+        assert!(shadows(&get("/users/{:uint}"), &get("/users/5")));
+        assert!(shadows(&get("/users/{:int}"), &get("/users/{:uint(1..5)}")));
+        assert!(shadows(&get("/users/{}"), &get("/users/{:one_of(a|b)}")));
+        assert!(shadows(&get("/files/{}"), &get("/files/{name}.{ext}")));
+        assert!(shadows(&get("/{:uint(1..=5)}"), &get("/3")));
+        assert!(shadows(&get("/caf%C3%A9"), &get("/caf\u{e9}")));
+        assert!(shadows(&Route::new_any("/{}", ()), &get("/users")));
+        assert!(shadows(&Route::catch_all("/api/", ()), &get("/api/users/{}")));
+        assert!(shadows(&Route::catch_all("/", ()), &get("/")));
+
+        assert!(!shadows(&get("/users/{:uint}"), &get("/users/{}")));
+        assert!(!shadows(&get("/users/{:uint}"), &get("/users/new")));
+        assert!(!shadows(&get("/users/{:uint(1..5)}"), &get("/users/{:uint}")));
+        assert!(!shadows(&get("/users/{:uint(1..5)}"), &get("/users/7")));
+        assert!(!shadows(&get("/users/{}"), &get("/users/")));
+        assert!(!shadows(&get("/users/{}"), &get("/users/{}/posts")));
+        assert!(!shadows(&get("/{{:string}}"), &get("/{}")));
+        assert!(!shadows(&get("/{}"), &Route::new("/users", "POST", ())));
+        assert!(!shadows(&get("/{}"), &Route::new_any("/users", ())));
+        assert!(!shadows(&get("/{}").with_host("example.com"), &get("/users")));
+        assert!(!shadows(&get("/{}").produces("text/html"), &get("/users")));
+        assert!(!shadows(&get("/api"), &Route::catch_all("/api", ())));
+    }
+}
@@ -0,0 +1,100 @@
+//! Routing for blocking servers, whose handlers return a response, instead
+//! of a future of one.  The request and response are the `http` crate's, so
+//! a server that has its own types (e.g. rouille, or tiny_http) converts to
+//! and from them around [`Router::respond`].
+
+use ::http::header::{HeaderValue, ALLOW, LOCATION};
+use ::http::{Method, Request, Response, StatusCode};
+use super::http::Dispatch;
+
+/// The handler that's stored as a part of every route in the router.  It's
+/// given the request, and the parameters of the path, and returns the
+/// response.
+pub type Handler<B> = super::http::Handler<B, Response<B>>;
+
+/// A single route, with a blocking [`Handler`].  It has the same shortcut
+/// methods as [`super::http::Route`].
+pub type Route<B> = super::http::Route<B, Response<B>>;
+
+/// The router type, with a blocking [`Handler`].  Requests are given to it
+/// with [`Router::respond`].
+pub type Router<B> = super::http::Router<B, Response<B>>;
+
+/// A builder for building routes, with a blocking [`Handler`].  It has the
+/// same shortcut methods as [`super::http::Build`].
+pub type Build<B> = super::http::Build<B, Response<B>>;
+
+impl<B: Default + 'static> Router<B> {
+    /// Routes the given request, and returns the response of the handler
+    /// that it matches.  This answers the request the same way the hyper
+    /// `Service` does when nothing matches: with a 204 No Content for an
+    /// automatic `OPTIONS` answer, a redirect with its `Location` for a
+    /// trailing slash (see [`super::http::Build::redirect_trailing_slash`]),
+    /// or an empty response with the status from
+    /// [`super::http::Router::dispatch`] otherwise.  The body of a response
+    /// to a `HEAD` request is dropped, but its headers are kept.
+    pub fn respond(&self, req: Request<B>) -> Response<B> {
+        let head = req.method() == Method::HEAD;
+        let mut response = match self.dispatch(req) {
+            Dispatch::Handled(response) => response,
+            Dispatch::Options(allow) => {
+                let mut response = status(StatusCode::NO_CONTENT);
+                if let Ok(allow) = HeaderValue::from_str(&allow) {
+                    response.headers_mut().insert(ALLOW, allow);
+                }
+                response
+            }
+            Dispatch::Redirect(code, location) => {
+                let mut response = status(code);
+                if let Ok(location) = HeaderValue::from_str(&location) {
+                    response.headers_mut().insert(LOCATION, location);
+                }
+                response
+            }
+            Dispatch::Status(code) => status(code),
+        };
+        if head {
+            *response.body_mut() = B::default();
+        }
+        response
+    }
+}
+
+fn status<B: Default>(code: StatusCode) -> Response<B> {
+    let mut response = Response::new(B::default());
+    *response.status_mut() = code;
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_respond() {
+        let mut build = Router::build();
+        build
+            .get("/users/{}", |_, params| Response::new(params[0].to_string().into_bytes()))
+            .post("/users", |req: Request<Vec<u8>>, _| Response::new(req.into_body()))
+            .head_fallback()
+            .options_fallback();
+        let router = build.finish();
+        let call = |method: Method, path: &str| {
+            let request = Request::builder()
+                .method(method)
+                .uri(path)
+                .body(b"jane".to_vec())
+                .unwrap();
+            router.respond(request)
+        };
+
+        assert_eq!(call(Method::GET, "/users/5").body(), b"5");
+        assert_eq!(call(Method::POST, "/users").body(), b"jane");
+        assert!(call(Method::HEAD, "/users/5").body().is_empty());
+
+        let response = call(Method::OPTIONS, "/users");
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert_eq!(response.headers()[ALLOW], "POST, OPTIONS");
+        assert_eq!(call(Method::GET, "/posts").status(), StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,290 @@
+//! Utilities for testing a hyper [`Router`] without binding a socket.  A
+//! [`TestClient`] gives requests straight to the router, the same way its
+//! `Service` would, and collects the response into a [`TestResponse`] that
+//! can be inspected, e.g.
+//! `client.get("/users/5").header("accept", "application/json").send().await`.
+
+use crate::hyper::{respond, Router};
+use futures::executor::{self, Notify, NotifyHandle, Spawn};
+use futures::{Async, Future as _, Stream as _};
+use hyper::header::{HeaderMap, HeaderName, HeaderValue};
+use hyper::{Body, Method, Request, StatusCode};
+use std::fmt::{Debug, Formatter, Result as FmtResult};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, Waker};
+
+/// Drives a router with requests built by the test, instead of ones from
+/// a connection.
+#[derive(Clone)]
+pub struct TestClient {
+    router: Router,
+}
+
+macro_rules! method {
+    ($(#$meta:tt)* $name:ident => $method:expr) => {
+        $(#$meta)*
+        pub fn $name(&self, path: &str) -> TestRequest<'_> {
+            self.request($method, path)
+        }
+    };
+}
+
+impl TestClient {
+    /// Creates a client for the given router.
+    pub fn new(router: Router) -> Self {
+        TestClient { router }
+    }
+
+    /// The router the client gives its requests to.
+    pub fn router(&self) -> &Router {
+        &self.router
+    }
+
+    /// Starts a request with the given method, for the given path (which
+    /// can have a query).
+    pub fn request(&self, method: Method, path: &str) -> TestRequest<'_> {
+        TestRequest {
+            client: self,
+            method,
+            path: path.to_string(),
+            headers: HeaderMap::new(),
+            body: Vec::new(),
+        }
+    }
+
+    method!(
+        /// Starts a `GET` request.
+        get => Method::GET
+    );
+    method!(
+        /// Starts a `POST` request.
+        post => Method::POST
+    );
+    method!(
+        /// Starts a `PUT` request.
+        put => Method::PUT
+    );
+    method!(
+        /// Starts a `PATCH` request.
+        patch => Method::PATCH
+    );
+    method!(
+        /// Starts a `DELETE` request.
+        delete => Method::DELETE
+    );
+    method!(
+        /// Starts a `HEAD` request.
+        head => Method::HEAD
+    );
+    method!(
+        /// Starts an `OPTIONS` request.
+        options => Method::OPTIONS
+    );
+}
+
+impl Debug for TestClient {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.write_str("TestClient")
+    }
+}
+
+impl From<Router> for TestClient {
+    fn from(router: Router) -> Self {
+        TestClient::new(router)
+    }
+}
+
+/// A request being built by a [`TestClient`].  See [`TestRequest::send`].
+#[derive(Debug)]
+pub struct TestRequest<'c> {
+    client: &'c TestClient,
+    method: Method,
+    path: String,
+    headers: HeaderMap,
+    body: Vec<u8>,
+}
+
+impl TestRequest<'_> {
+    /// Adds the given header to the request.
+    ///
+    /// # Panics
+    ///
+    /// This panics if the name or value isn't valid for a header.
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        let name = HeaderName::from_bytes(name.as_bytes())
+            .unwrap_or_else(|_| panic!("invalid header name: {:?}", name));
+        let value = HeaderValue::from_str(value)
+            .unwrap_or_else(|_| panic!("invalid header value: {:?}", value));
+        self.headers.append(name, value);
+        self
+    }
+
+    /// Gives the request the given body.
+    pub fn body<B: Into<Vec<u8>>>(mut self, body: B) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    /// Gives the request to the router, and waits for the whole response.
+    /// The router is driven by whatever executor the future is awaited on,
+    /// so a route with a timeout (which needs tokio's timer) should be
+    /// tested on a tokio runtime.
+    ///
+    /// # Panics
+    ///
+    /// This panics if the path isn't a valid URI.
+    pub fn send(self) -> impl Future<Output = Result<TestResponse, failure::Error>> + Send {
+        let path = &self.path;
+        let uri = path.parse().unwrap_or_else(|_| panic!("invalid path: {:?}", path));
+        let mut request = Request::new(Body::from(self.body));
+        *request.method_mut() = self.method;
+        *request.uri_mut() = uri;
+        *request.headers_mut() = self.headers;
+        let response = respond(&self.client.router, request)
+            .map_err(failure::Error::from)
+            .and_then(|response| {
+                let (parts, body) = response.into_parts();
+                body.concat2()
+                    .map_err(failure::Error::from)
+                    .map(move |body| TestResponse {
+                        status: parts.status,
+                        headers: parts.headers,
+                        body: body.to_vec(),
+                    })
+            });
+        Compat(executor::spawn(response))
+    }
+}
+
+/// The response to a request sent by a [`TestClient`], with its whole body.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestResponse {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Vec<u8>,
+}
+
+impl TestResponse {
+    /// The status of the response.
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    /// The headers of the response.
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    /// The value of the given header, if the response has it (and it's
+    /// valid text).  If the header was given more than once, this is the
+    /// first value.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name).and_then(|value| value.to_str().ok())
+    }
+
+    /// The body of the response.
+    pub fn body(&self) -> &[u8] {
+        &self.body
+    }
+
+    /// The body of the response as text, with any invalid UTF-8 replaced.
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+}
+
+/// Wakes the `std::future` task that's polling the `futures` 0.1 future.
+struct WakerNotify(Waker);
+
+impl Notify for WakerNotify {
+    fn notify(&self, _: usize) {
+        self.0.wake_by_ref()
+    }
+}
+
+/// A `futures` 0.1 future, as a `std::future`, so that it can be awaited;
+/// the opposite of the adapter used by [`crate::hyper::async_handler`].
+struct Compat<F>(Spawn<F>);
+
+impl<F: futures::Future + Unpin> Future for Compat<F> {
+    type Output = Result<F::Item, F::Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let notify = NotifyHandle::from(Arc::new(WakerNotify(cx.waker().clone())));
+        match self.0.poll_future_notify(&notify, 0) {
+            Ok(Async::Ready(item)) => Poll::Ready(Ok(item)),
+            Ok(Async::NotReady) => Poll::Pending,
+            Err(error) => Poll::Ready(Err(error)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hyper::reply;
+    use std::sync::{Condvar, Mutex};
+    use std::task::Wake;
+
+    /// Wakes the thread that's blocked on a future.
+    struct Unpark(Mutex<bool>, Condvar);
+
+    impl Wake for Unpark {
+        fn wake(self: Arc<Self>) {
+            *self.0.lock().unwrap() = true;
+            self.1.notify_one();
+        }
+    }
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let unpark = Arc::new(Unpark(Mutex::new(false), Condvar::new()));
+        let waker = Waker::from(unpark.clone());
+        let mut cx = Context::from_waker(&waker);
+        let mut future = Box::pin(future);
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+            let mut woken = unpark.0.lock().unwrap();
+            while !*woken {
+                woken = unpark.1.wait(woken).unwrap();
+            }
+            *woken = false;
+        }
+    }
+
+    #[test]
+    fn test_client() {
+        let mut build = Router::build();
+        build
+            .get("/users/{}", |req, params| {
+                let accept = req.headers().get("accept").map_or("", |value| value.to_str().unwrap());
+                reply::text(&format!("{} {}", params[0], accept))
+            })
+            .post("/echo", |req, _| {
+                Box::new(req.into_body().concat2().map_err(Into::into).map(|body| {
+                    hyper::Response::new(Body::from(body))
+                }))
+            })
+            .async_fn(Method::GET, "/later", |_, _| async {
+                Ok::<_, failure::Error>(hyper::Response::new(Body::from("later")))
+            });
+        let client = TestClient::new(build.finish());
+
+        let response = block_on(client.get("/users/5").header("accept", "text/plain").send()).unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.header("content-type"), Some("text/plain; charset=utf-8"));
+        assert_eq!(response.text(), "5 text/plain");
+
+        let response = block_on(client.post("/echo").body("hello").send()).unwrap();
+        assert_eq!(response.body(), b"hello");
+
+        let response = block_on(client.get("/later").send()).unwrap();
+        assert_eq!(response.text(), "later");
+
+        let response = block_on(client.delete("/users/5").send()).unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+}